@@ -0,0 +1,252 @@
+//! The chat protocol of Simperby.
+//!
+//! Every member broadcasts signed chats over a DMS, each referring to the last chat it has perceived.
+//! The longest chain is the candidate of the canonical chat ordering,
+//! and the consensus leader semifinalizes it by putting `ChatContent::Ack` on top of it.
+//! The chain up to the last ack is recorded in the block as a `ChatLog`.
+use simperby_common::*;
+use simperby_network::{
+    dms::{DistributedMessageSet as DMS, Message, MessageFilter},
+    primitives::{GossipNetwork, Storage},
+};
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+pub type Error = eyre::Error;
+
+pub fn generate_dms_key(header: &BlockHeader) -> String {
+    format!(
+        "chat-{}-{}",
+        header.height,
+        &header.to_hash256().to_string()[0..8]
+    )
+}
+
+struct ChatMessageFilter {
+    height: BlockHeight,
+    members: BTreeSet<PublicKey>,
+    validator_set: BTreeSet<PublicKey>,
+    chain_id: Hash256,
+}
+
+impl MessageFilter for ChatMessageFilter {
+    fn filter(&self, message: &Message) -> Result<(), String> {
+        let signed_chat: SignedChat =
+            serde_spb::from_str(message.data()).map_err(|e| e.to_string())?;
+        let signer = signed_chat.signature.signer();
+        if signer != message.signature().signer() {
+            return Err("DMS message signer does not match with chat signer".to_string());
+        }
+        if !self.members.contains(signer) {
+            return Err("the signer is not a member".to_string());
+        }
+        if signed_chat.chat.height != self.height {
+            return Err(format!(
+                "invalid chat height: expected {}, got {}",
+                self.height, signed_chat.chat.height
+            ));
+        }
+        if signed_chat.chat.content == ChatContent::Ack && !self.validator_set.contains(signer) {
+            return Err("the signer is not eligible to ack".to_string());
+        }
+        signed_chat
+            .signature
+            .verify(&signed_chat.chat, &self.chain_id)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Returns the canonical chat chain among the given chats.
+///
+/// It is the longest chain on top of the last semifinalized point (the deepest ack).
+/// Ties are broken by the earlier timestamp of the tip, and then by its hash.
+/// Chats whose predecessor is unknown are ignored.
+pub fn canonical_chain(chats: &[SignedChat]) -> Vec<SignedChat> {
+    let chats = chats
+        .iter()
+        .map(|chat| (chat.to_hash256(), chat))
+        .collect::<HashMap<_, _>>();
+    let chains = chats
+        .keys()
+        .filter_map(|hash| trace_chain(&chats, *hash))
+        .collect::<Vec<_>>();
+    let last_ack = select_longest(chains.iter().filter(|chain| {
+        chain.last().expect("a chain is never empty").chat.content == ChatContent::Ack
+    }));
+    let chain = if let Some(last_ack) = last_ack {
+        select_longest(
+            chains
+                .iter()
+                .filter(|chain| chain.get(last_ack.len() - 1) == last_ack.last()),
+        )
+    } else {
+        select_longest(chains.iter())
+    };
+    chain
+        .map(|chain| chain.iter().map(|x| (*x).clone()).collect())
+        .unwrap_or_default()
+}
+
+fn select_longest<'a, 'b>(
+    chains: impl Iterator<Item = &'b Vec<&'a SignedChat>>,
+) -> Option<&'b Vec<&'a SignedChat>> {
+    chains.max_by_key(|chain| {
+        let tip = chain.last().expect("a chain is never empty");
+        (
+            chain.len(),
+            Reverse(tip.chat.timestamp),
+            Reverse(tip.to_hash256()),
+        )
+    })
+}
+
+/// Returns the chain from the first chat to the given one, or `None` if it is not connected.
+fn trace_chain<'a>(
+    chats: &HashMap<Hash256, &'a SignedChat>,
+    hash: Hash256,
+) -> Option<Vec<&'a SignedChat>> {
+    let mut chain = vec![*chats.get(&hash)?];
+    while let Some(previous_hash) = chain.last().expect("never empty").chat.previous_hash {
+        // A valid chain never exceeds the number of chats.
+        if chain.len() > chats.len() {
+            return None;
+        }
+        chain.push(*chats.get(&previous_hash)?);
+    }
+    chain.reverse();
+    Some(chain)
+}
+
+pub struct Chat<N: GossipNetwork, S: Storage> {
+    pub dms: DMS<N, S>,
+    pub this_node_key: Option<PrivateKey>,
+    filter: Arc<ChatMessageFilter>,
+}
+
+impl<N: GossipNetwork, S: Storage> Chat<N, S> {
+    /// Creates a chat instance for the height next to `last_header`.
+    ///
+    /// `members` are the ones who are eligible to chat,
+    /// and the validators of `last_header` are the ones who are eligible to ack.
+    pub async fn new(
+        mut dms: DMS<N, S>,
+        last_header: &BlockHeader,
+        members: Vec<PublicKey>,
+        this_node_key: Option<PrivateKey>,
+    ) -> Result<Self, Error> {
+        let filter = Arc::new(ChatMessageFilter {
+            height: last_header.height + 1,
+            members: members.into_iter().collect(),
+            validator_set: last_header
+                .validator_set
+                .iter()
+                .map(|(public_key, _)| public_key.clone())
+                .collect(),
+            chain_id: dms.get_chain_id(),
+        });
+        dms.set_filter(Arc::clone(&filter) as Arc<dyn MessageFilter>);
+        Ok(Self {
+            dms,
+            this_node_key,
+            filter,
+        })
+    }
+
+    /// Reads all the chats received so far, regardless of their ordering.
+    pub async fn read_chats(&self) -> Result<Vec<SignedChat>, Error> {
+        self.dms
+            .read_messages()
+            .await?
+            .iter()
+            .map(|message| serde_spb::from_str(message.data()).map_err(Error::from))
+            .collect()
+    }
+
+    /// Reads the current canonical chat chain.
+    pub async fn read(&self) -> Result<Vec<SignedChat>, Error> {
+        Ok(canonical_chain(&self.read_chats().await?))
+    }
+
+    /// Reads the chat log up to the last semifinalized point,
+    /// or `None` if the chain has never been semifinalized.
+    pub async fn read_semifinalized(&self) -> Result<Option<ChatLog>, Error> {
+        let mut chats = self.read().await?;
+        while let Some(chat) = chats.last() {
+            if chat.chat.content == ChatContent::Ack {
+                return Ok(Some(ChatLog {
+                    height: self.filter.height,
+                    chats,
+                }));
+            }
+            chats.pop();
+        }
+        Ok(None)
+    }
+
+    /// Says the given text on top of the current canonical chain.
+    pub async fn chat(&mut self, text: String, timestamp: Timestamp) -> Result<(), Error> {
+        self.add_chat(ChatContent::Text(text), timestamp).await
+    }
+
+    /// Semifinalizes the current canonical chain.
+    ///
+    /// This node must be one of the validators.
+    pub async fn ack(&mut self, timestamp: Timestamp) -> Result<(), Error> {
+        self.add_chat(ChatContent::Ack, timestamp).await
+    }
+
+    async fn add_chat(&mut self, content: ChatContent, timestamp: Timestamp) -> Result<(), Error> {
+        let private_key = self
+            .this_node_key
+            .as_ref()
+            .ok_or_else(|| eyre::eyre!("this node is not a participant"))?;
+        let chain = self.read().await?;
+        let chat = simperby_common::Chat {
+            height: self.filter.height,
+            // Keep the chain in chronological order.
+            timestamp: chain
+                .last()
+                .map(|x| x.chat.timestamp.max(timestamp))
+                .unwrap_or(timestamp),
+            content,
+            previous_hash: chain.last().map(|x| x.to_hash256()),
+        };
+        let signed_chat = SignedChat {
+            signature: TypedSignature::sign(&chat, &self.filter.chain_id, private_key)?,
+            chat,
+        };
+        let data = serde_spb::to_string(&signed_chat).unwrap();
+        let message = Message::new(
+            data.clone(),
+            TypedSignature::sign(&data, &self.filter.chain_id, private_key)?,
+            &self.filter.chain_id,
+        )?;
+        self.filter.filter(&message).map_err(|e| eyre::eyre!(e))?;
+        self.dms.add_message(message).await?;
+        Ok(())
+    }
+
+    /// Broadcasts all the local messages.
+    pub async fn broadcast(&mut self) -> Result<(), Error> {
+        self.dms.broadcast_all().await?;
+        Ok(())
+    }
+
+    pub async fn fetch(&mut self) -> Result<(), Error> {
+        self.dms.fetch().await?;
+        Ok(())
+    }
+
+    /// Serves the chat protocol indefinitely.
+    ///
+    /// TODO: currently it just returns itself after the given time.
+    pub async fn serve(self, time_in_ms: u64) -> Result<Self, Error> {
+        let dms = self.dms.serve(time_in_ms).await?;
+        Ok(Self {
+            dms,
+            this_node_key: self.this_node_key,
+            filter: self.filter,
+        })
+    }
+}
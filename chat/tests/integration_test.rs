@@ -0,0 +1,137 @@
+use simperby_chat::*;
+use simperby_common::*;
+use simperby_network::*;
+use simperby_test_suite::*;
+
+fn sign_chat(
+    chain_id: &Hash256,
+    private_key: &PrivateKey,
+    content: ChatContent,
+    timestamp: Timestamp,
+    previous: Option<&SignedChat>,
+) -> SignedChat {
+    let chat = simperby_common::Chat {
+        height: 1,
+        timestamp,
+        content,
+        previous_hash: previous.map(|x| x.to_hash256()),
+    };
+    SignedChat {
+        signature: TypedSignature::sign(&chat, chain_id, private_key).unwrap(),
+        chat,
+    }
+}
+
+#[test]
+fn canonical_chain_1() {
+    let keys = (0..3).map(|i| generate_keypair([i])).collect::<Vec<_>>();
+    let chain_id = Hash256::hash("chat-test-chain");
+    let text = |s: &str| ChatContent::Text(s.to_owned());
+    let a = sign_chat(&chain_id, &keys[0].1, text("a"), 0, None);
+    let b = sign_chat(&chain_id, &keys[1].1, text("b"), 1, Some(&a));
+    // A fork on top of `a`, which is semifinalized by the leader.
+    let c = sign_chat(&chain_id, &keys[2].1, text("c"), 2, Some(&a));
+    let ack = sign_chat(&chain_id, &keys[0].1, ChatContent::Ack, 3, Some(&c));
+    // The longer one, but not on top of the last ack.
+    let b2 = sign_chat(&chain_id, &keys[1].1, text("b2"), 4, Some(&b));
+    let b3 = sign_chat(&chain_id, &keys[1].1, text("b3"), 5, Some(&b2));
+    let d = sign_chat(&chain_id, &keys[2].1, text("d"), 6, Some(&ack));
+    // An orphan chat, whose predecessor is never received.
+    let e = sign_chat(&chain_id, &keys[2].1, text("e"), 7, Some(&b3));
+    let orphan = sign_chat(&chain_id, &keys[2].1, text("orphan"), 8, Some(&e));
+
+    let chats = vec![
+        a.clone(),
+        b.clone(),
+        c.clone(),
+        b2.clone(),
+        b3.clone(),
+        orphan,
+    ];
+    assert_eq!(canonical_chain(&chats), vec![a.clone(), b, b2, b3]);
+    let chats = [chats, vec![ack.clone(), d.clone()]].concat();
+    assert_eq!(canonical_chain(&chats), vec![a, c, ack, d]);
+}
+
+#[tokio::test]
+async fn basic_1() {
+    setup_test();
+
+    let network_id = "chat-basic-1".to_string();
+    let (server_network_config, client_network_configs, peer) =
+        setup_server_client_nodes(network_id.clone(), 3).await;
+    let members = std::iter::once(&server_network_config)
+        .chain(client_network_configs.iter())
+        .map(|config| config.public_key.clone())
+        .collect::<Vec<_>>();
+    // Only the server is eligible to ack.
+    let (reserved_state, _) = test_utils::generate_standard_genesis(4);
+    let chain_id = reserved_state.genesis_info.chain_id();
+    let mut last_header = reserved_state.genesis_info.header;
+    last_header.validator_set = vec![(server_network_config.public_key.clone(), 1)];
+
+    let mut server_node = simperby_chat::Chat::new(
+        create_test_dms(
+            server_network_config.clone(),
+            network_id.clone(),
+            chain_id,
+            SharedKnownPeers::new_static(Default::default()),
+        )
+        .await,
+        &last_header,
+        members.clone(),
+        Some(server_network_config.private_key),
+    )
+    .await
+    .unwrap();
+
+    let mut client_nodes = Vec::new();
+    for network_config in client_network_configs.iter() {
+        client_nodes.push(
+            simperby_chat::Chat::new(
+                create_test_dms(
+                    network_config.clone(),
+                    network_id.clone(),
+                    chain_id,
+                    peer.clone(),
+                )
+                .await,
+                &last_header,
+                members.clone(),
+                Some(network_config.private_key.clone()),
+            )
+            .await
+            .unwrap(),
+        );
+    }
+
+    server_node.chat("hello".to_owned(), 0).await.unwrap();
+
+    let serve_task = tokio::spawn(async move {
+        let mut server_node = server_node.serve(5000).await.unwrap();
+        assert_eq!(server_node.read().await.unwrap().len(), 4);
+        assert_eq!(server_node.read_semifinalized().await.unwrap(), None);
+        server_node.ack(10).await.unwrap();
+        let chat_log = server_node.read_semifinalized().await.unwrap().unwrap();
+        assert_eq!(chat_log.height, 1);
+        assert_eq!(chat_log.chats.len(), 5);
+        assert_eq!(
+            chat_log.chats.last().unwrap().chat.content,
+            ChatContent::Ack
+        );
+    });
+
+    sleep_ms(1000).await;
+
+    for (i, node) in client_nodes.iter_mut().enumerate() {
+        node.fetch().await.unwrap();
+        assert_eq!(node.read().await.unwrap().len(), i + 1);
+        node.chat(format!("hi {i}"), i as Timestamp + 1)
+            .await
+            .unwrap();
+        node.broadcast().await.unwrap();
+        // A non-validator can't semifinalize the chain.
+        assert!(node.ack(i as Timestamp + 1).await.is_err());
+    }
+    serve_task.await.unwrap();
+}
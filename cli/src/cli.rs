@@ -25,6 +25,9 @@ pub enum CreateCommands {
         block_height: BlockHeight,
         proof: String,
         chain_name: String,
+        /// The conditions to revoke the delegation automatically, serialized.
+        #[clap(long, default_value = "[]")]
+        conditions: String,
     },
     /// An extra-agenda transaction that undelegates the consensus voting power and
     /// the governance voting power (if delegated).
@@ -35,7 +38,19 @@ pub enum CreateCommands {
         chain_name: String,
     },
     /// An extra-agenda transaction that reports a misbehaving validator.
-    TxReport, // TODO
+    ///
+    /// The two votes must be conflicting votes signed by the same validator
+    /// at the same height and round.
+    TxReport {
+        /// The first signed consensus vote, serialized.
+        first_vote: String,
+        /// The second signed consensus vote, serialized.
+        second_vote: String,
+    },
+    /// A chat log, semifinalizing the current chat chain.
+    ///
+    /// It must be created by the proposer of the next block.
+    ChatLog,
     /// A block waiting for finalization.
     Block,
     /// An agenda waiting for governance approval.
@@ -51,11 +66,18 @@ pub enum SignCommands {
         governance: bool,
         target_height: u64,
         chain_name: String,
+        /// The hash of the genesis header in hex, which the signature is bound to.
+        chain_id: String,
+        /// The conditions to revoke the delegation automatically, serialized.
+        #[clap(long, default_value = "[]")]
+        conditions: String,
     },
     TxUndelegate {
         delegator: MemberName,
         target_height: u64,
         chain_name: String,
+        /// The hash of the genesis header in hex, which the signature is bound to.
+        chain_id: String,
     },
     Custom {
         hash: String,
@@ -134,6 +156,10 @@ pub enum Commands {
         /// the consensus is 'global' so this option is not associated with any commit.
         #[clap(long, action)]
         show: bool,
+        /// If enabled, it starts recording the consensus events of this node,
+        /// which can be replayed by `replay`.
+        #[clap(long, action)]
+        record: bool,
     },
 
     // ----- Information Commands ----- //
@@ -143,6 +169,17 @@ pub enum Commands {
     Show { revision: String },
     /// Show the current status of the p2p network.
     Network,
+    /// Replay the recorded consensus events, printing each of them with its responses.
+    Replay {
+        /// The trace file to replay. If not specified, the trace of this node is replayed.
+        trace: Option<String>,
+        /// The number of the events to replay. If not specified, all of them are replayed.
+        #[clap(long)]
+        steps: Option<usize>,
+        /// If enabled, it compares the replayed state with the live state of this node.
+        #[clap(long, action)]
+        diff: bool,
+    },
 
     // ----- Network Commands ----- //
     /// Become a server node indefinitely, serving all message propagations and Git requests.
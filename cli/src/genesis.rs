@@ -76,10 +76,15 @@ pub async fn run_genesis_proposer(private_key: &str) {
             private_key,
             broadcast_interval_ms: None,
             fetch_interval_ms: None,
+            consensus_progress_interval_ms: None,
             public_repo_url: vec![],
             governance_port: 1155,
             consensus_port: 1166,
+            chat_port: 1188,
             repository_port: 1177,
+            governance_gossip_port: None,
+            consensus_gossip_port: None,
+            chat_gossip_port: None,
         },
         &dir,
     )
@@ -162,8 +167,7 @@ pub async fn run_genesis_non_proposer(private_key: &str) {
         "cd {dir}/repository/repo && git remote remove origin"
     ))
     .await;
-    let ports =
-        r#"{"dms-consensus-0-3f22f0a0":1166,"repository":1177,"dms-governance-0-3f22f0a0":1155}"#;
+    let ports = r#"{"dms-consensus-0-3f22f0a0":1166,"repository":1177,"dms-governance-0-3f22f0a0":1155,"dms-chat-0-3f22f0a0":1188}"#;
     let ports = serde_json::from_str(ports).unwrap();
     setup_peer(
         &dir,
@@ -192,10 +196,15 @@ pub async fn run_genesis_non_proposer(private_key: &str) {
             private_key,
             broadcast_interval_ms: None,
             fetch_interval_ms: None,
+            consensus_progress_interval_ms: None,
             public_repo_url: vec![],
             governance_port: 1155,
             consensus_port: 1166,
+            chat_port: 1188,
             repository_port: 1177,
+            governance_gossip_port: None,
+            consensus_gossip_port: None,
+            chat_gossip_port: None,
         },
         &dir,
     )
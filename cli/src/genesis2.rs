@@ -14,7 +14,7 @@ async fn shit() {
     println!("{}", rs.genesis_info.header.to_hash256());
     println!("{}", "7d86e58bc9f05726a7fc4b5a33feb82c535a3da8".to_owned().to_hash256());
 
-    verify::verify_finalization_proof(&rs.genesis_info.header, &rs.genesis_info.genesis_proof).unwrap();
+    verify::verify_finalization_proof(&rs.genesis_info.header, &rs.genesis_info.genesis_proof, &rs.genesis_info.chain_id()).unwrap();
 }
 
 #[tokio::test]
@@ -30,10 +30,15 @@ async fn fuck() {
         private_key,
         broadcast_interval_ms: None,
         fetch_interval_ms: None,
+        consensus_progress_interval_ms: None,
         public_repo_url: vec![],
         governance_port: 1155,
         consensus_port: 1166,
+        chat_port: 1188,
         repository_port: 1177,
+        governance_gossip_port: None,
+        consensus_gossip_port: None,
+        chat_gossip_port: None,
     }, "/Users/junhayang/pdao/genesis").await.unwrap();
 }
 
@@ -5,10 +5,22 @@ use eyre::{eyre, Result};
 use simperby_cli::cli::{self, *};
 use simperby_common::utils::get_timestamp;
 use simperby_node::{
-    clone, genesis, initialize, serve, simperby_common::*, simperby_repository::raw::RawRepository,
+    clone, genesis, initialize, serve,
+    simperby_common::*,
+    simperby_consensus::{EventTrace, Replayer},
+    simperby_repository::raw::RawRepository,
     CommitInfo, Config,
 };
 
+fn parse_hash(hash: &str) -> Result<Hash256> {
+    Ok(Hash256::from_array(
+        hex::decode(hash)?
+            .as_slice()
+            .try_into()
+            .map_err(|_| eyre!("a hash must be in 32 bytes"))?,
+    ))
+}
+
 async fn run(args: cli::Cli, path: String, config: Config) -> eyre::Result<()> {
     match args.command {
         Commands::Genesis => {
@@ -21,16 +33,25 @@ async fn run(args: cli::Cli, path: String, config: Config) -> eyre::Result<()> {
         Commands::Git => todo!(),
         Commands::Show { revision } => show(config, &path, revision).await?,
         Commands::Network => todo!(),
+        Commands::Replay {
+            trace: Some(trace),
+            steps,
+            diff: false,
+        } => {
+            let trace = serde_spb::from_str(&std::fs::read_to_string(trace)?)?;
+            replay(trace, steps)?;
+        }
         Commands::Serve => {
             serve(config, &path).await?;
         }
-        Commands::Chat { .. } => todo!("chat is not implemented yet"),
         Commands::Sign(SignCommands::TxDelegate {
             delegator,
             delegatee,
             governance,
             target_height,
             chain_name,
+            chain_id,
+            conditions,
         }) => {
             let delegation_transaction_data = DelegationTransactionData {
                 delegator,
@@ -39,12 +60,15 @@ async fn run(args: cli::Cli, path: String, config: Config) -> eyre::Result<()> {
                 block_height: target_height,
                 timestamp: get_timestamp(),
                 chain_name,
+                conditions: serde_spb::from_str(&conditions)
+                    .map_err(|_| eyre!("invalid conditions for a delegation transaction"))?,
             };
             println!(
                 "{:?}",
                 serde_spb::to_string(
                     &TypedSignature::<DelegationTransactionData>::sign(
                         &delegation_transaction_data,
+                        &parse_hash(&chain_id)?,
                         &config.private_key,
                     )
                     .map_err(|_| eyre!("failed to sign"))?
@@ -55,6 +79,7 @@ async fn run(args: cli::Cli, path: String, config: Config) -> eyre::Result<()> {
             delegator,
             target_height,
             chain_name,
+            chain_id,
         }) => {
             let undelegation_transaction_data = UndelegationTransactionData {
                 delegator,
@@ -67,6 +92,7 @@ async fn run(args: cli::Cli, path: String, config: Config) -> eyre::Result<()> {
                 serde_spb::to_string(
                     &TypedSignature::<UndelegationTransactionData>::sign(
                         &undelegation_transaction_data,
+                        &parse_hash(&chain_id)?,
                         &config.private_key,
                     )
                     .map_err(|_| eyre!("failed to sign"))?
@@ -74,12 +100,7 @@ async fn run(args: cli::Cli, path: String, config: Config) -> eyre::Result<()> {
             );
         }
         Commands::Sign(SignCommands::Custom { hash }) => {
-            let hash = Hash256::from_array(
-                hex::decode(hash)?
-                    .as_slice()
-                    .try_into()
-                    .map_err(|_| eyre!("a hash must be in 32 bytes"))?,
-            );
+            let hash = parse_hash(&hash)?;
             println!(
                 "{}",
                 hex::encode(
@@ -114,6 +135,7 @@ async fn run(args: cli::Cli, path: String, config: Config) -> eyre::Result<()> {
                     block_height,
                     proof,
                     chain_name,
+                    conditions,
                 }) => {
                     simperby_node
                         .create_extra_agenda_transaction(ExtraAgendaTransaction::Delegate(
@@ -129,6 +151,9 @@ async fn run(args: cli::Cli, path: String, config: Config) -> eyre::Result<()> {
                                     block_height,
                                     timestamp: get_timestamp(),
                                     chain_name,
+                                    conditions: serde_spb::from_str(&conditions).map_err(|_| {
+                                        eyre!("invalid conditions for a delegation transaction")
+                                    })?,
                                 },
                                 proof: serde_spb::from_str(&proof).map_err(|_| {
                                     eyre!("invalid proof for a delegation transaction")
@@ -161,8 +186,24 @@ async fn run(args: cli::Cli, path: String, config: Config) -> eyre::Result<()> {
                         ))
                         .await?;
                 }
-                Commands::Create(CreateCommands::TxReport) => {
-                    todo!("TxReport is not implemented yet")
+                Commands::Create(CreateCommands::TxReport {
+                    first_vote,
+                    second_vote,
+                }) => {
+                    simperby_node
+                        .create_extra_agenda_transaction(ExtraAgendaTransaction::Report(TxReport {
+                            first_vote: serde_spb::from_str(&first_vote).map_err(|_| {
+                                eyre!("invalid first vote for a report transaction")
+                            })?,
+                            second_vote: serde_spb::from_str(&second_vote).map_err(|_| {
+                                eyre!("invalid second vote for a report transaction")
+                            })?,
+                            timestamp: get_timestamp(),
+                        }))
+                        .await?;
+                }
+                Commands::Create(CreateCommands::ChatLog) => {
+                    simperby_node.create_chat_log().await?;
                 }
                 Commands::Create(CreateCommands::Block) => {
                     simperby_node.create_block().await?;
@@ -188,13 +229,62 @@ async fn run(args: cli::Cli, path: String, config: Config) -> eyre::Result<()> {
                         simperby_node.veto_block(commit_hash).await?;
                     }
                 }
-                Commands::Consensus { show } => {
+                Commands::Consensus { show, record } => {
+                    if record {
+                        simperby_node.start_consensus_trace_recording().await?;
+                    }
                     if show {
-                        // TODO: show the status of the consensus instead of making a progress.
-                    } else {
+                        print_consensus_status(&simperby_node.get_consensus_status().await?);
+                    } else if !record {
                         simperby_node.progress_for_consensus().await?;
                     }
                 }
+                Commands::Chat {
+                    message,
+                    otr,
+                    interactive,
+                } => {
+                    if otr {
+                        return Err(eyre!("off-the-record chat is not supported yet"));
+                    }
+                    if let Some(message) = message {
+                        simperby_node.chat(message).await?;
+                        simperby_node.broadcast().await?;
+                    }
+                    if interactive {
+                        let mut lines = std::io::stdin().lines();
+                        loop {
+                            simperby_node.fetch().await?;
+                            print_chat(&simperby_node).await?;
+                            let Some(line) = lines.next() else {
+                                break;
+                            };
+                            simperby_node.chat(line?).await?;
+                            simperby_node.broadcast().await?;
+                        }
+                    } else {
+                        print_chat(&simperby_node).await?;
+                    }
+                }
+                Commands::Replay { trace, steps, diff } => {
+                    let trace = if let Some(trace) = trace {
+                        serde_spb::from_str(&std::fs::read_to_string(trace)?)?
+                    } else {
+                        simperby_node.get_consensus_trace().await?
+                    };
+                    let replayer = replay(trace, steps)?;
+                    if diff {
+                        let differences = simperby_node.diff_consensus_state(&replayer);
+                        if differences.is_empty() {
+                            println!("no difference from the live state");
+                        }
+                        for difference in differences {
+                            println!("{}:", difference.field);
+                            println!("  replayed: {}", difference.this);
+                            println!("  live:     {}", difference.other);
+                        }
+                    }
+                }
                 Commands::Update => {
                     simperby_node.fetch().await?;
                 }
@@ -238,6 +328,92 @@ async fn main() -> eyre::Result<()> {
     Ok(())
 }
 
+async fn print_chat(node: &simperby_node::SimperbyNode) -> Result<()> {
+    for (name, chat) in node.read_chat().await? {
+        match chat.content {
+            ChatContent::Text(text) => println!("[{}] {name}: {text}", chat.timestamp),
+            ChatContent::Ack => println!("[{}] {name}: (ack)", chat.timestamp),
+        }
+    }
+    Ok(())
+}
+
+fn print_consensus_status(status: &simperby_node::ConsensusStatus) {
+    let block = |index: usize| {
+        status
+            .block_hashes
+            .get(index)
+            .map(|hash| hash.to_string())
+            .unwrap_or_else(|| format!("(unknown block #{index})"))
+    };
+    let vote = |proposal: Option<usize>| proposal.map(block).unwrap_or_else(|| "nil".to_owned());
+    let state = &status.state;
+    println!("height: {}", status.height);
+    println!("round: {} ({:?})", state.round, state.step);
+    if let (Some(value), Some(round)) = (state.locked_value, state.locked_round) {
+        println!("locked: {} at round {round}", block(value));
+    }
+    if let (Some(value), Some(round)) = (state.valid_value, state.valid_round) {
+        println!("valid: {} at round {round}", block(value));
+    }
+    for tally in &state.tallies {
+        for (kind, votes) in [
+            ("prevote", &tally.prevotes),
+            ("precommit", &tally.precommits),
+        ] {
+            for (proposal, power) in votes {
+                println!(
+                    "round {} {kind}: {} - {power}/{}",
+                    tally.round,
+                    vote(*proposal),
+                    state.total_voting_power
+                );
+            }
+        }
+    }
+    for (round, timestamp) in &state.propose_timeout_schedules {
+        println!("propose timeout of round {round} at {timestamp}");
+    }
+    for (round, timestamp) in &state.precommit_timeout_schedules {
+        println!("precommit timeout of round {round} at {timestamp}");
+    }
+    for hash in &status.vetoed_block_hashes {
+        println!("vetoed: {hash}");
+    }
+    if let Some((proposal, proof)) = &state.finalized {
+        println!("finalized: {}", block(*proposal));
+        for index in proof {
+            if let Some(public_key) = status.validators.get(*index) {
+                println!("  precommitted by {public_key}");
+            }
+        }
+    }
+}
+
+/// Replays the trace up to the given number of the events, printing each of them.
+fn replay(trace: EventTrace, steps: Option<usize>) -> Result<Replayer> {
+    let mut replayer = Replayer::new(trace);
+    while Some(replayer.get_position()) != steps {
+        let position = replayer.get_position();
+        let Some(record) = replayer.step()? else {
+            break;
+        };
+        println!("#{position} [{}] {:?}", record.timestamp, record.event);
+        for response in &record.responses {
+            println!("  => {response:?}");
+        }
+    }
+    let state = replayer.get_vetomint().get_state_snapshot();
+    println!(
+        "replayed {}/{} events: round {} ({:?})",
+        replayer.get_position(),
+        replayer.get_trace().records.len(),
+        state.round,
+        state.step
+    );
+    Ok(replayer)
+}
+
 /// For every type of commit,
 /// 1. Show the content.
 /// 2. Show the hash of it.
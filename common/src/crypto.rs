@@ -1,4 +1,5 @@
 //! A set of types and functions related to cryptography, that are widely used in the entire Simperby project.
+use ed25519_dalek::{Signer, Verifier};
 use secp256k1::{
     ecdsa::{RecoverableSignature, RecoveryId},
     Message, Secp256k1, SecretKey,
@@ -9,6 +10,11 @@ use std::fmt;
 use thiserror::Error;
 
 const EVM_EC_RECOVERY_OFFSET: u8 = 27;
+/// The tag byte of the Ed25519 keys and signatures (see `SignatureScheme`).
+const ED25519_TAG: u8 = 0xed;
+/// The minimum number of signatures that a thread verifies in a batch verification,
+/// below which spawning a thread costs more than it saves.
+const BATCH_VERIFICATION_MIN_CHUNK: usize = 8;
 
 #[derive(Error, Debug, Clone)]
 pub enum CryptoError {
@@ -17,6 +23,39 @@ pub enum CryptoError {
     InvalidFormat(String),
     #[error("verification failed")]
     VerificationFailed,
+    /// When a batch of signatures contains more than one signature by the same signer.
+    #[error("duplicate signer: {0}")]
+    DuplicateSigner(String),
+    /// When a signature is verified with a key of another scheme.
+    #[error("signature scheme mismatch: expected {expected:?}, got {actual:?}")]
+    SchemeMismatch {
+        expected: SignatureScheme,
+        actual: SignatureScheme,
+    },
+}
+
+/// A digital signature scheme that a key (and the signatures by it) belongs to.
+///
+/// The keys and signatures of every scheme share the same encodings,
+/// in which the scheme is identified by a tag byte.
+/// secp256k1 ones carry no tag and are encoded just as before the other schemes were introduced;
+/// the tags are chosen not to collide with them.
+///
+/// - `PublicKey`: the first byte. An Ed25519 key is `[0xed] ++ key (32) ++ [0; 32]`,
+///   whereas a secp256k1 key (uncompressed) always starts with `0x04`.
+/// - `Signature`: the last byte. An Ed25519 signature is `signature (64) ++ [0xed]`,
+///   whereas a secp256k1 signature always ends with the recovery id (`27` or `28`).
+/// - `PrivateKey`: an Ed25519 key is prefixed with `0xed` (33 bytes),
+///   whereas a secp256k1 key is the plain 32 bytes.
+#[derive(
+    Debug, Default, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Serialize, Deserialize,
+)]
+pub enum SignatureScheme {
+    /// ECDSA over secp256k1, with the recovery id (EVM-compatible).
+    #[default]
+    Secp256k1,
+    /// EdDSA over Curve25519. It does not support public key recovery.
+    Ed25519,
 }
 
 type Error = CryptoError;
@@ -242,8 +281,19 @@ impl Signature {
         }
     }
 
-    /// Creates a new signature from the given data and keys.
+    /// Creates a new signature from the given data and keys,
+    /// under the scheme of the private key.
     pub fn sign(data: Hash256, private_key: &PrivateKey) -> Result<Self, Error> {
+        if private_key.scheme == SignatureScheme::Ed25519 {
+            let signature = ed25519_dalek::SigningKey::from_bytes(&private_key.key.data)
+                .sign(data.as_ref())
+                .to_bytes();
+            let mut bytes = [ED25519_TAG; 65];
+            bytes[0..64].copy_from_slice(&signature);
+            return Ok(Signature {
+                signature: HexSerializedBytes { data: bytes },
+            });
+        }
         let private_key = secp256k1::SecretKey::from_slice(&private_key.key.data)
             .map_err(|_| Error::InvalidFormat("private key: [omitted]".to_owned()))?;
         let message = Message::from_slice(data.as_ref()).unwrap();
@@ -263,20 +313,92 @@ impl Signature {
         })
     }
 
-    /// Verifies the signature against the given data and public key.
+    /// Returns the scheme that this signature is created under.
+    pub fn scheme(&self) -> SignatureScheme {
+        if self.signature.data[64] == ED25519_TAG {
+            SignatureScheme::Ed25519
+        } else {
+            SignatureScheme::Secp256k1
+        }
+    }
+
+    /// Verifies the signature against the given data and public key,
+    /// under the scheme of the public key.
     pub fn verify(&self, data: Hash256, public_key: &PublicKey) -> Result<(), Error> {
+        self.verify_with(&Secp256k1::verification_only(), data, public_key)
+    }
+
+    /// Verifies multiple signatures on the same data, spreading them over the available cores.
+    ///
+    /// Returns the first error (in the given order) if any of them is invalid.
+    pub fn verify_batch(
+        data: Hash256,
+        signatures: &[(&Signature, &PublicKey)],
+    ) -> Result<(), Error> {
+        let threads = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        let chunk_size = (signatures.len() / threads).max(BATCH_VERIFICATION_MIN_CHUNK);
+        let verify_chunk = |chunk: &[(&Signature, &PublicKey)]| {
+            let secp = Secp256k1::verification_only();
+            chunk.iter().try_for_each(|(signature, public_key)| {
+                signature.verify_with(&secp, data, public_key)
+            })
+        };
+        if signatures.len() <= chunk_size {
+            return verify_chunk(signatures);
+        }
+        std::thread::scope(|scope| {
+            let handles = signatures
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || verify_chunk(chunk)))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .try_for_each(|handle| handle.join().expect("signature verification never panics"))
+        })
+    }
+
+    fn verify_with<C: secp256k1::Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        data: Hash256,
+        public_key: &PublicKey,
+    ) -> Result<(), Error> {
+        if self.scheme() != public_key.scheme() {
+            return Err(Error::SchemeMismatch {
+                expected: public_key.scheme(),
+                actual: self.scheme(),
+            });
+        }
+        if public_key.scheme() == SignatureScheme::Ed25519 {
+            let signature = ed25519_dalek::Signature::from_bytes(
+                self.signature.data[0..64].try_into().expect("64 bytes"),
+            );
+            return public_key
+                .ed25519_key()?
+                .verify(data.as_ref(), &signature)
+                .map_err(|_| Error::VerificationFailed);
+        }
         let signature = secp256k1::ecdsa::Signature::from_compact(&self.signature.data[0..64])
             .map_err(|_| Error::InvalidFormat(format!("signature: {self}")))?;
         let public_key = secp256k1::PublicKey::from_slice(&public_key.key.data)
             .map_err(|_| Error::InvalidFormat(format!("public_key: {public_key}")))?;
         let message = Message::from_slice(data.as_ref()).unwrap();
-        Secp256k1::verification_only()
-            .verify_ecdsa(&message, &signature, &public_key)
+        secp.verify_ecdsa(&message, &signature, &public_key)
             .map_err(|_| Error::VerificationFailed)
     }
 
     /// Recover a public key from the given signature.
+    ///
+    /// It is supported only for secp256k1.
     pub fn recover(&self, data: Hash256) -> Result<PublicKey, Error> {
+        if self.scheme() != SignatureScheme::Secp256k1 {
+            return Err(Error::InvalidFormat(format!(
+                "public key recovery is not supported by {:?}",
+                self.scheme()
+            )));
+        }
         let message = Message::from_slice(data.as_ref()).unwrap();
         let recovery_id = RecoveryId::from_i32(
             self.signature.data[64..65][0] as i32 - EVM_EC_RECOVERY_OFFSET as i32,
@@ -304,9 +426,26 @@ impl Signature {
     }
 }
 
+/// A type that can be signed as a `TypedSignature`.
+///
+/// The tag distinguishes the signatures of different types whose hashes might collide.
+pub trait Signable: ToHash256 {
+    /// The unique tag of the type, which is mixed in every signature of it.
+    const SIGNING_TAG: &'static str;
+}
+
+/// Computes the message that is actually signed for a `T` whose hash is `data`,
+/// on the chain identified by `chain_id` (the hash of its genesis header).
+///
+/// Binding the chain prevents a signature from being replayed on another chain
+/// that shares the same keys (e.g., a testnet and a mainnet).
+pub fn signing_message<T: Signable>(data: Hash256, chain_id: &Hash256) -> Hash256 {
+    Hash256::hash([T::SIGNING_TAG.as_bytes(), chain_id.as_ref(), data.as_ref()].concat())
+}
+
 /// A signature that is explicitly marked with the type of the signed data.
 ///
-/// This implies that the signature is created on `Hash256::hash(serde_spb::to_vec(T).unwrap())`.
+/// This implies that the signature is created on `signing_message::<T>(data.to_hash256(), chain_id)`.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Serialize, Deserialize)]
 pub struct TypedSignature<T> {
     signature: Signature,
@@ -315,14 +454,24 @@ pub struct TypedSignature<T> {
     _mark: std::marker::PhantomData<T>,
 }
 
-impl<T: ToHash256> TypedSignature<T> {
-    /// Creates a new signature from the given data and keys.
-    pub fn sign(data: &T, private_key: &PrivateKey) -> Result<Self, Error> {
-        let data = data.to_hash256();
-        Signature::sign(data, private_key).map(|signature| TypedSignature {
-            signature,
-            signer: private_key.public_key(),
-            _mark: std::marker::PhantomData,
+impl<T: Signable> TypedSignature<T> {
+    /// Creates a new signature from the given data and keys, bound to the given chain.
+    pub fn sign(data: &T, chain_id: &Hash256, private_key: &PrivateKey) -> Result<Self, Error> {
+        Self::sign_hash(data.to_hash256(), chain_id, private_key)
+    }
+
+    /// Creates a new signature from the hash of the data, for when only the hash is known.
+    pub fn sign_hash(
+        data: Hash256,
+        chain_id: &Hash256,
+        private_key: &PrivateKey,
+    ) -> Result<Self, Error> {
+        Signature::sign(signing_message::<T>(data, chain_id), private_key).map(|signature| {
+            TypedSignature {
+                signature,
+                signer: private_key.public_key(),
+                _mark: std::marker::PhantomData,
+            }
         })
     }
 
@@ -338,10 +487,34 @@ impl<T: ToHash256> TypedSignature<T> {
         &self.signer
     }
 
-    /// Verifies the signature against the given data and public key.
-    pub fn verify(&self, data: &T) -> Result<(), Error> {
-        let data = data.to_hash256();
-        self.signature.verify(data, &self.signer)
+    /// Verifies the signature against the given data and chain.
+    pub fn verify(&self, data: &T, chain_id: &Hash256) -> Result<(), Error> {
+        self.verify_hash(data.to_hash256(), chain_id)
+    }
+
+    /// Verifies the signature against the hash of the data and the chain.
+    pub fn verify_hash(&self, data: Hash256, chain_id: &Hash256) -> Result<(), Error> {
+        self.signature
+            .verify(signing_message::<T>(data, chain_id), &self.signer)
+    }
+
+    /// Verifies all the given signatures on the same data in parallel.
+    ///
+    /// It fails before verifying any of them if a signer appears more than once.
+    pub fn verify_batch(signatures: &[Self], data: &T, chain_id: &Hash256) -> Result<(), Error> {
+        let mut signers = std::collections::HashSet::new();
+        for signature in signatures {
+            if !signers.insert(&signature.signer) {
+                return Err(Error::DuplicateSigner(signature.signer.to_string()));
+            }
+        }
+        Signature::verify_batch(
+            signing_message::<T>(data.to_hash256(), chain_id),
+            &signatures
+                .iter()
+                .map(|signature| (&signature.signature, &signature.signer))
+                .collect::<Vec<_>>(),
+        )
     }
 
     pub fn get_raw_signature(&self) -> Signature {
@@ -404,13 +577,81 @@ impl PublicKey {
             key: HexSerializedBytes { data: key },
         })
     }
+
+    /// Constructs an Ed25519 public key from its 32-byte encoding.
+    pub fn from_array_ed25519(array: [u8; 32]) -> Result<Self, Error> {
+        ed25519_dalek::VerifyingKey::from_bytes(&array)
+            .map_err(|_| Error::InvalidFormat(format!("given bytes: {}", hex::encode(array))))?;
+        let mut key = [0; 65];
+        key[0] = ED25519_TAG;
+        key[1..33].copy_from_slice(&array);
+        Ok(PublicKey {
+            key: HexSerializedBytes { data: key },
+        })
+    }
+
+    /// Returns the scheme that this key belongs to.
+    pub fn scheme(&self) -> SignatureScheme {
+        if self.key.data[0] == ED25519_TAG {
+            SignatureScheme::Ed25519
+        } else {
+            SignatureScheme::Secp256k1
+        }
+    }
+
+    fn ed25519_key(&self) -> Result<ed25519_dalek::VerifyingKey, Error> {
+        ed25519_dalek::VerifyingKey::from_bytes(self.key.data[1..33].try_into().expect("32 bytes"))
+            .map_err(|_| Error::InvalidFormat(format!("public_key: {self}")))
+    }
 }
 
 /// A private key.
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Serialize, Deserialize)]
-#[serde(transparent)]
+///
+/// Unlike `PublicKey` and `Signature`, it is not of a fixed size when encoded
+/// (see `SignatureScheme` for the encoding).
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
 pub struct PrivateKey {
     pub key: HexSerializedBytes<32>,
+    scheme: SignatureScheme,
+}
+
+impl Serialize for PrivateKey {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::ser::Serializer,
+    {
+        let bytes = match self.scheme {
+            SignatureScheme::Secp256k1 => self.key.data.to_vec(),
+            SignatureScheme::Ed25519 => [&[ED25519_TAG][..], &self.key.data].concat(),
+        };
+        if serializer.is_human_readable() {
+            serializer.serialize_str(hex::encode(bytes).as_str())
+        } else {
+            serializer.serialize_bytes(&bytes)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PrivateKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        let bytes = if deserializer.is_human_readable() {
+            let s: String = Deserialize::deserialize(deserializer)?;
+            hex::decode(s).map_err(|e| serde::de::Error::custom(e.to_string()))?
+        } else {
+            Deserialize::deserialize(deserializer)?
+        };
+        let result = match bytes.len() {
+            32 => PrivateKey::from_array(bytes.try_into().expect("32 bytes")),
+            33 if bytes[0] == ED25519_TAG => {
+                PrivateKey::from_array_ed25519(bytes[1..].try_into().expect("32 bytes"))
+            }
+            _ => return Err(serde::de::Error::custom("invalid length")),
+        };
+        result.map_err(|e| serde::de::Error::custom(e.to_string()))
+    }
 }
 
 impl std::convert::AsRef<[u8]> for PrivateKey {
@@ -423,6 +664,7 @@ impl PrivateKey {
     pub fn zero() -> Self {
         Self {
             key: HexSerializedBytes::zero(),
+            scheme: SignatureScheme::Secp256k1,
         }
     }
 
@@ -432,10 +674,29 @@ impl PrivateKey {
             .secret_bytes();
         Ok(PrivateKey {
             key: HexSerializedBytes { data: key },
+            scheme: SignatureScheme::Secp256k1,
         })
     }
 
+    /// Constructs an Ed25519 private key from its 32-byte seed.
+    pub fn from_array_ed25519(array: [u8; 32]) -> Result<Self, Error> {
+        Ok(PrivateKey {
+            key: HexSerializedBytes { data: array },
+            scheme: SignatureScheme::Ed25519,
+        })
+    }
+
+    /// Returns the scheme that this key belongs to.
+    pub fn scheme(&self) -> SignatureScheme {
+        self.scheme
+    }
+
     pub fn public_key(&self) -> PublicKey {
+        if self.scheme == SignatureScheme::Ed25519 {
+            let public_key = ed25519_dalek::SigningKey::from_bytes(&self.key.data).verifying_key();
+            return PublicKey::from_array_ed25519(public_key.to_bytes())
+                .expect("invalid public key");
+        }
         let private_key = SecretKey::from_slice(&self.key.data).expect("invalid private key");
         let secp = Secp256k1::new();
         let public_key = private_key.public_key(&secp);
@@ -452,6 +713,23 @@ pub fn check_keypair_match(public_key: &PublicKey, private_key: &PrivateKey) ->
 
 /// Generates a new keypair using the seed.
 pub fn generate_keypair(seed: impl AsRef<[u8]>) -> (PublicKey, PrivateKey) {
+    generate_keypair_with_scheme(SignatureScheme::default(), seed)
+}
+
+/// Generates a new keypair of the given scheme using the seed.
+pub fn generate_keypair_with_scheme(
+    scheme: SignatureScheme,
+    seed: impl AsRef<[u8]>,
+) -> (PublicKey, PrivateKey) {
+    if scheme == SignatureScheme::Ed25519 {
+        let private_key = PrivateKey::from_array_ed25519(
+            Hash256::hash([b"ed25519".as_ref(), seed.as_ref()].concat())
+                .hash
+                .data,
+        )
+        .expect("invalid private key");
+        return (private_key.public_key(), private_key);
+    }
     let mut seed_: [u8; 32] = [0; 32];
     for (i, x) in Hash256::hash(seed).as_ref()[0..32].iter().enumerate() {
         seed_[i] = *x;
@@ -579,4 +857,125 @@ mod tests {
             hex::encode(recovered.as_ref())
         );
     }
+
+    #[test]
+    fn signature_verify_batch() {
+        let data = "hello world".to_owned();
+        let chain_id = Hash256::hash("chain");
+        // Large enough to be split over multiple threads.
+        let mut signatures = (0..50u8)
+            .map(|i| TypedSignature::sign(&data, &chain_id, &generate_keypair([i]).1).unwrap())
+            .collect::<Vec<_>>();
+        TypedSignature::verify_batch(&signatures, &data, &chain_id).unwrap();
+        TypedSignature::verify_batch(&signatures[..3], &data, &chain_id).unwrap();
+        TypedSignature::verify_batch(&[], &data, &chain_id).unwrap();
+
+        let forged = TypedSignature::sign(
+            &"hello world2".to_owned(),
+            &chain_id,
+            &generate_keypair([50]).1,
+        )
+        .unwrap();
+        signatures.insert(37, forged);
+        assert!(matches!(
+            TypedSignature::verify_batch(&signatures, &data, &chain_id),
+            Err(Error::VerificationFailed)
+        ));
+        signatures.remove(37);
+
+        signatures.push(signatures[5].clone());
+        assert!(matches!(
+            TypedSignature::verify_batch(&signatures, &data, &chain_id),
+            Err(Error::DuplicateSigner(_))
+        ));
+    }
+
+    #[test]
+    fn typed_signature_domain() {
+        let (public_key, private_key) = generate_keypair("hello world");
+        let (testnet, mainnet) = (Hash256::hash("testnet"), Hash256::hash("mainnet"));
+        let data = "hello world".to_owned();
+        let signature = TypedSignature::sign(&data, &testnet, &private_key).unwrap();
+        signature.verify(&data, &testnet).unwrap();
+        // Not replayable on another chain.
+        signature.verify(&data, &mainnet).unwrap_err();
+        // Not valid as a raw signature of the data.
+        signature
+            .get_raw_signature()
+            .verify(data.to_hash256(), &public_key)
+            .unwrap_err();
+
+        // The same hash signed as a different type is not valid.
+        let hash = Hash256::hash("block");
+        let signature =
+            TypedSignature::<crate::Agenda>::sign_hash(hash, &testnet, &private_key).unwrap();
+        signature.verify_hash(hash, &testnet).unwrap();
+        TypedSignature::<crate::BlockHeader>::new(signature.get_raw_signature(), public_key)
+            .verify_hash(hash, &testnet)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn ed25519() {
+        let (public_key, private_key) =
+            generate_keypair_with_scheme(SignatureScheme::Ed25519, "hello world");
+        assert_eq!(public_key.scheme(), SignatureScheme::Ed25519);
+        assert_eq!(private_key.scheme(), SignatureScheme::Ed25519);
+        check_keypair_match(&public_key, &private_key).unwrap();
+        let hash = Hash256::hash("hello world");
+        let signature = Signature::sign(hash, &private_key).unwrap();
+        assert_eq!(signature.scheme(), SignatureScheme::Ed25519);
+        signature.verify(hash, &public_key).unwrap();
+        signature
+            .verify(Hash256::hash("hello world2"), &public_key)
+            .unwrap_err();
+        signature.recover(hash).unwrap_err();
+
+        // Encoded in the same sizes as secp256k1, except for the tagged private key.
+        let encoded = serde_spb::to_string(&signature).unwrap();
+        assert_eq!(encoded.len(), 132);
+        assert_eq!(signature, serde_spb::from_str(&encoded).unwrap());
+        let encoded = serde_spb::to_string(&public_key).unwrap();
+        assert_eq!(encoded.len(), 132);
+        assert_eq!(public_key, serde_spb::from_str(&encoded).unwrap());
+        let encoded = serde_spb::to_string(&private_key).unwrap();
+        assert_eq!(encoded.len(), 68);
+        assert_eq!(private_key, serde_spb::from_str(&encoded).unwrap());
+    }
+
+    #[test]
+    fn signature_scheme_mismatch() {
+        let hash = Hash256::hash("hello world");
+        let (secp_public_key, secp_private_key) = generate_keypair("hello world");
+        let (ed_public_key, ed_private_key) =
+            generate_keypair_with_scheme(SignatureScheme::Ed25519, "hello world");
+        assert!(matches!(
+            Signature::sign(hash, &secp_private_key)
+                .unwrap()
+                .verify(hash, &ed_public_key),
+            Err(Error::SchemeMismatch { .. })
+        ));
+        assert!(matches!(
+            Signature::sign(hash, &ed_private_key)
+                .unwrap()
+                .verify(hash, &secp_public_key),
+            Err(Error::SchemeMismatch { .. })
+        ));
+
+        // A batch may mix the schemes.
+        let data = "hello world".to_owned();
+        let chain_id = Hash256::hash("chain");
+        let signatures = (0..20u8)
+            .map(|i| {
+                let scheme = if i % 2 == 0 {
+                    SignatureScheme::Secp256k1
+                } else {
+                    SignatureScheme::Ed25519
+                };
+                let private_key = generate_keypair_with_scheme(scheme, [i]).1;
+                TypedSignature::sign(&data, &chain_id, &private_key).unwrap()
+            })
+            .collect::<Vec<_>>();
+        TypedSignature::verify_batch(&signatures, &data, &chain_id).unwrap();
+    }
 }
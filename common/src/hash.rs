@@ -90,6 +90,24 @@ impl ToHash256 for UndelegationTransactionData {
     }
 }
 
+impl ToHash256 for ConsensusVote {
+    fn to_hash256(&self) -> Hash256 {
+        Hash256::hash(serde_spb::to_vec(self).unwrap())
+    }
+}
+
+impl ToHash256 for Chat {
+    fn to_hash256(&self) -> Hash256 {
+        Hash256::hash(serde_spb::to_vec(self).unwrap())
+    }
+}
+
+impl ToHash256 for SignedChat {
+    fn to_hash256(&self) -> Hash256 {
+        Hash256::hash(serde_spb::to_vec(self).unwrap())
+    }
+}
+
 impl ToHash256 for ChatLog {
     fn to_hash256(&self) -> Hash256 {
         Hash256::hash(serde_spb::to_vec(self).unwrap())
@@ -115,6 +133,47 @@ impl ToHash256 for Commit {
     }
 }
 
+impl Signable for String {
+    const SIGNING_TAG: &'static str = "string";
+}
+
+impl Signable for (String, String) {
+    const SIGNING_TAG: &'static str = "string-pair";
+}
+
+impl Signable for BlockHeader {
+    const SIGNING_TAG: &'static str = "block-header";
+}
+
+impl Signable for Agenda {
+    const SIGNING_TAG: &'static str = "agenda";
+}
+
+impl Signable for DelegationTransactionData {
+    const SIGNING_TAG: &'static str = "delegation";
+}
+
+impl Signable for UndelegationTransactionData {
+    const SIGNING_TAG: &'static str = "undelegation";
+}
+
+impl Signable for ConsensusVote {
+    const SIGNING_TAG: &'static str = "consensus-vote";
+}
+
+impl Signable for Chat {
+    const SIGNING_TAG: &'static str = "chat";
+}
+
+impl GenesisInfo {
+    /// Returns the identifier of the chain, which every `TypedSignature` is bound to.
+    ///
+    /// It is the hash of the genesis header, which differs across chains even with the same members.
+    pub fn chain_id(&self) -> Hash256 {
+        self.header.to_hash256()
+    }
+}
+
 impl Transaction {
     /// Returns the alternative hash of the transaction, which is for the Merkle tree.
     pub fn merkle_hash(&self) -> Hash256 {
@@ -9,23 +9,31 @@ pub struct LightClient {
     pub commit_roots: Vec<Hash256>,
     pub height_offset: u64,
     pub last_header: BlockHeader,
+    /// The chain that the finalization proofs are bound to (see `GenesisInfo::chain_id()`).
+    pub chain_id: Hash256,
 }
 
 impl LightClient {
-    /// Intializes a new light client with the initial header.
-    pub fn new(initial_header: BlockHeader) -> Self {
+    /// Intializes a new light client with the initial header of the given chain.
+    pub fn new(initial_header: BlockHeader, chain_id: Hash256) -> Self {
         Self {
             repository_roots: vec![initial_header.repository_merkle_root],
             commit_roots: vec![initial_header.commit_merkle_root],
             height_offset: initial_header.height,
             last_header: initial_header,
+            chain_id,
         }
     }
 
     /// Updates the header by providing the next block and the proof of it.
+    ///
+    /// The signatures of the proof are verified in parallel,
+    /// each under the signature scheme of its validator's key.
     pub fn update(&mut self, header: BlockHeader, proof: FinalizationProof) -> Result<(), String> {
-        verify::verify_header_to_header(&self.last_header, &header).map_err(|e| e.to_string())?;
-        verify::verify_finalization_proof(&header, &proof).map_err(|e| e.to_string())?;
+        verify::verify_header_to_header(&self.last_header, &header, &self.chain_id)
+            .map_err(|e| e.to_string())?;
+        verify::verify_finalization_proof(&header, &proof, &self.chain_id)
+            .map_err(|e| e.to_string())?;
         self.repository_roots.push(header.repository_merkle_root);
         self.commit_roots.push(header.commit_merkle_root);
         self.last_header = header;
@@ -53,13 +61,53 @@ impl LightClient {
             .is_ok()
     }
 
-    /// Verifies the state entry with its proof.
+    /// Verifies several transactions of the same block at once with a multiproof.
+    ///
+    /// `transactions` must be in the order of their leaf indices in the proof.
+    pub fn verify_transaction_commitments(
+        &self,
+        transactions: &[Transaction],
+        block_height: u64,
+        proof: MerkleMultiProof,
+    ) -> bool {
+        let messages = transactions
+            .iter()
+            .map(|transaction| serde_spb::to_vec(transaction).unwrap())
+            .collect::<Vec<_>>();
+        if block_height < self.height_offset
+            || block_height >= self.height_offset + self.commit_roots.len() as u64
+        {
+            return false;
+        }
+        proof
+            .verify(
+                self.commit_roots[(block_height - self.height_offset) as usize],
+                &messages,
+            )
+            .is_ok()
+    }
+
+    /// Verifies the content of the given path in the repository at the given height,
+    /// or the absence of the path if `content` is `None`.
+    ///
+    /// Note that the repository state of a block is the one right before the block commit.
     pub fn verify_state_commitment(
         &self,
-        _message: Vec<u8>,
-        _block_height: u64,
-        _proof: MerkleProof,
+        path: &str,
+        content: Option<&[u8]>,
+        block_height: u64,
+        proof: StateProof,
     ) -> bool {
-        todo!()
+        if block_height < self.height_offset
+            || block_height >= self.height_offset + self.repository_roots.len() as u64
+        {
+            return false;
+        }
+        let root = self.repository_roots[(block_height - self.height_offset) as usize];
+        match content {
+            Some(content) => proof.verify_inclusion(root, path, content),
+            None => proof.verify_non_inclusion(root, path),
+        }
+        .is_ok()
     }
 }
@@ -2,125 +2,165 @@ use crate::*;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+/// The domain separation prefix of the leaf nodes.
+const LEAF_PREFIX: u8 = 0;
+/// The domain separation prefix of the internal nodes.
+const NODE_PREFIX: u8 = 1;
+
 /// A Merkle tree that is created once but never modified.
 ///
 /// This is useful for per-block data such as transaction lists.
+///
+/// The leaf nodes and the internal nodes are hashed with different prefixes,
+/// so that an internal node can't be presented as a leaf.
 pub struct OneshotMerkleTree {
-    hash_list: Vec<Hash256>,
+    /// The levels of the tree, from the leaf nodes to the root.
+    ///
+    /// Given a hash list [1, 2, 3], the tree is built as below,
+    ///
+    /// ``` text
+    ///     6
+    ///   4   5
+    ///  1 2  3
+    /// ```
+    ///
+    /// which is represented as [[1, 2, 3], [4, 5], [6]] (with 1, 2, 3 hashed as leaves).
+    levels: Vec<Vec<Hash256>>,
 }
 
 impl OneshotMerkleTree {
     pub const EMPTY_HASH: Hash256 = Hash256::zero();
 
     /// Creates a new OneshotMerkleTree from the given data.
+    ///
+    /// The data may contain duplicates; each of them is a distinct leaf identified by its index.
     pub fn create(data: Vec<Hash256>) -> Self {
-        OneshotMerkleTree { hash_list: data }
+        let mut levels = vec![data.iter().map(hash_leaf).collect::<Vec<_>>()];
+        while levels.last().expect("never empty").len() > 1 {
+            let upper_level = levels
+                .last()
+                .expect("never empty")
+                .chunks(2)
+                .map(|pair| hash_node(&pair[0], pair.get(1)))
+                .collect();
+            levels.push(upper_level);
+        }
+        OneshotMerkleTree { levels }
+    }
+
+    /// Returns the number of the leaves.
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
     /// Creates a Merkle proof for a given data in the tree.
     ///
     /// Returns `None` if the data is not in the tree.
+    /// If there are duplicates, the proof is for the first one.
+    pub fn create_merkle_proof(&self, key: Hash256) -> Option<MerkleProof> {
+        let leaf = hash_leaf(&key);
+        let index = self.levels[0].iter().position(|x| x == &leaf)?;
+        self.create_merkle_proof_at(index)
+    }
+
+    /// Creates a Merkle proof for the leaf at the given index.
+    ///
+    /// Returns `None` if the index is out of range.
     ///
     /// Given a tree [[1, 2, 3], [4, 5], [6]],
     /// Merkle proof for 2 is [1, 5] and Merkle proof for 3 is [OnlyChild, 4].
     ///
     /// For `LeftChild` and `RightChild`, pair hash of the sibling node is given.
     /// For `OnlyChild`, only the instruction is given.
-    pub fn create_merkle_proof(&self, key: Hash256) -> Option<MerkleProof> {
-        if !self.hash_list.contains(&key) {
+    pub fn create_merkle_proof_at(&self, index: usize) -> Option<MerkleProof> {
+        if index >= self.len() {
             return None;
         }
-        let mut merkle_proof: MerkleProof = MerkleProof { proof: Vec::new() };
-        let mut merkle_tree: Vec<Vec<Hash256>> = Self::merkle_tree(&self.hash_list);
-        let mut target_hash: Hash256 = key;
-        // Pop because the root is never included in the Merkle proof
-        merkle_tree.pop();
-        for level in merkle_tree {
-            for pair in level.chunks(2) {
-                if pair.contains(&target_hash) {
-                    if pair.len() == 2 {
-                        let is_right_node: bool = pair[0] == target_hash;
-                        if is_right_node {
-                            merkle_proof
-                                .proof
-                                .push(MerkleProofEntry::RightChild(pair[is_right_node as usize]))
-                        } else {
-                            merkle_proof
-                                .proof
-                                .push(MerkleProofEntry::LeftChild(pair[is_right_node as usize]))
-                        }
-                        target_hash = Hash256::aggregate(&pair[0], &pair[1]);
-                    } else {
-                        merkle_proof.proof.push(MerkleProofEntry::OnlyChild);
-                        target_hash = Hash256::hash(pair[0]);
-                    };
-                }
-            }
+        let mut proof = Vec::new();
+        let mut index = index;
+        // The root is never included in the Merkle proof
+        for level in &self.levels[..self.levels.len() - 1] {
+            proof.push(if index % 2 == 1 {
+                MerkleProofEntry::LeftChild(level[index - 1])
+            } else if let Some(sibling) = level.get(index + 1) {
+                MerkleProofEntry::RightChild(*sibling)
+            } else {
+                MerkleProofEntry::OnlyChild
+            });
+            index /= 2;
         }
-        Some(merkle_proof)
+        Some(MerkleProof { proof })
     }
 
-    /// Creates a merkle tree from the given hash list.
-    ///
-    /// Merkle tree is returned in the form of Vec of Vec of Hash256.
+    /// Creates a single proof for the leaves at the given indices.
     ///
-    /// Given a hash list [1, 2, 3], merkle tree will be built as below,
+    /// The sibling nodes shared by the leaves are included only once,
+    /// and the nodes that can be calculated from the leaves are omitted.
     ///
-    /// ``` text
-    ///     6
-    ///   4   5
-    ///  1 2  3
-    /// ```
-    ///
-    /// which is represented as [[1, 2, 3], [4, 5], [6]].
-    ///
-    /// For nodes with siblings, their concatenated hash value is hashed up.
-    /// For nodes without siblings, its hash value is hashed up.
-    fn merkle_tree(hash_list: &[Hash256]) -> Vec<Vec<Hash256>> {
-        let mut merkle_tree: Vec<Vec<Hash256>> = vec![hash_list.to_vec()];
-        while !Self::is_fully_created(&merkle_tree) {
-            let mut upper_level_hash_list: Vec<Hash256> = Vec::new();
-            for pair in merkle_tree.last().unwrap().chunks(2) {
-                if pair.len() == 2 {
-                    upper_level_hash_list.push(Hash256::aggregate(&pair[0], &pair[1]));
-                } else {
-                    upper_level_hash_list.push(Hash256::hash(pair[0]));
+    /// Returns `None` if there is no index or any of them is out of range.
+    pub fn create_multiproof(&self, indices: &[usize]) -> Option<MerkleMultiProof> {
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        if indices.is_empty() || indices.iter().any(|&index| index >= self.len()) {
+            return None;
+        }
+        let proven_indices = indices.iter().map(|&index| index as u64).collect();
+        let mut hashes = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let mut upper_indices = Vec::new();
+            let mut i = 0;
+            while i < indices.len() {
+                let index = indices[i];
+                if index % 2 == 1 {
+                    hashes.push(level[index - 1]);
+                } else if index + 1 < level.len() {
+                    if indices.get(i + 1) == Some(&(index + 1)) {
+                        i += 1;
+                    } else {
+                        hashes.push(level[index + 1]);
+                    }
                 }
+                upper_indices.push(index / 2);
+                i += 1;
             }
-            merkle_tree.push(upper_level_hash_list);
+            indices = upper_indices;
         }
-        merkle_tree
-    }
-
-    /// Checks if a given merkle tree is fully created or is in progress.
-    ///
-    /// Is fully created if root exists and returns `true`.
-    /// Is in progress if root does not exist and returns `false`.
-    ///
-    /// Note that root exists in the last element of a merkle tree with length 1.
-    fn is_fully_created(merkle_tree: &[Vec<Hash256>]) -> bool {
-        merkle_tree.last().unwrap().len() == 1
+        Some(MerkleMultiProof {
+            leaf_count: self.len() as u64,
+            indices: proven_indices,
+            hashes,
+        })
     }
 
     /// Returns the root of the tree.
     ///
     /// If the tree is empty, this returns a `Self::EMPTY_HASH`.
-    ///
-    /// Never panics on unwrap because merkle_tree is initialized with `vec![hash_list.to_vec()]` where `hash_list` is not empty.
     pub fn root(&self) -> Hash256 {
-        if self.hash_list.is_empty() {
+        if self.is_empty() {
             Self::EMPTY_HASH
         } else {
-            Self::merkle_tree(&self.hash_list)
-                .last()
-                .unwrap()
-                .last()
-                .unwrap()
-                .to_owned()
+            self.levels.last().expect("never empty")[0]
         }
     }
 }
 
+fn hash_leaf(data: &Hash256) -> Hash256 {
+    Hash256::hash([&[LEAF_PREFIX], data.as_ref()].concat())
+}
+
+/// Hashes up a pair of nodes, or a node without a sibling.
+fn hash_node(left: &Hash256, right: Option<&Hash256>) -> Hash256 {
+    match right {
+        Some(right) => Hash256::hash([&[NODE_PREFIX], left.as_ref(), right.as_ref()].concat()),
+        None => Hash256::hash([&[NODE_PREFIX], left.as_ref()].concat()),
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct MerkleProof {
     pub proof: Vec<MerkleProofEntry>,
@@ -146,29 +186,267 @@ pub enum MerkleProofError {
 impl MerkleProof {
     /// Verifies whether the given data is in the block.
     pub fn verify(&self, root: Hash256, data: &[u8]) -> Result<(), MerkleProofError> {
-        let mut calculated_root: Hash256 = Hash256::hash(data);
+        let mut calculated_root: Hash256 = hash_leaf(&Hash256::hash(data));
         for node in &self.proof {
             calculated_root = match node {
                 MerkleProofEntry::LeftChild(pair_hash) => {
-                    Hash256::aggregate(pair_hash, &calculated_root)
+                    hash_node(pair_hash, Some(&calculated_root))
                 }
                 MerkleProofEntry::RightChild(pair_hash) => {
-                    Hash256::aggregate(&calculated_root, pair_hash)
+                    hash_node(&calculated_root, Some(pair_hash))
                 }
-                MerkleProofEntry::OnlyChild => Hash256::hash(calculated_root),
+                MerkleProofEntry::OnlyChild => hash_node(&calculated_root, None),
             };
         }
-        if root == calculated_root {
-            Ok(())
-        } else {
-            Err(MerkleProofError::UnmatchedRoot(
-                root.to_string(),
-                calculated_root.to_string(),
-            ))
+        check_root(root, calculated_root)
+    }
+}
+
+/// A Merkle proof for multiple leaves of a tree.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct MerkleMultiProof {
+    /// The number of the leaves in the tree.
+    pub leaf_count: u64,
+    /// The indices of the proven leaves, in ascending order.
+    pub indices: Vec<u64>,
+    /// The sibling nodes that can't be calculated from the proven leaves,
+    /// from the bottom level to the top, and from left to right in each level.
+    pub hashes: Vec<Hash256>,
+}
+
+impl MerkleMultiProof {
+    /// Verifies whether the given data are in the block, at `indices` respectively.
+    pub fn verify(&self, root: Hash256, data: &[impl AsRef<[u8]>]) -> Result<(), MerkleProofError> {
+        let malformed = |msg: &str| MerkleProofError::MalformedProof(msg.to_string());
+        if self.indices.is_empty() || self.indices.len() != data.len() {
+            return Err(malformed("the number of the indices and the data mismatch"));
+        }
+        if self.indices.windows(2).any(|pair| pair[0] >= pair[1])
+            || self.indices.last().expect("not empty") >= &self.leaf_count
+        {
+            return Err(malformed("invalid indices"));
+        }
+        let mut nodes = self
+            .indices
+            .iter()
+            .zip(data)
+            .map(|(index, data)| (*index, hash_leaf(&Hash256::hash(data))))
+            .collect::<Vec<_>>();
+        let mut hashes = self.hashes.iter();
+        let mut next_hash = || hashes.next().ok_or_else(|| malformed("too few hashes"));
+        let mut level_len = self.leaf_count;
+        while level_len > 1 {
+            let mut upper_nodes = Vec::new();
+            let mut i = 0;
+            while i < nodes.len() {
+                let (index, hash) = nodes[i];
+                let parent = if index % 2 == 1 {
+                    hash_node(next_hash()?, Some(&hash))
+                } else if index + 1 == level_len {
+                    hash_node(&hash, None)
+                } else if nodes.get(i + 1).map(|(index, _)| *index) == Some(index + 1) {
+                    i += 1;
+                    hash_node(&hash, Some(&nodes[i].1))
+                } else {
+                    hash_node(&hash, Some(next_hash()?))
+                };
+                upper_nodes.push((index / 2, parent));
+                i += 1;
+            }
+            nodes = upper_nodes;
+            level_len = level_len / 2 + level_len % 2;
         }
+        if next_hash().is_ok() {
+            return Err(malformed("too many hashes"));
+        }
+        check_root(root, nodes[0].1)
+    }
+}
+
+fn check_root(root: Hash256, calculated_root: Hash256) -> Result<(), MerkleProofError> {
+    if root == calculated_root {
+        Ok(())
+    } else {
+        Err(MerkleProofError::UnmatchedRoot(
+            root.to_string(),
+            calculated_root.to_string(),
+        ))
     }
 }
 
+/// A Merkle tree over the files of a repository, which commits to the content of each path.
+///
+/// The leaves are `(path, hash of the content)` pairs sorted by the path,
+/// so that it can prove the absence of a path as well as the presence.
+pub struct StateMerkleTree {
+    entries: Vec<(String, Hash256)>,
+    tree: OneshotMerkleTree,
+}
+
+impl StateMerkleTree {
+    /// Creates a new `StateMerkleTree` from the given paths and their contents.
+    pub fn create(files: Vec<(String, Vec<u8>)>) -> Self {
+        let mut entries = files
+            .into_iter()
+            .map(|(path, content)| (path, Hash256::hash(content)))
+            .collect::<Vec<_>>();
+        entries.sort();
+        entries.dedup_by(|a, b| a.0 == b.0);
+        let tree = OneshotMerkleTree::create(
+            entries
+                .iter()
+                .map(|(path, hash)| Hash256::hash(Self::leaf(path, *hash)))
+                .collect(),
+        );
+        Self { entries, tree }
+    }
+
+    /// Returns the root of the tree, which is `OneshotMerkleTree::EMPTY_HASH` if there is no file.
+    pub fn root(&self) -> Hash256 {
+        self.tree.root()
+    }
+
+    /// Creates an inclusion proof if the path exists, or a non-inclusion proof otherwise.
+    pub fn create_proof(&self, path: &str) -> StateProof {
+        let entry_proof = |index: usize| {
+            let (path, hash) = &self.entries[index];
+            StateProofEntry {
+                path: path.clone(),
+                content_hash: *hash,
+                proof: self
+                    .tree
+                    .create_merkle_proof_at(index)
+                    .expect("the entry is in the tree"),
+            }
+        };
+        match self
+            .entries
+            .binary_search_by(|(entry_path, _)| entry_path.as_str().cmp(path))
+        {
+            Ok(index) => StateProof::Inclusion(entry_proof(index).proof),
+            Err(index) => StateProof::NonInclusion {
+                lower: index.checked_sub(1).map(entry_proof),
+                upper: (index < self.entries.len()).then(|| entry_proof(index)),
+            },
+        }
+    }
+
+    /// Returns the data of the leaf for the given entry.
+    pub fn leaf(path: &str, content_hash: Hash256) -> Vec<u8> {
+        serde_spb::to_vec(&(path, content_hash)).unwrap()
+    }
+}
+
+/// A leaf of `StateMerkleTree` with its Merkle proof.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct StateProofEntry {
+    pub path: String,
+    pub content_hash: Hash256,
+    pub proof: MerkleProof,
+}
+
+/// A proof of the content of a path (or the absence of it) in a `StateMerkleTree`.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub enum StateProof {
+    Inclusion(MerkleProof),
+    /// The two adjacent leaves between which the path would be placed.
+    ///
+    /// `lower` is `None` if the path precedes every leaf,
+    /// and `upper` is `None` if the path follows every leaf.
+    NonInclusion {
+        lower: Option<StateProofEntry>,
+        upper: Option<StateProofEntry>,
+    },
+}
+
+impl StateProof {
+    /// Verifies that the given path had the given content.
+    pub fn verify_inclusion(
+        &self,
+        root: Hash256,
+        path: &str,
+        content: &[u8],
+    ) -> Result<(), MerkleProofError> {
+        match self {
+            StateProof::Inclusion(proof) => {
+                proof.verify(root, &StateMerkleTree::leaf(path, Hash256::hash(content)))
+            }
+            StateProof::NonInclusion { .. } => Err(MerkleProofError::MalformedProof(
+                "expected an inclusion proof".to_string(),
+            )),
+        }
+    }
+
+    /// Verifies that the given path did not exist.
+    pub fn verify_non_inclusion(&self, root: Hash256, path: &str) -> Result<(), MerkleProofError> {
+        let (lower, upper) = match self {
+            StateProof::NonInclusion { lower, upper } => (lower, upper),
+            StateProof::Inclusion(_) => {
+                return Err(MerkleProofError::MalformedProof(
+                    "expected a non-inclusion proof".to_string(),
+                ))
+            }
+        };
+        let verify_entry = |entry: &StateProofEntry| {
+            entry.proof.verify(
+                root,
+                &StateMerkleTree::leaf(&entry.path, entry.content_hash),
+            )
+        };
+        let malformed = |msg: &str| Err(MerkleProofError::MalformedProof(msg.to_string()));
+        match (lower, upper) {
+            (None, None) => {
+                if root != OneshotMerkleTree::EMPTY_HASH {
+                    return malformed("the tree is not empty");
+                }
+            }
+            (Some(lower), None) => {
+                verify_entry(lower)?;
+                if lower.path.as_str() >= path || !position(&lower.proof).2 {
+                    return malformed("the lower entry is not the last one before the path");
+                }
+            }
+            (None, Some(upper)) => {
+                verify_entry(upper)?;
+                if upper.path.as_str() <= path || !position(&upper.proof).1 {
+                    return malformed("the upper entry is not the first one after the path");
+                }
+            }
+            (Some(lower), Some(upper)) => {
+                verify_entry(lower)?;
+                verify_entry(upper)?;
+                if lower.path.as_str() >= path || upper.path.as_str() <= path {
+                    return malformed("the path is not between the two entries");
+                }
+                if lower.proof.proof.len() != upper.proof.proof.len()
+                    || position(&lower.proof).0 + 1 != position(&upper.proof).0
+                {
+                    return malformed("the two entries are not adjacent");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the index of the leaf that the proof is for,
+/// and whether it is the first and the last leaf respectively.
+fn position(proof: &MerkleProof) -> (u64, bool, bool) {
+    let mut index = 0;
+    let (mut is_first, mut is_last) = (true, true);
+    for (level, entry) in proof.proof.iter().enumerate() {
+        match entry {
+            MerkleProofEntry::LeftChild(_) => {
+                index |= 1 << level;
+                is_first = false;
+            }
+            MerkleProofEntry::RightChild(_) => is_last = false,
+            MerkleProofEntry::OnlyChild => (),
+        }
+    }
+    (index, is_first, is_last)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -265,4 +543,137 @@ mod test {
         assert!(root_hash != OneshotMerkleTree::EMPTY_HASH);
         assert!(MerkleProof::verify(&merkle_proof.unwrap(), root_hash, &[10]).is_ok());
     }
+
+    #[test]
+    /// Test if every leaf is provable by its index, even with duplicates.
+    fn proof_by_index_with_duplicates() {
+        for number in 1..18 {
+            let mut hash_list: Vec<Hash256> = create_hash_list(number);
+            hash_list.push(Hash256::hash([0]));
+            let merkle_tree: OneshotMerkleTree = OneshotMerkleTree::create(hash_list.clone());
+            let root_hash: Hash256 = merkle_tree.root();
+            for (index, data) in (0..number).chain([0]).enumerate() {
+                let merkle_proof = merkle_tree.create_merkle_proof_at(index).unwrap();
+                assert!(merkle_proof.verify(root_hash, &[data]).is_ok());
+            }
+            assert!(merkle_tree
+                .create_merkle_proof_at(hash_list.len())
+                .is_none());
+        }
+    }
+
+    #[test]
+    /// Test if an internal node can't be proven as a leaf.
+    fn internal_node_as_leaf() {
+        let merkle_tree: OneshotMerkleTree = OneshotMerkleTree::create(create_hash_list(4));
+        let root_hash: Hash256 = merkle_tree.root();
+        let merkle_proof = merkle_tree.create_merkle_proof_at(0).unwrap();
+        // The parent of the first two leaves, with the proof of it.
+        let internal_node = merkle_tree.levels[1][0];
+        let forged_proof = MerkleProof {
+            proof: merkle_proof.proof[1..].to_vec(),
+        };
+        assert!(forged_proof
+            .verify(root_hash, internal_node.as_ref())
+            .is_err());
+    }
+
+    #[test]
+    /// Test if multiproofs work for various subsets of the leaves.
+    fn multiproof() {
+        for number in 1..18u8 {
+            let merkle_tree: OneshotMerkleTree =
+                OneshotMerkleTree::create(create_hash_list(number));
+            let root_hash: Hash256 = merkle_tree.root();
+            for indices in [
+                vec![0],
+                vec![number as usize - 1],
+                (0..number as usize).collect(),
+                (0..number as usize).step_by(3).collect(),
+                (1..number as usize).step_by(2).collect(),
+            ] {
+                if indices.is_empty() {
+                    continue;
+                }
+                let multiproof = merkle_tree.create_multiproof(&indices).unwrap();
+                let data = indices.iter().map(|&i| [i as u8]).collect::<Vec<_>>();
+                assert!(multiproof.verify(root_hash, &data).is_ok());
+                // It must not be larger than the individual proofs.
+                assert!(
+                    multiproof.hashes.len()
+                        <= indices
+                            .iter()
+                            .map(|&i| merkle_tree.create_merkle_proof_at(i).unwrap().proof.len())
+                            .sum()
+                );
+                let mut wrong_data = data.clone();
+                wrong_data[0] = [42];
+                assert!(multiproof.verify(root_hash, &wrong_data).is_err());
+            }
+        }
+        let merkle_tree: OneshotMerkleTree = OneshotMerkleTree::create(create_hash_list(12));
+        assert!(merkle_tree.create_multiproof(&[]).is_none());
+        assert!(merkle_tree.create_multiproof(&[3, 12]).is_none());
+        let mut multiproof = merkle_tree.create_multiproof(&[3, 4, 9]).unwrap();
+        multiproof.indices = vec![3, 5, 9];
+        assert!(multiproof
+            .verify(merkle_tree.root(), &[[3], [4], [9]])
+            .is_err());
+    }
+
+    fn create_state_tree(number: u8) -> StateMerkleTree {
+        StateMerkleTree::create(
+            (0..number)
+                .map(|n| (format!("dir/{n:03}.json"), vec![n]))
+                .collect(),
+        )
+    }
+
+    #[test]
+    /// Test if inclusion proofs work for every path.
+    fn state_inclusion_proof() {
+        let tree = create_state_tree(11);
+        let root = tree.root();
+        for n in 0..11 {
+            let path = format!("dir/{n:03}.json");
+            let proof = tree.create_proof(&path);
+            assert!(proof.verify_inclusion(root, &path, &[n]).is_ok());
+            assert!(proof.verify_inclusion(root, &path, &[42]).is_err());
+            assert!(proof.verify_non_inclusion(root, &path).is_err());
+        }
+    }
+
+    #[test]
+    /// Test if non-inclusion proofs work before, between and after the leaves.
+    fn state_non_inclusion_proof() {
+        let tree = create_state_tree(11);
+        let root = tree.root();
+        for path in ["a.json", "dir/003.jsonx", "dir/010.json0", "z.json"] {
+            let proof = tree.create_proof(path);
+            assert!(proof.verify_non_inclusion(root, path).is_ok());
+            assert!(proof.verify_inclusion(root, path, &[3]).is_err());
+        }
+        // A proof for a different gap must not be accepted.
+        let proof = tree.create_proof("dir/003.jsonx");
+        assert!(proof.verify_non_inclusion(root, "dir/007.jsonx").is_err());
+        assert!(proof.verify_non_inclusion(root, "dir/005.json").is_err());
+        // Non-adjacent entries must not be accepted.
+        let proof = StateProof::NonInclusion {
+            lower: match tree.create_proof("dir/001.jsonx") {
+                StateProof::NonInclusion { lower, .. } => lower,
+                _ => unreachable!(),
+            },
+            upper: match tree.create_proof("dir/004.jsonx") {
+                StateProof::NonInclusion { upper, .. } => upper,
+                _ => unreachable!(),
+            },
+        };
+        assert!(proof.verify_non_inclusion(root, "dir/003.jsonx").is_err());
+
+        let empty = StateMerkleTree::create(Vec::new());
+        assert!(empty
+            .create_proof("a.json")
+            .verify_non_inclusion(empty.root(), "a.json")
+            .is_ok());
+    }
 }
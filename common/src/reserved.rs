@@ -1,6 +1,6 @@
 use crate::*;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 /// The partial set of the blockchain state which is reserved and protected.
 ///
@@ -18,67 +18,246 @@ pub struct ReservedState {
     pub consensus_leader_order: Vec<MemberName>,
     /// The semantic version of Simperby protocol for this network.
     pub version: String,
+    /// The parameters of the consensus, which must be identical across the validators.
+    #[serde(default)]
+    pub consensus_params: ConsensusParams,
+}
+
+/// The consensus parameters stored on chain, from which the consensus derives its own.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct ConsensusParams {
+    /// The timeout of the round 0, for both the propose and the precommit steps.
+    pub timeout_ms: u64,
+    pub repeat_round_for_first_leader: usize,
+    /// How the timeouts grow as the rounds go on.
+    #[serde(default)]
+    pub timeout_growth: TimeoutGrowth,
+    /// If enabled, the rounds are aligned to the wall clock,
+    /// starting from the timestamp of the last block.
+    #[serde(default)]
+    pub wall_clock_rounds: bool,
+    /// Which validator leads each round.
+    #[serde(default)]
+    pub leader_schedule: LeaderSchedule,
+}
+
+impl Default for ConsensusParams {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 10_000,
+            repeat_round_for_first_leader: 1,
+            timeout_growth: TimeoutGrowth::Constant,
+            wall_clock_rounds: false,
+            leader_schedule: LeaderSchedule::LeaderOrder,
+        }
+    }
+}
+
+impl ConsensusParams {
+    /// Checks that the parameters make sense, which every validator must agree on beforehand.
+    ///
+    /// The validator indices of `LeaderSchedule::Explicit` are not checked,
+    /// because the number of the validators may vary over the heights;
+    /// those out of range are skipped by the consensus.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.timeout_ms == 0 {
+            return Err("the timeout must be positive".to_owned());
+        }
+        match self.timeout_growth {
+            TimeoutGrowth::Constant => (),
+            TimeoutGrowth::Linear { max_timeout_ms, .. } if max_timeout_ms < self.timeout_ms => {
+                return Err(format!(
+                    "the maximum timeout {max_timeout_ms} is less than the timeout {}",
+                    self.timeout_ms
+                ));
+            }
+            TimeoutGrowth::Exponential {
+                factor_percent,
+                max_timeout_ms,
+            } => {
+                if factor_percent < 100 {
+                    return Err(format!(
+                        "the timeout must not shrink, but the factor is {factor_percent}%"
+                    ));
+                }
+                if max_timeout_ms < self.timeout_ms {
+                    return Err(format!(
+                        "the maximum timeout {max_timeout_ms} is less than the timeout {}",
+                        self.timeout_ms
+                    ));
+                }
+            }
+            TimeoutGrowth::Linear { .. } => (),
+        }
+        if let LeaderSchedule::Explicit(schedule) = &self.leader_schedule {
+            if schedule.iter().all(|(_, repeat)| *repeat == 0) {
+                return Err("the leader schedule names no leader".to_owned());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The growth of the consensus timeouts over the rounds.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+pub enum TimeoutGrowth {
+    /// Every round has the same timeout.
+    #[default]
+    Constant,
+    /// The timeout increases by `increment_ms` every round, up to `max_timeout_ms`.
+    Linear {
+        increment_ms: u64,
+        max_timeout_ms: u64,
+    },
+    /// The timeout is multiplied by `factor_percent / 100` every round, up to `max_timeout_ms`.
+    Exponential {
+        factor_percent: u64,
+        max_timeout_ms: u64,
+    },
+}
+
+/// The rule deciding the leader of each consensus round.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+pub enum LeaderSchedule {
+    /// The leaders follow `ReservedState::consensus_leader_order`,
+    /// the first one leading for `repeat_round_for_first_leader` rounds.
+    #[default]
+    LeaderOrder,
+    /// The validator of the index `0` leads the first `repeat_round_for_first_leader` rounds,
+    /// then all the validators take turns in the index order, one round each.
+    FirstLeaderRepeated,
+    /// The listed validators (by their indices in the validator set) take turns,
+    /// each leading for the given number of consecutive rounds, and the list repeats itself.
+    Explicit(Vec<(usize, usize)>),
+    /// Each validator leads in proportion to its voting power.
+    WeightedRoundRobin,
+}
+
+/// The voting power of a member after resolving the delegation chains.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct EffectivePower {
+    pub name: MemberName,
+    /// The member who finally exercises the consensus voting power of this member.
+    ///
+    /// It is this member itself if it has not delegated.
+    pub consensus_representative: MemberName,
+    /// The member who finally exercises the governance voting power of this member.
+    pub governance_representative: MemberName,
+    /// The consensus voting power exercised by this member,
+    /// which is the sum of its own and the delegated ones (0 if it has delegated).
+    pub consensus_voting_power: VotingPower,
+    /// The governance voting power exercised by this member (0 if it has delegated).
+    pub governance_voting_power: VotingPower,
 }
 
 impl ReservedState {
+    /// Returns the effective validator set, with the delegations applied.
+    ///
+    /// It is sorted by the name of the validators, which is the consensus leader order.
+    /// Expired delegations are already revoked at the last block boundary
+    /// (see `expire_delegations()`), so it is determined by the state alone.
     pub fn get_validator_set(&self) -> Result<Vec<(PublicKey, VotingPower)>, String> {
-        let validator_set = self
+        let powers = self.get_effective_powers()?;
+        self.collect_representatives(
+            powers
+                .iter()
+                .filter(|power| power.consensus_representative == power.name)
+                .map(|power| (&power.name, power.consensus_voting_power)),
+        )
+    }
+
+    /// Returns the effective governance set, with the delegations applied.
+    pub fn get_governance_set(&self) -> Result<Vec<(PublicKey, VotingPower)>, String> {
+        let powers = self.get_effective_powers()?;
+        self.collect_representatives(
+            powers
+                .iter()
+                .filter(|power| power.governance_representative == power.name)
+                .map(|power| (&power.name, power.governance_voting_power)),
+        )
+    }
+
+    /// Resolves the delegation chains of all the non-expelled members,
+    /// reporting the effective voting power of each of them in the member order.
+    pub fn get_effective_powers(&self) -> Result<Vec<EffectivePower>, String> {
+        let mut powers = self
             .members
             .iter()
+            .filter(|member| !member.expelled)
             .map(|member| {
-                let name = member
-                    .consensus_delegatee
-                    .as_ref()
-                    .map_or_else(|| member.name.as_str(), |name| name.as_str());
-                let public_key = self.query_public_key(&name.to_string()).ok_or_else(|| {
-                    format!("the public key of {name} is not found in the reserved state.")
-                })?;
-                Ok((public_key, member.consensus_voting_power))
+                Ok(EffectivePower {
+                    name: member.name.clone(),
+                    consensus_representative: self.resolve_representative(&member.name, false)?,
+                    governance_representative: self.resolve_representative(&member.name, true)?,
+                    consensus_voting_power: 0,
+                    governance_voting_power: 0,
+                })
             })
-            .try_fold(
-                BTreeMap::new(),
-                |mut map, result: Result<(crypto::PublicKey, u64), String>| {
-                    let (public_key, voting_power) = result?;
-                    map.entry(public_key)
-                        .and_modify(|v| *v += voting_power)
-                        .or_insert(voting_power);
-                    Ok::<BTreeMap<crypto::PublicKey, u64>, String>(map)
-                },
-            )?
-            .into_iter()
-            .collect::<Vec<_>>();
+            .collect::<Result<Vec<_>, String>>()?;
+        let indices = powers
+            .iter()
+            .enumerate()
+            .map(|(i, power)| (power.name.clone(), i))
+            .collect::<BTreeMap<_, _>>();
+        for member in self.members.iter().filter(|member| !member.expelled) {
+            let power = &powers[indices[&member.name]];
+            let (consensus, governance) = (
+                indices[&power.consensus_representative],
+                indices[&power.governance_representative],
+            );
+            powers[consensus].consensus_voting_power += member.consensus_voting_power;
+            powers[governance].governance_voting_power += member.governance_voting_power;
+        }
+        Ok(powers)
+    }
 
-        Ok(validator_set)
+    /// Follows the delegation chain from the given member to the one who finally exercises its voting power.
+    fn resolve_representative(
+        &self,
+        name: &MemberName,
+        governance: bool,
+    ) -> Result<MemberName, String> {
+        let mut visited = BTreeSet::new();
+        let mut current = name;
+        loop {
+            if !visited.insert(current) {
+                return Err(format!("the delegation of {name} forms a cycle"));
+            }
+            let member = self
+                .members
+                .iter()
+                .find(|member| &member.name == current)
+                .ok_or_else(|| format!("the delegatee {current} is not a member"))?;
+            if member.expelled && current != name {
+                return Err(format!("the delegatee {current} is expelled"));
+            }
+            let delegatee = if governance {
+                &member.governance_delegatee
+            } else {
+                &member.consensus_delegatee
+            };
+            match delegatee {
+                Some(delegatee) => current = delegatee,
+                None => return Ok(current.clone()),
+            }
+        }
     }
 
-    pub fn get_governance_set(&self) -> Result<Vec<(PublicKey, VotingPower)>, String> {
-        let governance_set = self
-            .members
-            .iter()
-            .map(|member| {
-                let name = member
-                    .governance_delegatee
-                    .as_ref()
-                    .map_or_else(|| member.name.as_str(), |name| name.as_str());
-                let public_key = self.query_public_key(&name.to_string()).ok_or_else(|| {
+    fn collect_representatives<'a>(
+        &self,
+        representatives: impl Iterator<Item = (&'a MemberName, VotingPower)>,
+    ) -> Result<Vec<(PublicKey, VotingPower)>, String> {
+        let mut representatives = representatives.collect::<Vec<_>>();
+        representatives.sort();
+        representatives
+            .into_iter()
+            .map(|(name, voting_power)| {
+                let public_key = self.query_public_key(name).ok_or_else(|| {
                     format!("the public key of {name} is not found in the reserved state.")
                 })?;
-                Ok((public_key, member.consensus_voting_power))
+                Ok((public_key, voting_power))
             })
-            .try_fold(
-                BTreeMap::new(),
-                |mut map, result: Result<(crypto::PublicKey, u64), String>| {
-                    let (public_key, voting_power) = result?;
-                    map.entry(public_key)
-                        .and_modify(|v| *v += voting_power)
-                        .or_insert(voting_power);
-                    Ok::<BTreeMap<crypto::PublicKey, u64>, String>(map)
-                },
-            )?
-            .into_iter()
-            .collect::<Vec<_>>();
-
-        Ok(governance_set)
+            .collect()
     }
 
     pub fn apply_delegate(&mut self, tx: &TxDelegate) -> Result<Self, String> {
@@ -88,9 +267,39 @@ impl ReservedState {
                 tx.data.delegator
             ));
         }
-        if tx.proof.verify(&tx.data).is_err() {
+        if tx
+            .proof
+            .verify(&tx.data, &self.genesis_info.chain_id())
+            .is_err()
+        {
             return Err("delegation proof verification failed".to_string());
         }
+        for name in [&tx.data.delegator, &tx.data.delegatee] {
+            match self.members.iter().find(|member| &member.name == name) {
+                None => return Err(format!("{name} is not a member")),
+                Some(member) if member.expelled => return Err(format!("{name} is expelled")),
+                Some(_) => (),
+            }
+        }
+        if tx
+            .data
+            .conditions
+            .contains(&DelegationCondition::UnlockIfDelegateeIsNotActive(0))
+        {
+            return Err("the delegatee must be inactive for at least one block".to_string());
+        }
+        let previous = self.clone();
+        let delegation_conditions = if tx.data.conditions.is_empty() {
+            None
+        } else {
+            Some(DelegationConditions {
+                conditions: tx.data.conditions.clone(),
+                block_height: tx.data.block_height,
+                timestamp: tx.data.timestamp,
+                validator_candidates_hash: self.validator_candidates_hash(),
+                inactive_blocks: 0,
+            })
+        };
         for delegator in &mut self.members {
             if delegator.name == tx.data.delegator {
                 if tx.data.governance {
@@ -99,14 +308,27 @@ impl ReservedState {
                 } else {
                     delegator.consensus_delegatee = Some(tx.data.delegatee.clone());
                 }
+                delegator.delegation_conditions = delegation_conditions;
                 break;
             }
         }
+        // Reject the delegation if it closes a cycle; `self` is left as it was.
+        if let Err(e) = self.get_effective_powers() {
+            *self = previous;
+            return Err(e);
+        }
+        // A delegator no longer leads the consensus rounds.
+        self.consensus_leader_order
+            .retain(|name| name != &tx.data.delegator);
         Ok(self.clone())
     }
 
     pub fn apply_undelegate(&mut self, tx: &TxUndelegate) -> Result<Self, String> {
-        if tx.proof.verify(&tx.data).is_err() {
+        if tx
+            .proof
+            .verify(&tx.data, &self.genesis_info.chain_id())
+            .is_err()
+        {
             return Err("delegation proof verification failed".to_string());
         }
         for delegator in &mut self.members {
@@ -114,15 +336,175 @@ impl ReservedState {
                 if delegator.consensus_delegatee.is_some() {
                     delegator.consensus_delegatee = None;
                     delegator.governance_delegatee = None;
+                    delegator.delegation_conditions = None;
                     break;
                 } else {
                     return Err("consensus delegatee is not set".to_string());
                 }
             }
         }
+        self.restore_leader(&tx.data.delegator);
+        Ok(self.clone())
+    }
+
+    /// Revokes the delegations whose conditions are met as of the given block.
+    ///
+    /// It must be applied at every block boundary, so that every node
+    /// expires the delegations at the same block.
+    pub fn expire_delegations(&mut self, block_header: &BlockHeader) {
+        let validator_candidates_hash = self.validator_candidates_hash();
+        let active_validators = block_header
+            .prev_block_finalization_proof
+            .iter()
+            .map(|signature| signature.signer().clone())
+            .collect::<BTreeSet<_>>();
+        // The one who actually signs is the end of the delegation chain.
+        let inactive_delegatees = self
+            .members
+            .iter()
+            .map(|member| match member.consensus_delegatee.as_ref() {
+                Some(delegatee) => match self
+                    .resolve_representative(delegatee, false)
+                    .ok()
+                    .and_then(|representative| self.query_public_key(&representative))
+                {
+                    Some(public_key) => !active_validators.contains(&public_key),
+                    None => true,
+                },
+                None => false,
+            })
+            .collect::<Vec<_>>();
+        let mut expired = Vec::new();
+        for (member, inactive_delegatee) in self.members.iter_mut().zip(inactive_delegatees) {
+            let (Some(_), Some(delegation)) = (
+                member.consensus_delegatee.as_ref(),
+                member.delegation_conditions.as_mut(),
+            ) else {
+                continue;
+            };
+            if delegation.conditions.iter().any(|condition| {
+                matches!(
+                    condition,
+                    DelegationCondition::UnlockIfDelegateeIsNotActive(_)
+                )
+            }) {
+                delegation.inactive_blocks = if inactive_delegatee {
+                    delegation.inactive_blocks + 1
+                } else {
+                    0
+                };
+            }
+            let is_met = |condition: &DelegationCondition| match condition {
+                DelegationCondition::UnlockAfterBlocks(blocks) => {
+                    block_header.height >= delegation.block_height.saturating_add(*blocks)
+                }
+                DelegationCondition::UnlockAfterSeconds(seconds) => {
+                    block_header.timestamp
+                        >= delegation
+                            .timestamp
+                            .saturating_add((*seconds as Timestamp).saturating_mul(1000))
+                }
+                DelegationCondition::UnlockIfDelegateeIsNotActive(blocks) => {
+                    delegation.inactive_blocks >= *blocks
+                }
+                DelegationCondition::UnlockIfValidatorSetChanges => {
+                    delegation.validator_candidates_hash != validator_candidates_hash
+                }
+            };
+            if delegation.conditions.iter().any(is_met) {
+                expired.push(member.name.clone());
+            }
+        }
+        for name in expired {
+            for member in &mut self.members {
+                if member.name == name {
+                    member.governance_delegatee = None;
+                    member.consensus_delegatee = None;
+                    member.delegation_conditions = None;
+                }
+            }
+            self.restore_leader(&name);
+        }
+    }
+
+    /// Puts the given member back to the consensus leader order, if eligible.
+    fn restore_leader(&mut self, name: &MemberName) {
+        if self
+            .members
+            .iter()
+            .any(|member| &member.name == name && !member.expelled)
+            && !self.consensus_leader_order.contains(name)
+        {
+            self.consensus_leader_order.push(name.clone());
+            self.consensus_leader_order.sort();
+        }
+    }
+
+    /// Returns the hash of the validator candidates, which are the non-expelled members.
+    fn validator_candidates_hash(&self) -> Hash256 {
+        let public_keys = self
+            .members
+            .iter()
+            .filter(|member| !member.expelled)
+            .map(|member| &member.public_key)
+            .collect::<Vec<_>>();
+        Hash256::hash(serde_spb::to_vec(&public_keys).unwrap())
+    }
+
+    /// Expels the validator who signed the conflicting votes of the given report.
+    ///
+    /// Note that the evidence itself must be verified beforehand
+    /// with `verify::verify_report()`, against the validator set of the reported height.
+    pub fn apply_report(&mut self, tx: &TxReport) -> Result<Self, String> {
+        let offender = tx.first_vote.signature.signer();
+        let offender_name = self
+            .query_name(offender)
+            .ok_or_else(|| format!("the reported validator {offender} is not a member"))?;
+        let mut restored = Vec::new();
+        for member in &mut self.members {
+            if member.name == offender_name {
+                if member.expelled {
+                    return Err(format!("{offender_name} is already expelled"));
+                }
+                member.expelled = true;
+                member.governance_voting_power = 0;
+                member.consensus_voting_power = 0;
+                member.governance_delegatee = None;
+                member.consensus_delegatee = None;
+                member.delegation_conditions = None;
+            } else {
+                // The delegators of the offender get their voting power back.
+                if member.governance_delegatee.as_ref() == Some(&offender_name) {
+                    member.governance_delegatee = None;
+                }
+                if member.consensus_delegatee.as_ref() == Some(&offender_name) {
+                    member.consensus_delegatee = None;
+                    member.delegation_conditions = None;
+                    restored.push(member.name.clone());
+                }
+            }
+        }
+        self.consensus_leader_order
+            .retain(|name| name != &offender_name);
+        for name in restored {
+            self.restore_leader(&name);
+        }
         Ok(self.clone())
     }
 
+    /// Checks that the public key of every member is of its declared type (`Member::key_type`).
+    pub fn verify_key_types(&self) -> Result<(), String> {
+        for member in &self.members {
+            if member.public_key.scheme() != member.key_type {
+                return Err(format!(
+                    "the public key of {} is not of the declared type {:?}",
+                    member.name, member.key_type
+                ));
+            }
+        }
+        Ok(())
+    }
+
     pub fn query_name(&self, public_key: &PublicKey) -> Option<MemberName> {
         for member in &self.members {
             if &member.public_key == public_key {
@@ -152,11 +534,14 @@ mod tests {
     fn create_member(keys: Vec<(PublicKey, PrivateKey)>, member_num: u8) -> Member {
         Member {
             public_key: keys[member_num as usize].0.clone(),
+            key_type: keys[member_num as usize].0.scheme(),
             name: format!("member-{member_num:04}"),
             governance_voting_power: 1,
             consensus_voting_power: 1,
             governance_delegatee: None,
             consensus_delegatee: None,
+            expelled: false,
+            delegation_conditions: None,
         }
     }
 
@@ -167,11 +552,14 @@ mod tests {
     ) -> Member {
         Member {
             public_key: keys[member_num as usize].0.clone(),
+            key_type: keys[member_num as usize].0.scheme(),
             name: format!("member-{member_num:04}"),
             governance_voting_power: 1,
             consensus_voting_power: 1,
             governance_delegatee: None,
             consensus_delegatee: Some(format!("member-{delegatee_member_num:04}")),
+            expelled: false,
+            delegation_conditions: None,
         }
     }
 
@@ -182,11 +570,14 @@ mod tests {
     ) -> Member {
         Member {
             public_key: keys[member_num as usize].0.clone(),
+            key_type: keys[member_num as usize].0.scheme(),
             name: format!("member-{member_num:04}"),
             governance_voting_power: 1,
             consensus_voting_power: 1,
             governance_delegatee: Some(format!("member-{delegatee_member_num:04}")),
             consensus_delegatee: None,
+            expelled: false,
+            delegation_conditions: None,
         }
     }
 
@@ -221,7 +612,10 @@ mod tests {
             header: genesis_header.clone(),
             genesis_proof: keys
                 .iter()
-                .map(|(_, private_key)| TypedSignature::sign(&genesis_header, private_key).unwrap())
+                .map(|(_, private_key)| {
+                    TypedSignature::sign(&genesis_header, &genesis_header.to_hash256(), private_key)
+                        .unwrap()
+                })
                 .collect::<Vec<_>>(),
             chain_name: "test-chain".to_string(),
         };
@@ -230,6 +624,7 @@ mod tests {
             members,
             consensus_leader_order: vec!["member-0003".to_string()],
             version: "0.1.0".to_string(),
+            consensus_params: ConsensusParams::default(),
         };
         assert_eq!(
             reserved_state.get_validator_set().unwrap(),
@@ -237,7 +632,6 @@ mod tests {
         );
     }
 
-    #[ignore]
     #[test]
     fn basic_validator_set2() {
         setup_test();
@@ -269,7 +663,10 @@ mod tests {
             header: genesis_header.clone(),
             genesis_proof: keys
                 .iter()
-                .map(|(_, private_key)| TypedSignature::sign(&genesis_header, private_key).unwrap())
+                .map(|(_, private_key)| {
+                    TypedSignature::sign(&genesis_header, &genesis_header.to_hash256(), private_key)
+                        .unwrap()
+                })
                 .collect::<Vec<_>>(),
             chain_name: "test-chain".to_string(),
         };
@@ -278,6 +675,7 @@ mod tests {
             members,
             consensus_leader_order: vec!["member-0001".to_string(), "member-0003".to_string()],
             version: "0.1.0".to_string(),
+            consensus_params: ConsensusParams::default(),
         };
         assert_eq!(
             reserved_state.get_validator_set().unwrap(),
@@ -316,7 +714,10 @@ mod tests {
             header: genesis_header.clone(),
             genesis_proof: keys
                 .iter()
-                .map(|(_, private_key)| TypedSignature::sign(&genesis_header, private_key).unwrap())
+                .map(|(_, private_key)| {
+                    TypedSignature::sign(&genesis_header, &genesis_header.to_hash256(), private_key)
+                        .unwrap()
+                })
                 .collect::<Vec<_>>(),
             chain_name: "test-chain".to_string(),
         };
@@ -328,6 +729,7 @@ mod tests {
                 .map(|i| format!("member-{i:04}"))
                 .collect::<Vec<_>>(),
             version: "0.1.0".to_string(),
+            consensus_params: ConsensusParams::default(),
         };
         assert_eq!(
             reserved_state.get_governance_set().unwrap(),
@@ -366,7 +768,10 @@ mod tests {
             header: genesis_header.clone(),
             genesis_proof: keys
                 .iter()
-                .map(|(_, private_key)| TypedSignature::sign(&genesis_header, private_key).unwrap())
+                .map(|(_, private_key)| {
+                    TypedSignature::sign(&genesis_header, &genesis_header.to_hash256(), private_key)
+                        .unwrap()
+                })
                 .collect::<Vec<_>>(),
             chain_name: "test-chain".to_string(),
         };
@@ -378,6 +783,7 @@ mod tests {
                 .map(|i| format!("member-{i:04}"))
                 .collect::<Vec<_>>(),
             version: "0.1.0".to_string(),
+            consensus_params: ConsensusParams::default(),
         };
         assert_eq!(
             reserved_state
@@ -391,6 +797,82 @@ mod tests {
         );
     }
 
+    fn sign_delegation(
+        state: &ReservedState,
+        keys: &[(PublicKey, PrivateKey)],
+        delegator: usize,
+        delegatee: &str,
+    ) -> TxDelegate {
+        let data = DelegationTransactionData {
+            delegator: state.members[delegator].name.clone(),
+            delegatee: delegatee.to_string(),
+            governance: true,
+            block_height: 0,
+            timestamp: 0,
+            chain_name: state.genesis_info.chain_name.clone(),
+            conditions: vec![],
+        };
+        let proof = TypedSignature::sign(&data, &state.genesis_info.chain_id(), &keys[delegator].1)
+            .unwrap();
+        TxDelegate { data, proof }
+    }
+
+    #[test]
+    fn transitive_delegation() {
+        setup_test();
+        let (mut state, keys) = generate_standard_genesis(4);
+        state.members[1].governance_voting_power = 10;
+        // member-0000 -> member-0001 -> member-0002
+        state
+            .apply_delegate(&sign_delegation(&state, &keys, 1, "member-0002"))
+            .unwrap();
+        state
+            .apply_delegate(&sign_delegation(&state, &keys, 0, "member-0001"))
+            .unwrap();
+
+        assert_eq!(
+            state.get_validator_set().unwrap(),
+            vec![(keys[2].0.clone(), 3), (keys[3].0.clone(), 1)]
+        );
+        assert_eq!(
+            state.get_governance_set().unwrap(),
+            vec![(keys[2].0.clone(), 12), (keys[3].0.clone(), 1)]
+        );
+        let powers = state.get_effective_powers().unwrap();
+        assert_eq!(powers[0].consensus_representative, "member-0002");
+        assert_eq!(powers[0].consensus_voting_power, 0);
+        assert_eq!(powers[1].governance_representative, "member-0002");
+        assert_eq!(powers[2].consensus_voting_power, 3);
+        assert_eq!(powers[2].governance_voting_power, 12);
+    }
+
+    #[test]
+    fn invalid_delegation_with_cycle() {
+        setup_test();
+        let (mut state, keys) = generate_standard_genesis(4);
+        state
+            .apply_delegate(&sign_delegation(&state, &keys, 0, "member-0001"))
+            .unwrap();
+        state
+            .apply_delegate(&sign_delegation(&state, &keys, 1, "member-0002"))
+            .unwrap();
+        let before = state.clone();
+        // member-0002 -> member-0000 closes the cycle.
+        assert!(state
+            .apply_delegate(&sign_delegation(&state, &keys, 2, "member-0000"))
+            .is_err());
+        assert_eq!(state, before);
+    }
+
+    #[test]
+    fn invalid_delegation_to_nonexistent_member() {
+        setup_test();
+        let (mut state, keys) = generate_standard_genesis(4);
+        assert!(state
+            .apply_delegate(&sign_delegation(&state, &keys, 0, "member-0009"))
+            .is_err());
+    }
+
     #[test]
     fn test_apply_delegate_on_governance_and_consensus_success() {
         // given
@@ -410,8 +892,14 @@ mod tests {
             block_height: 0,
             timestamp: 0,
             chain_name: reserved_state.genesis_info.chain_name.clone(),
+            conditions: vec![],
         };
-        let proof = TypedSignature::sign(&data, &delegator_private_key).unwrap();
+        let proof = TypedSignature::sign(
+            &data,
+            &reserved_state.genesis_info.chain_id(),
+            &delegator_private_key,
+        )
+        .unwrap();
 
         let tx = TxDelegate { data, proof };
         let new_state = reserved_state.apply_delegate(&tx);
@@ -472,8 +960,14 @@ mod tests {
             block_height: 0,
             timestamp: 0,
             chain_name: state.genesis_info.chain_name.clone(),
+            conditions: vec![],
         };
-        let proof = TypedSignature::sign(&data, &delegator_private_key).unwrap();
+        let proof = TypedSignature::sign(
+            &data,
+            &state.genesis_info.chain_id(),
+            &delegator_private_key,
+        )
+        .unwrap();
 
         let tx = TxDelegate { data, proof };
 
@@ -528,8 +1022,14 @@ mod tests {
             block_height: 0,
             timestamp: 0,
             chain_name: state.genesis_info.chain_name.clone(),
+            conditions: vec![],
         };
-        let proof = TypedSignature::sign(&data, &delegator_private_key).unwrap();
+        let proof = TypedSignature::sign(
+            &data,
+            &state.genesis_info.chain_id(),
+            &delegator_private_key,
+        )
+        .unwrap();
 
         let tx = TxDelegate { data, proof };
 
@@ -559,7 +1059,12 @@ mod tests {
             chain_name: reserved_state.genesis_info.chain_name.clone(),
         };
 
-        let proof = TypedSignature::sign(&data, &delegator_private_key).unwrap();
+        let proof = TypedSignature::sign(
+            &data,
+            &reserved_state.genesis_info.chain_id(),
+            &delegator_private_key,
+        )
+        .unwrap();
 
         let tx = TxUndelegate { data, proof };
         let undelegated_state = reserved_state.apply_undelegate(&tx);
@@ -613,7 +1118,12 @@ mod tests {
             chain_name: reserved_state.genesis_info.chain_name.clone(),
         };
 
-        let proof = TypedSignature::sign(&data, &delegator_private_key).unwrap();
+        let proof = TypedSignature::sign(
+            &data,
+            &reserved_state.genesis_info.chain_id(),
+            &delegator_private_key,
+        )
+        .unwrap();
 
         let tx = TxUndelegate { data, proof };
         let undelegated_state = reserved_state.apply_undelegate(&tx);
@@ -647,4 +1157,205 @@ mod tests {
             1
         );
     }
+
+    fn delegate_with_conditions(
+        state: &mut ReservedState,
+        keys: &[(PublicKey, PrivateKey)],
+        conditions: Vec<DelegationCondition>,
+    ) {
+        // delegator: member-0000, delegatee: member-0002
+        let data = DelegationTransactionData {
+            delegator: state.members[0].name.clone(),
+            delegatee: state.members[2].name.clone(),
+            governance: true,
+            block_height: 1,
+            timestamp: 1000,
+            chain_name: state.genesis_info.chain_name.clone(),
+            conditions,
+        };
+        let proof =
+            TypedSignature::sign(&data, &state.genesis_info.chain_id(), &keys[0].1).unwrap();
+        state.apply_delegate(&TxDelegate { data, proof }).unwrap();
+    }
+
+    fn expire_delegations(state: &ReservedState, header: &BlockHeader) -> ReservedState {
+        let mut state = state.clone();
+        state.expire_delegations(header);
+        state
+    }
+
+    #[test]
+    fn test_expire_delegations_after_blocks_and_seconds() {
+        setup_test();
+        let (mut state, keys) = generate_standard_genesis(4);
+        delegate_with_conditions(
+            &mut state,
+            &keys,
+            vec![
+                DelegationCondition::UnlockAfterBlocks(10),
+                DelegationCondition::UnlockAfterSeconds(60),
+            ],
+        );
+        let mut header = state.genesis_info.header.clone();
+        header.prev_block_finalization_proof = state.genesis_info.genesis_proof.clone();
+        header.height = 2;
+        header.timestamp = 2000;
+        let not_expired = expire_delegations(&state, &header);
+        assert_eq!(not_expired, state);
+        assert!(!not_expired
+            .consensus_leader_order
+            .contains(&"member-0000".to_string()));
+
+        // Expires by the time, before the height.
+        header.timestamp = 61_000;
+        let expired = expire_delegations(&state, &header);
+        assert_eq!(expired.members[0].consensus_delegatee, None);
+        assert_eq!(expired.members[0].governance_delegatee, None);
+        assert_eq!(expired.members[0].delegation_conditions, None);
+        assert!(expired
+            .consensus_leader_order
+            .contains(&"member-0000".to_string()));
+        assert_eq!(
+            expired.get_validator_set().unwrap(),
+            generate_standard_genesis(4).0.get_validator_set().unwrap()
+        );
+
+        // Expires by the height, before the time.
+        header.height = 11;
+        header.timestamp = 2000;
+        let expired = expire_delegations(&state, &header);
+        assert_eq!(expired.members[0].consensus_delegatee, None);
+    }
+
+    #[test]
+    fn test_expire_delegations_with_inactive_delegatee_or_validator_set_change() {
+        setup_test();
+        let (mut state, keys) = generate_standard_genesis(5);
+        let mut header = state.genesis_info.header.clone();
+        header.prev_block_finalization_proof = state.genesis_info.genesis_proof.clone();
+        header.height = 2;
+        let mut inactive_header = header.clone();
+        // member-0002 doesn't sign the finalization proof.
+        inactive_header
+            .prev_block_finalization_proof
+            .retain(|signature| signature.signer() != &keys[2].0);
+
+        delegate_with_conditions(
+            &mut state,
+            &keys,
+            vec![DelegationCondition::UnlockIfDelegateeIsNotActive(3)],
+        );
+        assert_eq!(expire_delegations(&state, &header), state);
+        // A block author alone can't revoke it by leaving out the signature of the delegatee.
+        let state = expire_delegations(&state, &inactive_header);
+        let state = expire_delegations(&state, &inactive_header);
+        assert_eq!(
+            state.members[0].consensus_delegatee,
+            Some(state.members[2].name.clone())
+        );
+        assert_eq!(
+            state.members[0]
+                .delegation_conditions
+                .as_ref()
+                .unwrap()
+                .inactive_blocks,
+            2
+        );
+        // The count restarts once the delegatee signs again.
+        let restarted = expire_delegations(&state, &header);
+        assert_eq!(
+            restarted.members[0]
+                .delegation_conditions
+                .as_ref()
+                .unwrap()
+                .inactive_blocks,
+            0
+        );
+        let restarted = expire_delegations(&restarted, &inactive_header);
+        assert!(restarted.members[0].consensus_delegatee.is_some());
+        let expired = expire_delegations(&state, &inactive_header);
+        assert_eq!(expired.members[0].consensus_delegatee, None);
+
+        let (mut state, keys) = generate_standard_genesis(5);
+        delegate_with_conditions(
+            &mut state,
+            &keys,
+            vec![DelegationCondition::UnlockIfValidatorSetChanges],
+        );
+        assert_eq!(expire_delegations(&state, &header), state);
+        state.members[4].expelled = true;
+        let expired = expire_delegations(&state, &header);
+        assert_eq!(expired.members[0].consensus_delegatee, None);
+        assert_eq!(expired.members[0].governance_delegatee, None);
+    }
+
+    #[test]
+    fn test_delegate_with_zero_inactive_blocks() {
+        setup_test();
+        let (mut state, keys) = generate_standard_genesis(4);
+        let data = DelegationTransactionData {
+            delegator: state.members[0].name.clone(),
+            delegatee: state.members[2].name.clone(),
+            governance: true,
+            block_height: 1,
+            timestamp: 1000,
+            chain_name: state.genesis_info.chain_name.clone(),
+            conditions: vec![DelegationCondition::UnlockIfDelegateeIsNotActive(0)],
+        };
+        let proof =
+            TypedSignature::sign(&data, &state.genesis_info.chain_id(), &keys[0].1).unwrap();
+        assert!(state.apply_delegate(&TxDelegate { data, proof }).is_err());
+    }
+
+    #[test]
+    fn test_apply_report() {
+        setup_test();
+        let (mut reserved_state, keys) = generate_delegated_genesis(4, true);
+        let sign_vote = |block_hash: Option<Hash256>| {
+            let vote = ConsensusVote {
+                kind: ConsensusVoteKind::Prevote,
+                height: 1,
+                round: 3,
+                block_hash,
+            };
+            SignedConsensusVote {
+                signature: TypedSignature::sign(
+                    &vote,
+                    &reserved_state.genesis_info.chain_id(),
+                    &keys[2].1,
+                )
+                .unwrap(),
+                vote,
+            }
+        };
+        // member-0002, the delegatee of member-0000, double-votes.
+        let tx = TxReport {
+            first_vote: sign_vote(Some(Hash256::hash("block1"))),
+            second_vote: sign_vote(Some(Hash256::hash("block2"))),
+            timestamp: 0,
+        };
+        let new_state = reserved_state.apply_report(&tx).unwrap();
+
+        assert!(new_state.members[2].expelled);
+        assert_eq!(new_state.members[0].consensus_delegatee, None);
+        assert_eq!(new_state.members[0].governance_delegatee, None);
+        assert!(!new_state
+            .consensus_leader_order
+            .contains(&"member-0002".to_string()));
+        assert_eq!(
+            new_state
+                .get_validator_set()
+                .unwrap()
+                .into_iter()
+                .collect::<HashSet<_>>(),
+            vec![
+                (keys[0].0.clone(), 1),
+                (keys[1].0.clone(), 1),
+                (keys[3].0.clone(), 1)
+            ]
+            .into_iter()
+            .collect::<HashSet<_>>()
+        );
+        assert!(reserved_state.apply_report(&tx).is_err());
+    }
 }
@@ -9,17 +9,26 @@ pub fn generate_standard_genesis(
         .into_iter()
         .map(|i| generate_keypair(format!("{i}")))
         .collect::<Vec<_>>();
+    (generate_genesis_with_keys(&keys), keys)
+}
+
+/// Generates a standard test chain config with the given key pairs of the members,
+/// which may be of different signature schemes.
+pub fn generate_genesis_with_keys(keys: &[(PublicKey, PrivateKey)]) -> ReservedState {
     let members = keys
         .iter()
         .enumerate()
         .map(|(i, (public_key, _))| Member {
             public_key: public_key.clone(),
+            key_type: public_key.scheme(),
             // lexicographically ordered
             name: format!("member-{i:04}"),
             governance_voting_power: 1,
             consensus_voting_power: 1,
             governance_delegatee: None,
             consensus_delegatee: None,
+            expelled: false,
+            delegation_conditions: None,
         })
         .collect::<Vec<_>>();
     let genesis_header = BlockHeader {
@@ -40,22 +49,22 @@ pub fn generate_standard_genesis(
         header: genesis_header.clone(),
         genesis_proof: keys
             .iter()
-            .map(|(_, private_key)| TypedSignature::sign(&genesis_header, private_key).unwrap())
+            .map(|(_, private_key)| {
+                TypedSignature::sign(&genesis_header, &genesis_header.to_hash256(), private_key)
+                    .unwrap()
+            })
             .collect::<Vec<_>>(),
         chain_name: "test-chain".to_string(),
     };
-    (
-        ReservedState {
-            genesis_info,
-            members,
-            consensus_leader_order: (0..member_number)
-                .into_iter()
-                .map(|i| format!("member-{i:04}"))
-                .collect::<Vec<_>>(),
-            version: SIMPERBY_CORE_PROTOCOL_VERSION.to_string(),
-        },
-        keys,
-    )
+    ReservedState {
+        genesis_info,
+        members,
+        consensus_leader_order: (0..keys.len())
+            .map(|i| format!("member-{i:04}"))
+            .collect::<Vec<_>>(),
+        version: SIMPERBY_CORE_PROTOCOL_VERSION.to_string(),
+        consensus_params: ConsensusParams::default(),
+    }
 }
 
 /// Generates a standard test chain config returning the genesis reserved-state
@@ -77,6 +86,7 @@ pub fn generate_delegated_genesis(
         .enumerate()
         .map(|(i, (public_key, _))| Member {
             public_key: public_key.clone(),
+            key_type: public_key.scheme(),
             // lexicographically ordered
             name: format!("member-{i:04}"),
             governance_voting_power: 1,
@@ -91,6 +101,8 @@ pub fn generate_delegated_genesis(
             } else {
                 None
             },
+            expelled: false,
+            delegation_conditions: None,
         })
         .collect::<Vec<_>>();
     let genesis_header = BlockHeader {
@@ -111,7 +123,10 @@ pub fn generate_delegated_genesis(
         header: genesis_header.clone(),
         genesis_proof: keys
             .iter()
-            .map(|(_, private_key)| TypedSignature::sign(&genesis_header, private_key).unwrap())
+            .map(|(_, private_key)| {
+                TypedSignature::sign(&genesis_header, &genesis_header.to_hash256(), private_key)
+                    .unwrap()
+            })
             .collect::<Vec<_>>(),
         chain_name: "test-chain".to_string(),
     };
@@ -124,6 +139,7 @@ pub fn generate_delegated_genesis(
                 .map(|i| format!("member-{i:04}"))
                 .collect::<Vec<_>>(),
             version: "0.1.0".to_string(),
+            consensus_params: ConsensusParams::default(),
         },
         keys,
     )
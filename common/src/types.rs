@@ -13,6 +13,12 @@ pub type MemberName = String;
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct Member {
     pub public_key: PublicKey,
+    /// The signature scheme of `public_key`.
+    ///
+    /// Every signature by this member is verified under it,
+    /// so it must agree with the scheme that the key is encoded with.
+    #[serde(default)]
+    pub key_type: SignatureScheme,
     /// The name of the member that will be used in human-readable interfaces.
     /// This must be unique.
     pub name: MemberName,
@@ -24,11 +30,51 @@ pub struct Member {
     /// If this member delegated its governance consensus power to another member,
     /// the delegatee.
     pub consensus_delegatee: Option<MemberName>,
-    // TODO: add various conditions for each delegation.
-    // - Unlock-Automatically-After-N-Blocks
-    // - Unlock-Automatically-After-T-Seconds
-    // - Unlock-If-The-Delegatee-Is-Not-Active
-    // - Unlock-If-The-Validator-Set-Changes
+    /// Whether this member has been expelled due to a reported misbehavior.
+    ///
+    /// An expelled member is never removed from the member list,
+    /// but has no voting power anymore.
+    #[serde(default)]
+    pub expelled: bool,
+    /// The conditions under which the current delegation (if any) is automatically revoked.
+    #[serde(default)]
+    pub delegation_conditions: Option<DelegationConditions>,
+}
+
+/// A condition under which a delegation is automatically revoked.
+///
+/// The conditions are evaluated at every block boundary
+/// (see `ReservedState::expire_delegations()`).
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub enum DelegationCondition {
+    /// Unlocks once the given number of blocks have passed since the delegation.
+    UnlockAfterBlocks(BlockHeight),
+    /// Unlocks once the given number of seconds have passed since the delegation.
+    UnlockAfterSeconds(u64),
+    /// Unlocks if the delegatee has not signed the finalization proofs (`prev_block_finalization_proof`)
+    /// of the given number of consecutive blocks.
+    ///
+    /// A single proof doesn't tell, as its author may leave out any signer
+    /// as long as more than 2/3 of the voting power remains.
+    UnlockIfDelegateeIsNotActive(BlockHeight),
+    /// Unlocks if the validator candidates (the non-expelled members) change.
+    UnlockIfValidatorSetChanges,
+}
+
+/// The conditions of a delegation in effect, with the context they are evaluated against.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct DelegationConditions {
+    pub conditions: Vec<DelegationCondition>,
+    /// The `block_height` of the delegation transaction.
+    pub block_height: BlockHeight,
+    /// The `timestamp` of the delegation transaction.
+    pub timestamp: Timestamp,
+    /// The hash of the validator candidates at the time of the delegation.
+    pub validator_candidates_hash: Hash256,
+    /// The number of the latest consecutive blocks whose finalization proofs
+    /// lack the signature of the delegatee, counted only for `UnlockIfDelegateeIsNotActive`.
+    #[serde(default)]
+    pub inactive_blocks: BlockHeight,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
@@ -45,7 +91,8 @@ pub struct BlockHeader {
     pub timestamp: Timestamp,
     /// The Merkle root of all the commits for this block.
     pub commit_merkle_root: Hash256,
-    /// The Merkle root of the non-essential state.
+    /// The Merkle root of the repository state (see `merkle_tree::StateMerkleTree`),
+    /// which is the tree of the commit right before this block.
     pub repository_merkle_root: Hash256,
     /// The effective validator set (delegation-applied) for the next block.
     ///
@@ -65,9 +112,42 @@ pub struct Agenda {
     pub transactions_hash: Hash256,
 }
 
+/// The chat chain of a height, semifinalized by the block proposer.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct ChatLog {
-    // TODO
+    pub height: BlockHeight,
+    /// The chats in the chain order.
+    ///
+    /// The last one must be a `ChatContent::Ack` signed by the author of the block.
+    pub chats: Vec<SignedChat>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub enum ChatContent {
+    Text(String),
+    /// A content-less chat by a consensus leader,
+    /// which semifinalizes the chain up to itself.
+    Ack,
+}
+
+/// A chat message broadcasted by a member during a height.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Chat {
+    pub height: BlockHeight,
+    pub timestamp: Timestamp,
+    pub content: ChatContent,
+    /// The hash of the last chat (`SignedChat`) of the chain that the author has perceived,
+    /// or `None` if this is the first chat of the height.
+    ///
+    /// Since every chat refers to its predecessor,
+    /// it transitively commits to all the previous chats in the chain.
+    pub previous_hash: Option<Hash256>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct SignedChat {
+    pub chat: Chat,
+    pub signature: TypedSignature<Chat>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
@@ -136,9 +216,35 @@ pub struct TxUndelegate {
     pub proof: TypedSignature<UndelegationTransactionData>,
 }
 
+/// An evidence of a double-voting: two conflicting consensus votes
+/// signed by the same validator at the same height and round.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct TxReport {
-    // TODO
+    pub first_vote: SignedConsensusVote,
+    pub second_vote: SignedConsensusVote,
+    pub timestamp: Timestamp,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum ConsensusVoteKind {
+    Prevote,
+    Precommit,
+}
+
+/// A vote cast by a validator in the consensus of a specific height and round.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct ConsensusVote {
+    pub kind: ConsensusVoteKind,
+    pub height: BlockHeight,
+    pub round: ConsensusRound,
+    /// The hash of the voted block, or `None` for a nil vote.
+    pub block_hash: Option<Hash256>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct SignedConsensusVote {
+    pub vote: ConsensusVote,
+    pub signature: TypedSignature<ConsensusVote>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
@@ -150,6 +256,11 @@ pub struct DelegationTransactionData {
     pub block_height: BlockHeight,
     pub timestamp: Timestamp,
     pub chain_name: String,
+    /// The conditions under which the delegation is automatically revoked.
+    ///
+    /// If empty, it lasts until the delegator undelegates.
+    #[serde(default)]
+    pub conditions: Vec<DelegationCondition>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
@@ -14,6 +14,8 @@ pub enum Error {
     CryptoError(String, CryptoError),
     #[error("invalid commit: applied {0} commit cannot be applied at {1} phase")]
     PhaseMismatch(String, String),
+    #[error("invalid reserved state: {0}")]
+    InvalidReservedState(String),
 }
 
 /// Verifies whether `h2` can be the direct child of `h1`.
@@ -22,7 +24,13 @@ pub enum Error {
 /// 1. block body (other commits)
 /// 2. finalization proof
 /// 3. protocol version of the node binary.
-pub fn verify_header_to_header(h1: &BlockHeader, h2: &BlockHeader) -> Result<(), Error> {
+///
+/// `chain_id` is the one that the signatures are bound to (see `GenesisInfo::chain_id()`).
+pub fn verify_header_to_header(
+    h1: &BlockHeader,
+    h2: &BlockHeader,
+    chain_id: &Hash256,
+) -> Result<(), Error> {
     if h2.height != h1.height + 1 {
         return Err(Error::InvalidArgument(format!(
             "invalid height: expected {}, got {}",
@@ -53,23 +61,26 @@ pub fn verify_header_to_header(h1: &BlockHeader, h2: &BlockHeader) -> Result<(),
             h1.timestamp, h2.timestamp
         )));
     }
-    verify_finalization_proof(h1, &h2.prev_block_finalization_proof)?;
+    verify_finalization_proof(h1, &h2.prev_block_finalization_proof, chain_id)?;
     Ok(())
 }
 
 /// Verifies the finalization proof of the given block header.
+///
+/// The signatures are verified in a batch (see `TypedSignature::verify_batch()`),
+/// so a proof with duplicate signers is rejected.
 pub fn verify_finalization_proof(
     header: &BlockHeader,
     block_finalization_proof: &FinalizationProof,
+    chain_id: &Hash256,
 ) -> Result<(), Error> {
     let total_voting_power: VotingPower = header.validator_set.iter().map(|(_, v)| v).sum();
-    let mut voted_validators = HashSet::new();
-    for signature in block_finalization_proof {
-        signature
-            .verify(header)
-            .map_err(|e| Error::CryptoError("invalid finalization proof".to_string(), e))?;
-        voted_validators.insert(signature.signer());
-    }
+    TypedSignature::verify_batch(block_finalization_proof, header, chain_id)
+        .map_err(|e| Error::CryptoError("invalid finalization proof".to_string(), e))?;
+    let voted_validators = block_finalization_proof
+        .iter()
+        .map(|signature| signature.signer())
+        .collect::<HashSet<_>>();
     let voted_voting_power: VotingPower = header
         .validator_set
         .iter()
@@ -84,6 +95,118 @@ pub fn verify_finalization_proof(
     Ok(())
 }
 
+/// Verifies the evidence of the given misbehavior report.
+///
+/// `validator_set` must be the one that performed the consensus of the reported height.
+pub fn verify_report(
+    report: &TxReport,
+    validator_set: &[(PublicKey, VotingPower)],
+    chain_id: &Hash256,
+) -> Result<(), Error> {
+    let (first, second) = (&report.first_vote, &report.second_vote);
+    if first.signature.signer() != second.signature.signer() {
+        return Err(Error::InvalidProof(format!(
+            "invalid report: the votes are signed by different validators {} and {}",
+            first.signature.signer(),
+            second.signature.signer()
+        )));
+    }
+    if first.vote.kind != second.vote.kind
+        || first.vote.height != second.vote.height
+        || first.vote.round != second.vote.round
+    {
+        return Err(Error::InvalidProof(format!(
+            "invalid report: the votes are not in the same step: {:?} and {:?}",
+            first.vote, second.vote
+        )));
+    }
+    if first.vote.block_hash == second.vote.block_hash {
+        return Err(Error::InvalidProof(
+            "invalid report: the votes do not conflict".to_string(),
+        ));
+    }
+    for vote in [first, second] {
+        vote.signature
+            .verify(&vote.vote, chain_id)
+            .map_err(|e| Error::CryptoError("invalid report: invalid signature".to_string(), e))?;
+    }
+    if !validator_set
+        .iter()
+        .any(|(public_key, _)| public_key == first.signature.signer())
+    {
+        return Err(Error::InvalidProof(format!(
+            "invalid report: {} is not in the validator set of height {}",
+            first.signature.signer(),
+            first.vote.height
+        )));
+    }
+    Ok(())
+}
+
+/// Verifies whether the given chat log is a well-formed, semifinalized chat chain.
+///
+/// - `members` are the members who are eligible to chat.
+/// - `validator_set` is the one that performs the consensus of the height,
+///   whose members are the only ones eligible to `ChatContent::Ack`.
+///
+/// Note that this does not check whether the last `ChatContent::Ack` is signed by the block author.
+pub fn verify_chat_log(
+    chat_log: &ChatLog,
+    members: &[PublicKey],
+    validator_set: &[(PublicKey, VotingPower)],
+    chain_id: &Hash256,
+) -> Result<(), Error> {
+    let mut previous: Option<&SignedChat> = None;
+    for signed_chat in chat_log.chats.iter() {
+        let chat = &signed_chat.chat;
+        if chat.height != chat_log.height {
+            return Err(Error::InvalidArgument(format!(
+                "invalid chat log: chat height mismatch: expected {}, got {}",
+                chat_log.height, chat.height
+            )));
+        }
+        if chat.previous_hash != previous.map(|x| x.to_hash256()) {
+            return Err(Error::InvalidArgument(format!(
+                "invalid chat log: broken chain at {}",
+                signed_chat.to_hash256()
+            )));
+        }
+        if let Some(previous) = previous {
+            if chat.timestamp < previous.chat.timestamp {
+                return Err(Error::InvalidArgument(format!(
+                    "invalid chat log: chat timestamp: expected larger than or equal to {}, got {}",
+                    previous.chat.timestamp, chat.timestamp
+                )));
+            }
+        }
+        signed_chat.signature.verify(chat, chain_id).map_err(|e| {
+            Error::CryptoError("invalid chat log: invalid signature".to_string(), e)
+        })?;
+        let signer = signed_chat.signature.signer();
+        if !members.contains(signer) {
+            return Err(Error::InvalidArgument(format!(
+                "invalid chat log: {signer} is not a member"
+            )));
+        }
+        if chat.content == ChatContent::Ack
+            && !validator_set
+                .iter()
+                .any(|(public_key, _)| public_key == signer)
+        {
+            return Err(Error::InvalidArgument(format!(
+                "invalid chat log: {signer} is not eligible to ack"
+            )));
+        }
+        previous = Some(signed_chat);
+    }
+    match previous {
+        Some(last) if last.chat.content == ChatContent::Ack => Ok(()),
+        _ => Err(Error::InvalidArgument(
+            "invalid chat log: not semifinalized by an ack".to_string(),
+        )),
+    }
+}
+
 // Phases of the `CommitSequenceVerifier`.
 //
 // Note that `Phase::X` is agenda phase where `Commit::X` is the last commit.
@@ -107,7 +230,8 @@ enum Phase {
     // Extra phase consists of `ExtraAgendaTransaction`s and `ChatLog`s.
     ExtraAgendaTransaction {
         last_extra_agenda_timestamp: Timestamp,
-        // TODO: add `ChatLog` here.
+        // The signer of the last ack of the chat log, if the chat log has been applied.
+        chat_log_leader: Option<PublicKey>,
     },
     // The block phase.
     Block,
@@ -128,6 +252,13 @@ pub struct CommitSequenceVerifier {
 impl CommitSequenceVerifier {
     /// Creates a new `CommitSequenceVerifier` with the given block header.
     pub fn new(start_header: BlockHeader, reserved_state: ReservedState) -> Result<Self, Error> {
+        reserved_state
+            .verify_key_types()
+            .map_err(Error::InvalidReservedState)?;
+        reserved_state
+            .consensus_params
+            .validate()
+            .map_err(|e| Error::InvalidReservedState(format!("invalid consensus params: {e}")))?;
         Ok(Self {
             header: start_header.clone(),
             phase: Phase::Block,
@@ -165,19 +296,206 @@ impl CommitSequenceVerifier {
     /// Note that due to the nature of the finalization proof (included in the next block)
     /// there is always an unverified last header (which may even not be the last commit).
     pub fn verify_last_header_finalization(&self, proof: &FinalizationProof) -> Result<(), Error> {
-        verify_finalization_proof(&self.header, proof)
+        verify_finalization_proof(
+            &self.header,
+            proof,
+            &self.reserved_state.genesis_info.chain_id(),
+        )
+    }
+
+    /// Verifies the given report and expels the reported validator from the reserved state.
+    ///
+    /// The votes of height `h` are cast by the validator set of the header at height `h - 1`,
+    /// so that header must be one of the headers received so far.
+    fn apply_report(&mut self, tx: &TxReport) -> Result<(), Error> {
+        let height = tx.first_vote.vote.height;
+        let validator_set = self
+            .get_block_headers()
+            .into_iter()
+            .find(|(header, _)| header.height + 1 == height)
+            .map(|(header, _)| header.validator_set)
+            .ok_or_else(|| {
+                Error::InvalidArgument(format!(
+                    "invalid report: the validator set of height {height} is not available"
+                ))
+            })?;
+        verify_report(
+            tx,
+            &validator_set,
+            &self.reserved_state.genesis_info.chain_id(),
+        )?;
+        self.reserved_state
+            .apply_report(tx)
+            .map_err(|e| Error::InvalidArgument(format!("invalid report: {e}")))?;
+        Ok(())
+    }
+
+    /// Verifies the given chat log against the current state,
+    /// returning the leader who semifinalized it.
+    fn verify_chat_log(&self, chat_log: &ChatLog) -> Result<PublicKey, Error> {
+        if chat_log.height != self.header.height + 1 {
+            return Err(Error::InvalidArgument(format!(
+                "invalid chat log height: expected {}, got {}",
+                self.header.height + 1,
+                chat_log.height
+            )));
+        }
+        let members = self
+            .reserved_state
+            .members
+            .iter()
+            .filter(|member| !member.expelled)
+            .map(|member| member.public_key.clone())
+            .collect::<Vec<_>>();
+        verify_chat_log(
+            chat_log,
+            &members,
+            &self.header.validator_set,
+            &self.reserved_state.genesis_info.chain_id(),
+        )?;
+        Ok(chat_log
+            .chats
+            .last()
+            .expect("already verified")
+            .signature
+            .signer()
+            .clone())
     }
 
     /// Verifies whether the given reserved state is valid from the current state.
-    pub fn verify_reserved_state(&self, _rs: &ReservedState) -> Result<(), Error> {
-        // TODO:
+    pub fn verify_reserved_state(&self, rs: &ReservedState) -> Result<(), Error> {
+        let current = &self.reserved_state;
         // 1. Check that the number of members is at least 4.
+        if rs.members.len() < 4 {
+            return Err(Error::InvalidReservedState(format!(
+                "the number of members must be at least 4, got {}",
+                rs.members.len()
+            )));
+        }
         // 2. Check that the version advances correctly.
+        let parse_version = |version: &str| {
+            semver::Version::parse(version)
+                .map_err(|e| Error::InvalidReservedState(format!("invalid version {version}: {e}")))
+        };
+        if parse_version(&rs.version)? < parse_version(&current.version)? {
+            return Err(Error::InvalidReservedState(format!(
+                "version must not go backward: from {} to {}",
+                current.version, rs.version
+            )));
+        }
         // 3. Check that `consensus_leader_order` is correct.
+        let mut leader_order = rs
+            .members
+            .iter()
+            .filter(|member| !member.expelled && member.consensus_delegatee.is_none())
+            .map(|member| member.name.clone())
+            .collect::<Vec<_>>();
+        leader_order.sort();
+        if rs.consensus_leader_order != leader_order {
+            return Err(Error::InvalidReservedState(format!(
+                "invalid consensus leader order: expected {:?}, got {:?}",
+                leader_order, rs.consensus_leader_order
+            )));
+        }
         // 4. Check that `genesis_info` stays the same.
-        // 5. Check that the newly added (if exists) `Member::name` is unique.
-        // 6. Check that `member` monotonicaly increases (refer to `Member::expelled`).
-        // 7. Check that the delegation state doesn't change.
+        if rs.genesis_info != current.genesis_info {
+            return Err(Error::InvalidReservedState(
+                "genesis info must not be changed".to_string(),
+            ));
+        }
+        // 5. Check that the newly added (if exists) `Member::name` is unique,
+        // and that every public key is of its declared type.
+        rs.verify_key_types().map_err(Error::InvalidReservedState)?;
+        let mut names = HashSet::new();
+        let mut public_keys = HashSet::new();
+        for member in rs.members.iter() {
+            if !names.insert(&member.name) {
+                return Err(Error::InvalidReservedState(format!(
+                    "duplicate member name: {}",
+                    member.name
+                )));
+            }
+            if !public_keys.insert(&member.public_key) {
+                return Err(Error::InvalidReservedState(format!(
+                    "duplicate member public key: {}",
+                    member.public_key
+                )));
+            }
+        }
+        // 6. Check that `member` monotonicaly increases (refer to `Member::expelled`),
+        // and that no member is expelled or restored (only a `TxReport` expels one).
+        if rs.members.len() < current.members.len() {
+            return Err(Error::InvalidReservedState(format!(
+                "members must not be removed: from {} to {} members",
+                current.members.len(),
+                rs.members.len()
+            )));
+        }
+        for (old, new) in current.members.iter().zip(rs.members.iter()) {
+            if old.name != new.name || old.public_key != new.public_key {
+                return Err(Error::InvalidReservedState(format!(
+                    "the existing member {} must not be replaced with {}",
+                    old.name, new.name
+                )));
+            }
+            if old.expelled && !new.expelled {
+                return Err(Error::InvalidReservedState(format!(
+                    "the expelled member {} must not be restored",
+                    old.name
+                )));
+            }
+            if !old.expelled && new.expelled {
+                return Err(Error::InvalidReservedState(format!(
+                    "the member {} must not be expelled but by a report",
+                    old.name
+                )));
+            }
+        }
+        if let Some(new) = rs.members[current.members.len()..]
+            .iter()
+            .find(|member| member.expelled)
+        {
+            return Err(Error::InvalidReservedState(format!(
+                "the new member {} must not be expelled",
+                new.name
+            )));
+        }
+        // 7. Check that the delegation state (including its conditions) doesn't change.
+        for (i, new) in rs.members.iter().enumerate() {
+            let (governance_delegatee, consensus_delegatee, delegation_conditions) =
+                current.members.get(i).map_or((None, None, None), |old| {
+                    (
+                        old.governance_delegatee.as_ref(),
+                        old.consensus_delegatee.as_ref(),
+                        old.delegation_conditions.as_ref(),
+                    )
+                });
+            if new.governance_delegatee.as_ref() != governance_delegatee
+                || new.consensus_delegatee.as_ref() != consensus_delegatee
+                || new.delegation_conditions.as_ref() != delegation_conditions
+            {
+                return Err(Error::InvalidReservedState(format!(
+                    "the delegation state of {} must not be changed",
+                    new.name
+                )));
+            }
+        }
+        // 8. Check that the consensus parameters (which an agenda may change) are valid.
+        rs.consensus_params
+            .validate()
+            .map_err(|e| Error::InvalidReservedState(format!("invalid consensus params: {e}")))?;
+        Ok(())
+    }
+
+    /// Applies the reserved state of the given transaction, if it carries one.
+    fn apply_transaction_diff(&mut self, tx: &Transaction) -> Result<(), Error> {
+        match &tx.diff {
+            Diff::Reserved(rs) | Diff::General(rs, _) => {
+                self.verify_reserved_state(rs)?;
+                self.reserved_state = *rs.clone();
+            }
+            Diff::None | Diff::NonReserved(_) => (),
+        }
         Ok(())
     }
 
@@ -185,7 +503,11 @@ impl CommitSequenceVerifier {
     pub fn apply_commit(&mut self, commit: &Commit) -> Result<(), Error> {
         match (commit, &mut self.phase) {
             (Commit::Block(block_header), Phase::AgendaProof { agenda_proof: _ }) => {
-                verify_header_to_header(&self.header, block_header)?;
+                verify_header_to_header(
+                    &self.header,
+                    block_header,
+                    &self.reserved_state.genesis_info.chain_id(),
+                )?;
                 // Verify commit merkle root
                 let commit_merkle_root =
                     BlockHeader::calculate_commit_merkle_root(&self.commits_for_next_block);
@@ -195,6 +517,7 @@ impl CommitSequenceVerifier {
                         commit_merkle_root, block_header.commit_merkle_root
                     )));
                 };
+                self.reserved_state.expire_delegations(block_header);
                 self.header = block_header.clone();
                 self.phase = Phase::Block;
                 self.commits_for_next_block = vec![];
@@ -203,9 +526,14 @@ impl CommitSequenceVerifier {
                 Commit::Block(block_header),
                 Phase::ExtraAgendaTransaction {
                     last_extra_agenda_timestamp,
+                    chat_log_leader,
                 },
             ) => {
-                verify_header_to_header(&self.header, block_header)?;
+                verify_header_to_header(
+                    &self.header,
+                    block_header,
+                    &self.reserved_state.genesis_info.chain_id(),
+                )?;
                 // Check if the block contains all the extra-agenda transactions.
                 if block_header.timestamp < *last_extra_agenda_timestamp {
                     return Err(Error::InvalidArgument(format!(
@@ -213,6 +541,15 @@ impl CommitSequenceVerifier {
                         last_extra_agenda_timestamp, block_header.timestamp
                     )));
                 }
+                // Check if the chat log is semifinalized by the block proposer.
+                if let Some(chat_log_leader) = chat_log_leader {
+                    if *chat_log_leader != block_header.author {
+                        return Err(Error::InvalidArgument(format!(
+                            "invalid block author: expected the leader who semifinalized the chat log {}, got {}",
+                            chat_log_leader, block_header.author
+                        )));
+                    }
+                }
                 // Verify commit hash
                 let commit_merkle_root =
                     BlockHeader::calculate_commit_merkle_root(&self.commits_for_next_block);
@@ -222,15 +559,14 @@ impl CommitSequenceVerifier {
                         commit_merkle_root, block_header.commit_merkle_root
                     )));
                 };
+                self.reserved_state.expire_delegations(block_header);
                 self.header = block_header.clone();
                 self.phase = Phase::Block;
                 self.commits_for_next_block = vec![];
             }
             (Commit::Transaction(tx), Phase::Block) => {
                 // Update reserved_state for reserved-diff transactions.
-                if let Diff::Reserved(rs) = &tx.diff {
-                    self.reserved_state = *rs.clone();
-                }
+                self.apply_transaction_diff(tx)?;
                 self.phase = Phase::Transaction {
                     last_transaction: tx.clone(),
                     preceding_transactions: vec![],
@@ -239,8 +575,7 @@ impl CommitSequenceVerifier {
             (
                 Commit::Transaction(tx),
                 Phase::Transaction {
-                    last_transaction,
-                    preceding_transactions,
+                    last_transaction, ..
                 },
             ) => {
                 // Check if transactions are in chronological order
@@ -251,11 +586,16 @@ impl CommitSequenceVerifier {
                     )));
                 }
                 // Update reserved_state for reserved-diff transactions.
-                if let Diff::Reserved(rs) = &tx.diff {
-                    self.reserved_state = *rs.clone();
+                self.apply_transaction_diff(tx)?;
+                // Still in the same phase; the transactions are appended in place
+                // rather than copied for every transaction.
+                if let Phase::Transaction {
+                    last_transaction,
+                    preceding_transactions,
+                } = &mut self.phase
+                {
+                    preceding_transactions.push(std::mem::replace(last_transaction, tx.clone()));
                 }
-                preceding_transactions.push(last_transaction.clone());
-                *last_transaction = tx.clone();
             }
             (Commit::Agenda(agenda), Phase::Block) => {
                 // Check if agenda is associated with the current block sequence.
@@ -334,16 +674,19 @@ impl CommitSequenceVerifier {
                     )));
                 }
                 // Verify the agenda proof
-                for signature in agenda_proof.proof.iter() {
-                    signature.verify(agenda).map_err(|e| {
-                        Error::CryptoError("invalid agenda proof: invalid signature".to_string(), e)
-                    })?;
-                }
+                TypedSignature::verify_batch(
+                    &agenda_proof.proof,
+                    agenda,
+                    &self.reserved_state.genesis_info.chain_id(),
+                )
+                .map_err(|e| {
+                    Error::CryptoError("invalid agenda proof: invalid signature".to_string(), e)
+                })?;
                 // Check if the agenda proof is signed by the majority of the governance participants
                 let governance_set = self
                     .reserved_state
                     .get_governance_set()
-                    .unwrap()
+                    .map_err(Error::InvalidReservedState)?
                     .into_iter()
                     .collect::<HashMap<_, _>>();
                 let total_weight = governance_set.values().sum::<u64>();
@@ -381,6 +724,7 @@ impl CommitSequenceVerifier {
                         })?;
                         self.phase = Phase::ExtraAgendaTransaction {
                             last_extra_agenda_timestamp: tx.data.timestamp,
+                            chat_log_leader: None,
                         };
                     }
                     ExtraAgendaTransaction::Undelegate(tx) => {
@@ -390,15 +734,23 @@ impl CommitSequenceVerifier {
                         })?;
                         self.phase = Phase::ExtraAgendaTransaction {
                             last_extra_agenda_timestamp: tx.data.timestamp,
+                            chat_log_leader: None,
+                        };
+                    }
+                    ExtraAgendaTransaction::Report(tx) => {
+                        self.apply_report(tx)?;
+                        self.phase = Phase::ExtraAgendaTransaction {
+                            last_extra_agenda_timestamp: tx.timestamp,
+                            chat_log_leader: None,
                         };
                     }
-                    ExtraAgendaTransaction::Report(_tx) => unimplemented!(),
                 }
             }
             (
                 Commit::ExtraAgendaTransaction(tx),
                 Phase::ExtraAgendaTransaction {
                     last_extra_agenda_timestamp,
+                    chat_log_leader,
                 },
             ) => {
                 match tx {
@@ -428,10 +780,67 @@ impl CommitSequenceVerifier {
                         }
                         *last_extra_agenda_timestamp = tx.data.timestamp;
                     }
-                    ExtraAgendaTransaction::Report(_tx) => unimplemented!(),
+                    ExtraAgendaTransaction::Report(tx) => {
+                        // Check if extra-agenda transactions are in chronological order
+                        if tx.timestamp < *last_extra_agenda_timestamp {
+                            return Err(Error::InvalidArgument(
+                                format!("invalid extra-agenda transaction timestamp: expected larger than or equal to the last transaction timestamp {}, got {}", last_extra_agenda_timestamp, tx.timestamp)
+                            ));
+                        }
+                        let last_extra_agenda_timestamp = tx.timestamp;
+                        let chat_log_leader = chat_log_leader.clone();
+                        self.apply_report(tx)?;
+                        self.phase = Phase::ExtraAgendaTransaction {
+                            last_extra_agenda_timestamp,
+                            chat_log_leader,
+                        };
+                    }
+                }
+            }
+            (Commit::ChatLog(chat_log), Phase::AgendaProof { agenda_proof: _ }) => {
+                let leader = self.verify_chat_log(chat_log)?;
+                self.phase = Phase::ExtraAgendaTransaction {
+                    last_extra_agenda_timestamp: chat_log
+                        .chats
+                        .last()
+                        .expect("already verified")
+                        .chat
+                        .timestamp,
+                    chat_log_leader: Some(leader),
+                };
+            }
+            (
+                Commit::ChatLog(chat_log),
+                Phase::ExtraAgendaTransaction {
+                    last_extra_agenda_timestamp,
+                    chat_log_leader,
+                },
+            ) => {
+                if chat_log_leader.is_some() {
+                    return Err(Error::InvalidArgument(
+                        "invalid chat log: the chat log has already been applied for this block"
+                            .to_string(),
+                    ));
                 }
+                let last_extra_agenda_timestamp = *last_extra_agenda_timestamp;
+                let leader = self.verify_chat_log(chat_log)?;
+                // Check if the final ack is in chronological order
+                let ack_timestamp = chat_log
+                    .chats
+                    .last()
+                    .expect("already verified")
+                    .chat
+                    .timestamp;
+                if ack_timestamp < last_extra_agenda_timestamp {
+                    return Err(Error::InvalidArgument(
+                        format!("invalid chat log timestamp: expected larger than or equal to the last transaction timestamp {last_extra_agenda_timestamp}, got {ack_timestamp}")
+                    ));
+                }
+                self.phase = Phase::ExtraAgendaTransaction {
+                    last_extra_agenda_timestamp: ack_timestamp,
+                    chat_log_leader: Some(leader),
+                };
             }
-            (Commit::ChatLog(_chat_log), _) => unimplemented!(),
             (commit, phase) => {
                 return Err(Error::PhaseMismatch(
                     format!("{commit:?}"),
@@ -490,11 +899,14 @@ mod test {
         for (i, (public_key, voting_power)) in validator_set.iter().enumerate() {
             members.push(Member {
                 public_key: public_key.clone(),
+                key_type: public_key.scheme(),
                 name: format!("member{i}").to_string(),
                 governance_voting_power: *voting_power,
                 consensus_voting_power: *voting_power,
                 governance_delegatee: None,
                 consensus_delegatee: None,
+                expelled: false,
+                delegation_conditions: None,
             });
         }
         members
@@ -527,6 +939,7 @@ mod test {
             genesis_info: GenesisInfo {
                 header: genesis_header.clone(),
                 genesis_proof: generate_unanimous_finalization_proof(
+                    &genesis_header.to_hash256(),
                     validator_keypair,
                     &genesis_header,
                 ),
@@ -535,6 +948,7 @@ mod test {
             members, // TODO: fix to not use genesis header
             consensus_leader_order,
             version: SIMPERBY_CORE_PROTOCOL_VERSION.to_string(),
+            consensus_params: ConsensusParams::default(),
         }
     }
 
@@ -570,18 +984,20 @@ mod test {
         time: Timestamp,
     ) -> Commit {
         // Update reserved reserved_state
-        validator_keypair.push(generate_keypair([3]));
+        let name = format!("member{}", validator_keypair.len());
+        validator_keypair.push(generate_keypair([validator_keypair.len() as u8]));
         reserved_state.members.push(Member {
             public_key: validator_keypair.last().unwrap().0.clone(),
-            name: format!("member{}", validator_keypair.len()),
+            key_type: validator_keypair.last().unwrap().0.scheme(),
+            name: name.clone(),
             governance_voting_power: 1,
             consensus_voting_power: 1,
             governance_delegatee: None,
             consensus_delegatee: None,
+            expelled: false,
+            delegation_conditions: None,
         });
-        reserved_state
-            .consensus_leader_order
-            .push("Dave".to_string());
+        reserved_state.consensus_leader_order.push(name);
         reserved_state.consensus_leader_order.sort();
         Commit::Transaction(Transaction {
             author: "doesn't matter".to_owned(),
@@ -597,13 +1013,14 @@ mod test {
     }
 
     fn generate_agenda_proof_commit(
+        chain_id: &Hash256,
         validator_keypair: &[(PublicKey, PrivateKey)],
         agenda: &Agenda,
         agenda_hash_value: Hash256,
     ) -> Commit {
         let mut agenda_proof: Vec<TypedSignature<Agenda>> = vec![];
         for (_, private_key) in validator_keypair {
-            agenda_proof.push(TypedSignature::sign(agenda, private_key).unwrap())
+            agenda_proof.push(TypedSignature::sign(agenda, chain_id, private_key).unwrap())
         }
         Commit::AgendaProof(AgendaProof {
             agenda_hash: agenda_hash_value,
@@ -614,17 +1031,19 @@ mod test {
     }
 
     fn generate_unanimous_finalization_proof(
+        chain_id: &Hash256,
         validator_keypair: &[(PublicKey, PrivateKey)],
         header: &BlockHeader,
     ) -> FinalizationProof {
         let mut finalization_proof: Vec<TypedSignature<BlockHeader>> = vec![];
         for (_, private_key) in validator_keypair {
-            finalization_proof.push(TypedSignature::sign(header, private_key).unwrap());
+            finalization_proof.push(TypedSignature::sign(header, chain_id, private_key).unwrap());
         }
         finalization_proof
     }
 
     fn generate_block_commit(
+        chain_id: &Hash256,
         validator_keypair: &[(PublicKey, PrivateKey)],
         author_index: usize,
         previous_header: BlockHeader,
@@ -635,6 +1054,7 @@ mod test {
         Commit::Block(BlockHeader {
             author: validator_keypair[author_index].0.clone(),
             prev_block_finalization_proof: generate_unanimous_finalization_proof(
+                chain_id,
                 validator_keypair,
                 &previous_header,
             ),
@@ -651,6 +1071,10 @@ mod test {
         })
     }
 
+    fn chain_id(csv: &CommitSequenceVerifier) -> Hash256 {
+        csv.reserved_state.genesis_info.chain_id()
+    }
+
     fn setup_test(
         validator_set_size: u8,
     ) -> (
@@ -721,6 +1145,7 @@ mod test {
         csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
         // Apply agenda-proof commit
         csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(&csv),
             &validator_keypair,
             &agenda,
             agenda.to_hash256(),
@@ -743,6 +1168,7 @@ mod test {
         csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
         // Apply agenda-proof commit
         csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(&csv),
             &validator_keypair,
             &agenda,
             agenda.to_hash256(),
@@ -765,6 +1191,7 @@ mod test {
         csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
         // Apply agenda-proof commit
         csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(&csv),
             &validator_keypair,
             &agenda,
             agenda.to_hash256(),
@@ -774,6 +1201,7 @@ mod test {
         csv.apply_commit(&Commit::Block(BlockHeader {
             author: validator_keypair[0].0.clone(),
             prev_block_finalization_proof: generate_unanimous_finalization_proof(
+                &chain_id(&csv),
                 &validator_keypair,
                 &csv.header,
             ),
@@ -808,6 +1236,7 @@ mod test {
         csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
         // Apply agenda-proof commit
         csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(&csv),
             &validator_keypair,
             &agenda,
             agenda.to_hash256(),
@@ -817,6 +1246,7 @@ mod test {
         csv.apply_commit(&Commit::Block(BlockHeader {
             author: validator_keypair[0].0.clone(),
             prev_block_finalization_proof: generate_unanimous_finalization_proof(
+                &chain_id(&csv),
                 &validator_keypair,
                 &csv.header,
             ),
@@ -851,6 +1281,7 @@ mod test {
         csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
         // Apply agenda-proof commit
         csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(&csv),
             &validator_keypair,
             &agenda,
             agenda.to_hash256(),
@@ -860,6 +1291,7 @@ mod test {
         csv.apply_commit(&Commit::Block(BlockHeader {
             author: generate_keypair([42]).0,
             prev_block_finalization_proof: generate_unanimous_finalization_proof(
+                &chain_id(&csv),
                 &validator_keypair,
                 &csv.header,
             ),
@@ -894,6 +1326,7 @@ mod test {
         csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
         // Apply agenda-proof commit
         csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(&csv),
             &validator_keypair,
             &agenda,
             agenda.to_hash256(),
@@ -903,6 +1336,7 @@ mod test {
         csv.apply_commit(&Commit::Block(BlockHeader {
             author: validator_keypair[0].0.clone(),
             prev_block_finalization_proof: generate_unanimous_finalization_proof(
+                &chain_id(&csv),
                 &validator_keypair,
                 &csv.header,
             ),
@@ -937,6 +1371,7 @@ mod test {
         csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
         // Apply agenda-proof commit
         csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(&csv),
             &validator_keypair,
             &agenda,
             agenda.to_hash256(),
@@ -947,6 +1382,7 @@ mod test {
             &validator_keypair,
             0,
             generate_unanimous_finalization_proof(
+                &chain_id(&csv),
                 &validator_keypair,
                 &generate_block_header(
                     &validator_keypair[1..],
@@ -981,6 +1417,7 @@ mod test {
         csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
         // Apply agenda-proof commit
         csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(&csv),
             &validator_keypair,
             &agenda,
             agenda.to_hash256(),
@@ -990,6 +1427,7 @@ mod test {
         csv.apply_commit(&Commit::Block(BlockHeader {
             author: validator_keypair[0].0.clone(),
             prev_block_finalization_proof: vec![generate_unanimous_finalization_proof(
+                &chain_id(&csv),
                 &validator_keypair,
                 &csv.header,
             )
@@ -1027,6 +1465,7 @@ mod test {
         csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
         // Apply agenda-proof commit
         csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(&csv),
             &validator_keypair,
             &agenda,
             agenda.to_hash256(),
@@ -1036,6 +1475,7 @@ mod test {
         csv.apply_commit(&Commit::Block(BlockHeader {
             author: validator_keypair[0].0.clone(),
             prev_block_finalization_proof: generate_unanimous_finalization_proof(
+                &chain_id(&csv),
                 &validator_keypair,
                 &csv.header,
             ),
@@ -1059,6 +1499,7 @@ mod test {
         let (validator_keypair, _, mut csv) = setup_test(4);
         // Apply block commit at block phase
         csv.apply_commit(&generate_block_commit(
+            &chain_id(&csv),
             &validator_keypair,
             0,
             csv.header.clone(),
@@ -1078,6 +1519,7 @@ mod test {
             .unwrap();
         // Apply block commit at transaction phase
         csv.apply_commit(&generate_block_commit(
+            &chain_id(&csv),
             &validator_keypair,
             0,
             csv.header.clone(),
@@ -1116,6 +1558,7 @@ mod test {
         csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
         // Apply block commit at agenda phase
         csv.apply_commit(&generate_block_commit(
+            &chain_id(&csv),
             &validator_keypair,
             0,
             csv.header.clone(),
@@ -1159,6 +1602,7 @@ mod test {
         csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
         // Apply agenda-proof commit
         csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(&csv),
             &validator_keypair,
             &agenda,
             agenda.to_hash256(),
@@ -1287,6 +1731,7 @@ mod test {
         csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
         // Apply agenda-proof commit
         csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(&csv),
             &validator_keypair,
             &agenda,
             agenda.to_hash256(),
@@ -1322,6 +1767,7 @@ mod test {
         csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
         // Apply agenda-proof commit with invalid height
         csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(&csv),
             &validator_keypair,
             &Agenda {
                 author: reserved_state.query_name(&validator_keypair[1].0).unwrap(),
@@ -1349,6 +1795,7 @@ mod test {
         csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
         // Apply agenda-proof commit with invalid agenda hash
         csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(&csv),
             &validator_keypair,
             &agenda,
             Hash256::zero(),
@@ -1371,6 +1818,7 @@ mod test {
         csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
         // Apply agenda-proof commit with invalid signature
         csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(&csv),
             &validator_keypair,
             &Agenda {
                 author: reserved_state.query_name(&validator_keypair[1].0).unwrap(),
@@ -1383,6 +1831,33 @@ mod test {
         .unwrap_err();
     }
 
+    #[test]
+    /// Test the case where the agenda proof commit is invalid because a signer signed twice.
+    fn invalid_agenda_proof_with_duplicate_signer() {
+        let (validator_keypair, reserved_state, mut csv) = setup_test(4);
+        // Apply agenda commit
+        let agenda_transactions_hash = calculate_agenda_transactions_hash(csv.phase.clone());
+        let agenda: Agenda = Agenda {
+            author: reserved_state.query_name(&validator_keypair[0].0).unwrap(),
+            timestamp: 1,
+            transactions_hash: agenda_transactions_hash,
+            height: csv.header.height + 1,
+        };
+        csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
+        // Apply agenda-proof commit whose majority is made up by a duplicate signature
+        csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(&csv),
+            &[
+                validator_keypair[0].clone(),
+                validator_keypair[0].clone(),
+                validator_keypair[1].clone(),
+            ],
+            &agenda,
+            agenda.to_hash256(),
+        ))
+        .unwrap_err();
+    }
+
     #[test]
     /// Test the case where the agenda proof commit is invalid because agenda proof already exists.
     fn phase_mismatch_for_agenda_proof_commit1() {
@@ -1398,6 +1873,7 @@ mod test {
         csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
         // Apply agenda-proof commit
         csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(&csv),
             &validator_keypair,
             &agenda,
             agenda.to_hash256(),
@@ -1405,6 +1881,7 @@ mod test {
         .unwrap();
         // Apply agenda-proof commit again
         csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(&csv),
             &validator_keypair,
             &agenda,
             agenda.to_hash256(),
@@ -1428,6 +1905,7 @@ mod test {
             height: csv.header.height + 1,
         };
         csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(&csv),
             &validator_keypair,
             &agenda,
             agenda.to_hash256(),
@@ -1457,6 +1935,7 @@ mod test {
             height: csv.header.height + 1,
         };
         csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(&csv),
             &validator_keypair,
             &agenda,
             agenda.to_hash256(),
@@ -1545,6 +2024,570 @@ mod test {
         todo!("Implement this test")
     }
 
-    // TODO: add test cases where the `Report` extra-agenda transactions are invalid.
-    // These test cases are TODO because the `Report` extra-agenda transaction is not implemented yet.
+    fn generate_reserved_state_transaction_commit(reserved_state: ReservedState) -> Commit {
+        Commit::Transaction(Transaction {
+            author: "doesn't matter".to_owned(),
+            timestamp: 0,
+            head: "Test reserved-diff commit".to_string(),
+            body: String::new(),
+            diff: Diff::Reserved(Box::new(reserved_state)),
+        })
+    }
+
+    #[test]
+    /// Test the case where the reserved state is updated by a general-diff transaction.
+    fn correct_reserved_state_with_general_diff() {
+        let (mut validator_keypair, mut reserved_state, mut csv) = setup_test(4);
+        let commit = generate_reserved_diff_transaction_commit(
+            &mut validator_keypair,
+            &mut reserved_state,
+            0,
+        );
+        let commit = if let Commit::Transaction(mut tx) = commit {
+            tx.diff = Diff::General(Box::new(reserved_state.clone()), Hash256::hash("diff"));
+            Commit::Transaction(tx)
+        } else {
+            unreachable!()
+        };
+        csv.apply_commit(&commit).unwrap();
+        assert_eq!(csv.get_reserved_state(), &reserved_state);
+    }
+
+    #[test]
+    /// Test the case where the reserved state is invalid because it has less than 4 members.
+    fn invalid_reserved_state_with_too_few_members() {
+        let (_, mut reserved_state, mut csv) = setup_test(4);
+        reserved_state.members.pop();
+        reserved_state.consensus_leader_order.pop();
+        assert!(matches!(
+            csv.apply_commit(&generate_reserved_state_transaction_commit(reserved_state)),
+            Err(Error::InvalidReservedState(_))
+        ));
+    }
+
+    #[test]
+    /// Test the case where the reserved state is invalid because the version goes backward.
+    fn invalid_reserved_state_with_invalid_version() {
+        let (_, mut reserved_state, mut csv) = setup_test(4);
+        reserved_state.version = "0.0.1".to_string();
+        assert!(matches!(
+            csv.apply_commit(&generate_reserved_state_transaction_commit(
+                reserved_state.clone()
+            )),
+            Err(Error::InvalidReservedState(_))
+        ));
+        reserved_state.version = "not-a-version".to_string();
+        assert!(matches!(
+            csv.apply_commit(&generate_reserved_state_transaction_commit(reserved_state)),
+            Err(Error::InvalidReservedState(_))
+        ));
+    }
+
+    #[test]
+    /// Test the case where the reserved state is invalid because of the consensus leader order.
+    fn invalid_reserved_state_with_invalid_consensus_leader_order() {
+        let (_, mut reserved_state, mut csv) = setup_test(4);
+        reserved_state.consensus_leader_order.reverse();
+        assert!(matches!(
+            csv.apply_commit(&generate_reserved_state_transaction_commit(
+                reserved_state.clone()
+            )),
+            Err(Error::InvalidReservedState(_))
+        ));
+        reserved_state.consensus_leader_order = vec!["member0".to_string()];
+        assert!(matches!(
+            csv.apply_commit(&generate_reserved_state_transaction_commit(reserved_state)),
+            Err(Error::InvalidReservedState(_))
+        ));
+    }
+
+    #[test]
+    /// Test the case where the reserved state is invalid because a member declares a wrong key type.
+    fn invalid_reserved_state_with_mismatched_key_type() {
+        let (_, mut reserved_state, mut csv) = setup_test(4);
+        reserved_state.members[0].key_type = SignatureScheme::Ed25519;
+        assert!(matches!(
+            csv.apply_commit(&generate_reserved_state_transaction_commit(reserved_state)),
+            Err(Error::InvalidReservedState(_))
+        ));
+    }
+
+    #[test]
+    /// Test the case where the consensus parameters are changed by a reserved-diff transaction.
+    fn correct_reserved_state_with_changed_consensus_params() {
+        let (_, mut reserved_state, mut csv) = setup_test(4);
+        reserved_state.consensus_params.timeout_ms = 3_000;
+        reserved_state.consensus_params.timeout_growth = TimeoutGrowth::Exponential {
+            factor_percent: 150,
+            max_timeout_ms: 60_000,
+        };
+        csv.apply_commit(&generate_reserved_state_transaction_commit(
+            reserved_state.clone(),
+        ))
+        .unwrap();
+        assert_eq!(csv.get_reserved_state(), &reserved_state);
+    }
+
+    #[test]
+    /// Test the case where the reserved state is invalid because of the invalid consensus parameters.
+    fn invalid_reserved_state_with_invalid_consensus_params() {
+        let (_, reserved_state, mut csv) = setup_test(4);
+        let mut zero_timeout = reserved_state.clone();
+        zero_timeout.consensus_params.timeout_ms = 0;
+        let mut shrinking_timeout = reserved_state;
+        shrinking_timeout.consensus_params.timeout_growth = TimeoutGrowth::Exponential {
+            factor_percent: 50,
+            max_timeout_ms: 60_000,
+        };
+        for reserved_state in [zero_timeout, shrinking_timeout] {
+            assert!(matches!(
+                csv.apply_commit(&generate_reserved_state_transaction_commit(reserved_state)),
+                Err(Error::InvalidReservedState(_))
+            ));
+        }
+    }
+
+    #[test]
+    /// Test the case where the reserved state is invalid because the genesis info is changed.
+    fn invalid_reserved_state_with_changed_genesis_info() {
+        let (_, mut reserved_state, mut csv) = setup_test(4);
+        reserved_state.genesis_info.chain_name = "Another Chain".to_string();
+        assert!(matches!(
+            csv.apply_commit(&generate_reserved_state_transaction_commit(reserved_state)),
+            Err(Error::InvalidReservedState(_))
+        ));
+    }
+
+    #[test]
+    /// Test the case where the reserved state is invalid because a new member has a duplicate name.
+    fn invalid_reserved_state_with_duplicate_member_name() {
+        let (mut validator_keypair, mut reserved_state, mut csv) = setup_test(4);
+        let commit = generate_reserved_diff_transaction_commit(
+            &mut validator_keypair,
+            &mut reserved_state,
+            0,
+        );
+        let commit = if let Commit::Transaction(mut tx) = commit {
+            let mut rs = reserved_state.clone();
+            rs.members.last_mut().unwrap().name = "member0".to_string();
+            rs.consensus_leader_order = vec![
+                "member0".to_string(),
+                "member0".to_string(),
+                "member1".to_string(),
+                "member2".to_string(),
+                "member3".to_string(),
+            ];
+            tx.diff = Diff::Reserved(Box::new(rs));
+            Commit::Transaction(tx)
+        } else {
+            unreachable!()
+        };
+        assert!(matches!(
+            csv.apply_commit(&commit),
+            Err(Error::InvalidReservedState(_))
+        ));
+    }
+
+    #[test]
+    /// Test the case where the reserved state is invalid because an existing member is removed.
+    fn invalid_reserved_state_with_removed_member() {
+        let (mut validator_keypair, mut reserved_state, mut csv) = setup_test(4);
+        csv.apply_commit(&generate_reserved_diff_transaction_commit(
+            &mut validator_keypair,
+            &mut reserved_state,
+            0,
+        ))
+        .unwrap();
+        let removed = reserved_state.members.remove(0);
+        reserved_state
+            .consensus_leader_order
+            .retain(|name| name != &removed.name);
+        assert!(matches!(
+            csv.apply_commit(&generate_reserved_state_transaction_commit(reserved_state)),
+            Err(Error::InvalidReservedState(_))
+        ));
+    }
+
+    #[test]
+    /// Test the case where the reserved state is invalid because an expelled member is restored.
+    fn invalid_reserved_state_with_restored_member() {
+        let (validator_keypair, reserved_state, mut csv) = setup_test(4);
+        setup_agenda_proof_phase(&validator_keypair, &reserved_state, &mut csv);
+        csv.apply_commit(&generate_report_commit(
+            &chain_id(&csv),
+            &validator_keypair[1].1,
+            1,
+            (Some(Hash256::hash("block1")), None),
+            2,
+        ))
+        .unwrap();
+        let mut csv =
+            CommitSequenceVerifier::new(csv.header.clone(), csv.get_reserved_state().clone())
+                .unwrap();
+        let mut reserved_state = csv.get_reserved_state().clone();
+        reserved_state.members[1].expelled = false;
+        reserved_state.consensus_leader_order = reserved_state
+            .members
+            .iter()
+            .map(|member| member.name.clone())
+            .collect();
+        assert!(matches!(
+            csv.apply_commit(&generate_reserved_state_transaction_commit(reserved_state)),
+            Err(Error::InvalidReservedState(_))
+        ));
+    }
+
+    #[test]
+    /// Test the case where the reserved state is invalid because a member is expelled without a report.
+    fn invalid_reserved_state_with_expelled_member() {
+        let (_, mut reserved_state, mut csv) = setup_test(4);
+        reserved_state.members[1].expelled = true;
+        let expelled = reserved_state.members[1].name.clone();
+        reserved_state
+            .consensus_leader_order
+            .retain(|name| name != &expelled);
+        assert!(matches!(
+            csv.apply_commit(&generate_reserved_state_transaction_commit(reserved_state)),
+            Err(Error::InvalidReservedState(_))
+        ));
+    }
+
+    #[test]
+    /// Test the case where the reserved state is invalid because the delegation state is changed.
+    fn invalid_reserved_state_with_changed_delegation() {
+        let (_, mut reserved_state, mut csv) = setup_test(4);
+        reserved_state.members[0].consensus_delegatee = Some("member1".to_string());
+        reserved_state
+            .consensus_leader_order
+            .retain(|name| name != "member0");
+        assert!(matches!(
+            csv.apply_commit(&generate_reserved_state_transaction_commit(reserved_state)),
+            Err(Error::InvalidReservedState(_))
+        ));
+    }
+
+    fn generate_report_commit(
+        chain_id: &Hash256,
+        private_key: &PrivateKey,
+        height: BlockHeight,
+        block_hashes: (Option<Hash256>, Option<Hash256>),
+        timestamp: Timestamp,
+    ) -> Commit {
+        let sign_vote = |block_hash| {
+            let vote = ConsensusVote {
+                kind: ConsensusVoteKind::Precommit,
+                height,
+                round: 0,
+                block_hash,
+            };
+            SignedConsensusVote {
+                signature: TypedSignature::sign(&vote, chain_id, private_key).unwrap(),
+                vote,
+            }
+        };
+        Commit::ExtraAgendaTransaction(ExtraAgendaTransaction::Report(TxReport {
+            first_vote: sign_vote(block_hashes.0),
+            second_vote: sign_vote(block_hashes.1),
+            timestamp,
+        }))
+    }
+
+    fn setup_agenda_proof_phase(
+        validator_keypair: &[(PublicKey, PrivateKey)],
+        reserved_state: &ReservedState,
+        csv: &mut CommitSequenceVerifier,
+    ) {
+        let agenda: Agenda = Agenda {
+            author: reserved_state.query_name(&validator_keypair[0].0).unwrap(),
+            timestamp: 1,
+            transactions_hash: calculate_agenda_transactions_hash(csv.phase.clone()),
+            height: csv.header.height + 1,
+        };
+        csv.apply_commit(&generate_agenda_commit(&agenda)).unwrap();
+        csv.apply_commit(&generate_agenda_proof_commit(
+            &chain_id(csv),
+            validator_keypair,
+            &agenda,
+            agenda.to_hash256(),
+        ))
+        .unwrap();
+    }
+
+    #[test]
+    /// Test the case where a conditional delegation expires at the next block.
+    fn correct_delegation_expiry() {
+        let (validator_keypair, reserved_state, mut csv) = setup_test(4);
+        setup_agenda_proof_phase(&validator_keypair, &reserved_state, &mut csv);
+        let data = DelegationTransactionData {
+            delegator: reserved_state.query_name(&validator_keypair[0].0).unwrap(),
+            delegatee: reserved_state.query_name(&validator_keypair[1].0).unwrap(),
+            governance: true,
+            block_height: 0,
+            timestamp: 2,
+            chain_name: reserved_state.genesis_info.chain_name.clone(),
+            conditions: vec![DelegationCondition::UnlockAfterBlocks(1)],
+        };
+        csv.apply_commit(&Commit::ExtraAgendaTransaction(
+            ExtraAgendaTransaction::Delegate(TxDelegate {
+                proof: TypedSignature::sign(&data, &chain_id(&csv), &validator_keypair[0].1)
+                    .unwrap(),
+                data: data.clone(),
+            }),
+        ))
+        .unwrap();
+        assert_eq!(
+            csv.get_reserved_state().members[0].consensus_delegatee,
+            Some(data.delegatee)
+        );
+        let start_header = csv.header.clone();
+        let commit_merkle_root =
+            BlockHeader::calculate_commit_merkle_root(&csv.commits_for_next_block);
+        csv.apply_commit(&generate_block_commit(
+            &chain_id(&csv),
+            &validator_keypair,
+            0,
+            start_header,
+            2,
+            commit_merkle_root,
+            Hash256::zero(),
+        ))
+        .unwrap();
+        let reserved_state = csv.get_reserved_state();
+        assert_eq!(reserved_state.members[0].consensus_delegatee, None);
+        assert_eq!(reserved_state.members[0].governance_delegatee, None);
+        assert!(reserved_state
+            .consensus_leader_order
+            .contains(&reserved_state.members[0].name));
+    }
+
+    #[test]
+    /// Test the case where the `Report` extra-agenda transaction is valid and expels the offender.
+    fn correct_report_transaction() {
+        let (validator_keypair, reserved_state, mut csv) = setup_test(4);
+        setup_agenda_proof_phase(&validator_keypair, &reserved_state, &mut csv);
+        csv.apply_commit(&generate_report_commit(
+            &chain_id(&csv),
+            &validator_keypair[1].1,
+            1,
+            (Some(Hash256::hash("block1")), None),
+            2,
+        ))
+        .unwrap();
+        let member = &csv.get_reserved_state().members[1];
+        assert!(member.expelled);
+        assert_eq!(member.consensus_voting_power, 0);
+        assert!(!csv
+            .get_reserved_state()
+            .consensus_leader_order
+            .contains(&member.name));
+        assert!(!csv
+            .get_reserved_state()
+            .get_validator_set()
+            .unwrap()
+            .iter()
+            .any(|(public_key, _)| public_key == &validator_keypair[1].0));
+        // The same offender can't be reported twice.
+        csv.apply_commit(&generate_report_commit(
+            &chain_id(&csv),
+            &validator_keypair[1].1,
+            1,
+            (Some(Hash256::hash("block1")), Some(Hash256::hash("block2"))),
+            3,
+        ))
+        .unwrap_err();
+    }
+
+    #[test]
+    /// Test the case where the `Report` extra-agenda transaction is invalid because the votes do not conflict.
+    fn invalid_report_transaction_with_non_conflicting_votes() {
+        let (validator_keypair, reserved_state, mut csv) = setup_test(4);
+        setup_agenda_proof_phase(&validator_keypair, &reserved_state, &mut csv);
+        csv.apply_commit(&generate_report_commit(
+            &chain_id(&csv),
+            &validator_keypair[1].1,
+            1,
+            (Some(Hash256::hash("block1")), Some(Hash256::hash("block1"))),
+            2,
+        ))
+        .unwrap_err();
+    }
+
+    #[test]
+    /// Test the case where the `Report` extra-agenda transaction is invalid because the votes are signed by different validators.
+    fn invalid_report_transaction_with_different_signers() {
+        let (validator_keypair, reserved_state, mut csv) = setup_test(4);
+        setup_agenda_proof_phase(&validator_keypair, &reserved_state, &mut csv);
+        let mut report = generate_report_commit(
+            &chain_id(&csv),
+            &validator_keypair[1].1,
+            1,
+            (Some(Hash256::hash("block1")), Some(Hash256::hash("block2"))),
+            2,
+        );
+        if let Commit::ExtraAgendaTransaction(ExtraAgendaTransaction::Report(tx)) = &mut report {
+            tx.second_vote.signature = TypedSignature::sign(
+                &tx.second_vote.vote,
+                &chain_id(&csv),
+                &validator_keypair[2].1,
+            )
+            .unwrap();
+        }
+        csv.apply_commit(&report).unwrap_err();
+    }
+
+    #[test]
+    /// Test the case where the `Report` extra-agenda transaction is invalid because the signer is not a validator.
+    fn invalid_report_transaction_with_invalid_signer() {
+        let (validator_keypair, reserved_state, mut csv) = setup_test(4);
+        setup_agenda_proof_phase(&validator_keypair, &reserved_state, &mut csv);
+        csv.apply_commit(&generate_report_commit(
+            &chain_id(&csv),
+            &generate_keypair([42]).1,
+            1,
+            (Some(Hash256::hash("block1")), Some(Hash256::hash("block2"))),
+            2,
+        ))
+        .unwrap_err();
+    }
+
+    #[test]
+    /// Test the case where the `Report` extra-agenda transaction is invalid because the validator set of the height is unknown.
+    fn invalid_report_transaction_with_unknown_height() {
+        let (validator_keypair, reserved_state, mut csv) = setup_test(4);
+        setup_agenda_proof_phase(&validator_keypair, &reserved_state, &mut csv);
+        csv.apply_commit(&generate_report_commit(
+            &chain_id(&csv),
+            &validator_keypair[1].1,
+            5,
+            (Some(Hash256::hash("block1")), Some(Hash256::hash("block2"))),
+            2,
+        ))
+        .unwrap_err();
+    }
+
+    /// Generates a chat chain where `None` stands for an ack.
+    fn generate_chat_log(
+        chain_id: &Hash256,
+        validator_keypair: &[(PublicKey, PrivateKey)],
+        height: BlockHeight,
+        chats: &[(usize, Option<&str>)],
+    ) -> ChatLog {
+        let mut chat_log = ChatLog {
+            height,
+            chats: vec![],
+        };
+        for (i, (author_index, text)) in chats.iter().enumerate() {
+            let chat = Chat {
+                height,
+                timestamp: i as Timestamp + 2,
+                content: match text {
+                    Some(text) => ChatContent::Text(text.to_string()),
+                    None => ChatContent::Ack,
+                },
+                previous_hash: chat_log.chats.last().map(|x| x.to_hash256()),
+            };
+            chat_log.chats.push(SignedChat {
+                signature: TypedSignature::sign(
+                    &chat,
+                    chain_id,
+                    &validator_keypair[*author_index].1,
+                )
+                .unwrap(),
+                chat,
+            });
+        }
+        chat_log
+    }
+
+    #[test]
+    /// Test the case where the chat log is semifinalized by the block proposer.
+    fn correct_chat_log() {
+        let (validator_keypair, reserved_state, mut csv) = setup_test(4);
+        setup_agenda_proof_phase(&validator_keypair, &reserved_state, &mut csv);
+        let chat_log = generate_chat_log(
+            &chain_id(&csv),
+            &validator_keypair,
+            1,
+            &[
+                (1, Some("hello")),
+                (2, Some("hi")),
+                (0, None),
+                (3, Some("bye")),
+                (1, None),
+            ],
+        );
+        let timestamp = chat_log.chats.last().unwrap().chat.timestamp;
+        csv.apply_commit(&Commit::ChatLog(chat_log.clone()))
+            .unwrap();
+        // Only one chat log is allowed for a block.
+        csv.apply_commit(&Commit::ChatLog(chat_log)).unwrap_err();
+        let start_header = csv.header.clone();
+        let commit_merkle_root =
+            BlockHeader::calculate_commit_merkle_root(&csv.commits_for_next_block);
+        // The block must be proposed by the leader who semifinalized the chat log.
+        csv.apply_commit(&generate_block_commit(
+            &chain_id(&csv),
+            &validator_keypair,
+            0,
+            start_header.clone(),
+            timestamp,
+            commit_merkle_root,
+            Hash256::zero(),
+        ))
+        .unwrap_err();
+        csv.apply_commit(&generate_block_commit(
+            &chain_id(&csv),
+            &validator_keypair,
+            1,
+            start_header,
+            timestamp,
+            commit_merkle_root,
+            Hash256::zero(),
+        ))
+        .unwrap();
+    }
+
+    #[test]
+    /// Test the case where the chat log is invalid because it is not a chain.
+    fn invalid_chat_log_with_broken_chain() {
+        let (validator_keypair, reserved_state, mut csv) = setup_test(4);
+        setup_agenda_proof_phase(&validator_keypair, &reserved_state, &mut csv);
+        let mut chat_log = generate_chat_log(
+            &chain_id(&csv),
+            &validator_keypair,
+            1,
+            &[(1, Some("hello")), (2, Some("hi")), (0, None)],
+        );
+        chat_log.chats.remove(1);
+        csv.apply_commit(&Commit::ChatLog(chat_log)).unwrap_err();
+    }
+
+    #[test]
+    /// Test the case where the chat log is invalid because it does not end with an ack.
+    fn invalid_chat_log_without_ack() {
+        let (validator_keypair, reserved_state, mut csv) = setup_test(4);
+        setup_agenda_proof_phase(&validator_keypair, &reserved_state, &mut csv);
+        let chat_log = generate_chat_log(
+            &chain_id(&csv),
+            &validator_keypair,
+            1,
+            &[(1, Some("hello")), (0, None), (2, Some("hi"))],
+        );
+        csv.apply_commit(&Commit::ChatLog(chat_log)).unwrap_err();
+    }
+
+    #[test]
+    /// Test the case where the chat log is invalid because a chat is signed by a non-member.
+    fn invalid_chat_log_with_invalid_signer() {
+        let (mut validator_keypair, reserved_state, mut csv) = setup_test(4);
+        setup_agenda_proof_phase(&validator_keypair, &reserved_state, &mut csv);
+        validator_keypair.push(generate_keypair([42]));
+        let chat_log = generate_chat_log(
+            &chain_id(&csv),
+            &validator_keypair,
+            1,
+            &[(4, Some("hello")), (0, None)],
+        );
+        csv.apply_commit(&Commit::ChatLog(chat_log)).unwrap_err();
+    }
 }
@@ -8,10 +8,11 @@ fn basic1() {
     let member_number = 10;
     let (rs, keys) = test_utils::generate_standard_genesis(member_number);
     let genesis_info = rs.genesis_info.clone();
+    let chain_id = genesis_info.chain_id();
     let genesis_header = rs.genesis_info.header.clone();
 
     let mut csv = CommitSequenceVerifier::new(genesis_header.clone(), rs.clone()).unwrap();
-    let mut light_client = LightClient::new(genesis_header);
+    let mut light_client = LightClient::new(genesis_header, chain_id);
 
     let tx = Transaction {
         author: "doesn't matter".to_owned(),
@@ -33,7 +34,7 @@ fn basic1() {
         agenda_hash: agenda.to_hash256(),
         proof: keys
             .iter()
-            .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+            .map(|(_, private_key)| TypedSignature::sign(&agenda, &chain_id, private_key).unwrap())
             .collect::<Vec<_>>(),
         timestamp: 0,
     }))
@@ -55,7 +56,9 @@ fn basic1() {
         .unwrap();
     let fp = keys
         .iter()
-        .map(|(_, private_key)| TypedSignature::sign(&block_header, private_key).unwrap())
+        .map(|(_, private_key)| {
+            TypedSignature::sign(&block_header, &chain_id, private_key).unwrap()
+        })
         .collect::<Vec<_>>();
     csv.verify_last_header_finalization(&fp).unwrap();
     light_client.update(block_header, fp).unwrap();
@@ -77,11 +80,12 @@ fn basic2() {
     let (reserved_state, keys): (ReservedState, Vec<(PublicKey, PrivateKey)>) =
         test_utils::generate_delegated_genesis(member_number, true);
     let genesis_info = reserved_state.genesis_info.clone();
+    let chain_id = genesis_info.chain_id();
     let genesis_header = reserved_state.genesis_info.header.clone();
 
     let mut csv =
         CommitSequenceVerifier::new(genesis_header.clone(), reserved_state.clone()).unwrap();
-    let mut light_client = LightClient::new(genesis_header);
+    let mut light_client = LightClient::new(genesis_header, chain_id);
 
     let tx = Transaction {
         author: "doesn't matter".to_owned(),
@@ -103,7 +107,7 @@ fn basic2() {
         agenda_hash: agenda.to_hash256(),
         proof: keys[1..]
             .iter()
-            .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+            .map(|(_, private_key)| TypedSignature::sign(&agenda, &chain_id, private_key).unwrap())
             .collect::<Vec<_>>(),
         timestamp: 0,
     }))
@@ -125,7 +129,9 @@ fn basic2() {
         .unwrap();
     let fp = keys
         .iter()
-        .map(|(_, private_key)| TypedSignature::sign(&block_header, private_key).unwrap())
+        .map(|(_, private_key)| {
+            TypedSignature::sign(&block_header, &chain_id, private_key).unwrap()
+        })
         .collect::<Vec<_>>();
     csv.verify_last_header_finalization(&fp).unwrap();
     light_client.update(block_header, fp).unwrap();
@@ -145,11 +151,12 @@ fn basic3() {
     let member_number = 10;
     let (rs, keys) = test_utils::generate_standard_genesis(member_number);
     let genesis_info = rs.genesis_info.clone();
+    let chain_id = genesis_info.chain_id();
     let genesis_header = rs.genesis_info.header.clone();
 
     let (height, timestamp) = (1, 0);
     let mut csv = CommitSequenceVerifier::new(genesis_header.clone(), rs.clone()).unwrap();
-    let mut light_client = LightClient::new(genesis_header);
+    let mut light_client = LightClient::new(genesis_header, chain_id);
 
     let tx = Transaction {
         author: "doesn't matter".to_owned(),
@@ -173,7 +180,7 @@ fn basic3() {
         agenda_hash: agenda.to_hash256(),
         proof: keys[1..]
             .iter()
-            .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+            .map(|(_, private_key)| TypedSignature::sign(&agenda, &chain_id, private_key).unwrap())
             .collect::<Vec<_>>(),
         timestamp,
     }))
@@ -186,9 +193,10 @@ fn basic3() {
         block_height: height,
         timestamp,
         chain_name: "PDAO-mainnet".to_owned(),
+        conditions: vec![],
     };
     let tx_delegate = ExtraAgendaTransaction::Delegate(TxDelegate {
-        proof: TypedSignature::sign(&data, &keys[0].1).unwrap(),
+        proof: TypedSignature::sign(&data, &chain_id, &keys[0].1).unwrap(),
         data,
     });
     csv.apply_commit(&Commit::ExtraAgendaTransaction(tx_delegate))
@@ -212,7 +220,9 @@ fn basic3() {
 
     let fp = keys
         .iter()
-        .map(|(_, private_key)| TypedSignature::sign(&block_header, private_key).unwrap())
+        .map(|(_, private_key)| {
+            TypedSignature::sign(&block_header, &chain_id, private_key).unwrap()
+        })
         .collect::<Vec<_>>();
     csv.verify_last_header_finalization(&fp).unwrap();
     light_client
@@ -259,7 +269,10 @@ fn basic3() {
             agenda_hash: agenda.to_hash256(),
             proof: keys
                 .iter()
-                .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+                .map(
+                    |(_, private_key)| TypedSignature::sign(&agenda, &chain_id, private_key)
+                        .unwrap()
+                )
                 .collect::<Vec<_>>(),
             timestamp
         })),
@@ -271,7 +284,7 @@ fn basic3() {
         agenda_hash: agenda.to_hash256(),
         proof: keys[1..]
             .iter()
-            .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+            .map(|(_, private_key)| TypedSignature::sign(&agenda, &chain_id, private_key).unwrap())
             .collect::<Vec<_>>(),
         timestamp,
     }))
@@ -284,7 +297,7 @@ fn basic3() {
         chain_name: "PDAO-mainnet".to_owned(),
     };
     let tx_delegate = ExtraAgendaTransaction::Undelegate(TxUndelegate {
-        proof: TypedSignature::sign(&data, &keys[0].1).unwrap(),
+        proof: TypedSignature::sign(&data, &chain_id, &keys[0].1).unwrap(),
         data,
     });
     csv.apply_commit(&Commit::ExtraAgendaTransaction(tx_delegate))
@@ -307,7 +320,9 @@ fn basic3() {
         .unwrap();
     let fp = keys
         .iter()
-        .map(|(_, private_key)| TypedSignature::sign(&block_header, private_key).unwrap())
+        .map(|(_, private_key)| {
+            TypedSignature::sign(&block_header, &chain_id, private_key).unwrap()
+        })
         .collect::<Vec<_>>();
     csv.verify_last_header_finalization(&fp).unwrap();
     light_client.update(block_header, fp).unwrap();
@@ -321,3 +336,165 @@ fn basic3() {
         Some(&(keys[0].0.clone(), 1))
     );
 }
+
+#[test]
+fn batch_transaction_commitments() {
+    let member_number = 4;
+    let (rs, keys) = test_utils::generate_standard_genesis(member_number);
+    let genesis_info = rs.genesis_info.clone();
+    let chain_id = genesis_info.chain_id();
+    let genesis_header = rs.genesis_info.header.clone();
+
+    let mut csv = CommitSequenceVerifier::new(genesis_header.clone(), rs.clone()).unwrap();
+    let mut light_client = LightClient::new(genesis_header, chain_id);
+
+    // Two identical transactions are included, which must be provable separately.
+    let txs = ["commit 1", "commit 2", "commit 1"]
+        .iter()
+        .map(|head| Transaction {
+            author: "doesn't matter".to_owned(),
+            timestamp: 0,
+            head: head.to_string(),
+            body: "".to_owned(),
+            diff: Diff::None,
+        })
+        .collect::<Vec<_>>();
+    for tx in &txs {
+        csv.apply_commit(&Commit::Transaction(tx.clone())).unwrap();
+    }
+    let agenda = Agenda {
+        height: 1,
+        author: rs.query_name(&keys[0].0).unwrap(),
+        timestamp: 0,
+        transactions_hash: Agenda::calculate_transactions_hash(&txs),
+    };
+    csv.apply_commit(&Commit::Agenda(agenda.clone())).unwrap();
+    csv.apply_commit(&Commit::AgendaProof(AgendaProof {
+        height: 1,
+        agenda_hash: agenda.to_hash256(),
+        proof: keys
+            .iter()
+            .map(|(_, private_key)| TypedSignature::sign(&agenda, &chain_id, private_key).unwrap())
+            .collect::<Vec<_>>(),
+        timestamp: 0,
+    }))
+    .unwrap();
+    let block_header = BlockHeader {
+        author: keys[0].0.clone(),
+        prev_block_finalization_proof: genesis_info.genesis_proof,
+        previous_hash: genesis_info.header.to_hash256(),
+        height: 1,
+        timestamp: 0,
+        commit_merkle_root: BlockHeader::calculate_commit_merkle_root(
+            &csv.get_total_commits()[1..],
+        ),
+        repository_merkle_root: Hash256::zero(),
+        validator_set: genesis_info.header.validator_set.clone(),
+        version: genesis_info.header.version,
+    };
+    csv.apply_commit(&Commit::Block(block_header.clone()))
+        .unwrap();
+    let fp = keys
+        .iter()
+        .map(|(_, private_key)| {
+            TypedSignature::sign(&block_header, &chain_id, private_key).unwrap()
+        })
+        .collect::<Vec<_>>();
+    csv.verify_last_header_finalization(&fp).unwrap();
+    light_client.update(block_header, fp).unwrap();
+    let commits = csv.get_total_commits();
+    let merkle_tree = OneshotMerkleTree::create(
+        commits[1..=(commits.len() - 2)]
+            .iter()
+            .map(|c| c.to_hash256())
+            .collect(),
+    );
+    for (index, tx) in txs.iter().enumerate() {
+        let merkle_proof = merkle_tree.create_merkle_proof_at(index).unwrap();
+        assert!(light_client.verify_transaction_commitment(tx, 1, merkle_proof));
+    }
+    let multiproof = merkle_tree.create_multiproof(&[0, 1, 2]).unwrap();
+    assert!(light_client.verify_transaction_commitments(&txs, 1, multiproof.clone()));
+    assert!(!light_client.verify_transaction_commitments(&txs[..2], 1, multiproof.clone()));
+    assert!(!light_client.verify_transaction_commitments(&txs, 0, multiproof));
+    let multiproof = merkle_tree.create_multiproof(&[0, 2]).unwrap();
+    assert!(light_client.verify_transaction_commitments(
+        &[txs[0].clone(), txs[2].clone()],
+        1,
+        multiproof
+    ));
+}
+
+#[test]
+fn mixed_signature_schemes() {
+    let keys = (0..6u8)
+        .map(|i| {
+            let scheme = if i % 2 == 0 {
+                SignatureScheme::Secp256k1
+            } else {
+                SignatureScheme::Ed25519
+            };
+            generate_keypair_with_scheme(scheme, [i])
+        })
+        .collect::<Vec<_>>();
+    let rs = test_utils::generate_genesis_with_keys(&keys);
+    let genesis_info = rs.genesis_info.clone();
+    let chain_id = genesis_info.chain_id();
+    let genesis_header = rs.genesis_info.header.clone();
+
+    // A member must declare the type of its key.
+    let mut invalid_rs = rs.clone();
+    invalid_rs.members[1].key_type = SignatureScheme::Secp256k1;
+    CommitSequenceVerifier::new(genesis_header.clone(), invalid_rs).unwrap_err();
+
+    let mut csv = CommitSequenceVerifier::new(genesis_header.clone(), rs.clone()).unwrap();
+    let mut light_client = LightClient::new(genesis_header, chain_id);
+    let agenda = Agenda {
+        height: 1,
+        author: rs.query_name(&keys[1].0).unwrap(),
+        timestamp: 0,
+        transactions_hash: Agenda::calculate_transactions_hash(&[]),
+    };
+    csv.apply_commit(&Commit::Agenda(agenda.clone())).unwrap();
+    csv.apply_commit(&Commit::AgendaProof(AgendaProof {
+        height: 1,
+        agenda_hash: agenda.to_hash256(),
+        proof: keys
+            .iter()
+            .map(|(_, private_key)| TypedSignature::sign(&agenda, &chain_id, private_key).unwrap())
+            .collect::<Vec<_>>(),
+        timestamp: 0,
+    }))
+    .unwrap();
+    let block_header = BlockHeader {
+        author: keys[1].0.clone(),
+        prev_block_finalization_proof: genesis_info.genesis_proof,
+        previous_hash: genesis_info.header.to_hash256(),
+        height: 1,
+        timestamp: 0,
+        commit_merkle_root: BlockHeader::calculate_commit_merkle_root(
+            &csv.get_total_commits()[1..],
+        ),
+        repository_merkle_root: Hash256::zero(),
+        validator_set: genesis_info.header.validator_set.clone(),
+        version: genesis_info.header.version,
+    };
+    csv.apply_commit(&Commit::Block(block_header.clone()))
+        .unwrap();
+    let mut fp = keys
+        .iter()
+        .map(|(_, private_key)| {
+            TypedSignature::sign(&block_header, &chain_id, private_key).unwrap()
+        })
+        .collect::<Vec<_>>();
+    csv.verify_last_header_finalization(&fp).unwrap();
+    light_client
+        .update(block_header.clone(), fp.clone())
+        .unwrap();
+
+    // A signature is checked under the scheme of its signer.
+    let mut light_client = LightClient::new(rs.genesis_info.header.clone(), chain_id);
+    fp[1] = TypedSignature::new(fp[0].get_raw_signature(), keys[1].0.clone());
+    csv.verify_last_header_finalization(&fp).unwrap_err();
+    light_client.update(block_header, fp).unwrap_err();
+}
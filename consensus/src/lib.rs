@@ -5,28 +5,55 @@ use eyre::eyre;
 use serde::{Deserialize, Serialize};
 use simperby_common::{
     crypto::{Hash256, PublicKey},
-    serde_spb, BlockHeader, BlockHeight, ConsensusRound, FinalizationProof, PrivateKey, Signature,
-    Timestamp, ToHash256, TypedSignature, VotingPower,
+    serde_spb, BlockHeader, BlockHeight, ConsensusRound, ConsensusVote, ConsensusVoteKind,
+    FinalizationProof, PrivateKey, ReservedState, Signature, SignedConsensusVote, Timestamp,
+    ToHash256, TxReport, TypedSignature, VotingPower,
 };
 use simperby_network::{
     dms::{DistributedMessageSet as DMS, Message, MessageFilter},
     primitives::{GossipNetwork, Storage},
 };
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot, RwLock};
 use vetomint::*;
 
+mod wal;
+
+pub use wal::{LoggedVote, WriteAheadLog};
+
 pub type ConsensusParameters = ConsensusParams;
+pub use vetomint::{
+    ConsensusStateSnapshot, ConsensusStep, EventRecord, EventTrace, LeaderSchedule, ReplayError,
+    Replayer, StateDifference, TimeoutGrowth, VoteTally,
+};
 pub type Error = eyre::Error;
 const STATE_FILE_NAME: &str = "state.json";
+/// The file of the `EventTrace` of the current height, if recording.
+///
+/// It is in JSON lines: the initial state comes first,
+/// followed by the records of each `progress()` in a line.
+pub const TRACE_FILE_NAME: &str = "trace.jsonl";
+/// The file of the `WriteAheadLog`, which survives any reset of the state of the same height.
+const WAL_FILE_NAME: &str = "wal.json";
 pub type Nil = ();
 const NIL_BLOCK_PROPOSAL_INDEX: BlockIdentifier = BlockIdentifier::MAX;
+/// The maximum number of the messages kept in the pending pool, shared equally by the validators.
+const MAX_PENDING_MESSAGES: usize = 1024;
+/// The maximum number of the messages of a validator in a round kept in the pending pool,
+/// which is enough for an honest one: a proposal, a prevote and a precommit.
+const MAX_PENDING_MESSAGES_PER_ROUND: usize = 3;
 
-/// The signed `String` is constructed by `format!("{}-prevote", block_hash)`.
-pub type Prevote = TypedSignature<String>;
-/// This can be verified by `precommit.get_raw_signature().verify(block_hash, signer)`
+/// The signed `ConsensusVote` is constructed by `generate_vote()`.
+pub type Prevote = TypedSignature<ConsensusVote>;
+/// This can be verified by `precommit.verify_hash(block_hash, chain_id)`
 /// where `block_hash` is the hash of `BlockHeader`.
 pub type Precommit = TypedSignature<BlockHeader>;
+/// The signed `ConsensusVote` is constructed by `generate_vote()`.
+///
+/// Unlike `Precommit`, which is for the finalization proof,
+/// it is bound to the round so that it can be an evidence of a double precommit.
+pub type PrecommitVote = TypedSignature<ConsensusVote>;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct State {
@@ -41,11 +68,26 @@ pub struct State {
     /// The set of the block hashes that have been verified.
     pub verified_block_hashes: Vec<Hash256>,
     /// The set of the block hashes that are rejected by the user.
+    ///
+    /// A proposal of any of these is fed to Vetomint with `favor: false`.
     pub vetoed_block_hashes: Vec<Hash256>,
+    /// The block that this node wants to propose, which can't be vetoed.
+    #[serde(default)]
+    pub proposal_candidate: Option<Hash256>,
 
     /// If true, any operation on this instance will fail; the user must
     /// run `new()` with the next height info.
     pub finalized: bool,
+
+    /// The blocks (among `verified_block_hashes`) to be attached to the proposals of this node.
+    #[serde(default)]
+    pub proposable_blocks: Vec<ProposedBlock>,
+
+    /// Whether the Vetomint events are being recorded to `TRACE_FILE_NAME`.
+    ///
+    /// It is carried over to the next height, whose recording starts anew.
+    #[serde(default)]
+    pub trace_recording: bool,
 }
 
 pub fn generate_dms_key(header: &BlockHeader) -> String {
@@ -56,6 +98,21 @@ pub fn generate_dms_key(header: &BlockHeader) -> String {
     )
 }
 
+/// Returns the vote of the given step for the height next to `header`.
+pub fn generate_vote(
+    kind: ConsensusVoteKind,
+    header: &BlockHeader,
+    round: ConsensusRound,
+    block_hash: Option<Hash256>,
+) -> ConsensusVote {
+    ConsensusVote {
+        kind,
+        height: header.height + 1,
+        round,
+        block_hash,
+    }
+}
+
 fn generate_height_info(
     header: &BlockHeader,
     consensus_params: ConsensusParams,
@@ -80,6 +137,85 @@ fn generate_height_info(
     Ok(info)
 }
 
+/// Derives the leader schedule from `ReservedState::consensus_leader_order`.
+///
+/// The first leader leads for `first_leader_repeat` rounds and the others for a round each,
+/// in the order, skipping those not in the validator set of the header.
+pub fn derive_leader_schedule(
+    reserved_state: &ReservedState,
+    header: &BlockHeader,
+    first_leader_repeat: usize,
+) -> Result<LeaderSchedule, Error> {
+    let schedule: Vec<_> = reserved_state
+        .consensus_leader_order
+        .iter()
+        .filter_map(|name| reserved_state.query_public_key(name))
+        .filter_map(|public_key| {
+            header
+                .validator_set
+                .iter()
+                .position(|(validator, _)| *validator == public_key)
+        })
+        .enumerate()
+        .map(|(i, index)| {
+            (
+                index,
+                if i == 0 {
+                    first_leader_repeat.max(1)
+                } else {
+                    1
+                },
+            )
+        })
+        .collect();
+    if schedule.is_empty() {
+        return Err(eyre!("no validator in the consensus leader order"));
+    }
+    Ok(LeaderSchedule::Explicit(schedule))
+}
+
+/// Returns the consensus parameters for the height next to the given header,
+/// converting the ones stored in the reserved state.
+pub fn derive_consensus_params(
+    reserved_state: &ReservedState,
+    header: &BlockHeader,
+) -> Result<ConsensusParams, Error> {
+    use simperby_common::reserved;
+    let params = &reserved_state.consensus_params;
+    let timeout_growth = match params.timeout_growth {
+        reserved::TimeoutGrowth::Constant => TimeoutGrowth::Constant,
+        reserved::TimeoutGrowth::Linear {
+            increment_ms,
+            max_timeout_ms,
+        } => TimeoutGrowth::Linear {
+            increment_ms,
+            max_timeout_ms,
+        },
+        reserved::TimeoutGrowth::Exponential {
+            factor_percent,
+            max_timeout_ms,
+        } => TimeoutGrowth::Exponential {
+            factor_percent,
+            max_timeout_ms,
+        },
+    };
+    let leader_schedule = match &params.leader_schedule {
+        reserved::LeaderSchedule::LeaderOrder => {
+            derive_leader_schedule(reserved_state, header, params.repeat_round_for_first_leader)?
+        }
+        reserved::LeaderSchedule::FirstLeaderRepeated => LeaderSchedule::FirstLeaderRepeated,
+        reserved::LeaderSchedule::Explicit(schedule) => LeaderSchedule::Explicit(schedule.clone()),
+        reserved::LeaderSchedule::WeightedRoundRobin => LeaderSchedule::WeightedRoundRobin,
+    };
+    Ok(ConsensusParams {
+        timeout_ms: params.timeout_ms,
+        repeat_round_for_first_leader: params.repeat_round_for_first_leader,
+        timeout_growth,
+        wall_clock_rounds: params.wall_clock_rounds,
+        leader_schedule,
+    })
+}
+
 async fn commit_state(state_storage: &mut impl Storage, state: &State) -> Result<(), Error> {
     state_storage
         .add_or_overwrite_file(STATE_FILE_NAME, serde_spb::to_string(state).unwrap())
@@ -87,6 +223,61 @@ async fn commit_state(state_storage: &mut impl Storage, state: &State) -> Result
     Ok(())
 }
 
+fn new_trace(state: &State) -> EventTrace {
+    EventTrace {
+        initial_state: state.vetomint.vetomint.clone(),
+        records: Vec::new(),
+    }
+}
+
+async fn commit_wal(state_storage: &mut impl Storage, wal: &WriteAheadLog) -> Result<(), Error> {
+    state_storage
+        .add_or_overwrite_file(WAL_FILE_NAME, serde_spb::to_string(wal).unwrap())
+        .await?;
+    Ok(())
+}
+
+/// Writes a new trace, starting from its initial state.
+async fn commit_trace(state_storage: &mut impl Storage, trace: &EventTrace) -> Result<(), Error> {
+    let mut content = format!("{}\n", serde_json::to_string(&trace.initial_state)?);
+    if !trace.records.is_empty() {
+        content += &format!("{}\n", serde_json::to_string(&trace.records)?);
+    }
+    state_storage
+        .add_or_overwrite_file(TRACE_FILE_NAME, content)
+        .await?;
+    Ok(())
+}
+
+/// Parses the trace, ignoring the last line if a crash has cut it off.
+fn parse_trace(raw_trace: &str) -> Result<EventTrace, Error> {
+    let complete = &raw_trace[..raw_trace.rfind('\n').map_or(0, |i| i + 1)];
+    let mut lines = complete.lines();
+    let initial_state = serde_json::from_str(
+        lines
+            .next()
+            .ok_or_else(|| eyre!("the trace has no initial state"))?,
+    )?;
+    let mut records = Vec::new();
+    for line in lines {
+        records.extend(serde_json::from_str::<Vec<EventRecord>>(line)?);
+    }
+    Ok(EventTrace {
+        initial_state,
+        records,
+    })
+}
+
+/// Removes the last line of the trace if a crash has cut it off,
+/// so that the records appended after it are read.
+async fn repair_trace(state_storage: &mut impl Storage) -> Result<(), Error> {
+    let raw_trace = state_storage.read_file(TRACE_FILE_NAME).await?;
+    if !raw_trace.is_empty() && !raw_trace.ends_with('\n') {
+        commit_trace(state_storage, &parse_trace(&raw_trace)?).await?;
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VetomintWrapper {
     pub vetomint: Vetomint,
@@ -113,17 +304,28 @@ impl VetomintWrapper {
     }
 }
 
+/// The block attached to a proposal, for the nodes which have not fetched it yet.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProposedBlock {
+    pub header: BlockHeader,
+    /// The hash of the agenda-proof commit that the block is built on, if any.
+    pub agenda_proof_hash: Option<Hash256>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ConsensusMessage {
     Proposal {
         round: ConsensusRound,
         valid_round: Option<ConsensusRound>,
         block_hash: Hash256,
+        /// If present, its header must be of `block_hash`.
+        #[serde(default)]
+        block: Option<ProposedBlock>,
     },
     NonNilPreVoted(ConsensusRound, Hash256, Prevote),
-    NonNilPreCommitted(ConsensusRound, Hash256, Precommit),
-    NilPreVoted(ConsensusRound),
-    NilPreCommitted(ConsensusRound),
+    NonNilPreCommitted(ConsensusRound, Hash256, Precommit, PrecommitVote),
+    NilPreVoted(ConsensusRound, Prevote),
+    NilPreCommitted(ConsensusRound, PrecommitVote),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -134,74 +336,261 @@ pub enum ProgressResult {
     NilPreVoted(ConsensusRound, Timestamp),
     NilPreCommitted(ConsensusRound, Timestamp),
     Finalized(Hash256, Timestamp, FinalizationProof),
-    ViolationReported(PublicKey, String, Timestamp),
+    /// It holds the evidence as a `Box` to flatten the variant size.
+    ViolationReported(Box<ViolationEvidence>, Timestamp),
+    /// The node refused to sign the first vote, as it conflicts with the second one
+    /// which was logged before (possibly right before a crash).
+    DoubleSignPrevented(LoggedVote, LoggedVote, Timestamp),
+}
+
+/// The conflicting messages of a validator, reported by Vetomint.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ViolationEvidence {
+    pub violator: PublicKey,
+    /// The description of the violation.
+    pub violation: String,
+    /// The message taken into the consensus.
+    ///
+    /// This and `second` are `None` if missing in the DMS (e.g., cleared meanwhile),
+    /// leaving the evidence partial.
+    pub first: Option<ConsensusMessage>,
+    /// The message conflicting with `first`, which was not taken.
+    pub second: Option<ConsensusMessage>,
+}
+
+impl ViolationEvidence {
+    /// Makes a report of the evidence, or returns `None` if it is of proposals, not of votes,
+    /// or if it is partial.
+    ///
+    /// `header` is the one that the consensus is performing on.
+    pub fn to_report(&self, header: &BlockHeader, timestamp: Timestamp) -> Option<TxReport> {
+        Some(TxReport {
+            first_vote: self.first.as_ref()?.get_vote(header)?,
+            second_vote: self.second.as_ref()?.get_vote(header)?,
+            timestamp,
+        })
+    }
+}
+
+impl ConsensusMessage {
+    /// Returns the block that this message is for, or `None` if it is a nil vote.
+    pub fn get_block_hash(&self) -> Option<Hash256> {
+        match self {
+            ConsensusMessage::Proposal { block_hash, .. }
+            | ConsensusMessage::NonNilPreVoted(_, block_hash, _)
+            | ConsensusMessage::NonNilPreCommitted(_, block_hash, ..) => Some(*block_hash),
+            ConsensusMessage::NilPreVoted(..) | ConsensusMessage::NilPreCommitted(..) => None,
+        }
+    }
+
+    /// Returns the round that this message is for.
+    pub fn get_round(&self) -> ConsensusRound {
+        match self {
+            ConsensusMessage::Proposal { round, .. }
+            | ConsensusMessage::NonNilPreVoted(round, ..)
+            | ConsensusMessage::NonNilPreCommitted(round, ..)
+            | ConsensusMessage::NilPreVoted(round, ..)
+            | ConsensusMessage::NilPreCommitted(round, ..) => *round,
+        }
+    }
+
+    /// Returns the signed vote of the message, or `None` if it is a proposal.
+    ///
+    /// `header` is the one that the consensus is performing on.
+    /// Two conflicting votes of a validator make a `TxReport`.
+    pub fn get_vote(&self, header: &BlockHeader) -> Option<SignedConsensusVote> {
+        let (kind, round, block_hash, signature) = match self {
+            ConsensusMessage::Proposal { .. } => return None,
+            ConsensusMessage::NonNilPreVoted(round, block_hash, signature) => (
+                ConsensusVoteKind::Prevote,
+                round,
+                Some(*block_hash),
+                signature,
+            ),
+            ConsensusMessage::NonNilPreCommitted(round, block_hash, _, signature) => (
+                ConsensusVoteKind::Precommit,
+                round,
+                Some(*block_hash),
+                signature,
+            ),
+            ConsensusMessage::NilPreVoted(round, signature) => {
+                (ConsensusVoteKind::Prevote, round, None, signature)
+            }
+            ConsensusMessage::NilPreCommitted(round, signature) => {
+                (ConsensusVoteKind::Precommit, round, None, signature)
+            }
+        };
+        Some(SignedConsensusVote {
+            vote: generate_vote(kind, header, *round, block_hash),
+            signature: signature.clone(),
+        })
+    }
+}
+
+/// A message in the pending pool of the message filter.
+struct PendingMessage {
+    message: Message,
+    round: ConsensusRound,
+    /// The block attached to the message (if a proposal), whose header has been verified.
+    block: Option<ProposedBlock>,
 }
 
 pub struct ConsensusMessageFilter {
     /// Note that it is even DESIRABLE to use a synchronous lock in the async context
     /// if it is guaranteed that the lock is not held for a long time.
     verified_block_hashes: Arc<parking_lot::RwLock<BTreeSet<Hash256>>>,
+    /// The messages which are valid but for a block not verified yet.
+    pending_messages: Arc<parking_lot::RwLock<BTreeMap<Hash256, PendingMessage>>>,
+    /// The header that the proposed blocks must be built on.
+    block_header: BlockHeader,
     validator_set: BTreeSet<PublicKey>,
+    chain_id: Hash256,
 }
 
 impl MessageFilter for ConsensusMessageFilter {
+    /// Rejects a message for a block not verified yet, but keeps it in the pending pool
+    /// (see `keep_pending()`) so that it is accepted once the block is verified.
     fn filter(&self, message: &Message) -> Result<(), String> {
+        let consensus_message = self.verify_message(message)?;
+        let Some(block_hash) = consensus_message.get_block_hash() else {
+            return Ok(());
+        };
+        if self.verified_block_hashes.read().contains(&block_hash) {
+            return Ok(());
+        }
+        if self.keep_pending(message, consensus_message) {
+            Err(format!(
+                "the block hash is not verified yet (kept pending): {block_hash}"
+            ))
+        } else {
+            Err(format!("the block hash is not verified yet: {block_hash}"))
+        }
+    }
+}
+
+impl ConsensusMessageFilter {
+    /// Keeps the message in the pending pool, returning whether it is kept.
+    ///
+    /// Each validator has an equal share of the pool, filled with its messages of the latest rounds,
+    /// so that one can't push out the messages of the others with made-up ones.
+    fn keep_pending(&self, message: &Message, consensus_message: ConsensusMessage) -> bool {
+        let round = consensus_message.get_round();
+        let hash = message.to_hash256();
+        let signer = message.signature().signer();
+        let mut pending_messages = self.pending_messages.write();
+        if pending_messages.contains_key(&hash) {
+            return true;
+        }
+        let signer_messages: Vec<(Hash256, ConsensusRound)> = pending_messages
+            .iter()
+            .filter(|(_, pending)| pending.message.signature().signer() == signer)
+            .map(|(hash, pending)| (*hash, pending.round))
+            .collect();
+        if signer_messages.iter().filter(|(_, r)| *r == round).count()
+            >= MAX_PENDING_MESSAGES_PER_ROUND
+        {
+            return false;
+        }
+        let share =
+            (MAX_PENDING_MESSAGES / self.validator_set.len()).max(MAX_PENDING_MESSAGES_PER_ROUND);
+        if signer_messages.len() >= share {
+            // Evicts the oldest round of the signer, if older than the message.
+            let oldest_round = signer_messages
+                .iter()
+                .map(|(_, r)| *r)
+                .min()
+                .expect("the share is not empty");
+            if oldest_round >= round {
+                return false;
+            }
+            for (hash, r) in signer_messages {
+                if r == oldest_round {
+                    pending_messages.remove(&hash);
+                }
+            }
+        }
+        pending_messages.insert(
+            hash,
+            PendingMessage {
+                message: message.clone(),
+                round,
+                block: match consensus_message {
+                    ConsensusMessage::Proposal { block, .. } => block,
+                    _ => None,
+                },
+            },
+        );
+        true
+    }
+
+    /// Verifies everything of the message but whether its block is verified.
+    fn verify_message(&self, message: &Message) -> Result<ConsensusMessage, String> {
         let signer = message.signature().signer();
         if !self.validator_set.contains(signer) {
             return Err("the signer is not in the validator set".to_string());
         }
         let consensus_message =
             serde_spb::from_str::<ConsensusMessage>(message.data()).map_err(|e| e.to_string())?;
-        match consensus_message {
-            ConsensusMessage::Proposal { block_hash, .. } => self.verify_block_hash(block_hash),
-            ConsensusMessage::NonNilPreVoted(_, block_hash, prevote) => {
-                if signer != prevote.signer() {
-                    return Err("DMS message signer does not match with prevote signer".to_string());
+        match &consensus_message {
+            ConsensusMessage::Proposal {
+                block_hash,
+                block: Some(block),
+                ..
+            } => {
+                if block.header.to_hash256() != *block_hash {
+                    return Err("the attached block header does not match the hash".to_string());
                 }
-                let original_data = format!("{}-{}", block_hash, "prevote");
-                prevote.verify(&original_data).map_err(|e| e.to_string())?;
-                self.verify_block_hash(block_hash)
+                simperby_common::verify::verify_header_to_header(
+                    &self.block_header,
+                    &block.header,
+                    &self.chain_id,
+                )
+                .map_err(|e| format!("invalid attached block header: {e}"))?;
             }
-            ConsensusMessage::NonNilPreCommitted(_, block_hash, precommit) => {
+            ConsensusMessage::NonNilPreCommitted(_, block_hash, precommit, _) => {
                 if signer != precommit.signer() {
                     return Err(
                         "DMS message signer does not match with precommit signer".to_string()
                     );
                 }
                 precommit
-                    .get_raw_signature()
-                    .verify(block_hash, signer)
+                    .verify_hash(*block_hash, &self.chain_id)
                     .map_err(|e| e.to_string())?;
-                self.verify_block_hash(block_hash)
             }
-            _ => Ok(()),
+            _ => (),
         }
-    }
-}
-
-impl ConsensusMessageFilter {
-    fn verify_block_hash(&self, block_hash: Hash256) -> Result<(), String> {
-        if self.verified_block_hashes.read().contains(&block_hash) {
-            Ok(())
-        } else {
-            Err(format!("the block hash is not verified yet: {block_hash}"))
+        if let Some(vote) = consensus_message.get_vote(&self.block_header) {
+            if signer != vote.signature.signer() {
+                return Err("DMS message signer does not match with vote signer".to_string());
+            }
+            vote.signature
+                .verify(&vote.vote, &self.chain_id)
+                .map_err(|e| e.to_string())?;
         }
+        Ok(consensus_message)
     }
 }
 
 pub struct Consensus<N: GossipNetwork, S: Storage> {
-    /// The distributed consensus message set.
-    dms: DMS<N, S>,
+    /// The distributed consensus message set, shared with the serving tasks while serving.
+    dms: Arc<RwLock<DMS<N, S>>>,
     /// The local storage for the consensus state.
     state_storage: S,
     /// The cache of the consensus state.
     state: State,
+    /// The votes of this node, which are committed to the storage before being signed,
+    /// unlike the `state` which is committed after.
+    wal: WriteAheadLog,
     /// The set of the block hashes that have been verified, shared by the message filter.
     ///
     /// Note that there is the exactly same copy in the `state`.
     verified_block_hashes: Arc<parking_lot::RwLock<BTreeSet<Hash256>>>,
+    /// The pending pool of the message filter, which is kept only in memory.
+    pending_messages: Arc<parking_lot::RwLock<BTreeMap<Hash256, PendingMessage>>>,
     /// (If participated) the private key of this node
     this_node_key: Option<PrivateKey>,
+    /// The channels to which every `ProgressResult` is sent.
+    subscribers: Vec<mpsc::UnboundedSender<ProgressResult>>,
 }
 
 impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
@@ -224,12 +613,26 @@ impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
             round_zero_timestamp,
             this_node_key.clone().unwrap(),
         )?;
-        let state = if let Ok(raw_state) = state_storage.read_file(STATE_FILE_NAME).await {
+        // The votes of this height must be kept in any case; a broken log is not trusted to be empty.
+        let wal = match state_storage.read_file(WAL_FILE_NAME).await {
+            Ok(raw_wal) => Some(serde_spb::from_str::<WriteAheadLog>(&raw_wal)?),
+            Err(_) => None,
+        }
+        .filter(|wal| wal.height == block_header.height)
+        .unwrap_or_else(|| WriteAheadLog::new(block_header.height));
+        let mut state = if let Ok(raw_state) = state_storage.read_file(STATE_FILE_NAME).await {
             let state: State = serde_spb::from_str(&raw_state)?;
             if block_header != state.block_header {
+                let new_state = State {
+                    trace_recording: state.trace_recording,
+                    ..new_state
+                };
                 dms.clear(generate_dms_key(&block_header)).await?;
                 state_storage.remove_all_files().await?;
                 commit_state(&mut state_storage, &new_state).await?;
+                if new_state.trace_recording {
+                    commit_trace(&mut state_storage, &new_trace(&new_state)).await?;
+                }
                 new_state
             } else {
                 state
@@ -240,27 +643,40 @@ impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
             commit_state(&mut state_storage, &new_state).await?;
             new_state
         };
+        commit_wal(&mut state_storage, &wal).await?;
+        if state.trace_recording {
+            repair_trace(&mut state_storage).await?;
+            state.vetomint.vetomint.start_recording();
+        }
         let verified_block_hashes = Arc::new(parking_lot::RwLock::new(BTreeSet::from_iter(
             state.verified_block_hashes.iter().cloned(),
         )));
+        let pending_messages = Arc::new(parking_lot::RwLock::new(BTreeMap::new()));
         dms.set_filter(Arc::new(ConsensusMessageFilter {
             verified_block_hashes: Arc::clone(&verified_block_hashes),
+            pending_messages: Arc::clone(&pending_messages),
+            block_header: state.block_header.clone(),
             validator_set: state
                 .block_header
                 .validator_set
                 .iter()
                 .map(|(pk, _)| pk.clone())
                 .collect(),
+            chain_id: dms.get_chain_id(),
         }));
         Ok(Self {
-            dms,
+            dms: Arc::new(RwLock::new(dms)),
             state_storage,
             state,
+            wal,
             verified_block_hashes,
+            pending_messages,
             this_node_key,
+            subscribers: Vec::new(),
         })
     }
 
+    /// Registers a verified block, accepting the pending messages for it.
     pub async fn register_verified_block_hash(&mut self, hash: Hash256) -> Result<(), Error> {
         self.abort_if_finalized()?;
         self.state.verified_block_hashes.push(hash);
@@ -268,13 +684,88 @@ impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
         self.state_storage
             .add_or_overwrite_file(STATE_FILE_NAME, serde_spb::to_string(&self.state).unwrap())
             .await?;
-        Ok(())
+        self.accept_pending_messages().await
+    }
+
+    /// Registers a verified block like `register_verified_block_hash()`,
+    /// also attaching it to the proposals of this node.
+    pub async fn register_verified_block(&mut self, block: ProposedBlock) -> Result<(), Error> {
+        self.abort_if_finalized()?;
+        self.state.proposable_blocks.push(block.clone());
+        self.register_verified_block_hash(block.header.to_hash256())
+            .await
+    }
+
+    /// Returns the blocks attached to the pending proposals, whose headers have been verified
+    /// to be built on the current one.
+    ///
+    /// The user should verify each of them against the repository (e.g. its agenda proof)
+    /// and register it with `register_verified_block_hash()`,
+    /// so that the consensus proceeds without fetching the block.
+    pub async fn get_pending_proposed_blocks(&self) -> Result<Vec<ProposedBlock>, Error> {
+        let mut blocks: Vec<ProposedBlock> = Vec::new();
+        for block in self
+            .pending_messages
+            .read()
+            .values()
+            .filter_map(|pending| pending.block.as_ref())
+        {
+            if !blocks.contains(block) {
+                blocks.push(block.clone());
+            }
+        }
+        Ok(blocks)
+    }
+
+    /// Reads the current state of the Vetomint state machine.
+    ///
+    /// The blocks in it are identified by their indices in `get_verified_block_hashes()`.
+    pub async fn read_consensus_state(&self) -> Result<ConsensusStateSnapshot, Error> {
+        Ok(self.state.vetomint.vetomint.get_state_snapshot())
+    }
+
+    /// Starts recording the Vetomint events of this height (and the following heights)
+    /// to `TRACE_FILE_NAME`, from the current state.
+    ///
+    /// It does nothing if already recording.
+    pub async fn start_trace_recording(&mut self) -> Result<(), Error> {
+        if self.state.trace_recording {
+            return Ok(());
+        }
+        self.state.trace_recording = true;
+        self.state.vetomint.vetomint.start_recording();
+        commit_trace(&mut self.state_storage, &new_trace(&self.state)).await?;
+        self.commit_state_to_storage().await
+    }
+
+    /// Reads the recorded trace of this height.
+    pub async fn read_trace(&self) -> Result<EventTrace, Error> {
+        if !self.state.trace_recording {
+            return Err(eyre!("the consensus events are not being recorded"));
+        }
+        parse_trace(&self.state_storage.read_file(TRACE_FILE_NAME).await?)
+    }
+
+    /// Returns the current state, to be compared with a replayed one.
+    pub fn get_state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn get_write_ahead_log(&self) -> &WriteAheadLog {
+        &self.wal
     }
 
-    // Todo: Read public state from the vetomint FSM.
-    // pub async fn read_consensus_state(&self) -> Result<ConsensusState, Error> {
-    //     Ok(self.state.vetomint.state)
-    // }
+    pub fn get_block_header(&self) -> &BlockHeader {
+        &self.state.block_header
+    }
+
+    pub fn get_verified_block_hashes(&self) -> &[Hash256] {
+        &self.state.verified_block_hashes
+    }
+
+    pub fn get_vetoed_block_hashes(&self) -> &[Hash256] {
+        &self.state.vetoed_block_hashes
+    }
 
     pub async fn set_proposal_candidate(
         &mut self,
@@ -286,6 +777,7 @@ impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
         let consensus_event = ConsensusEvent::BlockCandidateUpdated {
             proposal: block_index,
         };
+        self.state.proposal_candidate = Some(block_hash);
         let responses = self
             .state
             .vetomint
@@ -293,12 +785,37 @@ impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
         let result = self
             .process_multiple_responses(responses, timestamp)
             .await?;
+        self.commit_state_to_storage().await?;
+        self.append_trace_records().await?;
         Ok(result)
     }
 
+    /// Vetoes the given block, so that its proposals are not favored from now on.
+    ///
+    /// It does not affect the proposals that have been already processed.
     pub async fn veto_block(&mut self, block_hash: Hash256) -> Result<(), Error> {
         self.abort_if_finalized()?;
-        self.state.vetoed_block_hashes.push(block_hash);
+        if self.state.proposal_candidate == Some(block_hash) {
+            return Err(eyre!(
+                "block {} is set as the proposal candidate of this node",
+                block_hash
+            ));
+        }
+        if !self.state.vetoed_block_hashes.contains(&block_hash) {
+            self.state.vetoed_block_hashes.push(block_hash);
+            self.commit_state_to_storage().await?;
+        }
+        Ok(())
+    }
+
+    /// Withdraws the veto on the given block.
+    pub async fn unveto_block(&mut self, block_hash: Hash256) -> Result<(), Error> {
+        self.abort_if_finalized()?;
+        if !self.state.vetoed_block_hashes.contains(&block_hash) {
+            return Err(eyre!("block {} is not vetoed", block_hash));
+        }
+        self.state.vetoed_block_hashes.retain(|h| *h != block_hash);
+        self.commit_state_to_storage().await?;
         Ok(())
     }
 
@@ -318,6 +835,8 @@ impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
         let result = self
             .process_multiple_responses(responses, timestamp)
             .await?;
+        self.commit_state_to_storage().await?;
+        self.append_trace_records().await?;
         Ok(result)
     }
 
@@ -334,6 +853,8 @@ impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
         self.abort_if_finalized()?;
         let messages = self
             .dms
+            .read()
+            .await
             .read_messages()
             .await?
             .into_iter()
@@ -366,38 +887,83 @@ impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
             .updated_messages
             .extend(messages.into_iter().map(|m| m.to_hash256()));
         self.state.vetomint = vetomint_copy;
-        // Note, Todo: For now, storage errors are not handled.
+        // Even if this fails after the broadcast, the votes are safe in the write-ahead log.
         self.commit_state_to_storage().await?;
+        self.append_trace_records().await?;
         Ok(final_result)
     }
 
     /// Broadcasts all the local messages.
     pub async fn broadcast(&mut self) -> Result<(), Error> {
-        self.dms.broadcast_all().await?;
+        self.dms.read().await.broadcast_all().await?;
         Ok(())
     }
 
     pub async fn fetch(&mut self) -> Result<(), Error> {
-        self.dms.fetch().await
+        self.dms.read().await.fetch().await
     }
 
-    /// Serves the consensus protocol indefinitely.
-    ///
-    /// Note: currently it just returns itself after the given time.
-    /// Todo: Implement the following:
-    /// 1. It does `DistributedMessageSet::serve()`.
-    /// 2. It does `Consensus::progress()` continuously.
-    ///
-    /// Note: Step 2 is likely to be changed because it does not notify the user
-    ///       and automatically progresses.
+    /// Serves the DMS for the given time, without making any progress.
     pub async fn serve(self, time_in_ms: u64) -> Result<Self, Error> {
-        let dms = self.dms.serve(time_in_ms).await?;
-        Ok(Self { dms, ..self })
+        DMS::serve_until(
+            Arc::clone(&self.dms),
+            tokio::time::sleep(std::time::Duration::from_millis(time_in_ms)),
+        )
+        .await?;
+        Ok(self)
+    }
+
+    /// Serves the DMS while calling `progress()` on every `progress_interval_ms`,
+    /// which drives the timer and processes the messages arrived meanwhile.
+    ///
+    /// It returns once the block is finalized, or when `time_in_ms` (if given) passes,
+    /// or as soon as serving the DMS fails.
+    /// The results are delivered to the subscribers (see `subscribe()`).
+    pub async fn serve_and_progress(
+        mut self,
+        progress_interval_ms: u64,
+        time_in_ms: Option<u64>,
+    ) -> Result<Self, Error> {
+        self.abort_if_finalized()?;
+        let (stop_sender, stop_receiver) = oneshot::channel::<()>();
+        let serve_task = DMS::serve_until(Arc::clone(&self.dms), async {
+            let _ = stop_receiver.await;
+        });
+        let deadline = time_in_ms.map(|time_in_ms| {
+            tokio::time::Instant::now() + std::time::Duration::from_millis(time_in_ms)
+        });
+        let progress_task = async {
+            let mut interval =
+                tokio::time::interval(std::time::Duration::from_millis(progress_interval_ms));
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            while !self.state.finalized
+                && !matches!(deadline, Some(deadline) if tokio::time::Instant::now() >= deadline)
+            {
+                interval.tick().await;
+                // A failure here (e.g., of the storage) may be recovered in the next round.
+                if let Err(e) = self.progress(simperby_common::utils::get_timestamp()).await {
+                    log::warn!("failed to progress the consensus: {}", e);
+                }
+            }
+            let _ = stop_sender.send(());
+            Ok(())
+        };
+        // A failure of serving (e.g., of binding the port) stops the progress right away.
+        futures::future::try_join(serve_task, progress_task).await?;
+        Ok(self)
+    }
+
+    /// Subscribes to the results of every progress made from now on,
+    /// whether by `serve_and_progress()` or by the other methods.
+    pub fn subscribe(&mut self) -> mpsc::UnboundedReceiver<ProgressResult> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.subscribers.push(sender);
+        receiver
     }
 
     /// Reads all consensus messages with its signer in the dms.
     pub async fn read_messages(&self) -> Result<Vec<(ConsensusMessage, PublicKey)>, Error> {
-        let raw_messages = self.dms.read_messages().await?;
+        let raw_messages = self.dms.read().await.read_messages().await?;
         let messages = raw_messages
             .into_iter()
             .map(|m| {
@@ -445,7 +1011,10 @@ impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
             updated_messages: BTreeSet::new(),
             verified_block_hashes: vec![],
             vetoed_block_hashes: vec![],
+            proposal_candidate: None,
+            proposable_blocks: vec![],
             finalized: false,
+            trace_recording: false,
         };
         Ok(state)
     }
@@ -473,15 +1042,102 @@ impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
             .map_err(|_| eyre!("failed to commit consensus state to the storage"))
     }
 
+    /// Appends the Vetomint events recorded since the last call to the trace.
+    ///
+    /// It must be called right after the state is committed,
+    /// so that the trace ends at the committed state.
+    async fn append_trace_records(&mut self) -> Result<(), Error> {
+        let records = self.state.vetomint.vetomint.take_records();
+        if records.is_empty() {
+            return Ok(());
+        }
+        // A single line, which is read only if written completely.
+        self.state_storage
+            .append_to_file(
+                TRACE_FILE_NAME,
+                format!("{}\n", serde_json::to_string(&records)?),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Adds the pending messages whose blocks have been verified to the DMS.
+    async fn accept_pending_messages(&mut self) -> Result<(), Error> {
+        let accepted: Vec<Message> = {
+            let verified_block_hashes = self.verified_block_hashes.read();
+            let mut pending_messages = self.pending_messages.write();
+            let hashes: Vec<Hash256> = pending_messages
+                .iter()
+                .filter(|(_, pending)| {
+                    let consensus_message =
+                        serde_spb::from_str::<ConsensusMessage>(pending.message.data())
+                            .expect("this must be already verified by the message filter");
+                    matches!(
+                        consensus_message.get_block_hash(),
+                        Some(hash) if verified_block_hashes.contains(&hash)
+                    )
+                })
+                .map(|(hash, _)| *hash)
+                .collect();
+            hashes
+                .iter()
+                .filter_map(|hash| pending_messages.remove(hash))
+                .map(|pending| pending.message)
+                .collect()
+        };
+        for message in accepted {
+            self.dms.read().await.add_message(message).await?;
+        }
+        Ok(())
+    }
+
+    /// Logs the vote durably before it is signed, unless it conflicts with a logged one,
+    /// which is then returned.
+    async fn write_ahead(&mut self, vote: &LoggedVote) -> Result<Option<LoggedVote>, Error> {
+        if let Some(logged) = self.wal.find_conflict(vote) {
+            log::warn!("refused to sign {vote:?}, which conflicts with {logged:?}");
+            return Ok(Some(logged.clone()));
+        }
+        if !self.wal.contains(vote) {
+            self.wal.votes.push(vote.clone());
+            commit_wal(&mut self.state_storage, &self.wal)
+                .await
+                .map_err(|_| eyre!("failed to commit the write-ahead log to the storage"))?;
+        }
+        Ok(None)
+    }
+
+    /// Signs the vote of this node for the given step.
+    async fn sign_vote(
+        &self,
+        kind: ConsensusVoteKind,
+        round: ConsensusRound,
+        block_hash: Option<Hash256>,
+    ) -> Result<TypedSignature<ConsensusVote>, Error> {
+        let private_key = self
+            .this_node_key
+            .as_ref()
+            .ok_or_else(|| eyre!("this node is not a validator"))?;
+        let vote = generate_vote(kind, &self.state.block_header, round, block_hash);
+        Ok(TypedSignature::sign(
+            &vote,
+            &self.dms.read().await.get_chain_id(),
+            private_key,
+        )?)
+    }
+
     async fn add_consensus_message(
         &mut self,
         consensus_message: &ConsensusMessage,
     ) -> Result<(), Error> {
         let serialized = serde_spb::to_string(consensus_message).unwrap();
-        let signature = TypedSignature::sign(&serialized, self.this_node_key.as_ref().unwrap())
-            .expect("invalid(malformed) private key");
-        let message = Message::new(serialized, signature).expect("signature just created");
-        self.dms.add_message(message).await
+        let chain_id = self.dms.read().await.get_chain_id();
+        let signature =
+            TypedSignature::sign(&serialized, &chain_id, self.this_node_key.as_ref().unwrap())
+                .expect("invalid(malformed) private key");
+        let message =
+            Message::new(serialized, signature, &chain_id).expect("signature just created");
+        self.dms.read().await.add_message(message).await
     }
 
     async fn process_multiple_responses(
@@ -499,6 +1155,11 @@ impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
                 break;
             }
         }
+        self.subscribers.retain(|subscriber| {
+            final_result
+                .iter()
+                .all(|result| subscriber.send(result.clone()).is_ok())
+        });
         Ok(final_result)
     }
 
@@ -525,6 +1186,7 @@ impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
                 round,
                 valid_round,
                 block_hash,
+                ..
             } => {
                 let valid_round = valid_round.map(|r| r as usize);
                 let index = self
@@ -551,7 +1213,7 @@ impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
                     round: *round as usize,
                 }
             }
-            ConsensusMessage::NonNilPreCommitted(round, block_hash, _) => {
+            ConsensusMessage::NonNilPreCommitted(round, block_hash, ..) => {
                 let index = self
                     .get_block_index(block_hash)
                     .expect("this must be already verified by the message filter");
@@ -561,12 +1223,12 @@ impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
                     round: *round as usize,
                 }
             }
-            ConsensusMessage::NilPreVoted(round) => ConsensusEvent::Prevote {
+            ConsensusMessage::NilPreVoted(round, _) => ConsensusEvent::Prevote {
                 proposal: None,
                 signer,
                 round: *round as usize,
             },
-            ConsensusMessage::NilPreCommitted(round) => ConsensusEvent::Precommit {
+            ConsensusMessage::NilPreCommitted(round, _) => ConsensusEvent::Precommit {
                 proposal: None,
                 signer,
                 round: *round as usize,
@@ -598,10 +1260,25 @@ impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
                     .verified_block_hashes
                     .get(proposal)
                     .expect("the block to propose is not in verified_block_hashes");
+                let vote = LoggedVote::Proposal {
+                    round: round as u64,
+                    valid_round,
+                    block_hash,
+                };
+                if let Some(logged) = self.write_ahead(&vote).await? {
+                    return Ok(ProgressResult::DoubleSignPrevented(vote, logged, timestamp));
+                }
+                let block = self
+                    .state
+                    .proposable_blocks
+                    .iter()
+                    .find(|block| block.header.to_hash256() == block_hash)
+                    .cloned();
                 let consensus_message = ConsensusMessage::Proposal {
                     round: round as u64,
                     valid_round,
                     block_hash,
+                    block,
                 };
                 self.add_consensus_message(&consensus_message).await?;
                 Ok(ProgressResult::Proposed(
@@ -611,29 +1288,31 @@ impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
                 ))
             }
             ConsensusResponse::BroadcastPrevote { proposal, round } => {
-                let private_key = self
-                    .this_node_key
-                    .as_ref()
-                    .ok_or_else(|| eyre!("this node is not a validator"))?;
-                let (consensus_message, progress_result) = if let Some(block_index) = proposal {
-                    let block_hash = *self
+                let block_hash = proposal.map(|block_index| {
+                    *self
                         .state
                         .verified_block_hashes
                         .get(block_index)
-                        .expect("the block to propose is not in verified_block_hashes");
-                    let message = ConsensusMessage::NonNilPreVoted(
-                        round as u64,
-                        block_hash,
-                        TypedSignature::sign(
-                            &format!("{}-{}", block_hash, "prevote"),
-                            private_key,
-                        )?,
-                    );
+                        .expect("the block to vote is not in verified_block_hashes")
+                });
+                let vote = LoggedVote::Prevote {
+                    round: round as u64,
+                    block_hash,
+                };
+                if let Some(logged) = self.write_ahead(&vote).await? {
+                    return Ok(ProgressResult::DoubleSignPrevented(vote, logged, timestamp));
+                }
+                let signature = self
+                    .sign_vote(ConsensusVoteKind::Prevote, round as u64, block_hash)
+                    .await?;
+                let (consensus_message, progress_result) = if let Some(block_hash) = block_hash {
+                    let message =
+                        ConsensusMessage::NonNilPreVoted(round as u64, block_hash, signature);
                     let result =
                         ProgressResult::NonNilPreVoted(round as u64, block_hash, timestamp);
                     (message, result)
                 } else {
-                    let message = ConsensusMessage::NilPreVoted(round as u64);
+                    let message = ConsensusMessage::NilPreVoted(round as u64, signature);
                     let result = ProgressResult::NilPreVoted(round as u64, timestamp);
                     (message, result)
                 };
@@ -641,29 +1320,43 @@ impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
                 Ok(progress_result)
             }
             ConsensusResponse::BroadcastPrecommit { proposal, round } => {
-                let private_key = self
-                    .this_node_key
-                    .as_ref()
-                    .ok_or_else(|| eyre!("this node is not a validator"))?;
-                let (consensus_message, progress_result) = if let Some(block_index) = proposal {
-                    let block_hash = *self
+                let block_hash = proposal.map(|block_index| {
+                    *self
                         .state
                         .verified_block_hashes
                         .get(block_index)
-                        .expect("the block to propose is not in verified_block_hashes");
+                        .expect("the block to vote is not in verified_block_hashes")
+                });
+                let vote = LoggedVote::Precommit {
+                    round: round as u64,
+                    block_hash,
+                };
+                if let Some(logged) = self.write_ahead(&vote).await? {
+                    return Ok(ProgressResult::DoubleSignPrevented(vote, logged, timestamp));
+                }
+                let signature = self
+                    .sign_vote(ConsensusVoteKind::Precommit, round as u64, block_hash)
+                    .await?;
+                let (consensus_message, progress_result) = if let Some(block_hash) = block_hash {
+                    let private_key = self
+                        .this_node_key
+                        .as_ref()
+                        .ok_or_else(|| eyre!("this node is not a validator"))?;
                     let message = ConsensusMessage::NonNilPreCommitted(
                         round as u64,
                         block_hash,
-                        TypedSignature::new(
-                            Signature::sign(block_hash, private_key)?,
-                            private_key.public_key(),
-                        ),
+                        Precommit::sign_hash(
+                            block_hash,
+                            &self.dms.read().await.get_chain_id(),
+                            private_key,
+                        )?,
+                        signature,
                     );
                     let result =
                         ProgressResult::NonNilPreCommitted(round as u64, block_hash, timestamp);
                     (message, result)
                 } else {
-                    let message = ConsensusMessage::NilPreCommitted(round as u64);
+                    let message = ConsensusMessage::NilPreCommitted(round as u64, signature);
                     let result = ProgressResult::NilPreCommitted(round as u64, timestamp);
                     (message, result)
                 };
@@ -692,7 +1385,9 @@ impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
                     .map(|(cm, _)| cm);
                 let proof = precommits_for_proof
                     .map(|cm| match cm {
-                        ConsensusMessage::NonNilPreCommitted(_, _, precommit) => precommit.clone(),
+                        ConsensusMessage::NonNilPreCommitted(_, _, precommit, _) => {
+                            precommit.clone()
+                        }
                         _ => panic!(
                             "consensus::read_precommits should return only `NonNilPreCommitted`"
                         ),
@@ -703,19 +1398,81 @@ impl<N: GossipNetwork, S: Storage> Consensus<N, S> {
             }
             ConsensusResponse::ViolationReport {
                 violator,
-                description,
+                violation,
             } => {
                 let pubkey = self
                     .state
                     .block_header
                     .validator_set
                     .get(violator)
-                    .expect("oob access to validators")
+                    .ok_or_else(|| eyre!("oob access to validator_set"))?
                     .0
                     .clone();
+                let messages: Vec<_> = self
+                    .read_messages()
+                    .await?
+                    .into_iter()
+                    .filter(|(_, signer)| *signer == pubkey)
+                    .map(|(message, _)| message)
+                    .collect();
+                let (kind, round, first, second) = match violation {
+                    Violation::DoubleProposal {
+                        round,
+                        first,
+                        second,
+                    } => (None, round, Some(first), Some(second)),
+                    Violation::DoublePrevote {
+                        round,
+                        first,
+                        second,
+                    } => (Some(ConsensusVoteKind::Prevote), round, first, second),
+                    Violation::DoublePrecommit {
+                        round,
+                        first,
+                        second,
+                    } => (Some(ConsensusVoteKind::Precommit), round, first, second),
+                };
+                let round = round as ConsensusRound;
+                // Both of the messages should be in the DMS, as every event comes from there,
+                // but they may be missing if the DMS has been changed meanwhile.
+                let find_message = |proposal: Option<BlockIdentifier>| {
+                    let block_hash = match proposal {
+                        Some(index) => Some(*self.state.verified_block_hashes.get(index)?),
+                        None => None,
+                    };
+                    let message = messages
+                        .iter()
+                        .find(|message| match (kind, message) {
+                            (
+                                None,
+                                ConsensusMessage::Proposal {
+                                    round: proposal_round,
+                                    block_hash: proposal_hash,
+                                    ..
+                                },
+                            ) => *proposal_round == round && Some(*proposal_hash) == block_hash,
+                            (Some(kind), _) => matches!(
+                                message.get_vote(&self.state.block_header),
+                                Some(signed) if signed.vote.kind == kind
+                                    && signed.vote.round == round
+                                    && signed.vote.block_hash == block_hash
+                            ),
+                            _ => false,
+                        })
+                        .cloned();
+                    if message.is_none() {
+                        log::warn!("the violating message of {} is not in the DMS", pubkey);
+                    }
+                    message
+                };
+                let (first, second) = (find_message(first), find_message(second));
                 Ok(ProgressResult::ViolationReported(
-                    pubkey,
-                    description,
+                    Box::new(ViolationEvidence {
+                        violator: pubkey,
+                        violation: violation.to_string(),
+                        first,
+                        second,
+                    }),
                     timestamp,
                 ))
             }
@@ -0,0 +1,65 @@
+//! The write-ahead log of the votes signed by this node.
+//!
+//! A vote is logged (and persisted) before it is signed and broadcast,
+//! so that a node which crashed before committing its state
+//! can never sign another vote that conflicts with the one it may have already sent.
+use super::*;
+
+/// A proposal or a vote of this node, logged before being signed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LoggedVote {
+    Proposal {
+        round: ConsensusRound,
+        valid_round: Option<ConsensusRound>,
+        block_hash: Hash256,
+    },
+    /// `None` for the nil prevote.
+    Prevote {
+        round: ConsensusRound,
+        block_hash: Option<Hash256>,
+    },
+    /// `None` for the nil precommit.
+    Precommit {
+        round: ConsensusRound,
+        block_hash: Option<Hash256>,
+    },
+}
+
+impl LoggedVote {
+    /// Returns whether both are of the same kind and the same round but not identical,
+    /// which would be a double-signing.
+    pub fn conflicts_with(&self, other: &LoggedVote) -> bool {
+        let same_slot = match (self, other) {
+            (Self::Proposal { round: r1, .. }, Self::Proposal { round: r2, .. })
+            | (Self::Prevote { round: r1, .. }, Self::Prevote { round: r2, .. })
+            | (Self::Precommit { round: r1, .. }, Self::Precommit { round: r2, .. }) => r1 == r2,
+            _ => false,
+        };
+        same_slot && self != other
+    }
+}
+
+/// The votes of this node for a single height.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriteAheadLog {
+    pub height: BlockHeight,
+    pub votes: Vec<LoggedVote>,
+}
+
+impl WriteAheadLog {
+    pub fn new(height: BlockHeight) -> Self {
+        Self {
+            height,
+            votes: Vec::new(),
+        }
+    }
+
+    /// Returns the logged vote that the given one conflicts with, if any.
+    pub fn find_conflict(&self, vote: &LoggedVote) -> Option<&LoggedVote> {
+        self.votes.iter().find(|logged| vote.conflicts_with(logged))
+    }
+
+    pub fn contains(&self, vote: &LoggedVote) -> bool {
+        self.votes.contains(vote)
+    }
+}
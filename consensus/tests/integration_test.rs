@@ -1,6 +1,5 @@
 use common::{
-    crypto::{Signature, TypedSignature},
-    BlockHeight, FinalizationProof, PrivateKey, Timestamp,
+    crypto::TypedSignature, BlockHeight, FinalizationProof, PrivateKey, Timestamp, ToHash256,
 };
 use itertools::Itertools;
 #[allow(unused_imports)]
@@ -9,17 +8,21 @@ use simperby_common::{
     self as common,
     crypto::{Hash256, PublicKey},
     utils::get_timestamp,
-    BlockHeader, VotingPower,
+    BlockHeader, ConsensusVoteKind, VotingPower,
+};
+use simperby_consensus::{
+    derive_consensus_params, derive_leader_schedule, generate_vote, Consensus, ConsensusMessage,
+    LoggedVote, Precommit, PrecommitVote, Prevote, ProgressResult, ProposedBlock, Replayer,
+    ViolationEvidence,
 };
-use simperby_consensus::{Consensus, ConsensusMessage, Precommit, Prevote, ProgressResult};
 use simperby_network::{
-    primitives::Storage, storage::StorageImpl, NetworkConfig, SharedKnownPeers,
+    dms::Message, primitives::Storage, storage::StorageImpl, NetworkConfig, Peer, SharedKnownPeers,
 };
 use simperby_test_suite as test_suite;
 use std::fmt::Debug;
 use std::iter::once;
 use test_suite::*;
-use vetomint::ConsensusParams;
+use vetomint::{ConsensusParams, LeaderSchedule, TimeoutGrowth};
 
 fn get_initial_block_header(validator_set: Vec<(PublicKey, VotingPower)>) -> BlockHeader {
     BlockHeader {
@@ -65,15 +68,35 @@ fn assert_eq_unordered<T: Eq + PartialEq + Debug>(expected: &Vec<T>, actual: &Ve
     }
 }
 
+/// The chain that every signature in these tests is bound to.
+fn chain_id() -> Hash256 {
+    Hash256::hash("consensus-test-chain")
+}
+
+/// The prevote in round 0 on top of the initial block header.
 fn prevote(block_hash: Hash256, privkey: &PrivateKey) -> Prevote {
-    TypedSignature::sign(&format!("{}-{}", block_hash, "prevote"), privkey).unwrap()
+    let vote = generate_vote(
+        ConsensusVoteKind::Prevote,
+        &get_initial_block_header(Vec::new()),
+        0,
+        Some(block_hash),
+    );
+    TypedSignature::sign(&vote, &chain_id(), privkey).unwrap()
 }
 
 fn precommit(block_hash: Hash256, privkey: &PrivateKey) -> Precommit {
-    TypedSignature::new(
-        Signature::sign(block_hash, privkey).unwrap(),
-        privkey.public_key(),
-    )
+    Precommit::sign_hash(block_hash, &chain_id(), privkey).unwrap()
+}
+
+/// The precommit vote in round 0 on top of the initial block header.
+fn precommit_vote(block_hash: Hash256, privkey: &PrivateKey) -> PrecommitVote {
+    let vote = generate_vote(
+        ConsensusVoteKind::Precommit,
+        &get_initial_block_header(Vec::new()),
+        0,
+        Some(block_hash),
+    );
+    TypedSignature::sign(&vote, &chain_id(), privkey).unwrap()
 }
 
 /// This may panic.
@@ -91,6 +114,9 @@ async fn single_server_propose_1() {
     let params = ConsensusParams {
         timeout_ms: 60 * 1_000,
         repeat_round_for_first_leader: 100,
+        timeout_growth: TimeoutGrowth::Constant,
+        wall_clock_rounds: false,
+        leader_schedule: LeaderSchedule::FirstLeaderRepeated,
     };
     let round_zero_timestamp = get_timestamp();
 
@@ -106,6 +132,7 @@ async fn single_server_propose_1() {
         create_test_dms(
             server_config.clone(),
             dms_key.clone(),
+            chain_id(),
             SharedKnownPeers::new_static(vec![]),
         )
         .await,
@@ -117,10 +144,11 @@ async fn single_server_propose_1() {
     )
     .await
     .unwrap();
+    server_node.start_trace_recording().await.unwrap();
     let mut other_nodes = Vec::new();
     for config in &other_configs {
         let consensus = Consensus::new(
-            create_test_dms(config.clone(), dms_key.clone(), peers.clone()).await,
+            create_test_dms(config.clone(), dms_key.clone(), chain_id(), peers.clone()).await,
             create_storage(create_temp_dir()).await,
             block_header.clone(),
             params.clone(),
@@ -196,6 +224,7 @@ async fn single_server_propose_1() {
                 round: 0,
                 valid_round: None,
                 block_hash: dummy_block_hash,
+                block: None,
             },
             server_config.public_key.clone(),
         ),
@@ -223,6 +252,7 @@ async fn single_server_propose_1() {
                     0,
                     dummy_block_hash,
                     precommit(dummy_block_hash, &config.private_key),
+                    precommit_vote(dummy_block_hash, &config.private_key),
                 ),
                 config.public_key.clone(),
             ));
@@ -287,6 +317,7 @@ async fn single_server_propose_1() {
             0,
             dummy_block_hash,
             precommit(dummy_block_hash, &server_config.private_key),
+            precommit_vote(dummy_block_hash, &server_config.private_key),
         ),
         server_config.public_key.clone(),
     ));
@@ -296,6 +327,7 @@ async fn single_server_propose_1() {
                 0,
                 dummy_block_hash,
                 precommit(dummy_block_hash, &config.private_key),
+                precommit_vote(dummy_block_hash, &config.private_key),
             ),
             config.public_key.clone(),
         ));
@@ -317,6 +349,15 @@ async fn single_server_propose_1() {
         _ => panic!("expect finalization"),
     }
 
+    // The recorded events rebuild the exact state of the server node.
+    let mut replayer = Replayer::new(server_node.read_trace().await.unwrap());
+    replayer.run_to_end().unwrap();
+    assert!(!replayer.get_trace().records.is_empty());
+    assert_eq!(
+        replayer.diff(&server_node.get_state().vetomint.vetomint),
+        vec![]
+    );
+
     // Action: Non-server nodes fetch and progress.
     // Expected: No operation.
     let serve_task = tokio::spawn(async { server_node.serve(3_000).await });
@@ -344,6 +385,9 @@ async fn single_seperate_server_propose_1() {
     let params = ConsensusParams {
         timeout_ms: 60 * 1_000, // 1 minute
         repeat_round_for_first_leader: 100,
+        timeout_growth: TimeoutGrowth::Constant,
+        wall_clock_rounds: false,
+        leader_schedule: LeaderSchedule::FirstLeaderRepeated,
     };
     let round_zero_timestamp = get_timestamp();
 
@@ -359,7 +403,7 @@ async fn single_seperate_server_propose_1() {
     let mut nodes = Vec::new();
     for config in once(&server_config).chain(other_configs.iter()) {
         let consensus = Consensus::new(
-            create_test_dms(config.clone(), dms_key.clone(), peers.clone()).await,
+            create_test_dms(config.clone(), dms_key.clone(), chain_id(), peers.clone()).await,
             create_storage(create_temp_dir()).await,
             block_header.clone(),
             params.clone(),
@@ -379,6 +423,7 @@ async fn single_seperate_server_propose_1() {
         create_test_dms(
             server_config.clone(),
             dms_key,
+            chain_id(),
             SharedKnownPeers::new_static(vec![]),
         )
         .await,
@@ -438,3 +483,888 @@ async fn single_seperate_server_propose_1() {
     serve_task.abort();
     let _ = serve_task.await;
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pending_proposal_for_unverified_block() {
+    setup_test();
+    let (network_id, dms_key) = get_network_id_and_dms_key("pending_proposal_for_unverified_block");
+    let params = ConsensusParams {
+        timeout_ms: 60 * 1_000,
+        repeat_round_for_first_leader: 100,
+        timeout_growth: TimeoutGrowth::Constant,
+        wall_clock_rounds: false,
+        leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+    };
+    let round_zero_timestamp = get_timestamp();
+    let (server_config, other_configs, peers) =
+        setup_server_client_nodes(network_id.clone(), 3).await;
+    let block_header = configs_to_block_header(
+        once(&server_config).chain(&other_configs).collect(),
+        vec![1, 1, 1, 1],
+    );
+    let proposed_block = ProposedBlock {
+        header: BlockHeader {
+            author: server_config.public_key.clone(),
+            prev_block_finalization_proof: once(&server_config)
+                .chain(&other_configs)
+                .map(|config| precommit(block_header.to_hash256(), &config.private_key))
+                .collect(),
+            previous_hash: block_header.to_hash256(),
+            height: 1,
+            ..block_header.clone()
+        },
+        agenda_proof_hash: None,
+    };
+    let block_hash = proposed_block.header.to_hash256();
+
+    let mut server_node = Consensus::new(
+        create_test_dms(
+            server_config.clone(),
+            dms_key.clone(),
+            chain_id(),
+            SharedKnownPeers::new_static(vec![]),
+        )
+        .await,
+        create_storage(create_temp_dir()).await,
+        block_header.clone(),
+        params.clone(),
+        round_zero_timestamp,
+        Some(server_config.private_key.clone()),
+    )
+    .await
+    .unwrap();
+    let mut late_node = Consensus::new(
+        create_test_dms(other_configs[0].clone(), dms_key, chain_id(), peers).await,
+        create_storage(create_temp_dir()).await,
+        block_header.clone(),
+        params,
+        round_zero_timestamp,
+        Some(other_configs[0].private_key.clone()),
+    )
+    .await
+    .unwrap();
+    server_node
+        .register_verified_block(proposed_block.clone())
+        .await
+        .unwrap();
+    server_node
+        .set_proposal_candidate(block_hash, get_timestamp())
+        .await
+        .unwrap();
+
+    // The late node has not fetched the block, but has the one attached to the proposal.
+    let serve_task = tokio::spawn(async { server_node.serve(3_000).await });
+    late_node.fetch().await.unwrap();
+    assert!(late_node.read_messages().await.unwrap().is_empty());
+    let pending_blocks = late_node.get_pending_proposed_blocks().await.unwrap();
+    assert_eq!(pending_blocks, vec![proposed_block.clone()]);
+
+    // The pending messages are accepted as soon as the attached block is verified.
+    for block in pending_blocks {
+        late_node
+            .register_verified_block_hash(block.header.to_hash256())
+            .await
+            .unwrap();
+    }
+    assert!(late_node
+        .get_pending_proposed_blocks()
+        .await
+        .unwrap()
+        .is_empty());
+    let expected_received_messages = vec![
+        (
+            ConsensusMessage::Proposal {
+                round: 0,
+                valid_round: None,
+                block_hash,
+                block: Some(proposed_block),
+            },
+            server_config.public_key.clone(),
+        ),
+        (
+            ConsensusMessage::NonNilPreVoted(
+                0,
+                block_hash,
+                prevote(block_hash, &server_config.private_key),
+            ),
+            server_config.public_key.clone(),
+        ),
+    ];
+    assert_eq_unordered(
+        &expected_received_messages,
+        &late_node.read_messages().await.unwrap(),
+    );
+    let timestamp = get_timestamp();
+    assert_eq!(
+        late_node.progress(timestamp).await.unwrap(),
+        vec![ProgressResult::NonNilPreVoted(0, block_hash, timestamp)]
+    );
+    serve_task.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn vetoed_block_proposal() {
+    setup_test();
+    let (network_id, dms_key) = get_network_id_and_dms_key("vetoed_block_proposal");
+    let params = ConsensusParams {
+        timeout_ms: 60 * 1_000,
+        repeat_round_for_first_leader: 100,
+        timeout_growth: TimeoutGrowth::Constant,
+        wall_clock_rounds: false,
+        leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+    };
+    let round_zero_timestamp = get_timestamp();
+    let (server_config, other_configs, peers) =
+        setup_server_client_nodes(network_id.clone(), 3).await;
+    let block_header = configs_to_block_header(
+        once(&server_config).chain(&other_configs).collect(),
+        vec![1, 1, 1, 1],
+    );
+    let block_hash = Hash256::hash("vetoed_block");
+
+    let mut server_node = Consensus::new(
+        create_test_dms(
+            server_config.clone(),
+            dms_key.clone(),
+            chain_id(),
+            SharedKnownPeers::new_static(vec![]),
+        )
+        .await,
+        create_storage(create_temp_dir()).await,
+        block_header.clone(),
+        params.clone(),
+        round_zero_timestamp,
+        Some(server_config.private_key.clone()),
+    )
+    .await
+    .unwrap();
+    server_node
+        .register_verified_block_hash(block_hash)
+        .await
+        .unwrap();
+    server_node
+        .set_proposal_candidate(block_hash, get_timestamp())
+        .await
+        .unwrap();
+    // A node can't veto its own proposal.
+    assert!(server_node.veto_block(block_hash).await.is_err());
+
+    let storage_directory = create_temp_dir();
+    let start = |storage: StorageImpl| {
+        let (config, dms_key, peers, params, block_header) = (
+            other_configs[0].clone(),
+            dms_key.clone(),
+            peers.clone(),
+            params.clone(),
+            block_header.clone(),
+        );
+        async move {
+            Consensus::new(
+                create_test_dms(config.clone(), dms_key, chain_id(), peers).await,
+                storage,
+                block_header,
+                params,
+                round_zero_timestamp,
+                Some(config.private_key),
+            )
+            .await
+            .unwrap()
+        }
+    };
+    let mut node = start(create_storage(storage_directory.clone()).await).await;
+    node.register_verified_block_hash(block_hash).await.unwrap();
+    node.veto_block(block_hash).await.unwrap();
+    node.unveto_block(block_hash).await.unwrap();
+    assert!(node.unveto_block(block_hash).await.is_err());
+    node.veto_block(block_hash).await.unwrap();
+
+    // The veto survives a restart.
+    drop(node);
+    let mut node = start(StorageImpl::open(&storage_directory).await.unwrap()).await;
+    assert_eq!(node.get_vetoed_block_hashes(), &[block_hash]);
+
+    let serve_task = tokio::spawn(async { server_node.serve(3_000).await });
+    node.fetch().await.unwrap();
+    let timestamp = get_timestamp();
+    assert_eq!(
+        node.progress(timestamp).await.unwrap(),
+        vec![ProgressResult::NilPreVoted(0, timestamp)]
+    );
+    serve_task.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn trace_with_torn_append() {
+    setup_test();
+    let (network_id, dms_key) = get_network_id_and_dms_key("trace_with_torn_append");
+    let params = ConsensusParams {
+        timeout_ms: 60 * 1_000,
+        repeat_round_for_first_leader: 100,
+        timeout_growth: TimeoutGrowth::Constant,
+        wall_clock_rounds: false,
+        leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+    };
+    let round_zero_timestamp = get_timestamp();
+    let (server_config, other_configs, _) = setup_server_client_nodes(network_id, 3).await;
+    let block_header = configs_to_block_header(
+        once(&server_config).chain(&other_configs).collect(),
+        vec![1, 1, 1, 1],
+    );
+    let block_hash = Hash256::hash("block");
+    let storage_directory = create_temp_dir();
+    let start = |storage: StorageImpl| {
+        let (config, dms_key, params, block_header) = (
+            server_config.clone(),
+            dms_key.clone(),
+            params.clone(),
+            block_header.clone(),
+        );
+        async move {
+            Consensus::new(
+                create_test_dms(
+                    config.clone(),
+                    dms_key,
+                    chain_id(),
+                    SharedKnownPeers::new_static(vec![]),
+                )
+                .await,
+                storage,
+                block_header,
+                params,
+                round_zero_timestamp,
+                Some(config.private_key),
+            )
+            .await
+            .unwrap()
+        }
+    };
+    let mut node = start(create_storage(storage_directory.clone()).await).await;
+    node.start_trace_recording().await.unwrap();
+    node.register_verified_block_hash(block_hash).await.unwrap();
+    node.set_proposal_candidate(block_hash, get_timestamp())
+        .await
+        .unwrap();
+    let trace = node.read_trace().await.unwrap();
+    assert!(!trace.records.is_empty());
+
+    // A crash in the middle of an append leaves a part of the line.
+    drop(node);
+    std::io::Write::write_all(
+        &mut std::fs::OpenOptions::new()
+            .append(true)
+            .open(format!(
+                "{storage_directory}/{}",
+                simperby_consensus::TRACE_FILE_NAME
+            ))
+            .unwrap(),
+        b"[{\"event",
+    )
+    .unwrap();
+    let mut node = start(StorageImpl::open(&storage_directory).await.unwrap()).await;
+    assert_eq!(
+        node.read_trace().await.unwrap().records,
+        trace.records,
+        "the torn line is ignored"
+    );
+
+    // The records appended after the torn line are read as well.
+    node.progress(get_timestamp()).await.unwrap();
+    let mut replayer = Replayer::new(node.read_trace().await.unwrap());
+    replayer.run_to_end().unwrap();
+    assert!(replayer.get_trace().records.len() > trace.records.len());
+    assert_eq!(replayer.diff(&node.get_state().vetomint.vetomint), vec![]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn serve_and_progress_until_finalization() {
+    setup_test();
+    let (network_id, dms_key) = get_network_id_and_dms_key("serve_and_progress");
+    let params = ConsensusParams {
+        timeout_ms: 60 * 1_000,
+        repeat_round_for_first_leader: 100,
+        timeout_growth: TimeoutGrowth::Constant,
+        wall_clock_rounds: false,
+        leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+    };
+    let round_zero_timestamp = get_timestamp();
+    let (server_config, other_configs, _) = setup_server_client_nodes(network_id.clone(), 3).await;
+    let configs: Vec<_> = once(&server_config).chain(&other_configs).collect();
+    let block_header = configs_to_block_header(configs.clone(), vec![1, 1, 1, 1]);
+    let dummy_block_hash = Hash256::hash("dummy_block");
+
+    // Every node is connected to all the others, since a finalized one stops serving.
+    let mut nodes = Vec::new();
+    for config in &configs {
+        let peers = configs
+            .iter()
+            .filter(|other| other.public_key != config.public_key)
+            .map(|other| Peer {
+                public_key: other.public_key.clone(),
+                name: other.public_key.to_string(),
+                address: "127.0.0.1:1".parse().unwrap(),
+                ports: other.ports.clone(),
+                message: "".to_owned(),
+                recently_seen_timestamp: 0,
+            })
+            .collect();
+        let mut node = Consensus::new(
+            create_test_dms(
+                (*config).clone(),
+                dms_key.clone(),
+                chain_id(),
+                SharedKnownPeers::new_static(peers),
+            )
+            .await,
+            create_storage(create_temp_dir()).await,
+            block_header.clone(),
+            params.clone(),
+            round_zero_timestamp,
+            Some(config.private_key.clone()),
+        )
+        .await
+        .unwrap();
+        node.register_verified_block_hash(dummy_block_hash)
+            .await
+            .unwrap();
+        let results = node.subscribe();
+        nodes.push((node, results));
+    }
+    nodes[0]
+        .0
+        .set_proposal_candidate(dummy_block_hash, get_timestamp())
+        .await
+        .unwrap();
+
+    // Nobody calls `progress()` by hand.
+    let tasks = nodes
+        .into_iter()
+        .map(|(node, results)| {
+            tokio::spawn(async move {
+                let node = node.serve_and_progress(100, Some(60_000)).await.unwrap();
+                (node, results)
+            })
+        })
+        .collect::<Vec<_>>();
+    for task in tasks {
+        let (node, mut results) = task.await.unwrap();
+        assert!(node.get_state().finalized);
+        let mut finalized = false;
+        while let Ok(result) = results.try_recv() {
+            if let ProgressResult::Finalized(block_hash, _, _) = result {
+                assert_eq!(block_hash, dummy_block_hash);
+                finalized = true;
+            }
+        }
+        assert!(finalized);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn serve_and_progress_without_port() {
+    setup_test();
+    let (network_id, dms_key) = get_network_id_and_dms_key("serve_and_progress_without_port");
+    let params = ConsensusParams {
+        timeout_ms: 60 * 1_000,
+        repeat_round_for_first_leader: 100,
+        timeout_growth: TimeoutGrowth::Constant,
+        wall_clock_rounds: false,
+        leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+    };
+    let (mut server_config, other_configs, _) = setup_server_client_nodes(network_id, 1).await;
+    server_config.ports.clear();
+    let block_header = configs_to_block_header(
+        once(&server_config).chain(&other_configs).collect(),
+        vec![1, 1],
+    );
+    let node = Consensus::new(
+        create_test_dms(
+            server_config.clone(),
+            dms_key,
+            chain_id(),
+            SharedKnownPeers::new_static(vec![]),
+        )
+        .await,
+        create_storage(create_temp_dir()).await,
+        block_header,
+        params,
+        get_timestamp(),
+        Some(server_config.private_key.clone()),
+    )
+    .await
+    .unwrap();
+
+    // It fails right away, instead of progressing forever without the network.
+    let result = tokio::time::timeout(
+        std::time::Duration::from_secs(10),
+        node.serve_and_progress(100, None),
+    )
+    .await
+    .expect("serve_and_progress() must stop on a failure of serving");
+    assert!(result.is_err());
+}
+
+#[test]
+fn leader_schedule_from_reserved_state() {
+    setup_test();
+    let (mut reserved_state, _) = common::test_utils::generate_standard_genesis(4);
+    // The validator set in a different order than the names.
+    let mut validator_set = reserved_state.get_validator_set().unwrap();
+    validator_set.reverse();
+    let block_header = get_initial_block_header(validator_set);
+    reserved_state.consensus_leader_order = vec![
+        "member-0002".to_string(),
+        "non-member".to_string(),
+        "member-0000".to_string(),
+    ];
+    assert_eq!(
+        derive_leader_schedule(&reserved_state, &block_header, 3).unwrap(),
+        LeaderSchedule::Explicit(vec![(1, 3), (3, 1)])
+    );
+    reserved_state.consensus_leader_order = vec!["non-member".to_string()];
+    assert!(derive_leader_schedule(&reserved_state, &block_header, 3).is_err());
+}
+
+#[test]
+fn consensus_params_from_reserved_state() {
+    setup_test();
+    let (mut reserved_state, _) = common::test_utils::generate_standard_genesis(4);
+    let block_header = get_initial_block_header(reserved_state.get_validator_set().unwrap());
+    reserved_state
+        .consensus_params
+        .repeat_round_for_first_leader = 2;
+    reserved_state.consensus_leader_order = vec!["member-0003".to_string()];
+    // By default, the leaders follow the leader order.
+    assert_eq!(
+        derive_consensus_params(&reserved_state, &block_header)
+            .unwrap()
+            .leader_schedule,
+        LeaderSchedule::Explicit(vec![(3, 2)])
+    );
+    // The default schedule of Vetomint can be chosen explicitly.
+    reserved_state.consensus_params.leader_schedule = common::LeaderSchedule::FirstLeaderRepeated;
+    let params = derive_consensus_params(&reserved_state, &block_header).unwrap();
+    assert_eq!(params.leader_schedule, LeaderSchedule::FirstLeaderRepeated);
+    assert_eq!(params.repeat_round_for_first_leader, 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn restart_without_double_signing() {
+    setup_test();
+    let (network_id, dms_key) = get_network_id_and_dms_key("restart_without_double_signing");
+    let params = ConsensusParams {
+        timeout_ms: 60 * 1_000,
+        repeat_round_for_first_leader: 100,
+        timeout_growth: TimeoutGrowth::Constant,
+        wall_clock_rounds: false,
+        leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+    };
+    let round_zero_timestamp = get_timestamp();
+    let (server_config, other_configs, _) = setup_server_client_nodes(network_id, 3).await;
+    let block_header = configs_to_block_header(
+        once(&server_config).chain(&other_configs).collect(),
+        vec![1, 1, 1, 1],
+    );
+    let storage_directory = create_temp_dir();
+    let start = |storage: StorageImpl| {
+        let (config, dms_key, params, block_header) = (
+            server_config.clone(),
+            dms_key.clone(),
+            params.clone(),
+            block_header.clone(),
+        );
+        async move {
+            Consensus::new(
+                create_test_dms(
+                    config.clone(),
+                    dms_key,
+                    chain_id(),
+                    SharedKnownPeers::new_static(vec![]),
+                )
+                .await,
+                storage,
+                block_header,
+                params,
+                round_zero_timestamp,
+                Some(config.private_key),
+            )
+            .await
+            .unwrap()
+        }
+    };
+
+    let mut node = start(create_storage(storage_directory.clone()).await).await;
+    let stale_state = node.get_state().clone();
+    let first_block_hash = Hash256::hash("first_block");
+    node.register_verified_block_hash(first_block_hash)
+        .await
+        .unwrap();
+    let timestamp = get_timestamp();
+    assert_eq!(
+        node.set_proposal_candidate(first_block_hash, timestamp)
+            .await
+            .unwrap(),
+        vec![
+            ProgressResult::Proposed(0, first_block_hash, timestamp),
+            ProgressResult::NonNilPreVoted(0, first_block_hash, timestamp),
+        ]
+    );
+
+    // The node crashes after broadcasting, before its state is committed.
+    drop(node);
+    let mut storage = StorageImpl::open(&storage_directory).await.unwrap();
+    storage
+        .add_or_overwrite_file(
+            "state.json",
+            simperby_common::serde_spb::to_string(&stale_state).unwrap(),
+        )
+        .await
+        .unwrap();
+    drop(storage);
+
+    // After the restart, it would propose and prevote another block in the same round.
+    // (Re-signing the same votes would be fine.)
+    let mut node = start(StorageImpl::open(&storage_directory).await.unwrap()).await;
+    let second_block_hash = Hash256::hash("second_block");
+    node.register_verified_block_hash(second_block_hash)
+        .await
+        .unwrap();
+    let timestamp = get_timestamp();
+    assert_eq!(
+        node.set_proposal_candidate(second_block_hash, timestamp)
+            .await
+            .unwrap(),
+        vec![
+            ProgressResult::DoubleSignPrevented(
+                LoggedVote::Proposal {
+                    round: 0,
+                    valid_round: None,
+                    block_hash: second_block_hash,
+                },
+                LoggedVote::Proposal {
+                    round: 0,
+                    valid_round: None,
+                    block_hash: first_block_hash,
+                },
+                timestamp
+            ),
+            ProgressResult::DoubleSignPrevented(
+                LoggedVote::Prevote {
+                    round: 0,
+                    block_hash: Some(second_block_hash),
+                },
+                LoggedVote::Prevote {
+                    round: 0,
+                    block_hash: Some(first_block_hash),
+                },
+                timestamp
+            ),
+        ]
+    );
+    // Nothing has been signed for the second block.
+    assert!(node
+        .read_messages()
+        .await
+        .unwrap()
+        .iter()
+        .all(|(message, _)| !matches!(
+            message,
+            ConsensusMessage::Proposal { block_hash, .. }
+            | ConsensusMessage::NonNilPreVoted(_, block_hash, _)
+            if *block_hash == second_block_hash
+        )));
+    assert_eq!(node.get_write_ahead_log().votes.len(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn report_double_prevote() {
+    setup_test();
+    let (network_id, dms_key) = get_network_id_and_dms_key("report_double_prevote");
+    let params = ConsensusParams {
+        timeout_ms: 60 * 1_000,
+        repeat_round_for_first_leader: 100,
+        timeout_growth: TimeoutGrowth::Constant,
+        wall_clock_rounds: false,
+        leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+    };
+    let round_zero_timestamp = get_timestamp();
+    let (server_config, other_configs, _) = setup_server_client_nodes(network_id, 3).await;
+    let block_header = configs_to_block_header(
+        once(&server_config).chain(&other_configs).collect(),
+        vec![1, 1, 1, 1],
+    );
+
+    // The leader runs two nodes with the same key, each prevoting a different block.
+    let mut votes = Vec::new();
+    for block_hash in [Hash256::hash("first_block"), Hash256::hash("second_block")] {
+        let mut node = Consensus::new(
+            create_test_dms(
+                server_config.clone(),
+                dms_key.clone(),
+                chain_id(),
+                SharedKnownPeers::new_static(vec![]),
+            )
+            .await,
+            create_storage(create_temp_dir()).await,
+            block_header.clone(),
+            params.clone(),
+            round_zero_timestamp,
+            Some(server_config.private_key.clone()),
+        )
+        .await
+        .unwrap();
+        node.register_verified_block_hash(block_hash).await.unwrap();
+        node.set_proposal_candidate(block_hash, get_timestamp())
+            .await
+            .unwrap();
+        let messages = node.read_messages().await.unwrap();
+        let vote = messages
+            .iter()
+            .find_map(|(message, _)| message.get_vote(&block_header))
+            .unwrap();
+        assert_eq!(vote.vote.block_hash, Some(block_hash));
+        votes.push(vote);
+    }
+
+    let report = common::TxReport {
+        second_vote: votes.pop().unwrap(),
+        first_vote: votes.pop().unwrap(),
+        timestamp: get_timestamp(),
+    };
+    common::verify::verify_report(&report, &block_header.validator_set, &chain_id()).unwrap();
+    // The votes are bound to the chain.
+    assert!(common::verify::verify_report(
+        &report,
+        &block_header.validator_set,
+        &Hash256::hash("another-chain")
+    )
+    .is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn violation_evidence() {
+    setup_test();
+    let (network_id, dms_key) = get_network_id_and_dms_key("violation_evidence");
+    let params = ConsensusParams {
+        timeout_ms: 60 * 1_000,
+        repeat_round_for_first_leader: 100,
+        timeout_growth: TimeoutGrowth::Constant,
+        wall_clock_rounds: false,
+        leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+    };
+    let round_zero_timestamp = get_timestamp();
+    let (server_config, other_configs, peers) = setup_server_client_nodes(network_id, 3).await;
+    let block_header = configs_to_block_header(
+        once(&server_config).chain(&other_configs).collect(),
+        vec![1, 1, 1, 1],
+    );
+    let block_hashes = [Hash256::hash("first_block"), Hash256::hash("second_block")];
+
+    let mut node = Consensus::new(
+        create_test_dms(other_configs[0].clone(), dms_key.clone(), chain_id(), peers).await,
+        create_storage(create_temp_dir()).await,
+        block_header.clone(),
+        params.clone(),
+        round_zero_timestamp,
+        Some(other_configs[0].private_key.clone()),
+    )
+    .await
+    .unwrap();
+    for block_hash in block_hashes {
+        node.register_verified_block_hash(block_hash).await.unwrap();
+    }
+
+    // The leader runs two nodes with the same key, each proposing and prevoting a different block.
+    let mut leader_messages = Vec::new();
+    for block_hash in block_hashes {
+        let mut leader_node = Consensus::new(
+            create_test_dms(
+                server_config.clone(),
+                dms_key.clone(),
+                chain_id(),
+                SharedKnownPeers::new_static(vec![]),
+            )
+            .await,
+            create_storage(create_temp_dir()).await,
+            block_header.clone(),
+            params.clone(),
+            round_zero_timestamp,
+            Some(server_config.private_key.clone()),
+        )
+        .await
+        .unwrap();
+        leader_node
+            .register_verified_block_hash(block_hash)
+            .await
+            .unwrap();
+        leader_node
+            .set_proposal_candidate(block_hash, get_timestamp())
+            .await
+            .unwrap();
+        leader_messages.extend(
+            leader_node
+                .read_messages()
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|(message, _)| message),
+        );
+        let serve_task = tokio::spawn(async { leader_node.serve(3_000).await });
+        node.fetch().await.unwrap();
+        serve_task.await.unwrap().unwrap();
+    }
+
+    let timestamp = get_timestamp();
+    let evidences: Vec<_> = node
+        .progress(timestamp)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter_map(|result| match result {
+            ProgressResult::ViolationReported(evidence, _) => Some(evidence),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(evidences.len(), 2);
+    for evidence in &evidences {
+        assert_eq!(evidence.violator, server_config.public_key);
+        assert_ne!(evidence.first, evidence.second);
+        assert!(leader_messages.contains(evidence.first.as_ref().unwrap()));
+        assert!(leader_messages.contains(evidence.second.as_ref().unwrap()));
+    }
+    // The double proposal is not a vote, unlike the double prevote.
+    let (proposals, prevotes): (Vec<_>, Vec<_>) = evidences
+        .iter()
+        .partition(|evidence| matches!(evidence.first, Some(ConsensusMessage::Proposal { .. })));
+    assert_eq!(proposals[0].to_report(&block_header, timestamp), None);
+    let report = prevotes[0].to_report(&block_header, timestamp).unwrap();
+    common::verify::verify_report(&report, &block_header.validator_set, &chain_id()).unwrap();
+    // A partial evidence (e.g., with the DMS cleared meanwhile) makes no report.
+    let partial = ViolationEvidence {
+        second: None,
+        ..(**prevotes[0]).clone()
+    };
+    assert_eq!(partial.to_report(&block_header, timestamp), None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pending_pool_shared_by_validators() {
+    setup_test();
+    let (network_id, dms_key) = get_network_id_and_dms_key("pending_pool_shared_by_validators");
+    let params = ConsensusParams {
+        timeout_ms: 60 * 1_000,
+        repeat_round_for_first_leader: 100,
+        timeout_growth: TimeoutGrowth::Constant,
+        wall_clock_rounds: false,
+        leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+    };
+    let (server_config, other_configs, peers) = setup_server_client_nodes(network_id, 3).await;
+    let block_header = configs_to_block_header(
+        once(&server_config).chain(&other_configs).collect(),
+        vec![1, 1, 1, 1],
+    );
+    let sign_prevote = |round, block_hash, privkey: &PrivateKey| {
+        let vote = generate_vote(
+            ConsensusVoteKind::Prevote,
+            &block_header,
+            round,
+            Some(block_hash),
+        );
+        let message = ConsensusMessage::NonNilPreVoted(
+            round,
+            block_hash,
+            TypedSignature::sign(&vote, &chain_id(), privkey).unwrap(),
+        );
+        let data = common::serde_spb::to_string(&message).unwrap();
+        let signature = TypedSignature::sign(&data, &chain_id(), privkey).unwrap();
+        Message::new(data, signature, &chain_id()).unwrap()
+    };
+    let spam_hash = |round: u64, i: u64| Hash256::hash(format!("spam-{round}-{i}"));
+
+    // The server relays an honest prevote, along with too many prevotes of its own
+    // for made-up blocks: one in each of the rounds 0..300, and five in the round 300.
+    let dms = create_test_dms(
+        server_config.clone(),
+        dms_key.clone(),
+        chain_id(),
+        SharedKnownPeers::new_static(vec![]),
+    )
+    .await;
+    let block_hash = Hash256::hash("block");
+    dms.add_message(sign_prevote(0, block_hash, &other_configs[1].private_key))
+        .await
+        .unwrap();
+    for round in 0..300 {
+        dms.add_message(sign_prevote(
+            round,
+            spam_hash(round, 0),
+            &server_config.private_key,
+        ))
+        .await
+        .unwrap();
+    }
+    for i in 0..5 {
+        dms.add_message(sign_prevote(
+            300,
+            spam_hash(300, i),
+            &server_config.private_key,
+        ))
+        .await
+        .unwrap();
+    }
+    let serve_task = tokio::spawn(async { dms.serve(3_000).await });
+    let mut node = Consensus::new(
+        create_test_dms(other_configs[0].clone(), dms_key, chain_id(), peers).await,
+        create_storage(create_temp_dir()).await,
+        block_header.clone(),
+        params,
+        get_timestamp(),
+        Some(other_configs[0].private_key.clone()),
+    )
+    .await
+    .unwrap();
+    node.fetch().await.unwrap();
+    serve_task.await.unwrap().unwrap();
+
+    let count_messages = |messages: Vec<(ConsensusMessage, PublicKey)>, signer: &PublicKey| {
+        messages
+            .into_iter()
+            .filter(|(_, public_key)| public_key == signer)
+            .count()
+    };
+    // The honest prevote is kept in its own share.
+    node.register_verified_block_hash(block_hash).await.unwrap();
+    assert_eq!(
+        count_messages(
+            node.read_messages().await.unwrap(),
+            &other_configs[1].public_key
+        ),
+        1
+    );
+    // Only three of the spams are kept for a round.
+    for i in 0..5 {
+        node.register_verified_block_hash(spam_hash(300, i))
+            .await
+            .unwrap();
+    }
+    assert_eq!(
+        count_messages(
+            node.read_messages().await.unwrap(),
+            &server_config.public_key
+        ),
+        3
+    );
+    // The oldest rounds are evicted.
+    node.register_verified_block_hash(spam_hash(299, 0))
+        .await
+        .unwrap();
+    node.register_verified_block_hash(spam_hash(0, 0))
+        .await
+        .unwrap();
+    assert_eq!(
+        count_messages(
+            node.read_messages().await.unwrap(),
+            &server_config.public_key
+        ),
+        4
+    );
+}
@@ -25,6 +25,7 @@ pub struct GovernanceStatus {
 struct Vote {
     pub agenda_hash: Hash256,
     pub voter: PublicKey,
+    /// The raw signature of `TypedSignature<Agenda>`, which becomes a part of the agenda proof.
     pub signature: Signature,
 }
 
@@ -59,15 +60,19 @@ impl<N: GossipNetwork, S: Storage> Governance<N, S> {
     }
 
     pub async fn vote(&mut self, agenda_hash: Hash256) -> Result<(), Error> {
+        let chain_id = self.dms.get_chain_id();
+        let private_key = self.this_node_key.as_ref().unwrap();
         let data = serde_spb::to_string(&Vote {
             agenda_hash,
-            voter: self.this_node_key.as_ref().unwrap().public_key(),
-            signature: Signature::sign(agenda_hash, self.this_node_key.as_ref().unwrap())?,
+            voter: private_key.public_key(),
+            signature: TypedSignature::<Agenda>::sign_hash(agenda_hash, &chain_id, private_key)?
+                .get_raw_signature(),
         })
         .unwrap();
         let message = Message::new(
             data.clone(),
-            TypedSignature::sign(&data, self.this_node_key.as_ref().unwrap())?,
+            TypedSignature::sign(&data, &chain_id, private_key)?,
+            &chain_id,
         )?;
 
         self.dms.add_message(message).await?;
@@ -15,6 +15,7 @@ async fn basic_1() {
         create_test_dms(
             server_network_config.clone(),
             network_id.clone(),
+            Hash256::hash(&network_id),
             SharedKnownPeers::new_static(Default::default()),
         )
         .await,
@@ -27,7 +28,13 @@ async fn basic_1() {
     for network_config in client_network_configs.iter() {
         client_nodes.push((
             Governance::new(
-                create_test_dms(network_config.clone(), network_id.clone(), peer.clone()).await,
+                create_test_dms(
+                    network_config.clone(),
+                    network_id.clone(),
+                    Hash256::hash(&network_id),
+                    peer.clone(),
+                )
+                .await,
                 Some(network_config.private_key.clone()),
             )
             .await
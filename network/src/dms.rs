@@ -1,12 +1,13 @@
 use super::Storage;
 use super::*;
+use crate::reconciliation::*;
+use crate::secure::{run_server_until, ChannelConfig, RpcClient};
 use async_trait::async_trait;
 use eyre::eyre;
 use futures::prelude::*;
 use serde_tc::http::*;
 use serde_tc::{serde_tc_full, StubCall};
 use simperby_common::*;
-use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::RwLock;
@@ -21,8 +22,13 @@ pub struct Message {
 }
 
 impl Message {
-    pub fn new(data: String, signature: TypedSignature<String>) -> Result<Self, CryptoError> {
-        signature.verify(&data)?;
+    /// Creates a message, verifying its signature on the chain identified by `chain_id`.
+    pub fn new(
+        data: String,
+        signature: TypedSignature<String>,
+        chain_id: &Hash256,
+    ) -> Result<Self, CryptoError> {
+        signature.verify(&data, chain_id)?;
         Ok(Self { data, signature })
     }
 
@@ -54,8 +60,8 @@ pub struct RawMessage {
 }
 
 impl RawMessage {
-    pub fn into_message(self) -> eyre::Result<Message> {
-        Message::new(self.data, self.signature).map_err(|e| eyre!(e))
+    pub fn into_message(self, chain_id: &Hash256) -> eyre::Result<Message> {
+        Message::new(self.data, self.signature, chain_id).map_err(|e| eyre!(e))
     }
 
     pub fn from_message(message: Message) -> Self {
@@ -71,23 +77,41 @@ pub struct State {
     pub dms_key: DmsKey,
 }
 
-/// The interface that will be wrapped into an HTTP RPC server for the peers.
+/// The interface that will be wrapped into an RPC server for the peers.
 #[serde_tc_full]
 trait DistributedMessageSetRpcInterface: Send + Sync + 'static {
     /// Returns the messages except `knowns`.
+    ///
+    /// This is the full exchange, which the reconciliation falls back to
+    /// when the sets are too different.
     async fn get_message(
         &self,
         dms_key: DmsKey,
         knowns: Vec<Hash256>,
     ) -> Result<Vec<RawMessage>, String>;
 
+    /// Returns `None` if the messages of this node have the given root digest,
+    /// or the digests of their buckets otherwise.
+    async fn get_digest(
+        &self,
+        dms_key: DmsKey,
+        root: Hash256,
+    ) -> Result<Option<Vec<BucketDigest>>, String>;
+
+    /// Returns the messages in the given buckets except `knowns`.
+    async fn get_messages_in_buckets(
+        &self,
+        dms_key: DmsKey,
+        buckets: Vec<u8>,
+        knowns: Vec<Hash256>,
+    ) -> Result<Vec<RawMessage>, String>;
+
     /// Requests this node to accept a new message.
     async fn add_messages(&self, dms_key: DmsKey, messages: Vec<RawMessage>) -> Result<(), String>;
 }
 
 struct DmsWrapper<N: GossipNetwork, S: Storage> {
-    #[allow(clippy::type_complexity)]
-    dms: Arc<parking_lot::RwLock<Option<Arc<RwLock<DistributedMessageSet<N, S>>>>>>,
+    dms: ServedSet<DistributedMessageSet<N, S>>,
 }
 
 #[async_trait]
@@ -97,46 +121,36 @@ impl<N: GossipNetwork, S: Storage> DistributedMessageSetRpcInterface for DmsWrap
         dms_key: DmsKey,
         knowns: Vec<Hash256>,
     ) -> Result<Vec<RawMessage>, String> {
-        let dms = Arc::clone(
-            self.dms
-                .read()
-                .as_ref()
-                .ok_or_else(|| "server terminated".to_owned())?,
-        );
-        let mut messages = dms
-            .read()
-            .await
-            .read_messages()
-            .await
-            .map_err(|e| e.to_string())?;
-        let dms_key_ = dms.read().await.key.clone();
-        if dms_key != dms.read().await.key {
-            return Err(format!("key mismatch: requested {dms_key}, but {dms_key_}"));
-        }
-        let knowns: HashSet<_> = knowns.into_iter().collect();
-        let messages: Vec<_> = messages
-            .drain(..)
-            .filter(|m| !knowns.contains(&m.to_hash256()))
-            .map(RawMessage::from_message)
-            .collect();
-        Ok(messages)
+        let messages = serve_messages(&self.dms, &dms_key, knowns).await?;
+        Ok(messages.into_iter().map(RawMessage::from_message).collect())
+    }
+
+    async fn get_digest(
+        &self,
+        dms_key: DmsKey,
+        root: Hash256,
+    ) -> Result<Option<Vec<BucketDigest>>, String> {
+        serve_digest(&self.dms, &dms_key, root).await
+    }
+
+    async fn get_messages_in_buckets(
+        &self,
+        dms_key: DmsKey,
+        buckets: Vec<u8>,
+        knowns: Vec<Hash256>,
+    ) -> Result<Vec<RawMessage>, String> {
+        let messages =
+            serve_messages_in_buckets(&self.dms, &dms_key, Some(buckets), knowns).await?;
+        Ok(messages.into_iter().map(RawMessage::from_message).collect())
     }
 
     async fn add_messages(&self, dms_key: DmsKey, messages: Vec<RawMessage>) -> Result<(), String> {
-        let dms = Arc::clone(
-            self.dms
-                .read()
-                .as_ref()
-                .ok_or_else(|| "server terminated".to_owned())?,
-        );
-        let dms_key_ = dms.read().await.key.clone();
-        if dms_key != dms.read().await.key {
-            return Err(format!("key mismatch: requested {dms_key}, but {dms_key_}"));
-        }
+        let dms = get_served_set(&self.dms, &dms_key).await?;
+        let chain_id = dms.read().await.config.chain_id;
         for message in messages {
-            let message = message.into_message().map_err(|e| e.to_string())?;
+            let message = message.into_message(&chain_id).map_err(|e| e.to_string())?;
             DistributedMessageSet::<N, S>::add_message_but_not_broadcast(
-                &mut (*dms.write().await.storage.write().await),
+                &mut (*dms.read().await.storage.write().await),
                 message,
             )
             .await
@@ -146,6 +160,37 @@ impl<N: GossipNetwork, S: Storage> DistributedMessageSetRpcInterface for DmsWrap
     }
 }
 
+/// A peer to fetch the messages from.
+struct RpcPeer<'a> {
+    stub: &'a DistributedMessageSetRpcInterfaceStub,
+    dms_key: &'a DmsKey,
+}
+
+#[async_trait]
+impl MessageSetPeer for RpcPeer<'_> {
+    type Message = RawMessage;
+
+    async fn get_messages(&self, knowns: Vec<Hash256>) -> Result<Vec<RawMessage>, Error> {
+        flatten_rpc_result(self.stub.get_message(self.dms_key.clone(), knowns).await)
+    }
+
+    async fn get_digest(&self, root: Hash256) -> Result<Option<Vec<BucketDigest>>, Error> {
+        flatten_rpc_result(self.stub.get_digest(self.dms_key.clone(), root).await)
+    }
+
+    async fn get_messages_in_buckets(
+        &self,
+        buckets: Vec<u8>,
+        knowns: Vec<Hash256>,
+    ) -> Result<Vec<RawMessage>, Error> {
+        flatten_rpc_result(
+            self.stub
+                .get_messages_in_buckets(self.dms_key.clone(), buckets, knowns)
+                .await,
+        )
+    }
+}
+
 struct DummyFilter;
 
 impl MessageFilter for DummyFilter {
@@ -189,6 +234,8 @@ pub struct Config {
     pub broadcast_interval: Option<Duration>,
     /// The interval of the direct-peer fetch. If none, it will fetch only in `fetch()`, not in `serve()`.
     pub fetch_interval: Option<Duration>,
+    /// The chain that the messages are signed for (see `GenesisInfo::chain_id()`).
+    pub chain_id: Hash256,
 }
 
 impl<N: GossipNetwork, S: Storage> DistributedMessageSet<N, S> {
@@ -237,46 +284,53 @@ impl<N: GossipNetwork, S: Storage> DistributedMessageSet<N, S> {
         self.key.clone()
     }
 
+    /// Returns the chain that the messages of this DMS are bound to.
+    pub fn get_chain_id(&self) -> Hash256 {
+        self.config.chain_id
+    }
+
     pub fn set_filter(&mut self, filter: Arc<dyn MessageFilter>) {
         self.filter = filter;
     }
 
     /// Fetches unknown messages from the peers using an RPC protocol,
     /// and adds them to the local storage.
-    pub async fn fetch(&mut self) -> Result<(), Error> {
+    pub async fn fetch(&self) -> Result<(), Error> {
+        let known_messages = SetDigest::new(&self.read_message_hashes().await?);
         let mut tasks = Vec::new();
-        let messages = self.read_messages().await?;
-        let known_messages = messages
-            .into_iter()
-            .map(|m| m.to_hash256())
-            .collect::<Vec<_>>();
 
         for peer in self.peers.read().await {
             let storage = Arc::clone(&self.storage);
             let filter = Arc::clone(&self.filter);
             let port_key = format!("dms-{}", self.key);
-            let known_messages_ = known_messages.clone();
+            let known_messages = &known_messages;
             let key = self.key.clone();
+            let chain_id = self.config.chain_id;
+            let channel_config = ChannelConfig::from(&self.config.network_config);
             let task = async move {
-                let stub = DistributedMessageSetRpcInterfaceStub::new(Box::new(HttpClient::new(
-                    format!(
-                        "{}:{}/dms",
-                        peer.address.ip(),
-                        peer.ports
-                            .get(&port_key)
-                            .ok_or_else(|| eyre!("can't find port key: {}", port_key))?
-                    ),
-                    reqwest::Client::new(),
+                let port = peer
+                    .ports
+                    .get(&port_key)
+                    .ok_or_else(|| eyre!("can't find port key: {}", port_key))?;
+                let stub = DistributedMessageSetRpcInterfaceStub::new(Box::new(RpcClient::new(
+                    SocketAddrV4::new(*peer.address.ip(), *port),
+                    "dms",
+                    channel_config,
+                    peer.public_key.clone(),
                 )));
-                let raw_messages = stub
-                    .get_message(key, known_messages_)
-                    .await
-                    .map_err(|e| eyre!("{}", e))?
-                    .map_err(|e| eyre!(e))?;
+                let rpc_peer = RpcPeer {
+                    stub: &stub,
+                    dms_key: &key,
+                };
+                let raw_messages = fetch_unknowns(&rpc_peer, known_messages).await?;
                 let mut storage = storage.write().await;
                 for raw_message in raw_messages {
-                    let message = raw_message.into_message()?;
-                    filter.filter(&message).map_err(|e| eyre!("{}", e))?;
+                    let message = raw_message.into_message(&chain_id)?;
+                    // A rejected message does not spoil the others from the same peer.
+                    if let Err(e) = filter.filter(&message) {
+                        log::debug!("rejected a message fetched from {:?}: {}", peer, e);
+                        continue;
+                    }
                     Self::add_message_but_not_broadcast(&mut *storage, message).await?;
                 }
                 Result::<(), Error>::Ok(())
@@ -296,7 +350,7 @@ impl<N: GossipNetwork, S: Storage> DistributedMessageSet<N, S> {
     ///
     /// Note that it is guaranteed that the message will not be broadcasted unless it
     /// is successfully added to the storage. (but it is not guaranteed for the other way around)
-    pub async fn add_message(&mut self, message: Message) -> Result<(), Error> {
+    pub async fn add_message(&self, message: Message) -> Result<(), Error> {
         Self::add_message_but_not_broadcast(&mut *(self.storage.write().await), message.clone())
             .await?;
         Ok(())
@@ -314,16 +368,18 @@ impl<N: GossipNetwork, S: Storage> DistributedMessageSet<N, S> {
         for peer in self.peers.read().await {
             let port_key = format!("dms-{}", self.key);
             let messages_ = messages.clone();
+            let channel_config = ChannelConfig::from(&self.config.network_config);
+            let label = format!("RPC message add to {}", peer.public_key);
             let task = async move {
-                let stub = DistributedMessageSetRpcInterfaceStub::new(Box::new(HttpClient::new(
-                    format!(
-                        "{}:{}/dms",
-                        peer.address.ip(),
-                        peer.ports
-                            .get(&port_key)
-                            .ok_or_else(|| eyre!("can't find port key: {}", port_key))?
-                    ),
-                    reqwest::Client::new(),
+                let port = peer
+                    .ports
+                    .get(&port_key)
+                    .ok_or_else(|| eyre!("can't find port key: {}", port_key))?;
+                let stub = DistributedMessageSetRpcInterfaceStub::new(Box::new(RpcClient::new(
+                    SocketAddrV4::new(*peer.address.ip(), *port),
+                    "dms",
+                    channel_config,
+                    peer.public_key,
                 )));
                 stub.add_messages(self.key.clone(), messages_.clone())
                     .await
@@ -331,7 +387,7 @@ impl<N: GossipNetwork, S: Storage> DistributedMessageSet<N, S> {
                     .map_err(|e| eyre!(e))?;
                 Result::<(), Error>::Ok(())
             };
-            tasks1.push((task, format!("RPC message add to {}", peer.public_key)));
+            tasks1.push((task, label));
         }
         let peers_ = self.peers.read().await;
         let tasks2 = messages.into_iter().map(|message| {
@@ -343,6 +399,7 @@ impl<N: GossipNetwork, S: Storage> DistributedMessageSet<N, S> {
                     N::broadcast(
                         &network_config,
                         &peers,
+                        &self.key,
                         serde_spb::to_vec(&message).unwrap(),
                     )
                     .await?;
@@ -387,11 +444,45 @@ impl<N: GossipNetwork, S: Storage> DistributedMessageSet<N, S> {
             .collect::<Result<Vec<RawMessage>, _>>()?;
         let messages = messages
             .into_iter()
-            .map(|d| d.into_message())
+            .map(|d| d.into_message(&self.config.chain_id))
             .collect::<Result<Vec<_>, _>>()?;
         Ok(messages)
     }
 
+    /// Reads the hashes of the messages from the names of their files,
+    /// which is much cheaper than `read_messages()`.
+    pub async fn read_message_hashes(&self) -> Result<Vec<Hash256>, Error> {
+        self.storage
+            .read()
+            .await
+            .list_files()
+            .await?
+            .into_iter()
+            .filter(|f| f != STATE_FILE_PATH)
+            .filter_map(|f| Some(f.strip_suffix(".json")?.to_owned()))
+            .map(|hash| Ok(serde_json::from_value(serde_json::Value::String(hash))?))
+            .collect()
+    }
+
+    /// Reads the messages of the given hashes from the storage,
+    /// which is cheaper than `read_messages()` for a part of them.
+    pub async fn read_messages_of(&self, hashes: &[Hash256]) -> Result<Vec<Message>, Error> {
+        let tasks = hashes.iter().map(|hash| async move {
+            self.storage
+                .read()
+                .await
+                .read_file(&format!("{}.json", hash))
+                .await
+        });
+        let data = future::join_all(tasks)
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+        data.into_iter()
+            .map(|d| serde_spb::from_str::<RawMessage>(&d)?.into_message(&self.config.chain_id))
+            .collect()
+    }
+
     async fn add_message_but_not_broadcast(
         storage: &mut impl Storage,
         message: Message,
@@ -412,7 +503,12 @@ impl<N: GossipNetwork, S: Storage> DistributedMessageSet<N, S> {
         Ok(())
     }
 
-    async fn serve_rpc(this: Arc<RwLock<Self>>, rpc_port: u16) -> Result<(), Error> {
+    async fn serve_rpc(
+        this: Arc<RwLock<Self>>,
+        rpc_port: u16,
+        stop: impl Future<Output = ()>,
+    ) -> Result<(), Error> {
+        let channel_config = ChannelConfig::from(&this.read().await.config.network_config);
         let wrapped_this = Arc::new(parking_lot::RwLock::new(Some(this)));
         let wrapped_this_ = Arc::clone(&wrapped_this);
 
@@ -425,8 +521,9 @@ impl<N: GossipNetwork, S: Storage> DistributedMessageSet<N, S> {
             }
         }
         let _drop_helper = DropHelper { wrapped_this };
-        run_server(
+        run_server_until(
             rpc_port,
+            channel_config,
             [(
                 "dms".to_owned(),
                 create_http_object(Arc::new(DmsWrapper { dms: wrapped_this_ })
@@ -435,9 +532,9 @@ impl<N: GossipNetwork, S: Storage> DistributedMessageSet<N, S> {
             .iter()
             .cloned()
             .collect(),
+            stop,
         )
-        .await;
-        Ok(())
+        .await
     }
 
     async fn serve_fetch(this: Arc<RwLock<Self>>) -> Result<(), Error> {
@@ -447,7 +544,8 @@ impl<N: GossipNetwork, S: Storage> DistributedMessageSet<N, S> {
             return Result::<(), Error>::Ok(());
         };
         loop {
-            if let Err(e) = Self::fetch(&mut *this.write().await).await {
+            // Only a read lock, as the peers may be fetching from this at the same time.
+            if let Err(e) = Self::fetch(&*this.read().await).await {
                 log::warn!("failed to parse message from the RPC-fetch: {}", e);
             }
             tokio::time::sleep(interval).await;
@@ -469,15 +567,25 @@ impl<N: GossipNetwork, S: Storage> DistributedMessageSet<N, S> {
     }
 
     async fn serve_gossip(this: Arc<RwLock<Self>>) -> Result<(), Error> {
-        let mut recv = N::serve(
+        let (mut recv, task) = N::serve(
             this.read().await.config.network_config.clone(),
+            this.read().await.key.clone(),
             this.read().await.peers.clone(),
         )
         .await?;
-        while let Some(m) = recv.0.recv().await {
+        // The gossip node stops serving together with this.
+        struct AbortOnDrop<T>(tokio::task::JoinHandle<T>);
+        impl<T> Drop for AbortOnDrop<T> {
+            fn drop(&mut self) {
+                self.0.abort();
+            }
+        }
+        let _task = AbortOnDrop(task);
+        let chain_id = this.read().await.config.chain_id;
+        while let Some(m) = recv.recv().await {
             let result = async {
                 let message: RawMessage = serde_spb::from_slice(&m)?;
-                let message = message.into_message()?;
+                let message = message.into_message(&chain_id)?;
                 this.read()
                     .await
                     .filter
@@ -502,17 +610,35 @@ impl<N: GossipNetwork, S: Storage> DistributedMessageSet<N, S> {
     ///
     /// TODO: currently it just returns itself after the given time.
     pub async fn serve(self, time_in_ms: u64) -> Result<Self, Error> {
-        let port_key = format!("dms-{}", self.key);
-        let port = *self
+        let this = Arc::new(RwLock::new(self));
+        Self::serve_until(
+            Arc::clone(&this),
+            tokio::time::sleep(std::time::Duration::from_millis(time_in_ms)),
+        )
+        .await?;
+        Ok(Arc::try_unwrap(this).unwrap().into_inner())
+    }
+
+    /// Serves like `serve()` until the given future completes,
+    /// on a shared instance which can be used meanwhile.
+    pub async fn serve_until(
+        this: Arc<RwLock<Self>>,
+        stop: impl Future<Output = ()> + Send,
+    ) -> Result<(), Error> {
+        let port_key = format!("dms-{}", this.read().await.key);
+        let port = *this
+            .read()
+            .await
             .config
             .network_config
             .ports
             .get(&port_key)
             .ok_or_else(|| eyre!(format!("`ports` has no field of {port_key}")))?;
 
-        let this = Arc::new(RwLock::new(self));
+        // The RPC server stops on its own, waiting for the calls in progress
+        // which may be holding `this`.
         let this_ = Arc::clone(&this);
-        let rpc_task = async move { Self::serve_rpc(this_, port).await.map(|_| false) };
+        let rpc_task = async move { Self::serve_rpc(this_, port, stop).await.map(|_| true) };
         let this_ = Arc::clone(&this);
         let fetch_task = async move { Self::serve_fetch(this_).await.map(|_| false) };
         let this_ = Arc::clone(&this);
@@ -525,9 +651,6 @@ impl<N: GossipNetwork, S: Storage> DistributedMessageSet<N, S> {
             fetch_task.boxed(),
             broadcast_task.boxed(),
             gossip_task.boxed(),
-            tokio::time::sleep(std::time::Duration::from_millis(time_in_ms))
-                .map(|_| Ok(true))
-                .boxed(),
         ];
         loop {
             let (result, _, remaining_futures) = future::select_all(tasks).await;
@@ -537,7 +660,24 @@ impl<N: GossipNetwork, S: Storage> DistributedMessageSet<N, S> {
             }
             tasks = remaining_futures;
         }
-        Ok(Arc::try_unwrap(this).unwrap().into_inner())
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<N: GossipNetwork, S: Storage> MessageSet for DistributedMessageSet<N, S> {
+    type Message = Message;
+
+    fn dms_key(&self) -> &str {
+        &self.key
+    }
+
+    async fn read_message_hashes(&self) -> Result<Vec<Hash256>, Error> {
+        DistributedMessageSet::read_message_hashes(self).await
+    }
+
+    async fn read_messages_of(&self, hashes: &[Hash256]) -> Result<Vec<Message>, Error> {
+        DistributedMessageSet::read_messages_of(self, hashes).await
     }
 }
 
@@ -623,6 +763,7 @@ mod tests {
                 fetch_interval: Some(std::time::Duration::from_millis(500)),
                 broadcast_interval: Some(std::time::Duration::from_millis(500)),
                 network_config,
+                chain_id: Hash256::zero(),
             },
             peers,
         )
@@ -632,7 +773,7 @@ mod tests {
 
     #[tokio::test]
     async fn single_1() {
-        let mut dms = setup(
+        let dms = setup(
             NetworkConfig {
                 network_id: "doesn't matter".to_owned(),
                 ports: Default::default(),
@@ -649,7 +790,12 @@ mod tests {
             let msg = format!("{i}");
             dms.add_message(Message {
                 data: msg.clone(),
-                signature: TypedSignature::sign(&msg, &network_config.private_key).unwrap(),
+                signature: TypedSignature::sign(
+                    &msg,
+                    &Hash256::zero(),
+                    &network_config.private_key,
+                )
+                .unwrap(),
             })
             .await
             .unwrap();
@@ -670,7 +816,7 @@ mod tests {
 
     async fn run_non_server_node_1(
         index: usize,
-        mut dms: Dms,
+        dms: Dms,
         my_numbers: Vec<usize>,
         other_numbers: Vec<usize>,
         network_config: NetworkConfig,
@@ -680,7 +826,12 @@ mod tests {
             let msg = format!("{i}");
             dms.add_message(Message {
                 data: msg.clone(),
-                signature: TypedSignature::sign(&msg, &network_config.private_key).unwrap(),
+                signature: TypedSignature::sign(
+                    &msg,
+                    &Hash256::zero(),
+                    &network_config.private_key,
+                )
+                .unwrap(),
             })
             .await
             .unwrap();
@@ -758,6 +909,101 @@ mod tests {
         handle.await.unwrap();
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reconciliation_traffic() {
+        setup_test();
+        let (server_network_config, client_network_configs, _) =
+            generate_node_configs(dispense_port(), 2);
+        let client_network_config = &client_network_configs[0];
+        let key = server_network_config.network_id.clone();
+        let dms = setup(
+            server_network_config.clone(),
+            SharedKnownPeers::new(Default::default()),
+        )
+        .await;
+        let message = |data: String| {
+            let signature =
+                TypedSignature::sign(&data, &Hash256::zero(), &server_network_config.private_key)
+                    .unwrap();
+            Message::new(data, signature, &Hash256::zero()).unwrap()
+        };
+
+        // The client lacks the last few messages of the server, and has a few of its own.
+        let (size, server_only, client_only) = (3000, 5, 3);
+        let server_messages: Vec<_> = (0..size).map(|i| message(format!("{i}"))).collect();
+        for m in &server_messages {
+            dms.add_message(m.clone()).await.unwrap();
+        }
+        let server_hashes: Vec<_> = server_messages.iter().map(|m| m.to_hash256()).collect();
+        let client_hashes: Vec<_> = server_hashes[..size - server_only]
+            .iter()
+            .cloned()
+            .chain((0..client_only).map(|i| message(format!("client-{i}")).to_hash256()))
+            .collect();
+
+        let server_public_key = server_network_config.public_key.clone();
+        let port = server_network_config.ports[&format!("dms-{key}")];
+        // The server is up for the whole test, however slow the exchanges are.
+        let (stop_sender, stop_receiver) = tokio::sync::oneshot::channel::<()>();
+        let serve_task = tokio::spawn(Dms::serve_until(Arc::new(RwLock::new(dms)), async {
+            let _ = stop_receiver.await;
+        }));
+        sleep(200).await;
+        let bytes = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let stub = DistributedMessageSetRpcInterfaceStub::new(Box::new(CountingCall {
+            inner: RpcClient::new(
+                SocketAddrV4::new("127.0.0.1".parse().unwrap(), port),
+                "dms",
+                ChannelConfig::from(client_network_config),
+                server_public_key,
+            ),
+            bytes: Arc::clone(&bytes),
+        }));
+        let take_bytes = || bytes.swap(0, std::sync::atomic::Ordering::SeqCst);
+        let data = |messages: Vec<RawMessage>| {
+            messages
+                .into_iter()
+                .map(|m| m.data)
+                .collect::<std::collections::BTreeSet<_>>()
+        };
+        let expected: std::collections::BTreeSet<_> = server_messages[size - server_only..]
+            .iter()
+            .map(|m| m.data().to_owned())
+            .collect();
+
+        let fetched = stub
+            .get_message(key.clone(), client_hashes.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(data(fetched), expected);
+        let full_exchange = take_bytes();
+
+        let peer = RpcPeer {
+            stub: &stub,
+            dms_key: &key,
+        };
+        let fetched = fetch_unknowns(&peer, &SetDigest::new(&client_hashes))
+            .await
+            .unwrap();
+        assert_eq!(data(fetched), expected);
+        let reconciliation = take_bytes();
+        assert!(
+            reconciliation * 10 < full_exchange,
+            "{reconciliation} bytes for the reconciliation, {full_exchange} bytes for the full exchange"
+        );
+
+        // Nothing but the root digests for the same sets.
+        assert!(fetch_unknowns(&peer, &SetDigest::new(&server_hashes))
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(take_bytes() < 1024);
+
+        stop_sender.send(()).unwrap();
+        serve_task.await.unwrap().unwrap();
+    }
+
     // Same, but the server node is not online from the beginning
     #[tokio::test]
     async fn multi_dummy_gn_single_sn_2() {
@@ -796,4 +1042,88 @@ mod tests {
         join_all(tasks).await;
         handle.await.unwrap();
     }
+
+    /// A member who knows only an offline peer receives the messages
+    /// relayed over the gossip network by the peers that know it.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn gossip_relay_to_rarely_online_member() {
+        setup_test();
+        type TcpDms = DistributedMessageSet<crate::gossip::TcpGossipNetwork, StorageImpl>;
+        let network_id = generate_random_string();
+        let keys = (0..4)
+            .map(|_| generate_keypair_random())
+            .collect::<Vec<_>>();
+        let dms_port_key = format!("dms-{network_id}");
+        let gossip_port_key = crate::gossip::gossip_port_key(&network_id);
+        let configs = keys
+            .iter()
+            .map(|(public_key, private_key)| NetworkConfig {
+                network_id: network_id.clone(),
+                ports: [
+                    (dms_port_key.clone(), dispense_port()),
+                    (gossip_port_key.clone(), dispense_port()),
+                ]
+                .into_iter()
+                .collect(),
+                members: keys.iter().map(|(x, _)| x).cloned().collect(),
+                public_key: public_key.clone(),
+                private_key: private_key.clone(),
+            })
+            .collect::<Vec<_>>();
+        // Only the gossip port is known to the others, except for the offline one.
+        let peers = configs
+            .iter()
+            .enumerate()
+            .map(|(i, config)| Peer {
+                public_key: config.public_key.clone(),
+                name: format!("{i}"),
+                address: SocketAddrV4::new("127.0.0.1".parse().unwrap(), 1),
+                ports: config
+                    .ports
+                    .iter()
+                    .filter(|(key, _)| i == 3 || **key == gossip_port_key)
+                    .map(|(key, port)| (key.clone(), *port))
+                    .collect(),
+                message: "".to_owned(),
+                recently_seen_timestamp: 0,
+            })
+            .collect::<Vec<_>>();
+
+        // The origin knows the relay, which knows the rarely-online member,
+        // which knows only the offline one.
+        let mut nodes = Vec::new();
+        for (config, known_peer) in configs.iter().zip(&peers[1..]).take(3) {
+            let path = create_temp_dir();
+            StorageImpl::create(&path).await.unwrap();
+            let dms = TcpDms::new(
+                StorageImpl::open(&path).await.unwrap(),
+                network_id.clone(),
+                dms::Config {
+                    fetch_interval: Some(std::time::Duration::from_millis(500)),
+                    broadcast_interval: Some(std::time::Duration::from_millis(500)),
+                    network_config: config.clone(),
+                    chain_id: Hash256::zero(),
+                },
+                SharedKnownPeers::new_static(vec![known_peer.clone()]),
+            )
+            .await
+            .unwrap();
+            nodes.push(dms);
+        }
+        let msg = "hello".to_owned();
+        nodes[0]
+            .add_message(Message {
+                data: msg.clone(),
+                signature: TypedSignature::sign(&msg, &Hash256::zero(), &configs[0].private_key)
+                    .unwrap(),
+            })
+            .await
+            .unwrap();
+        let nodes = join_all(nodes.into_iter().map(|dms| dms.serve(3_000))).await;
+        let messages = nodes[2].as_ref().unwrap().read_messages().await.unwrap();
+        assert_eq!(
+            messages.into_iter().map(|x| x.data).collect::<Vec<_>>(),
+            vec![msg]
+        );
+    }
 }
@@ -1,5 +1,7 @@
 use super::Storage;
 use super::*;
+use crate::reconciliation::*;
+use crate::secure::{run_server, ChannelConfig, RpcClient};
 use async_trait::async_trait;
 use eyre::eyre;
 use futures::future::join;
@@ -7,7 +9,6 @@ use futures::prelude::*;
 use serde_tc::http::*;
 use serde_tc::{serde_tc_full, StubCall};
 use simperby_common::*;
-use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::RwLock;
@@ -27,8 +28,9 @@ impl Message {
         data: String,
         dms_key: DmsKey,
         signature: TypedSignature<(String, DmsKey)>,
+        chain_id: &Hash256,
     ) -> Result<Self, CryptoError> {
-        signature.verify(&(data.clone(), dms_key.clone()))?;
+        signature.verify(&(data.clone(), dms_key.clone()), chain_id)?;
         Ok(Self {
             data,
             dms_key,
@@ -65,8 +67,8 @@ pub struct RawMessage {
 }
 
 impl RawMessage {
-    pub fn try_into_message(self) -> eyre::Result<Message> {
-        Message::new(self.data, self.dms_key, self.signature).map_err(|e| eyre!(e))
+    pub fn try_into_message(self, chain_id: &Hash256) -> eyre::Result<Message> {
+        Message::new(self.data, self.dms_key, self.signature, chain_id).map_err(|e| eyre!(e))
     }
 
     pub fn from_message(message: Message) -> Self {
@@ -82,6 +84,8 @@ impl RawMessage {
 pub struct Config {
     pub dms_key: String,
     pub peers: Vec<PublicKey>,
+    /// The chain that the messages are signed for (see `GenesisInfo::chain_id()`).
+    pub chain_id: Hash256,
 }
 
 pub struct DistributedMessageSet<S> {
@@ -196,11 +200,50 @@ impl<S: Storage> DistributedMessageSet<S> {
             .collect::<Result<Vec<RawMessage>, _>>()?;
         let messages = messages
             .into_iter()
-            .map(|d| d.try_into_message())
+            .map(|d| d.try_into_message(&self.config.chain_id))
             .collect::<Result<Vec<_>, _>>()?;
         Ok(messages)
     }
 
+    /// Reads the hashes of the messages from the names of their files,
+    /// which is much cheaper than `read_messages()`.
+    pub async fn read_message_hashes(&self) -> Result<Vec<Hash256>, Error> {
+        self.storage
+            .read()
+            .await
+            .list_files()
+            .await?
+            .into_iter()
+            .filter_map(|f| {
+                Some(
+                    f.strip_prefix("message-")?
+                        .strip_suffix(".json")?
+                        .to_owned(),
+                )
+            })
+            .map(|hash| Ok(serde_json::from_value(serde_json::Value::String(hash))?))
+            .collect()
+    }
+
+    /// Reads the messages of the given hashes from the storage,
+    /// which is cheaper than `read_messages()` for a part of them.
+    pub async fn read_messages_of(&self, hashes: &[Hash256]) -> Result<Vec<Message>, Error> {
+        let tasks = hashes.iter().map(|hash| async move {
+            self.storage
+                .read()
+                .await
+                .read_file(&format!("message-{}.json", hash))
+                .await
+        });
+        let data = future::join_all(tasks)
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+        data.into_iter()
+            .map(|d| serde_spb::from_str::<RawMessage>(&d)?.try_into_message(&self.config.chain_id))
+            .collect()
+    }
+
     /// Signs the given message and adds it to the storage.
     pub async fn add_message(&mut self, data: String) -> Result<(), Error> {
         let message = Message {
@@ -208,6 +251,7 @@ impl<S: Storage> DistributedMessageSet<S> {
             dms_key: self.config.dms_key.clone(),
             signature: TypedSignature::sign(
                 &(data, self.config.dms_key.clone()),
+                &self.config.chain_id,
                 &self.private_key,
             )?,
         };
@@ -240,39 +284,36 @@ impl<S: Storage> DistributedMessageSet<S> {
         this: Arc<RwLock<Self>>,
         network_config: &ClientNetworkConfig,
     ) -> Result<(), Error> {
+        let known_messages = SetDigest::new(&this.read().await.read_message_hashes().await?);
         let mut tasks = Vec::new();
-        let messages = this.read().await.read_messages().await?;
-        let known_messages = messages
-            .into_iter()
-            .map(|m| m.to_hash256())
-            .collect::<Vec<_>>();
 
         for peer in &network_config.peers {
-            let known_messages_ = known_messages.clone();
+            let known_messages = &known_messages;
             let this_ = Arc::clone(&this);
             let task = async move {
                 let this_read = this_.read().await;
                 let filter = Arc::clone(&this_read.filter);
                 let port_key = format!("dms-{}", this_read.config.dms_key);
-                let stub = DistributedMessageSetRpcInterfaceStub::new(Box::new(HttpClient::new(
-                    format!(
-                        "{}:{}/dms",
-                        peer.address.ip(),
-                        peer.ports
-                            .get(&port_key)
-                            .ok_or_else(|| eyre!("can't find port key: {}", port_key))?
-                    ),
-                    reqwest::Client::new(),
+                let port = peer
+                    .ports
+                    .get(&port_key)
+                    .ok_or_else(|| eyre!("can't find port key: {}", port_key))?;
+                let stub = DistributedMessageSetRpcInterfaceStub::new(Box::new(RpcClient::new(
+                    SocketAddrV4::new(*peer.address.ip(), *port),
+                    "dms",
+                    ChannelConfig::from(network_config),
+                    peer.public_key.clone(),
                 )));
-                let raw_messages = stub
-                    .get_messages(this_read.config.dms_key.clone(), known_messages_)
-                    .await
-                    .map_err(|e| eyre!("{}", e))?
-                    .map_err(|e| eyre!(e))?;
+                let rpc_peer = RpcPeer {
+                    stub: &stub,
+                    dms_key: &this_read.config.dms_key,
+                };
+                let raw_messages = fetch_unknowns(&rpc_peer, known_messages).await?;
                 // Important: drop the lock before `write()`
+                let chain_id = this_read.config.chain_id;
                 drop(this_read);
                 for raw_message in raw_messages {
-                    let message = raw_message.try_into_message()?;
+                    let message = raw_message.try_into_message(&chain_id)?;
                     filter.filter(&message).map_err(|e| eyre!("{}", e))?;
                     this_.write().await.add_raw_message(message).await?;
                 }
@@ -312,15 +353,15 @@ impl<S: Storage> DistributedMessageSet<S> {
             let port_key = format!("dms-{key}");
             let messages_ = messages.clone();
             let task = async move {
-                let stub = DistributedMessageSetRpcInterfaceStub::new(Box::new(HttpClient::new(
-                    format!(
-                        "{}:{}/dms",
-                        peer.address.ip(),
-                        peer.ports
-                            .get(&port_key)
-                            .ok_or_else(|| eyre!("can't find port key: {}", port_key))?
-                    ),
-                    reqwest::Client::new(),
+                let port = peer
+                    .ports
+                    .get(&port_key)
+                    .ok_or_else(|| eyre!("can't find port key: {}", port_key))?;
+                let stub = DistributedMessageSetRpcInterfaceStub::new(Box::new(RpcClient::new(
+                    SocketAddrV4::new(*peer.address.ip(), *port),
+                    "dms",
+                    ChannelConfig::from(network_config),
+                    peer.public_key.clone(),
                 )));
                 stub.add_messages(key.clone(), messages_.clone())
                     .await
@@ -344,25 +385,41 @@ impl<S: Storage> DistributedMessageSet<S> {
     }
 }
 
-/// The interface that will be wrapped into an HTTP RPC server for the peers.
+/// The interface that will be wrapped into an RPC server for the peers.
 #[serde_tc_full]
 trait DistributedMessageSetRpcInterface: Send + Sync + 'static {
     /// Returns the messages except `knowns`.
+    ///
+    /// This is the full exchange, which the reconciliation falls back to
+    /// when the sets are too different.
     async fn get_messages(
         &self,
         dms_key: DmsKey,
         knowns: Vec<Hash256>,
     ) -> Result<Vec<RawMessage>, String>;
 
+    /// Returns `None` if the messages of this node have the given root digest,
+    /// or the digests of their buckets otherwise.
+    async fn get_digest(
+        &self,
+        dms_key: DmsKey,
+        root: Hash256,
+    ) -> Result<Option<Vec<BucketDigest>>, String>;
+
+    /// Returns the messages in the given buckets except `knowns`.
+    async fn get_messages_in_buckets(
+        &self,
+        dms_key: DmsKey,
+        buckets: Vec<u8>,
+        knowns: Vec<Hash256>,
+    ) -> Result<Vec<RawMessage>, String>;
+
     /// Requests this node to accept a new message.
     async fn add_messages(&self, dms_key: DmsKey, messages: Vec<RawMessage>) -> Result<(), String>;
 }
 
 struct DmsWrapper<S: Storage> {
-    #[allow(clippy::type_complexity)]
-    /// This is an `Option` because we have to explicitly drop the server
-    /// (it could live forever in the RPC server (`axum`) otherwise)
-    dms: Arc<parking_lot::RwLock<Option<Arc<RwLock<DistributedMessageSet<S>>>>>>,
+    dms: ServedSet<DistributedMessageSet<S>>,
 }
 
 #[async_trait]
@@ -372,44 +429,36 @@ impl<S: Storage> DistributedMessageSetRpcInterface for DmsWrapper<S> {
         dms_key: DmsKey,
         knowns: Vec<Hash256>,
     ) -> Result<Vec<RawMessage>, String> {
-        let dms = Arc::clone(
-            self.dms
-                .read()
-                .as_ref()
-                .ok_or_else(|| "server terminated".to_owned())?,
-        );
-        let mut messages = dms
-            .read()
-            .await
-            .read_messages()
-            .await
-            .map_err(|e| e.to_string())?;
-        let dms_key_ = dms.read().await.config.dms_key.clone();
-        if dms_key != dms_key_ {
-            return Err(format!("key mismatch: requested {dms_key}, but {dms_key_}"));
-        }
-        let knowns: HashSet<_> = knowns.into_iter().collect();
-        let messages: Vec<_> = messages
-            .drain(..)
-            .filter(|m| !knowns.contains(&m.to_hash256()))
-            .map(RawMessage::from_message)
-            .collect();
-        Ok(messages)
+        let messages = serve_messages(&self.dms, &dms_key, knowns).await?;
+        Ok(messages.into_iter().map(RawMessage::from_message).collect())
+    }
+
+    async fn get_digest(
+        &self,
+        dms_key: DmsKey,
+        root: Hash256,
+    ) -> Result<Option<Vec<BucketDigest>>, String> {
+        serve_digest(&self.dms, &dms_key, root).await
+    }
+
+    async fn get_messages_in_buckets(
+        &self,
+        dms_key: DmsKey,
+        buckets: Vec<u8>,
+        knowns: Vec<Hash256>,
+    ) -> Result<Vec<RawMessage>, String> {
+        let messages =
+            serve_messages_in_buckets(&self.dms, &dms_key, Some(buckets), knowns).await?;
+        Ok(messages.into_iter().map(RawMessage::from_message).collect())
     }
 
     async fn add_messages(&self, dms_key: DmsKey, messages: Vec<RawMessage>) -> Result<(), String> {
-        let dms = Arc::clone(
-            self.dms
-                .read()
-                .as_ref()
-                .ok_or_else(|| "server terminated".to_owned())?,
-        );
-        let dms_key_ = dms.read().await.config.dms_key.clone();
-        if dms_key != dms.read().await.config.dms_key {
-            return Err(format!("key mismatch: requested {dms_key}, but {dms_key_}"));
-        }
+        let dms = get_served_set(&self.dms, &dms_key).await?;
+        let chain_id = dms.read().await.config.chain_id;
         for message in messages {
-            let message = message.try_into_message().map_err(|e| e.to_string())?;
+            let message = message
+                .try_into_message(&chain_id)
+                .map_err(|e| e.to_string())?;
             dms.write()
                 .await
                 .add_raw_message(message)
@@ -420,6 +469,54 @@ impl<S: Storage> DistributedMessageSetRpcInterface for DmsWrapper<S> {
     }
 }
 
+/// A peer to fetch the messages from.
+struct RpcPeer<'a> {
+    stub: &'a DistributedMessageSetRpcInterfaceStub,
+    dms_key: &'a DmsKey,
+}
+
+#[async_trait]
+impl MessageSetPeer for RpcPeer<'_> {
+    type Message = RawMessage;
+
+    async fn get_messages(&self, knowns: Vec<Hash256>) -> Result<Vec<RawMessage>, Error> {
+        flatten_rpc_result(self.stub.get_messages(self.dms_key.clone(), knowns).await)
+    }
+
+    async fn get_digest(&self, root: Hash256) -> Result<Option<Vec<BucketDigest>>, Error> {
+        flatten_rpc_result(self.stub.get_digest(self.dms_key.clone(), root).await)
+    }
+
+    async fn get_messages_in_buckets(
+        &self,
+        buckets: Vec<u8>,
+        knowns: Vec<Hash256>,
+    ) -> Result<Vec<RawMessage>, Error> {
+        flatten_rpc_result(
+            self.stub
+                .get_messages_in_buckets(self.dms_key.clone(), buckets, knowns)
+                .await,
+        )
+    }
+}
+
+#[async_trait]
+impl<S: Storage> MessageSet for DistributedMessageSet<S> {
+    type Message = Message;
+
+    fn dms_key(&self) -> &str {
+        &self.config.dms_key
+    }
+
+    async fn read_message_hashes(&self) -> Result<Vec<Hash256>, Error> {
+        DistributedMessageSet::read_message_hashes(self).await
+    }
+
+    async fn read_messages_of(&self, hashes: &[Hash256]) -> Result<Vec<Message>, Error> {
+        DistributedMessageSet::read_messages_of(self, hashes).await
+    }
+}
+
 struct DummyFilter;
 
 impl MessageFilter for DummyFilter {
@@ -439,6 +536,7 @@ pub async fn serve<S: Storage>(
         .get(&port_key)
         .ok_or_else(|| eyre!(format!("`ports` has no field of {port_key}")))?;
 
+    let channel_config = ChannelConfig::from(&network_config);
     let rpc_task = async move {
         let wrapped_dms = Arc::new(parking_lot::RwLock::new(Some(dms)));
         let wrapped_dms_ = Arc::clone(&wrapped_dms);
@@ -453,6 +551,7 @@ pub async fn serve<S: Storage>(
         let _drop_helper = DropHelper { wrapped_dms };
         run_server(
             *port,
+            channel_config,
             [(
                 "dms".to_owned(),
                 create_http_object(Arc::new(DmsWrapper { dms: wrapped_dms_ })
@@ -462,10 +561,9 @@ pub async fn serve<S: Storage>(
             .cloned()
             .collect(),
         )
-        .await;
+        .await
     };
-    rpc_task.await;
-    Ok(())
+    rpc_task.await
 }
 
 /// Runs a DMS client with auto-sync. This function will block the current thread.
@@ -586,12 +684,14 @@ mod tests {
 
     #[tokio::test]
     async fn single_1() {
+        setup_test();
         let key = generate_random_string();
         let network_config = generate_node_configs(dispense_port(), 1).0;
         let mut dms = create_dms(
             Config {
                 dms_key: key,
                 peers: vec![],
+                chain_id: Hash256::zero(),
             },
             network_config.private_key.clone(),
         )
@@ -642,6 +742,7 @@ mod tests {
 
     #[tokio::test]
     async fn multi_1() {
+        setup_test();
         let (server_network_config, client_network_configs) =
             generate_node_configs(dispense_port(), 5);
         let key = server_network_config.network_id.clone();
@@ -651,6 +752,7 @@ mod tests {
                 Config {
                     dms_key: key.clone(),
                     peers: vec![],
+                    chain_id: Hash256::zero(),
                 },
                 server_network_config.private_key.clone(),
             )
@@ -666,6 +768,7 @@ mod tests {
                     Config {
                         dms_key: key.clone(),
                         peers: vec![],
+                        chain_id: Hash256::zero(),
                     },
                     client_network_config.private_key.clone(),
                 )
@@ -699,4 +802,103 @@ mod tests {
             );
         }
     }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reconciliation_traffic() {
+        setup_test();
+        let (server_network_config, client_network_configs) =
+            generate_node_configs(dispense_port(), 2);
+        let client_network_config = &client_network_configs[0];
+        let key = server_network_config.network_id.clone();
+        let config = Config {
+            dms_key: key.clone(),
+            peers: vec![],
+            chain_id: Hash256::zero(),
+        };
+        let mut server_dms =
+            create_dms(config.clone(), server_network_config.private_key.clone()).await;
+        let mut client_dms = create_dms(config, client_network_config.private_key.clone()).await;
+
+        // The client lacks the last few messages of the server, and has a few of its own.
+        let (size, server_only, client_only) = (3000, 5, 3);
+        for i in 0..size {
+            server_dms.add_message(format!("{i}")).await.unwrap();
+        }
+        let mut server_messages = server_dms.read_messages().await.unwrap();
+        server_messages.sort_by_key(|m| m.data.parse::<usize>().unwrap());
+        for message in &server_messages[..size - server_only] {
+            client_dms.add_raw_message(message.clone()).await.unwrap();
+        }
+        for i in 0..client_only {
+            client_dms.add_message(format!("client-{i}")).await.unwrap();
+        }
+        let client_hashes: Vec<_> = client_dms
+            .read_messages()
+            .await
+            .unwrap()
+            .iter()
+            .map(|m| m.to_hash256())
+            .collect();
+
+        let server_public_key = server_network_config.public_key.clone();
+        let port = server_network_config.ports[&format!("dms-{key}")];
+        tokio::spawn(serve(
+            Arc::new(RwLock::new(server_dms)),
+            server_network_config,
+        ));
+        sleep_ms(200).await;
+        let bytes = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let stub = DistributedMessageSetRpcInterfaceStub::new(Box::new(CountingCall {
+            inner: RpcClient::new(
+                SocketAddrV4::new("127.0.0.1".parse().unwrap(), port),
+                "dms",
+                ChannelConfig::from(client_network_config),
+                server_public_key,
+            ),
+            bytes: Arc::clone(&bytes),
+        }));
+        let take_bytes = || bytes.swap(0, std::sync::atomic::Ordering::SeqCst);
+        let data = |messages: Vec<RawMessage>| {
+            messages
+                .into_iter()
+                .map(|m| m.data)
+                .collect::<std::collections::BTreeSet<_>>()
+        };
+        let expected = data(
+            server_messages[size - server_only..]
+                .iter()
+                .cloned()
+                .map(RawMessage::from_message)
+                .collect(),
+        );
+
+        let fetched = stub
+            .get_messages(key.clone(), client_hashes.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(data(fetched), expected);
+        let full_exchange = take_bytes();
+
+        let client_digest = SetDigest::new(&client_hashes);
+        let peer = RpcPeer {
+            stub: &stub,
+            dms_key: &key,
+        };
+        let fetched = fetch_unknowns(&peer, &client_digest).await.unwrap();
+        assert_eq!(data(fetched), expected);
+        let reconciliation = take_bytes();
+        assert!(
+            reconciliation * 10 < full_exchange,
+            "{reconciliation} bytes for the reconciliation, {full_exchange} bytes for the full exchange"
+        );
+
+        // Nothing but the root digests for the same sets.
+        let server_hashes: Vec<_> = server_messages.iter().map(|m| m.to_hash256()).collect();
+        assert!(fetch_unknowns(&peer, &SetDigest::new(&server_hashes))
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(take_bytes() < 1024);
+    }
 }
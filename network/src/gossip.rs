@@ -0,0 +1,349 @@
+//! A gossip network flooding the messages over TCP.
+//!
+//! A message is sent to at most `FANOUT` random peers, each of which relays it
+//! to its own peers in the same way until its TTL runs out.
+//! A node delivers and relays a message only once, identifying it by the hash.
+//!
+//! Since every peer relays, a member receives the messages
+//! from whichever peers that know it happen to be online,
+//! not only from the peers it knows.
+//!
+//! The messages are sent on the secure channels, so only the members take part in it.
+use super::*;
+use crate::secure::{bind, ChannelConfig, SecureStream};
+use eyre::eyre;
+use futures::prelude::*;
+use rand::seq::SliceRandom;
+use simperby_common::serde_spb;
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+
+/// The maximum number of the peers that a node sends or relays a message to.
+pub const FANOUT: usize = 4;
+/// The number of the relays after which a message is no longer propagated.
+pub const TTL: u8 = 6;
+/// How long a delivered message is remembered, to ignore its duplicates.
+const SEEN_DURATION: Duration = Duration::from_secs(60);
+const TIMEOUT: Duration = Duration::from_secs(5);
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// Returns the key of the port (in `NetworkConfig::ports` and `Peer::ports`)
+/// on which the gossip network of the given topic is served.
+pub fn gossip_port_key(topic: &str) -> String {
+    format!("gossip-{topic}")
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Envelope {
+    network_id: String,
+    topic: String,
+    /// The number of the relays left.
+    ttl: u8,
+    data: Vec<u8>,
+}
+
+/// The hashes of the recently delivered messages.
+#[derive(Default)]
+struct SeenMessages {
+    hashes: HashSet<Hash256>,
+    /// In the order of delivery.
+    expirations: VecDeque<(Instant, Hash256)>,
+}
+
+impl SeenMessages {
+    /// Remembers the message, returning whether it has not been seen recently.
+    fn insert(&mut self, hash: Hash256) -> bool {
+        let now = Instant::now();
+        while matches!(
+            self.expirations.front(),
+            Some((time, _)) if now.duration_since(*time) > SEEN_DURATION
+        ) {
+            let (_, expired) = self.expirations.pop_front().unwrap();
+            self.hashes.remove(&expired);
+        }
+        if !self.hashes.insert(hash) {
+            return false;
+        }
+        self.expirations.push_back((now, hash));
+        true
+    }
+}
+
+/// Chooses at most `FANOUT` random peers serving the topic.
+fn choose_targets(peers: &[Peer], topic: &str) -> Vec<(SocketAddrV4, PublicKey)> {
+    let port_key = gossip_port_key(topic);
+    let mut targets: Vec<_> = peers
+        .iter()
+        .filter_map(|peer| {
+            peer.ports.get(&port_key).map(|port| {
+                (
+                    SocketAddrV4::new(*peer.address.ip(), *port),
+                    peer.public_key.clone(),
+                )
+            })
+        })
+        .collect();
+    targets.shuffle(&mut rand::thread_rng());
+    targets.truncate(FANOUT);
+    targets
+}
+
+async fn send(
+    config: &ChannelConfig,
+    (address, peer): &(SocketAddrV4, PublicKey),
+    frame: &[u8],
+) -> Result<(), Error> {
+    let mut stream = SecureStream::connect(*address, config, peer).await?;
+    tokio::time::timeout(TIMEOUT, async {
+        stream.send(frame).await?;
+        stream.shutdown().await
+    })
+    .await?
+}
+
+async fn send_all(
+    config: &ChannelConfig,
+    peers: &[Peer],
+    envelope: &Envelope,
+) -> Result<(), Error> {
+    let frame = serde_spb::to_vec(envelope)?;
+    let targets = choose_targets(peers, &envelope.topic);
+    let results = future::join_all(targets.iter().map(|target| send(config, target, &frame))).await;
+    for (result, (target, _)) in results.into_iter().zip(targets) {
+        if let Err(e) = result {
+            log::debug!("failed to send a gossip message to {}: {}", target, e);
+        }
+    }
+    Ok(())
+}
+
+/// Delivers the message received from the stream if new, relaying it to the peers.
+async fn handle(
+    stream: TcpStream,
+    config: &ChannelConfig,
+    topic: &str,
+    peers: &SharedKnownPeers,
+    seen: &parking_lot::Mutex<SeenMessages>,
+    send_delivery: &mpsc::Sender<Vec<u8>>,
+) -> Result<(), Error> {
+    let mut stream = SecureStream::accept(stream, config).await?;
+    let frame = tokio::time::timeout(TIMEOUT, stream.receive()).await??;
+    let envelope: Envelope = serde_spb::from_slice(&frame)?;
+    if envelope.network_id != config.network_id || envelope.topic != topic {
+        return Err(eyre!(
+            "gossip message of another network or topic: {}/{}",
+            envelope.network_id,
+            envelope.topic
+        ));
+    }
+    if !seen.lock().insert(Hash256::hash(&envelope.data)) {
+        return Ok(());
+    }
+    if send_delivery.send(envelope.data.clone()).await.is_err() {
+        // The receiver has been dropped; nothing to serve.
+        return Ok(());
+    }
+    if envelope.ttl > 0 {
+        let envelope = Envelope {
+            ttl: envelope.ttl - 1,
+            ..envelope
+        };
+        send_all(config, &peers.read().await, &envelope).await?;
+    }
+    Ok(())
+}
+
+pub struct TcpGossipNetwork;
+
+#[async_trait]
+impl GossipNetwork for TcpGossipNetwork {
+    async fn broadcast(
+        config: &NetworkConfig,
+        known_peers: &[Peer],
+        topic: &str,
+        message: Vec<u8>,
+    ) -> Result<(), Error> {
+        let envelope = Envelope {
+            network_id: config.network_id.clone(),
+            topic: topic.to_owned(),
+            ttl: TTL,
+            data: message,
+        };
+        send_all(&ChannelConfig::from(config), known_peers, &envelope).await
+    }
+
+    /// If there is no port for the topic in `config`, it only sends messages
+    /// and serves nothing.
+    async fn serve(
+        config: NetworkConfig,
+        topic: String,
+        peers: SharedKnownPeers,
+    ) -> Result<
+        (
+            mpsc::Receiver<Vec<u8>>,
+            tokio::task::JoinHandle<Result<(), Error>>,
+        ),
+        Error,
+    > {
+        let (send_delivery, recv) = mpsc::channel(CHANNEL_CAPACITY);
+        let Some(port) = config.ports.get(&gossip_port_key(&topic)).copied() else {
+            log::info!("no port to serve the gossip network of {}", topic);
+            let task = tokio::spawn(async move {
+                let _send_delivery = send_delivery;
+                future::pending::<()>().await;
+                Ok(())
+            });
+            return Ok((recv, task));
+        };
+        let listener = bind(port).await?;
+        let task = tokio::spawn(async move {
+            let context = Arc::new((
+                ChannelConfig::from(&config),
+                topic,
+                peers,
+                parking_lot::Mutex::new(SeenMessages::default()),
+                send_delivery,
+            ));
+            loop {
+                let (stream, address) = listener.accept().await?;
+                let context = Arc::clone(&context);
+                tokio::spawn(async move {
+                    let (config, topic, peers, seen, send_delivery) = &*context;
+                    if let Err(e) = handle(stream, config, topic, peers, seen, send_delivery).await
+                    {
+                        log::debug!("failed to handle a gossip message from {}: {}", address, e);
+                    }
+                });
+            }
+        });
+        Ok((recv, task))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simperby_test_suite::*;
+    use std::net::Ipv4Addr;
+
+    const TOPIC: &str = "test";
+
+    /// Returns the configs and peers of the nodes each serving the topic.
+    fn generate_nodes(network_id: &str, size: usize) -> (Vec<NetworkConfig>, Vec<Peer>) {
+        let keys: Vec<_> = (0..size).map(|_| generate_keypair_random()).collect();
+        let configs: Vec<_> = keys
+            .iter()
+            .map(|(public_key, private_key)| NetworkConfig {
+                network_id: network_id.to_owned(),
+                ports: [(gossip_port_key(TOPIC), dispense_port())]
+                    .into_iter()
+                    .collect(),
+                members: keys.iter().map(|(x, _)| x.clone()).collect(),
+                public_key: public_key.clone(),
+                private_key: private_key.clone(),
+            })
+            .collect();
+        let peers = configs
+            .iter()
+            .map(|config| Peer {
+                public_key: config.public_key.clone(),
+                name: config.public_key.to_string(),
+                address: SocketAddrV4::new(Ipv4Addr::LOCALHOST, 1),
+                ports: config.ports.clone(),
+                message: "".to_owned(),
+                recently_seen_timestamp: 0,
+            })
+            .collect();
+        (configs, peers)
+    }
+
+    async fn serve_all(
+        configs: &[NetworkConfig],
+        known_peers: Vec<Vec<Peer>>,
+    ) -> Vec<mpsc::Receiver<Vec<u8>>> {
+        let mut receivers = Vec::new();
+        for (config, peers) in configs.iter().zip(known_peers) {
+            let (recv, _) = TcpGossipNetwork::serve(
+                config.clone(),
+                TOPIC.to_owned(),
+                SharedKnownPeers::new_static(peers),
+            )
+            .await
+            .unwrap();
+            receivers.push(recv);
+        }
+        receivers
+    }
+
+    async fn receive_all(receiver: &mut mpsc::Receiver<Vec<u8>>) -> Vec<Vec<u8>> {
+        tokio::time::sleep(Duration::from_millis(1_000)).await;
+        let mut messages = Vec::new();
+        while let Ok(message) = receiver.try_recv() {
+            messages.push(message);
+        }
+        messages
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn relay_within_ttl() {
+        setup_test();
+        // A chain of the nodes, each knowing only the next one.
+        let size = TTL as usize + 3;
+        let (configs, peers) = generate_nodes("relay_within_ttl", size);
+        let mut receivers = serve_all(
+            &configs,
+            (0..size)
+                .map(|i| peers.get(i + 1).cloned().into_iter().collect())
+                .collect(),
+        )
+        .await;
+        let message = b"hello".to_vec();
+        // Duplicates are delivered only once.
+        for _ in 0..2 {
+            TcpGossipNetwork::broadcast(&configs[0], &peers[1..2], TOPIC, message.clone())
+                .await
+                .unwrap();
+        }
+        // The first hop is the broadcast itself, followed by `TTL` relays.
+        for (i, receiver) in receivers.iter_mut().enumerate().skip(1) {
+            let expected = if i <= TTL as usize + 1 {
+                vec![message.clone()]
+            } else {
+                vec![]
+            };
+            assert_eq!(receive_all(receiver).await, expected, "node {i}");
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn limited_fanout() {
+        setup_test();
+        let size = FANOUT + 4;
+        let (configs, peers) = generate_nodes("limited_fanout", size);
+        let mut receivers = serve_all(&configs, vec![vec![]; size]).await;
+        TcpGossipNetwork::broadcast(&configs[0], &peers[1..], TOPIC, b"hello".to_vec())
+            .await
+            .unwrap();
+        let mut delivered = 0;
+        for receiver in receivers.iter_mut().skip(1) {
+            delivered += receive_all(receiver).await.len();
+        }
+        assert_eq!(delivered, FANOUT);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn ignore_other_topics() {
+        setup_test();
+        let (configs, mut peers) = generate_nodes("ignore_other_topics", 2);
+        let mut receivers = serve_all(&configs, vec![vec![]; 2]).await;
+        // The node is listening on the port of `TOPIC`.
+        let port = peers[1].ports[&gossip_port_key(TOPIC)];
+        peers[1].ports = [(gossip_port_key("another"), port)].into_iter().collect();
+        TcpGossipNetwork::broadcast(&configs[0], &peers[1..], "another", b"hello".to_vec())
+            .await
+            .unwrap();
+        assert!(receive_all(&mut receivers[1]).await.is_empty());
+    }
+}
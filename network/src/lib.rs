@@ -1,8 +1,10 @@
 pub mod dms;
 pub mod dms2;
-#[cfg(never)]
-mod peer_discovery;
+pub mod gossip;
+pub mod peer_discovery;
 pub mod primitives;
+pub mod reconciliation;
+pub mod secure;
 pub mod storage;
 
 use async_trait::async_trait;
@@ -14,7 +16,13 @@ use std::{net::SocketAddrV4, sync::Arc};
 use tokio::sync::RwLock;
 
 pub type Error = eyre::Error;
-pub type Dms = dms::DistributedMessageSet<primitives::DummyGossipNetwork, storage::StorageImpl>;
+pub type Dms = dms::DistributedMessageSet<gossip::TcpGossipNetwork, storage::StorageImpl>;
+
+/// Returns the hash used in place of the chain id for the signatures bound to the network,
+/// not to a chain.
+fn network_hash(network_id: &str) -> Hash256 {
+    Hash256::hash(network_id.as_bytes())
+}
 
 /// The information of a network peer that is discovered by the discovery protocol.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
@@ -119,20 +127,28 @@ impl SharedKnownPeers {
 /// For every method,
 /// - If the given directory is empty, it fails (except `create()`).
 /// - It locks the storage.
-/// - If the given directory is locked (possibly by another instance of `DistributedMessageSet`),
+/// - If the given directory is locked (possibly by another instance of `PeerDiscovery`),
 /// it will `await` until the lock is released.
 #[async_trait]
 pub trait PeerDiscovery {
     /// Creates a new and empty storage with the given directory.
-    /// Fails if there is already a directory.
+    /// Fails if there is already a storage of known peers in the directory.
     async fn create(storage_directory: &str) -> Result<(), Error>;
 
+    /// Adds the given peers (e.g., the initial ones from the configuration) to the storage,
+    /// replacing the known ones with the same public keys.
+    async fn add_peers(storage_directory: &str, peers: Vec<Peer>) -> Result<(), Error>;
+
     /// Serves the discovery protocol indefinitely, updating the known peers on the storage.
     ///
-    /// - It may discard members in the storage who are not in `NetworkConfig::members`.
+    /// - `this_node` is the record of this node advertised to the others,
+    /// whose `address` is where the discovery protocol is served.
+    /// - It discards members in the storage who are not in `NetworkConfig::members`.
+    /// - The storage stays locked until the returned task finishes or is aborted.
     async fn serve(
         storage_directory: &str,
         network_config: &NetworkConfig,
+        this_node: Peer,
     ) -> Result<(SharedKnownPeers, tokio::task::JoinHandle<Result<(), Error>>), Error>;
 
     /// Reads the known peers from the storage.
@@ -0,0 +1,461 @@
+//! The peer discovery protocol over the RPC.
+//!
+//! Every node periodically announces its own `Peer` record, signed by itself,
+//! to the peers it knows, receiving the records known to them in return.
+//! Thus a node eventually learns all the members reachable from its initially known peers.
+use super::*;
+use crate::secure::{run_server, ChannelConfig, RpcClient};
+use crate::storage::StorageImpl;
+use eyre::eyre;
+use futures::prelude::*;
+use serde_tc::http::*;
+use serde_tc::{serde_tc_full, StubCall};
+use simperby_common::{serde_spb, utils::get_timestamp};
+use std::time::Duration;
+
+const KNOWN_PEERS_FILE_NAME: &str = "known_peers.json";
+/// The name of the RPC object served on `Peer::address`.
+const RPC_OBJECT_NAME: &str = "discovery";
+const ANNOUNCE_INTERVAL: Duration = Duration::from_millis(1_000);
+
+impl ToHash256 for Peer {
+    fn to_hash256(&self) -> Hash256 {
+        Hash256::hash(serde_spb::to_vec(self).unwrap())
+    }
+}
+
+impl Signable for Peer {
+    const SIGNING_TAG: &'static str = "peer";
+}
+
+/// A `Peer` record signed by the peer itself.
+///
+/// The `recently_seen_timestamp` of the record is when it was signed,
+/// so that a newer record of a peer replaces the older one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedPeer {
+    pub peer: Peer,
+    pub signature: TypedSignature<Peer>,
+}
+
+impl SignedPeer {
+    pub fn sign(peer: Peer, network_id: &str, private_key: &PrivateKey) -> Result<Self, Error> {
+        let signature = TypedSignature::sign(&peer, &network_hash(network_id), private_key)?;
+        Ok(Self { peer, signature })
+    }
+
+    /// Verifies that the record is signed by the peer itself on the given network.
+    pub fn verify(&self, network_id: &str) -> Result<(), Error> {
+        if self.signature.signer() != &self.peer.public_key {
+            return Err(eyre!(
+                "the record of {} is signed by another key",
+                self.peer.public_key
+            ));
+        }
+        self.signature
+            .verify(&self.peer, &network_hash(network_id))
+            .map_err(|e| eyre!(e))
+    }
+}
+
+/// The interface that will be wrapped into an RPC server for the peers.
+#[serde_tc_full]
+trait PeerDiscoveryRpcInterface: Send + Sync + 'static {
+    /// Accepts the record of the caller,
+    /// returning the records known to this node including its own.
+    async fn exchange(
+        &self,
+        network_id: String,
+        record: SignedPeer,
+    ) -> Result<Vec<SignedPeer>, String>;
+}
+
+struct Discovery {
+    storage: StorageImpl,
+    network_config: NetworkConfig,
+    this_node: Peer,
+    /// The latest records received from the peers.
+    records: HashMap<PublicKey, SignedPeer>,
+    known_peers: SharedKnownPeers,
+}
+
+impl Discovery {
+    fn sign_this_node(&self) -> Result<SignedPeer, Error> {
+        let peer = Peer {
+            recently_seen_timestamp: get_timestamp(),
+            ..self.this_node.clone()
+        };
+        SignedPeer::sign(
+            peer,
+            &self.network_config.network_id,
+            &self.network_config.private_key,
+        )
+    }
+
+    /// Accepts the valid records of the other members that are newer than the known ones,
+    /// committing the known peers to the storage.
+    async fn update(&mut self, records: Vec<SignedPeer>) -> Result<(), Error> {
+        let now = get_timestamp();
+        for record in records {
+            let public_key = &record.peer.public_key;
+            if *public_key == self.network_config.public_key
+                || !self.network_config.members.contains(public_key)
+            {
+                continue;
+            }
+            if matches!(
+                self.records.get(public_key),
+                Some(known) if known.peer.recently_seen_timestamp >= record.peer.recently_seen_timestamp
+            ) {
+                continue;
+            }
+            if let Err(e) = record.verify(&self.network_config.network_id) {
+                log::warn!("rejected a peer record: {}", e);
+                continue;
+            }
+            // Not to trust a clock running ahead.
+            let peer = Peer {
+                recently_seen_timestamp: record.peer.recently_seen_timestamp.min(now),
+                ..record.peer.clone()
+            };
+            self.known_peers.add_or_replace(peer).await;
+            self.records.insert(public_key.clone(), record);
+        }
+        write_known_peers(&mut self.storage, &self.known_peers.read().await).await
+    }
+
+    async fn serve_rpc(this: Arc<RwLock<Self>>, port: u16) -> Result<(), Error> {
+        let channel_config = ChannelConfig::from(&this.read().await.network_config);
+        let wrapped_this = Arc::new(parking_lot::RwLock::new(Some(this)));
+        let wrapped_this_ = Arc::clone(&wrapped_this);
+
+        // The connections may outlive the server; this releases the storage lock anyway.
+        struct DropHelper<T> {
+            wrapped_this: Arc<parking_lot::RwLock<Option<Arc<RwLock<T>>>>>,
+        }
+        impl<T> Drop for DropHelper<T> {
+            fn drop(&mut self) {
+                self.wrapped_this.write().take().unwrap();
+            }
+        }
+        let _drop_helper = DropHelper { wrapped_this };
+        run_server(
+            port,
+            channel_config,
+            [(
+                RPC_OBJECT_NAME.to_owned(),
+                create_http_object(Arc::new(DiscoveryWrapper {
+                    discovery: wrapped_this_,
+                }) as Arc<dyn PeerDiscoveryRpcInterface>),
+            )]
+            .into_iter()
+            .collect(),
+        )
+        .await?;
+        Err(eyre!("the discovery server on port {} stopped", port))
+    }
+
+    async fn serve_announce(this: Arc<RwLock<Self>>) -> Result<(), Error> {
+        loop {
+            if let Err(e) = Self::announce(&this).await {
+                log::warn!("failed to announce this node: {}", e);
+            }
+            tokio::time::sleep(ANNOUNCE_INTERVAL).await;
+        }
+    }
+
+    /// Exchanges the records with all the known peers.
+    async fn announce(this: &Arc<RwLock<Self>>) -> Result<(), Error> {
+        // Not to hold the lock during the requests, which the peers may be sending to this.
+        let (channel_config, record, peers) = {
+            let this = this.read().await;
+            (
+                ChannelConfig::from(&this.network_config),
+                this.sign_this_node()?,
+                this.known_peers.read().await,
+            )
+        };
+        let tasks = peers.iter().map(|peer| {
+            let (channel_config, record) = (channel_config.clone(), record.clone());
+            async move {
+                let network_id = channel_config.network_id.clone();
+                let stub = PeerDiscoveryRpcInterfaceStub::new(Box::new(RpcClient::new(
+                    peer.address,
+                    RPC_OBJECT_NAME,
+                    channel_config,
+                    peer.public_key.clone(),
+                )));
+                stub.exchange(network_id, record)
+                    .await
+                    .map_err(|e| eyre!("{}", e))?
+                    .map_err(|e| eyre!(e))
+            }
+        });
+        let mut records = Vec::new();
+        for (result, peer) in future::join_all(tasks).await.into_iter().zip(peers.iter()) {
+            match result {
+                Ok(x) => records.extend(x),
+                Err(e) => log::debug!("failed to exchange records with {:?}: {}", peer, e),
+            }
+        }
+        this.write().await.update(records).await
+    }
+}
+
+struct DiscoveryWrapper {
+    #[allow(clippy::type_complexity)]
+    discovery: Arc<parking_lot::RwLock<Option<Arc<RwLock<Discovery>>>>>,
+}
+
+#[async_trait]
+impl PeerDiscoveryRpcInterface for DiscoveryWrapper {
+    async fn exchange(
+        &self,
+        network_id: String,
+        record: SignedPeer,
+    ) -> Result<Vec<SignedPeer>, String> {
+        let discovery = Arc::clone(
+            self.discovery
+                .read()
+                .as_ref()
+                .ok_or_else(|| "server terminated".to_owned())?,
+        );
+        let mut discovery = discovery.write().await;
+        if network_id != discovery.network_config.network_id {
+            return Err(format!("unknown network id: {network_id}"));
+        }
+        let caller = record.peer.public_key.clone();
+        if !discovery.network_config.members.contains(&caller) {
+            return Err(format!("{caller} is not a member"));
+        }
+        record.verify(&network_id).map_err(|e| e.to_string())?;
+        discovery
+            .update(vec![record])
+            .await
+            .map_err(|e| e.to_string())?;
+        let mut records = vec![discovery.sign_this_node().map_err(|e| e.to_string())?];
+        records.extend(
+            discovery
+                .records
+                .values()
+                .filter(|record| record.peer.public_key != caller)
+                .cloned(),
+        );
+        Ok(records)
+    }
+}
+
+async fn read_known_peers(storage: &StorageImpl) -> Result<Vec<Peer>, Error> {
+    Ok(serde_spb::from_str(
+        &storage.read_file(KNOWN_PEERS_FILE_NAME).await?,
+    )?)
+}
+
+async fn write_known_peers(storage: &mut StorageImpl, peers: &[Peer]) -> Result<(), Error> {
+    storage
+        .add_or_overwrite_file(KNOWN_PEERS_FILE_NAME, serde_spb::to_string(&peers)?)
+        .await?;
+    Ok(())
+}
+
+pub struct PeerDiscoveryImpl {}
+
+#[async_trait]
+impl PeerDiscovery for PeerDiscoveryImpl {
+    async fn create(storage_directory: &str) -> Result<(), Error> {
+        if let Ok(storage) = StorageImpl::open(storage_directory).await {
+            if storage
+                .list_files()
+                .await?
+                .iter()
+                .any(|file| file == KNOWN_PEERS_FILE_NAME)
+            {
+                return Err(eyre!(
+                    "there is already a storage of known peers in {}",
+                    storage_directory
+                ));
+            }
+        }
+        StorageImpl::create(storage_directory).await?;
+        let mut storage = StorageImpl::open(storage_directory).await?;
+        write_known_peers(&mut storage, &[]).await
+    }
+
+    async fn add_peers(storage_directory: &str, peers: Vec<Peer>) -> Result<(), Error> {
+        let mut storage = StorageImpl::open(storage_directory).await?;
+        let mut known_peers = read_known_peers(&storage).await?;
+        for peer in peers {
+            known_peers.retain(|known_peer| known_peer.public_key != peer.public_key);
+            known_peers.push(peer);
+        }
+        write_known_peers(&mut storage, &known_peers).await
+    }
+
+    async fn serve(
+        storage_directory: &str,
+        network_config: &NetworkConfig,
+        this_node: Peer,
+    ) -> Result<(SharedKnownPeers, tokio::task::JoinHandle<Result<(), Error>>), Error> {
+        if this_node.public_key != network_config.public_key {
+            return Err(eyre!(
+                "the record of this node has a different public key: {}",
+                this_node.public_key
+            ));
+        }
+        let mut storage = StorageImpl::open(storage_directory).await?;
+        let mut peers = read_known_peers(&storage).await?;
+        peers.retain(|peer| {
+            network_config.members.contains(&peer.public_key)
+                && peer.public_key != network_config.public_key
+        });
+        write_known_peers(&mut storage, &peers).await?;
+
+        let known_peers = SharedKnownPeers::new(Arc::new(RwLock::new(peers)));
+        let port = this_node.address.port();
+        let discovery = Arc::new(RwLock::new(Discovery {
+            storage,
+            network_config: network_config.clone(),
+            this_node,
+            records: HashMap::new(),
+            known_peers: known_peers.clone(),
+        }));
+        let task = tokio::spawn(async move {
+            tokio::select! {
+                result = Discovery::serve_rpc(Arc::clone(&discovery), port) => result,
+                result = Discovery::serve_announce(discovery) => result,
+            }
+        });
+        Ok((known_peers, task))
+    }
+
+    async fn read_known_peers(storage_directory: &str) -> Result<Vec<Peer>, Error> {
+        let storage = StorageImpl::open(storage_directory).await?;
+        read_known_peers(&storage).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simperby_test_suite::*;
+
+    fn generate_configs(
+        network_id: &str,
+        members: usize,
+        others: usize,
+    ) -> (Vec<NetworkConfig>, Vec<Peer>) {
+        let keys: Vec<_> = (0..members + others)
+            .map(|_| generate_keypair_random())
+            .collect();
+        let configs: Vec<_> = keys
+            .iter()
+            .map(|(public_key, private_key)| NetworkConfig {
+                network_id: network_id.to_owned(),
+                ports: [(format!("dms-{network_id}"), dispense_port())]
+                    .into_iter()
+                    .collect(),
+                members: keys[0..members].iter().map(|(x, _)| x.clone()).collect(),
+                public_key: public_key.clone(),
+                private_key: private_key.clone(),
+            })
+            .collect();
+        let peers = configs
+            .iter()
+            .map(|config| Peer {
+                public_key: config.public_key.clone(),
+                name: config.public_key.to_string(),
+                address: SocketAddrV4::new("127.0.0.1".parse().unwrap(), dispense_port()),
+                ports: config.ports.clone(),
+                message: "".to_owned(),
+                recently_seen_timestamp: 0,
+            })
+            .collect();
+        (configs, peers)
+    }
+
+    fn sorted_keys(peers: &[Peer]) -> Vec<PublicKey> {
+        let mut keys: Vec<_> = peers.iter().map(|peer| peer.public_key.clone()).collect();
+        keys.sort();
+        keys
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn discover_all_members() {
+        setup_test();
+        let (configs, peers) = generate_configs("discover_all_members", 4, 1);
+        let (members, non_member) = (&peers[0..4], &peers[4]);
+        let start_timestamp = get_timestamp();
+
+        // Everyone knows only the first member, which knows none.
+        let mut directories = Vec::new();
+        let mut nodes = Vec::new();
+        for (config, peer) in configs.iter().zip(&peers) {
+            let directory = create_temp_dir();
+            PeerDiscoveryImpl::create(&directory).await.unwrap();
+            assert!(PeerDiscoveryImpl::create(&directory).await.is_err());
+            if peer.public_key != peers[0].public_key {
+                PeerDiscoveryImpl::add_peers(&directory, vec![peers[0].clone()])
+                    .await
+                    .unwrap();
+            }
+            // A non-member in the storage is discarded.
+            if peer.public_key == peers[1].public_key {
+                PeerDiscoveryImpl::add_peers(&directory, vec![non_member.clone()])
+                    .await
+                    .unwrap();
+            }
+            nodes.push(
+                PeerDiscoveryImpl::serve(&directory, config, peer.clone())
+                    .await
+                    .unwrap(),
+            );
+            directories.push(directory);
+        }
+        tokio::time::sleep(ANNOUNCE_INTERVAL * 4).await;
+
+        for ((known_peers, task), member) in nodes.into_iter().zip(members) {
+            let expected = sorted_keys(
+                &members
+                    .iter()
+                    .filter(|peer| peer.public_key != member.public_key)
+                    .cloned()
+                    .collect::<Vec<_>>(),
+            );
+            assert_eq!(sorted_keys(&known_peers.read().await), expected);
+            task.abort();
+        }
+        for (directory, member) in directories.iter().zip(members) {
+            let known_peers = PeerDiscoveryImpl::read_known_peers(directory)
+                .await
+                .unwrap();
+            assert_eq!(known_peers.len(), members.len() - 1);
+            assert!(!known_peers.iter().any(|peer| peer == non_member
+                || peer.public_key == member.public_key
+                || peer.recently_seen_timestamp < start_timestamp));
+            for peer in known_peers {
+                let original = members
+                    .iter()
+                    .find(|member| member.public_key == peer.public_key)
+                    .unwrap();
+                assert_eq!(peer.address, original.address);
+                assert_eq!(peer.ports, original.ports);
+            }
+        }
+    }
+
+    #[test]
+    fn signed_peer() {
+        setup_test();
+        let (configs, peers) = generate_configs("signed_peer", 2, 0);
+        let record =
+            SignedPeer::sign(peers[0].clone(), "signed_peer", &configs[0].private_key).unwrap();
+        record.verify("signed_peer").unwrap();
+        assert!(record.verify("another_network").is_err());
+        let forged =
+            SignedPeer::sign(peers[0].clone(), "signed_peer", &configs[1].private_key).unwrap();
+        assert!(forged.verify("signed_peer").is_err());
+        let mut tampered = record;
+        tampered.peer.address = peers[1].address;
+        assert!(tampered.verify("signed_peer").is_err());
+    }
+}
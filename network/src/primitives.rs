@@ -20,12 +20,20 @@ pub trait Storage: Send + Sync + 'static {
     async fn list_files(&self) -> Result<Vec<String>, StorageError>;
 
     /// Adds the given file to the storage.
+    ///
+    /// It is durable and atomic when it returns; a crash leaves either the old or the new content.
     async fn add_or_overwrite_file(
         &mut self,
         name: &str,
         content: String,
     ) -> Result<(), StorageError>;
 
+    /// Appends the given content to the file, creating it if there is none.
+    ///
+    /// It is durable when it returns, but unlike `add_or_overwrite_file()`,
+    /// a crash may leave only a part of the content at the end of the file.
+    async fn append_to_file(&mut self, name: &str, content: String) -> Result<(), StorageError>;
+
     /// Reads the given file.
     async fn read_file(&self, name: &str) -> Result<String, StorageError>;
 
@@ -50,12 +58,16 @@ pub trait PeerDiscoveryPrimitive: Send + Sync + 'static {
 }
 
 /// The p2p gossip network.
+///
+/// The `topic` distinguishes the independent gossip networks among the same peers
+/// (e.g., one for each `DistributedMessageSet`).
 #[async_trait]
 pub trait GossipNetwork: Send + Sync + 'static {
     /// Broadcasts a message to the network.
     async fn broadcast(
         config: &NetworkConfig,
         known_peers: &[Peer],
+        topic: &str,
         message: Vec<u8>,
     ) -> Result<(), Error>;
 
@@ -63,6 +75,7 @@ pub trait GossipNetwork: Send + Sync + 'static {
     /// serving (propagating) messages broadcasted over the network.
     async fn serve(
         config: NetworkConfig,
+        topic: String,
         peers: SharedKnownPeers,
     ) -> Result<
         (
@@ -80,6 +93,7 @@ impl GossipNetwork for DummyGossipNetwork {
     async fn broadcast(
         _config: &NetworkConfig,
         _known_peers: &[Peer],
+        _topic: &str,
         _message: Vec<u8>,
     ) -> Result<(), Error> {
         Ok(())
@@ -87,6 +101,7 @@ impl GossipNetwork for DummyGossipNetwork {
 
     async fn serve(
         _config: NetworkConfig,
+        _topic: String,
         _peers: SharedKnownPeers,
     ) -> Result<
         (
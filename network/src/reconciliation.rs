@@ -0,0 +1,298 @@
+//! The set reconciliation with which the DMS peers find the messages that either side lacks,
+//! without sending every known hash.
+//!
+//! The hashes are split into `BUCKETS` buckets by their first byte,
+//! and a set is summarized by the digest of each bucket and the root digest over them.
+//! Two peers compare the roots first, then the bucket digests,
+//! and finally the client sends its hashes only in the buckets that differ,
+//! for which the peer returns the messages that the client lacks.
+//! The traffic is thus proportional to the difference of the sets, not to their size.
+//!
+//! The bucket digests are truncated to keep them small; in the unlikely case that
+//! the roots differ but the buckets do not, the peers fall back to the full exchange.
+use super::*;
+use eyre::eyre;
+use std::collections::HashSet;
+
+pub const BUCKETS: usize = 256;
+/// If more buckets than this differ, it is cheaper to exchange all the hashes at once
+/// than to compare the digests first.
+pub const MAX_DIFFERING_BUCKETS: usize = BUCKETS / 2;
+
+pub fn bucket_of(hash: &Hash256) -> u8 {
+    hash.as_ref()[0]
+}
+
+/// The first 8 bytes of the hash of the sorted hashes in a bucket,
+/// or zero for an empty bucket.
+pub type BucketDigest = u64;
+
+/// A set of hashes with the digests of its buckets.
+///
+/// It is computed once and shared among the peers to reconcile with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetDigest {
+    /// Sorted and deduplicated.
+    hashes: Vec<Hash256>,
+    buckets: Vec<BucketDigest>,
+}
+
+impl SetDigest {
+    pub fn new<'a>(hashes: impl IntoIterator<Item = &'a Hash256>) -> Self {
+        let mut hashes: Vec<_> = hashes.into_iter().copied().collect();
+        hashes.sort();
+        hashes.dedup();
+        let mut buckets = vec![Vec::new(); BUCKETS];
+        for hash in &hashes {
+            buckets[bucket_of(hash) as usize].extend_from_slice(hash.as_ref());
+        }
+        let buckets = buckets
+            .into_iter()
+            .map(|bucket| {
+                // Most of the buckets are empty for a small set, so they are not hashed.
+                if bucket.is_empty() {
+                    return 0;
+                }
+                let hash = Hash256::hash(bucket);
+                BucketDigest::from_be_bytes(hash.as_ref()[0..8].try_into().unwrap())
+            })
+            .collect();
+        Self { hashes, buckets }
+    }
+
+    pub fn hashes(&self) -> &[Hash256] {
+        &self.hashes
+    }
+
+    pub fn root(&self) -> Hash256 {
+        Hash256::hash(
+            self.buckets
+                .iter()
+                .flat_map(|digest| digest.to_be_bytes())
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    pub fn buckets(&self) -> &[BucketDigest] {
+        &self.buckets
+    }
+
+    /// Returns the buckets whose digests differ from the given ones of another set.
+    pub fn differing_buckets(&self, other: &[BucketDigest]) -> Vec<u8> {
+        (0..BUCKETS)
+            .filter(|&i| other.get(i) != Some(&self.buckets[i]))
+            .map(|i| i as u8)
+            .collect()
+    }
+}
+
+/// Returns the hashes that fall in the given buckets.
+pub fn hashes_in_buckets(hashes: &[Hash256], buckets: &[u8]) -> Vec<Hash256> {
+    let buckets: HashSet<_> = buckets.iter().collect();
+    hashes
+        .iter()
+        .filter(|hash| buckets.contains(&bucket_of(hash)))
+        .copied()
+        .collect()
+}
+
+/// A message set that reconciles with the peers, implemented by the DMSs.
+#[async_trait]
+pub trait MessageSet: Send + Sync + 'static {
+    type Message: ToHash256 + Send;
+
+    /// The key identifying the set among the peers.
+    fn dms_key(&self) -> &str;
+
+    /// Reads the hashes of the messages, which must be cheaper than reading the messages.
+    async fn read_message_hashes(&self) -> Result<Vec<Hash256>, Error>;
+
+    /// Reads the messages of the given hashes, which must be in the set.
+    async fn read_messages_of(&self, hashes: &[Hash256]) -> Result<Vec<Self::Message>, Error>;
+}
+
+/// The message set served to the peers.
+///
+/// This is an `Option` because it has to be explicitly taken out when the server terminates.
+pub(crate) type ServedSet<T> = Arc<parking_lot::RwLock<Option<Arc<RwLock<T>>>>>;
+
+/// Returns the served set, checking that it is the requested one.
+pub(crate) async fn get_served_set<T: MessageSet>(
+    served: &ServedSet<T>,
+    dms_key: &str,
+) -> Result<Arc<RwLock<T>>, String> {
+    let set = Arc::clone(
+        served
+            .read()
+            .as_ref()
+            .ok_or_else(|| "server terminated".to_owned())?,
+    );
+    let dms_key_ = set.read().await.dms_key().to_owned();
+    if dms_key != dms_key_ {
+        return Err(format!("key mismatch: requested {dms_key}, but {dms_key_}"));
+    }
+    Ok(set)
+}
+
+/// Returns the messages of the served set except `knowns`, which is the full exchange.
+pub(crate) async fn serve_messages<T: MessageSet>(
+    served: &ServedSet<T>,
+    dms_key: &str,
+    knowns: Vec<Hash256>,
+) -> Result<Vec<T::Message>, String> {
+    serve_messages_in_buckets(served, dms_key, None, knowns).await
+}
+
+/// Returns `None` if the served set has the given root digest,
+/// or the digests of its buckets otherwise.
+pub(crate) async fn serve_digest<T: MessageSet>(
+    served: &ServedSet<T>,
+    dms_key: &str,
+    root: Hash256,
+) -> Result<Option<Vec<BucketDigest>>, String> {
+    let set = get_served_set(served, dms_key).await?;
+    let hashes = set.read().await.read_message_hashes().await;
+    let digest = SetDigest::new(&hashes.map_err(|e| e.to_string())?);
+    if digest.root() == root {
+        return Ok(None);
+    }
+    Ok(Some(digest.buckets().to_vec()))
+}
+
+/// Returns the messages of the served set in the given buckets (or all of them for `None`)
+/// except `knowns`.
+pub(crate) async fn serve_messages_in_buckets<T: MessageSet>(
+    served: &ServedSet<T>,
+    dms_key: &str,
+    buckets: Option<Vec<u8>>,
+    knowns: Vec<Hash256>,
+) -> Result<Vec<T::Message>, String> {
+    let set = get_served_set(served, dms_key).await?;
+    let set = set.read().await;
+    let hashes = set.read_message_hashes().await.map_err(|e| e.to_string())?;
+    let buckets: Option<HashSet<_>> = buckets.map(|buckets| buckets.into_iter().collect());
+    let knowns: HashSet<_> = knowns.into_iter().collect();
+    // Only the messages to be sent are read.
+    let hashes: Vec<_> = hashes
+        .into_iter()
+        .filter(|hash| {
+            let in_buckets = match &buckets {
+                Some(buckets) => buckets.contains(&bucket_of(hash)),
+                None => true,
+            };
+            in_buckets && !knowns.contains(hash)
+        })
+        .collect();
+    set.read_messages_of(&hashes)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// The RPC calls to a peer serving a message set.
+#[async_trait]
+pub trait MessageSetPeer: Send + Sync {
+    /// The message as transferred, before verification.
+    type Message: Send;
+
+    /// Returns the messages of the peer except `knowns`.
+    async fn get_messages(&self, knowns: Vec<Hash256>) -> Result<Vec<Self::Message>, Error>;
+
+    /// Returns `None` if the messages of the peer have the given root digest,
+    /// or the digests of their buckets otherwise.
+    async fn get_digest(&self, root: Hash256) -> Result<Option<Vec<BucketDigest>>, Error>;
+
+    /// Returns the messages of the peer in the given buckets except `knowns`.
+    async fn get_messages_in_buckets(
+        &self,
+        buckets: Vec<u8>,
+        knowns: Vec<Hash256>,
+    ) -> Result<Vec<Self::Message>, Error>;
+}
+
+/// Fetches the messages of the peer that are not in `knowns`,
+/// reconciling the sets to transfer only the difference.
+pub async fn fetch_unknowns<P: MessageSetPeer>(
+    peer: &P,
+    knowns: &SetDigest,
+) -> Result<Vec<P::Message>, Error> {
+    let Some(buckets) = peer.get_digest(knowns.root()).await? else {
+        return Ok(Vec::new());
+    };
+    let differing = knowns.differing_buckets(&buckets);
+    if differing.is_empty() || differing.len() > MAX_DIFFERING_BUCKETS {
+        return peer.get_messages(knowns.hashes().to_vec()).await;
+    }
+    let knowns = hashes_in_buckets(knowns.hashes(), &differing);
+    peer.get_messages_in_buckets(differing, knowns).await
+}
+
+/// Flattens the result of an RPC call made through a `serde_tc` stub.
+pub(crate) fn flatten_rpc_result<T, E1: std::fmt::Display, E2: std::fmt::Display>(
+    result: Result<Result<T, E2>, E1>,
+) -> Result<T, Error> {
+    result
+        .map_err(|e| eyre!("{}", e))?
+        .map_err(|e| eyre!("{}", e))
+}
+
+/// Counts the bytes of the arguments and the results of the RPC calls.
+#[cfg(test)]
+pub(crate) struct CountingCall {
+    pub inner: crate::secure::RpcClient,
+    pub bytes: Arc<std::sync::atomic::AtomicUsize>,
+}
+
+#[cfg(test)]
+#[async_trait]
+impl serde_tc::StubCall for CountingCall {
+    type Error = anyhow::Error;
+
+    async fn call(&self, method: &'static str, params: String) -> Result<String, Self::Error> {
+        let params_size = params.len();
+        let result = self.inner.call(method, params).await?;
+        self.bytes.fetch_add(
+            params_size + result.len(),
+            std::sync::atomic::Ordering::SeqCst,
+        );
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hashes(range: std::ops::Range<usize>) -> Vec<Hash256> {
+        range.map(|i| Hash256::hash(i.to_be_bytes())).collect()
+    }
+
+    #[test]
+    fn digest() {
+        let set = hashes(0..1000);
+        let digest = SetDigest::new(&set);
+        // Regardless of the order and the duplicates.
+        let mut shuffled = set.clone();
+        shuffled.reverse();
+        shuffled.extend(hashes(0..10));
+        assert_eq!(SetDigest::new(&shuffled), digest);
+
+        let mut other = set[2..].to_vec();
+        other.extend(hashes(1000..1001));
+        let other_digest = SetDigest::new(&other);
+        assert_ne!(other_digest.root(), digest.root());
+        let mut expected: Vec<_> = [set[0], set[1], hashes(1000..1001)[0]]
+            .iter()
+            .map(bucket_of)
+            .collect();
+        expected.sort();
+        expected.dedup();
+        assert_eq!(digest.differing_buckets(other_digest.buckets()), expected);
+        assert_eq!(
+            hashes_in_buckets(&set, &expected)
+                .into_iter()
+                .filter(|hash| !other.contains(hash))
+                .collect::<Vec<_>>(),
+            set[0..2].to_vec()
+        );
+    }
+}
@@ -0,0 +1,586 @@
+//! The authenticated and encrypted channel on which every network service runs.
+//!
+//! A connection starts with a Noise XX handshake using a fresh static key,
+//! which each side binds to its own identity by sending a signature on it.
+//! Only the members of the network are accepted,
+//! and the initiator also checks that it has reached the peer it intended to.
+//!
+//! On top of the channel, `run_server()` and `RpcClient` provide the same RPC
+//! that `serde_tc::http` does, serving a single call per connection.
+use super::*;
+use eyre::eyre;
+use futures::prelude::*;
+use serde_tc::http::HttpInterface;
+use serde_tc::{DispatchStringDictAsync, StubCall};
+use std::net::Ipv4Addr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const NOISE_PARAMS: &str = "Noise_XX_25519_ChaChaPoly_BLAKE2s";
+/// The maximum size of a Noise message, including the authentication tag.
+const MAX_NOISE_MESSAGE_SIZE: usize = 65535;
+const TAG_SIZE: usize = 16;
+const MAX_CHUNK_SIZE: usize = MAX_NOISE_MESSAGE_SIZE - TAG_SIZE;
+/// The maximum size of a message sent on the channel.
+pub const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long an RPC call may take once the channel is established, on either side,
+/// so that a stalled peer does not hold the call forever.
+const CALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The identity of this node and the members to accept.
+#[derive(Debug, Clone)]
+pub struct ChannelConfig {
+    pub network_id: String,
+    pub members: Vec<PublicKey>,
+    pub public_key: PublicKey,
+    pub private_key: PrivateKey,
+}
+
+impl From<&NetworkConfig> for ChannelConfig {
+    fn from(config: &NetworkConfig) -> Self {
+        Self {
+            network_id: config.network_id.clone(),
+            members: config.members.clone(),
+            public_key: config.public_key.clone(),
+            private_key: config.private_key.clone(),
+        }
+    }
+}
+
+impl From<&ServerNetworkConfig> for ChannelConfig {
+    fn from(config: &ServerNetworkConfig) -> Self {
+        Self {
+            network_id: config.network_id.clone(),
+            members: config.members.clone(),
+            public_key: config.public_key.clone(),
+            private_key: config.private_key.clone(),
+        }
+    }
+}
+
+impl From<&ClientNetworkConfig> for ChannelConfig {
+    fn from(config: &ClientNetworkConfig) -> Self {
+        Self {
+            network_id: config.network_id.clone(),
+            members: config.members.clone(),
+            public_key: config.public_key.clone(),
+            private_key: config.private_key.clone(),
+        }
+    }
+}
+
+/// The Noise static key of a connection.
+#[derive(Debug, Clone)]
+struct NoiseKey(Vec<u8>);
+
+impl ToHash256 for NoiseKey {
+    fn to_hash256(&self) -> Hash256 {
+        Hash256::hash(&self.0)
+    }
+}
+
+impl Signable for NoiseKey {
+    const SIGNING_TAG: &'static str = "noise-static-key";
+}
+
+/// Returns the handshake payload proving that `static_key` belongs to this node.
+fn sign_static_key(static_key: &[u8], config: &ChannelConfig) -> Result<Vec<u8>, Error> {
+    let signature = TypedSignature::sign(
+        &NoiseKey(static_key.to_vec()),
+        &network_hash(&config.network_id),
+        &config.private_key,
+    )?;
+    Ok(serde_json::to_vec(&signature)?)
+}
+
+/// Returns the member who has signed the static key of the other side.
+fn verify_static_key(
+    payload: &[u8],
+    static_key: Option<&[u8]>,
+    config: &ChannelConfig,
+) -> Result<PublicKey, Error> {
+    let static_key = static_key.ok_or_else(|| eyre!("the peer has sent no static key"))?;
+    let signature: TypedSignature<NoiseKey> = serde_json::from_slice(payload)?;
+    signature.verify(
+        &NoiseKey(static_key.to_vec()),
+        &network_hash(&config.network_id),
+    )?;
+    let public_key = signature.signer().clone();
+    if !config.members.contains(&public_key) {
+        return Err(eyre!("{} is not a member of the network", public_key));
+    }
+    Ok(public_key)
+}
+
+async fn write_frame(stream: &mut TcpStream, frame: &[u8]) -> Result<(), Error> {
+    stream.write_u16(frame.len() as u16).await?;
+    stream.write_all(frame).await?;
+    Ok(())
+}
+
+async fn read_frame(stream: &mut TcpStream) -> Result<Vec<u8>, Error> {
+    let length = stream.read_u16().await?;
+    let mut frame = vec![0; length as usize];
+    stream.read_exact(&mut frame).await?;
+    Ok(frame)
+}
+
+/// An established channel to a member.
+pub struct SecureStream {
+    stream: TcpStream,
+    transport: snow::TransportState,
+    peer: PublicKey,
+}
+
+impl SecureStream {
+    /// Connects to the member `peer` listening on the address.
+    pub async fn connect(
+        address: SocketAddrV4,
+        config: &ChannelConfig,
+        peer: &PublicKey,
+    ) -> Result<Self, Error> {
+        tokio::time::timeout(HANDSHAKE_TIMEOUT, async {
+            let stream = TcpStream::connect(address).await?;
+            let stream = Self::initiate(stream, config).await?;
+            if stream.peer != *peer {
+                return Err(eyre!(
+                    "expected {} at {} but reached {}",
+                    peer,
+                    address,
+                    stream.peer
+                ));
+            }
+            Ok(stream)
+        })
+        .await?
+    }
+
+    /// Establishes the channel on an incoming connection, if it is from a member.
+    pub async fn accept(stream: TcpStream, config: &ChannelConfig) -> Result<Self, Error> {
+        tokio::time::timeout(HANDSHAKE_TIMEOUT, Self::respond(stream, config)).await?
+    }
+
+    async fn initiate(mut stream: TcpStream, config: &ChannelConfig) -> Result<Self, Error> {
+        let builder = snow::Builder::new(NOISE_PARAMS.parse()?);
+        let keypair = builder.generate_keypair()?;
+        let mut noise = builder
+            .local_private_key(&keypair.private)
+            .build_initiator()?;
+        let mut buffer = vec![0; MAX_NOISE_MESSAGE_SIZE];
+        // -> e
+        let length = noise.write_message(&[], &mut buffer)?;
+        write_frame(&mut stream, &buffer[..length]).await?;
+        // <- e, ee, s, es
+        let length = noise.read_message(&read_frame(&mut stream).await?, &mut buffer)?;
+        let peer = verify_static_key(&buffer[..length], noise.get_remote_static(), config)?;
+        // -> s, se
+        let payload = sign_static_key(&keypair.public, config)?;
+        let length = noise.write_message(&payload, &mut buffer)?;
+        write_frame(&mut stream, &buffer[..length]).await?;
+        Ok(Self {
+            stream,
+            transport: noise.into_transport_mode()?,
+            peer,
+        })
+    }
+
+    async fn respond(mut stream: TcpStream, config: &ChannelConfig) -> Result<Self, Error> {
+        let builder = snow::Builder::new(NOISE_PARAMS.parse()?);
+        let keypair = builder.generate_keypair()?;
+        let mut noise = builder
+            .local_private_key(&keypair.private)
+            .build_responder()?;
+        let mut buffer = vec![0; MAX_NOISE_MESSAGE_SIZE];
+        // -> e
+        noise.read_message(&read_frame(&mut stream).await?, &mut buffer)?;
+        // <- e, ee, s, es
+        let payload = sign_static_key(&keypair.public, config)?;
+        let length = noise.write_message(&payload, &mut buffer)?;
+        write_frame(&mut stream, &buffer[..length]).await?;
+        // -> s, se
+        let length = noise.read_message(&read_frame(&mut stream).await?, &mut buffer)?;
+        let peer = verify_static_key(&buffer[..length], noise.get_remote_static(), config)?;
+        Ok(Self {
+            stream,
+            transport: noise.into_transport_mode()?,
+            peer,
+        })
+    }
+
+    /// Returns the public key of the member on the other side.
+    pub fn peer(&self) -> &PublicKey {
+        &self.peer
+    }
+
+    async fn write_encrypted(&mut self, chunk: &[u8]) -> Result<(), Error> {
+        let mut buffer = vec![0; chunk.len() + TAG_SIZE];
+        let length = self.transport.write_message(chunk, &mut buffer)?;
+        write_frame(&mut self.stream, &buffer[..length]).await
+    }
+
+    async fn read_encrypted(&mut self) -> Result<Vec<u8>, Error> {
+        let frame = read_frame(&mut self.stream).await?;
+        let mut buffer = vec![0; frame.len()];
+        let length = self.transport.read_message(&frame, &mut buffer)?;
+        buffer.truncate(length);
+        Ok(buffer)
+    }
+
+    /// Sends a message, which is split into as many Noise messages as needed.
+    pub async fn send(&mut self, message: &[u8]) -> Result<(), Error> {
+        if message.len() > MAX_MESSAGE_SIZE {
+            return Err(eyre!("too large message: {} bytes", message.len()));
+        }
+        self.write_encrypted(&(message.len() as u32).to_be_bytes())
+            .await?;
+        for chunk in message.chunks(MAX_CHUNK_SIZE) {
+            self.write_encrypted(chunk).await?;
+        }
+        self.stream.flush().await?;
+        Ok(())
+    }
+
+    pub async fn receive(&mut self) -> Result<Vec<u8>, Error> {
+        let header: [u8; 4] = self
+            .read_encrypted()
+            .await?
+            .try_into()
+            .map_err(|_| eyre!("invalid message header"))?;
+        let length = u32::from_be_bytes(header) as usize;
+        if length > MAX_MESSAGE_SIZE {
+            return Err(eyre!("too large message: {} bytes", length));
+        }
+        let mut message = Vec::with_capacity(length);
+        while message.len() < length {
+            message.extend(self.read_encrypted().await?);
+        }
+        if message.len() != length {
+            return Err(eyre!("message longer than its header: {} bytes", length));
+        }
+        Ok(message)
+    }
+
+    pub async fn shutdown(&mut self) -> Result<(), Error> {
+        self.stream.shutdown().await?;
+        Ok(())
+    }
+}
+
+/// Binds the port on all interfaces.
+///
+/// A port may be still held for a moment by the server that has just been aborted.
+pub(crate) async fn bind(port: u16) -> Result<TcpListener, Error> {
+    let address = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port);
+    let mut retries = 10;
+    loop {
+        match TcpListener::bind(address).await {
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse && retries > 0 => {
+                retries -= 1;
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            result => return Ok(result?),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RpcRequest {
+    object: String,
+    method: String,
+    /// The arguments encoded in a JSON object, as `serde_tc` does.
+    params: String,
+}
+
+/// Runs an RPC server of the given objects, the same as `serde_tc::http::run_server()`
+/// except that it is served to the members only over the secure channels.
+pub async fn run_server(
+    port: u16,
+    config: ChannelConfig,
+    objects: HashMap<String, Arc<dyn HttpInterface>>,
+) -> Result<(), Error> {
+    run_server_until(port, config, objects, future::pending()).await
+}
+
+/// Runs an RPC server like `run_server()` until the given future completes.
+///
+/// It then stops accepting calls, and aborts and waits for the calls in progress,
+/// so that none of them holds the objects once it returns.
+pub async fn run_server_until(
+    port: u16,
+    config: ChannelConfig,
+    objects: HashMap<String, Arc<dyn HttpInterface>>,
+    stop: impl Future<Output = ()>,
+) -> Result<(), Error> {
+    let listener = bind(port).await?;
+    let context = Arc::new((config, objects));
+    let mut calls = tokio::task::JoinSet::new();
+    futures::pin_mut!(stop);
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, address) = accepted?;
+                let context = Arc::clone(&context);
+                calls.spawn(async move {
+                    let (config, objects) = &*context;
+                    if let Err(e) = serve_call(stream, config, objects).await {
+                        log::debug!("failed to serve an RPC call from {}: {}", address, e);
+                    }
+                });
+            }
+            Some(_) = calls.join_next(), if !calls.is_empty() => {}
+            _ = &mut stop => {
+                calls.shutdown().await;
+                return Ok(());
+            }
+        }
+    }
+}
+
+async fn serve_call(
+    stream: TcpStream,
+    config: &ChannelConfig,
+    objects: &HashMap<String, Arc<dyn HttpInterface>>,
+) -> Result<(), Error> {
+    let mut stream = SecureStream::accept(stream, config).await?;
+    tokio::time::timeout(CALL_TIMEOUT, async {
+        let request: RpcRequest = serde_json::from_slice(&stream.receive().await?)?;
+        let response: Result<String, String> = match objects.get(&request.object) {
+            Some(object) => {
+                DispatchStringDictAsync::dispatch(object.as_ref(), &request.method, &request.params)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            None => Err(format!("object not found: {}", request.object)),
+        };
+        stream.send(&serde_json::to_vec(&response)?).await?;
+        stream.shutdown().await
+    })
+    .await?
+}
+
+/// An RPC client for the stubs generated by `serde_tc`,
+/// connecting to the server on the secure channel for each call.
+pub struct RpcClient {
+    address: SocketAddrV4,
+    object: String,
+    config: ChannelConfig,
+    /// The member expected to be serving on `address`.
+    peer: PublicKey,
+}
+
+impl RpcClient {
+    pub fn new(
+        address: SocketAddrV4,
+        object: &str,
+        config: ChannelConfig,
+        peer: PublicKey,
+    ) -> Self {
+        Self {
+            address,
+            object: object.to_owned(),
+            config,
+            peer,
+        }
+    }
+
+    async fn call_(&self, method: &str, params: String) -> Result<String, Error> {
+        let mut stream = SecureStream::connect(self.address, &self.config, &self.peer).await?;
+        let request = RpcRequest {
+            object: self.object.clone(),
+            method: method.to_owned(),
+            params,
+        };
+        let response = tokio::time::timeout(CALL_TIMEOUT, async {
+            stream.send(&serde_json::to_vec(&request)?).await?;
+            stream.receive().await
+        })
+        .await??;
+        let response: Result<String, String> = serde_json::from_slice(&response)?;
+        response.map_err(|e| eyre!("RPC call `{}` failed: {}", method, e))
+    }
+}
+
+#[async_trait]
+impl StubCall for RpcClient {
+    type Error = anyhow::Error;
+
+    async fn call(&self, method: &'static str, params: String) -> Result<String, Self::Error> {
+        self.call_(method, params)
+            .await
+            .map_err(|e| anyhow::anyhow!("{}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_tc::http::create_http_object;
+    use serde_tc::serde_tc_full;
+    use simperby_test_suite::*;
+
+    #[serde_tc_full]
+    trait EchoInterface: Send + Sync + 'static {
+        async fn echo(&self, message: String) -> String;
+    }
+
+    struct Echo;
+
+    #[async_trait]
+    impl EchoInterface for Echo {
+        async fn echo(&self, message: String) -> String {
+            message
+        }
+    }
+
+    /// Returns the configs of the given number of members and a non-member.
+    fn generate_configs(network_id: &str, members: usize) -> (Vec<ChannelConfig>, ChannelConfig) {
+        let keys: Vec<_> = (0..=members).map(|_| generate_keypair_random()).collect();
+        let mut configs: Vec<_> = keys
+            .iter()
+            .map(|(public_key, private_key)| ChannelConfig {
+                network_id: network_id.to_owned(),
+                members: keys[0..members].iter().map(|(x, _)| x.clone()).collect(),
+                public_key: public_key.clone(),
+                private_key: private_key.clone(),
+            })
+            .collect();
+        let non_member = configs.pop().unwrap();
+        (configs, non_member)
+    }
+
+    fn serve_echo(port: u16, config: ChannelConfig) -> tokio::task::JoinHandle<Result<(), Error>> {
+        tokio::spawn(run_server(
+            port,
+            config,
+            [(
+                "echo".to_owned(),
+                create_http_object(Arc::new(Echo) as Arc<dyn EchoInterface>),
+            )]
+            .into_iter()
+            .collect(),
+        ))
+    }
+
+    fn echo_stub(port: u16, config: &ChannelConfig, server: &PublicKey) -> EchoInterfaceStub {
+        EchoInterfaceStub::new(Box::new(RpcClient::new(
+            SocketAddrV4::new(Ipv4Addr::LOCALHOST, port),
+            "echo",
+            config.clone(),
+            server.clone(),
+        )))
+    }
+
+    #[tokio::test]
+    async fn encrypted_round_trip() {
+        setup_test();
+        let (configs, _) = generate_configs("encrypted_round_trip", 2);
+        let port = dispense_port();
+        let listener = bind(port).await.unwrap();
+        let server_config = configs[0].clone();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = SecureStream::accept(stream, &server_config).await.unwrap();
+            let message = stream.receive().await.unwrap();
+            stream.send(&message).await.unwrap();
+            stream.peer().clone()
+        });
+        let mut stream = SecureStream::connect(
+            SocketAddrV4::new(Ipv4Addr::LOCALHOST, port),
+            &configs[1],
+            &configs[0].public_key,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stream.peer(), &configs[0].public_key);
+        // Spans several Noise messages.
+        let message: Vec<u8> = (0..MAX_CHUNK_SIZE * 3 + 7).map(|i| i as u8).collect();
+        stream.send(&message).await.unwrap();
+        assert_eq!(stream.receive().await.unwrap(), message);
+        assert_eq!(server.await.unwrap(), configs[1].public_key);
+    }
+
+    #[tokio::test]
+    async fn rpc_among_members() {
+        setup_test();
+        let (configs, _) = generate_configs("rpc_among_members", 2);
+        let port = dispense_port();
+        let server = serve_echo(port, configs[0].clone());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let stub = echo_stub(port, &configs[1], &configs[0].public_key);
+        assert_eq!(stub.echo("hello".to_owned()).await.unwrap(), "hello");
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn stop_with_stalled_call() {
+        setup_test();
+        let (configs, _) = generate_configs("stop_with_stalled_call", 2);
+        let port = dispense_port();
+        let echo = create_http_object(Arc::new(Echo) as Arc<dyn EchoInterface>);
+        let (send_stop, recv_stop) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(run_server_until(
+            port,
+            configs[0].clone(),
+            [("echo".to_owned(), Arc::clone(&echo))]
+                .into_iter()
+                .collect(),
+            recv_stop.map(|_| ()),
+        ));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // Establishes the channel, but never sends the request.
+        let _stream = SecureStream::connect(
+            SocketAddrV4::new(Ipv4Addr::LOCALHOST, port),
+            &configs[1],
+            &configs[0].public_key,
+        )
+        .await
+        .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        send_stop.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        // The call has been dropped together with the server.
+        assert_eq!(Arc::strong_count(&echo), 1);
+    }
+
+    #[tokio::test]
+    async fn reject_non_member() {
+        setup_test();
+        let (configs, non_member) = generate_configs("reject_non_member", 2);
+        let port = dispense_port();
+        let server = serve_echo(port, configs[0].clone());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // The server rejects the client.
+        let stub = echo_stub(port, &non_member, &configs[0].public_key);
+        assert!(stub.echo("hello".to_owned()).await.is_err());
+        // The client rejects the server.
+        let mut not_trusting = configs[1].clone();
+        not_trusting.members.retain(|x| *x != configs[0].public_key);
+        let stub = echo_stub(port, &not_trusting, &configs[0].public_key);
+        assert!(stub.echo("hello".to_owned()).await.is_err());
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn reject_unexpected_server() {
+        setup_test();
+        let (configs, _) = generate_configs("reject_unexpected_server", 3);
+        let port = dispense_port();
+        let server = serve_echo(port, configs[0].clone());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // A member, but not the one the client intends to reach.
+        let stub = echo_stub(port, &configs[1], &configs[2].public_key);
+        assert!(stub.echo("hello".to_owned()).await.is_err());
+        // Nor on another network.
+        let mut another_network = configs[1].clone();
+        another_network.network_id = "another".to_owned();
+        let stub = echo_stub(port, &another_network, &configs[0].public_key);
+        assert!(stub.echo("hello".to_owned()).await.is_err());
+        server.abort();
+    }
+}
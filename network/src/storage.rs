@@ -4,6 +4,9 @@ use fs2::FileExt;
 use futures::stream::*;
 use tokio::{fs, io::AsyncWriteExt, task::spawn_blocking};
 
+/// The suffix of a file being written, which is renamed to the actual name once complete.
+const TEMP_FILE_SUFFIX: &str = ".tmp";
+
 pub struct StorageImpl {
     lock_file: Option<std::fs::File>,
     path: String,
@@ -47,7 +50,7 @@ impl Storage for StorageImpl {
         Ok(files
             .into_iter()
             .map(|file| file.file_name().into_string().unwrap())
-            .filter(|file| file != "lock")
+            .filter(|file| file != "lock" && !file.ends_with(TEMP_FILE_SUFFIX))
             .collect())
     }
 
@@ -56,10 +59,37 @@ impl Storage for StorageImpl {
         name: &str,
         content: String,
     ) -> Result<(), StorageError> {
-        let mut file = fs::File::create(format!("{}/{}", self.path, name)).await?;
+        let path = format!("{}/{}", self.path, name);
+        let temp_path = format!("{path}{TEMP_FILE_SUFFIX}");
+        let mut file = fs::File::create(&temp_path).await?;
         file.write_all(content.as_bytes()).await?;
         // IMPORTANT!
         file.flush().await?;
+        file.sync_all().await?;
+        fs::rename(&temp_path, &path).await?;
+        // The rename itself is durable only after the directory is synced.
+        #[cfg(unix)]
+        fs::File::open(&self.path).await?.sync_all().await?;
+        Ok(())
+    }
+
+    async fn append_to_file(&mut self, name: &str, content: String) -> Result<(), StorageError> {
+        let path = format!("{}/{}", self.path, name);
+        #[cfg(unix)]
+        let created = !fs::try_exists(&path).await?;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+        file.write_all(content.as_bytes()).await?;
+        file.flush().await?;
+        file.sync_all().await?;
+        // The new file itself is durable only after the directory is synced.
+        #[cfg(unix)]
+        if created {
+            fs::File::open(&self.path).await?.sync_all().await?;
+        }
         Ok(())
     }
 
@@ -129,6 +159,44 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn interrupted_write() {
+        let dir = gerenate_random_storage_directory();
+        StorageImpl::create(&dir).await.unwrap();
+        let mut storage = StorageImpl::open(&dir).await.unwrap();
+        storage
+            .add_or_overwrite_file("file", "old".to_owned())
+            .await
+            .unwrap();
+        // A write cut off by a crash, before the rename.
+        fs::write(format!("{dir}/file{TEMP_FILE_SUFFIX}"), "ne")
+            .await
+            .unwrap();
+        assert_eq!(storage.read_file("file").await.unwrap(), "old");
+        assert_eq!(storage.list_files().await.unwrap(), vec!["file".to_owned()]);
+        storage
+            .add_or_overwrite_file("file", "new".to_owned())
+            .await
+            .unwrap();
+        assert_eq!(storage.read_file("file").await.unwrap(), "new");
+        assert_eq!(storage.list_files().await.unwrap(), vec!["file".to_owned()]);
+    }
+
+    #[tokio::test]
+    async fn append() {
+        let dir = gerenate_random_storage_directory();
+        StorageImpl::create(&dir).await.unwrap();
+        let mut storage = StorageImpl::open(&dir).await.unwrap();
+        for content in ["a", "b", "c"] {
+            storage
+                .append_to_file("file", content.to_owned())
+                .await
+                .unwrap();
+        }
+        assert_eq!(storage.read_file("file").await.unwrap(), "abc");
+        assert_eq!(storage.list_files().await.unwrap(), vec!["file".to_owned()]);
+    }
+
     #[tokio::test]
     async fn never_interrupted() {
         let dir = gerenate_random_storage_directory();
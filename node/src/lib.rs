@@ -32,6 +32,7 @@
 pub mod node;
 
 pub use simperby_common;
+pub use simperby_consensus;
 pub use simperby_network;
 pub use simperby_repository;
 
@@ -39,6 +40,7 @@ use eyre::Result;
 use serde::{Deserialize, Serialize};
 use simperby_common::crypto::*;
 use simperby_common::*;
+use simperby_consensus::ConsensusStateSnapshot;
 use simperby_governance::Governance;
 use simperby_network::{Peer, SharedKnownPeers};
 use simperby_repository::raw::{RawRepository, RawRepositoryImpl, SemanticCommit};
@@ -54,6 +56,9 @@ pub struct Config {
 
     pub broadcast_interval_ms: Option<u64>,
     pub fetch_interval_ms: Option<u64>,
+    /// If set, `serve()` makes progress on the consensus on this interval.
+    #[serde(default)]
+    pub consensus_progress_interval_ms: Option<u64>,
 
     /// Public repos (usually mirrors) for the read-only accesses
     ///
@@ -62,12 +67,30 @@ pub struct Config {
 
     pub governance_port: u16,
     pub consensus_port: u16,
+    pub chat_port: u16,
     pub repository_port: u16,
+
+    /// If set, each DMS also serves the gossip network of its messages on the port.
+    #[serde(default)]
+    pub governance_gossip_port: Option<u16>,
+    #[serde(default)]
+    pub consensus_gossip_port: Option<u16>,
+    #[serde(default)]
+    pub chat_gossip_port: Option<u16>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ConsensusStatus {
-    // TODO
+    /// The height of the block being agreed on.
+    pub height: BlockHeight,
+    /// The state of the consensus, in which a block is identified by its index in `block_hashes`.
+    pub state: ConsensusStateSnapshot,
+    /// The hashes of the block candidates that this node has verified.
+    pub block_hashes: Vec<Hash256>,
+    /// The hashes of the block candidates that this node has vetoed.
+    pub vetoed_block_hashes: Vec<Hash256>,
+    /// The public keys of the validators, indexed by `ValidatorIndex` of `state`.
+    pub validators: Vec<PublicKey>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -105,7 +128,7 @@ pub enum CommitInfo {
 }
 
 pub type SimperbyNode = node::Node<
-    simperby_network::primitives::DummyGossipNetwork,
+    simperby_network::gossip::TcpGossipNetwork,
     simperby_network::storage::StorageImpl,
     simperby_repository::raw::RawRepositoryImpl,
 >;
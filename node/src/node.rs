@@ -1,7 +1,11 @@
 use super::*;
 use eyre::eyre;
 use simperby_common::utils::get_timestamp;
-use simperby_consensus::{Consensus, ConsensusParameters, ProgressResult};
+use simperby_consensus::{
+    derive_consensus_params, Consensus, EventTrace, ProgressResult, ProposedBlock, Replayer,
+    StateDifference,
+};
+use simperby_network::gossip::gossip_port_key;
 use simperby_network::primitives::{GossipNetwork, Storage};
 use simperby_network::NetworkConfig;
 use simperby_network::{dms, storage::StorageImpl, Dms, Peer, SharedKnownPeers};
@@ -14,6 +18,7 @@ pub struct Node<N: GossipNetwork, S: Storage, R: RawRepository> {
     repository: DistributedRepository<R>,
     governance: Governance<N, S>,
     consensus: Consensus<N, S>,
+    chat: simperby_chat::Chat<N, S>,
 
     last_reserved_state: ReservedState,
     #[allow(dead_code)]
@@ -45,6 +50,14 @@ impl SimperbyNode {
         let reserved_state = repository.get_reserved_state().await?;
         let governance_dms_key = simperby_governance::generate_dms_key(&last_finalized_header);
         let consensus_dms_key = simperby_consensus::generate_dms_key(&last_finalized_header);
+        let chat_dms_key = simperby_chat::generate_dms_key(&last_finalized_header);
+        let gossip_ports = [
+            (&governance_dms_key, config.governance_gossip_port),
+            (&consensus_dms_key, config.consensus_gossip_port),
+            (&chat_dms_key, config.chat_gossip_port),
+        ]
+        .into_iter()
+        .filter_map(|(dms_key, port)| port.map(|port| (gossip_port_key(dms_key), port)));
         let network_config = NetworkConfig {
             network_id: reserved_state.genesis_info.chain_name.clone(),
             ports: vec![
@@ -56,9 +69,11 @@ impl SimperbyNode {
                     format!("dms-{}", consensus_dms_key.clone()),
                     config.consensus_port,
                 ),
+                (format!("dms-{}", chat_dms_key.clone()), config.chat_port),
                 ("repository".to_owned(), config.repository_port),
             ]
             .into_iter()
+            .chain(gossip_ports)
             .collect(),
             members: reserved_state
                 .members
@@ -72,6 +87,7 @@ impl SimperbyNode {
             fetch_interval: Some(std::time::Duration::from_millis(500)),
             broadcast_interval: Some(std::time::Duration::from_millis(500)),
             network_config: network_config.clone(),
+            chain_id: reserved_state.genesis_info.chain_id(),
         };
 
         // Step 2: initialize the governance module
@@ -105,12 +121,27 @@ impl SimperbyNode {
             dms,
             consensus_state_storage,
             last_finalized_header.clone(),
-            // TODO: replace params and timestamp with proper values
-            ConsensusParameters {
-                timeout_ms: 10000000,
-                repeat_round_for_first_leader: 100,
-            },
-            0,
+            derive_consensus_params(&reserved_state, &last_finalized_header)?,
+            // The round 0 of the next height begins when the last block was made.
+            last_finalized_header.timestamp,
+            Some(config.private_key.clone()),
+        )
+        .await?;
+
+        // Step 4: initialize the chat module
+        let dms_path = format!("{path}/chat/dms");
+        StorageImpl::create(&dms_path).await.unwrap();
+        let storage = StorageImpl::open(&dms_path).await.unwrap();
+        let dms = Dms::new(storage, chat_dms_key, dms_config.clone(), peers.clone()).await?;
+        let chat = simperby_chat::Chat::new(
+            dms,
+            &last_finalized_header,
+            reserved_state
+                .members
+                .iter()
+                .filter(|member| !member.expelled)
+                .map(|member| member.public_key.clone())
+                .collect(),
             Some(config.private_key.clone()),
         )
         .await?;
@@ -119,6 +150,7 @@ impl SimperbyNode {
             repository,
             governance,
             consensus,
+            chat,
             last_reserved_state: reserved_state,
             last_finalized_header,
             path: path.to_owned(),
@@ -172,9 +204,13 @@ impl SimperbyNode {
             .repository
             .create_block(self.config.public_key.clone())
             .await?;
-        // automatically set as my proposal
+        let agenda_proof_hash = self.repository.get_agenda_proof_hash(commit_hash).await?;
+        // automatically set as my proposal, attaching the block for the members who have not fetched it
         self.consensus
-            .register_verified_block_hash(header.to_hash256())
+            .register_verified_block(ProposedBlock {
+                header: header.clone(),
+                agenda_proof_hash,
+            })
             .await?;
         self.consensus
             .set_proposal_candidate(header.to_hash256(), get_timestamp())
@@ -204,6 +240,20 @@ impl SimperbyNode {
         Ok(())
     }
 
+    /// Creates a chat log commit on the `work` branch.
+    ///
+    /// This node semifinalizes the current chat chain and records it,
+    /// so it must be the proposer of the next block.
+    pub async fn create_chat_log(&mut self) -> Result<CommitHash> {
+        self.chat.ack(get_timestamp()).await?;
+        let chat_log = self
+            .chat
+            .read_semifinalized()
+            .await?
+            .ok_or_else(|| eyre!("failed to semifinalize the chat chain"))?;
+        self.repository.create_chat_log(&chat_log).await
+    }
+
     /// Votes on the agenda corresponding to the given `agenda_commit` and propagates the result.
     pub async fn vote(&mut self, agenda_commit: CommitHash) -> Result<()> {
         let valid_agendas = self.repository.get_agendas().await?;
@@ -222,12 +272,36 @@ impl SimperbyNode {
 
     /// Vetoes the current round.
     pub async fn veto_round(&mut self) -> Result<()> {
-        unimplemented!()
+        let round = self.consensus.read_consensus_state().await?.round;
+        self.consensus
+            .veto_round(round as ConsensusRound, get_timestamp())
+            .await?;
+        Ok(())
     }
 
-    /// Vetoes the given block.
-    pub async fn veto_block(&mut self, _block_commit: CommitHash) -> Result<()> {
-        unimplemented!()
+    /// Vetoes the given block, or withdraws the veto if it is already vetoed.
+    pub async fn veto_block(&mut self, block_commit: CommitHash) -> Result<()> {
+        let semantic_commit = self
+            .repository
+            .get_raw()
+            .read_semantic_commit(block_commit)
+            .await?;
+        let block_hash = match simperby_repository::format::from_semantic_commit(semantic_commit)? {
+            Commit::Block(block_header) => block_header.to_hash256(),
+            _ => return Err(eyre!("commit {} is not a block commit", block_commit)),
+        };
+        if self
+            .consensus
+            .get_vetoed_block_hashes()
+            .contains(&block_hash)
+        {
+            self.consensus.unveto_block(block_hash).await?;
+            self.repository.unveto(block_commit).await?;
+        } else {
+            self.consensus.veto_block(block_hash).await?;
+            self.repository.veto(block_commit).await?;
+        }
+        Ok(())
     }
 
     /// Shows information about the given commit.
@@ -286,9 +360,64 @@ impl SimperbyNode {
         Ok(format!("{result:?}"))
     }
 
+    /// Says the given message on the chat.
+    pub async fn chat(&mut self, message: String) -> Result<()> {
+        self.chat.chat(message, get_timestamp()).await?;
+        Ok(())
+    }
+
+    /// Semifinalizes the current chat chain as a consensus leader.
+    pub async fn ack_chat(&mut self) -> Result<()> {
+        self.chat.ack(get_timestamp()).await?;
+        Ok(())
+    }
+
+    /// Reads the current canonical chat chain with the names of the authors.
+    pub async fn read_chat(&self) -> Result<Vec<(MemberName, Chat)>> {
+        Ok(self
+            .chat
+            .read()
+            .await?
+            .into_iter()
+            .map(|signed_chat| {
+                let name = self
+                    .last_reserved_state
+                    .query_name(signed_chat.signature.signer())
+                    .unwrap_or_else(|| signed_chat.signature.signer().to_string());
+                (name, signed_chat.chat)
+            })
+            .collect())
+    }
+
     /// Gets the current status of the consensus.
     pub async fn get_consensus_status(&self) -> Result<ConsensusStatus> {
-        todo!()
+        let block_header = self.consensus.get_block_header();
+        Ok(ConsensusStatus {
+            height: block_header.height + 1,
+            state: self.consensus.read_consensus_state().await?,
+            block_hashes: self.consensus.get_verified_block_hashes().to_vec(),
+            vetoed_block_hashes: self.consensus.get_vetoed_block_hashes().to_vec(),
+            validators: block_header
+                .validator_set
+                .iter()
+                .map(|(public_key, _)| public_key.clone())
+                .collect(),
+        })
+    }
+
+    /// Starts recording the consensus events, which continues over the heights.
+    pub async fn start_consensus_trace_recording(&mut self) -> Result<()> {
+        self.consensus.start_trace_recording().await
+    }
+
+    /// Reads the recorded consensus events of the current height.
+    pub async fn get_consensus_trace(&self) -> Result<EventTrace> {
+        self.consensus.read_trace().await
+    }
+
+    /// Compares the given replayed consensus state with the live one.
+    pub fn diff_consensus_state(&self, replayed: &Replayer) -> Vec<StateDifference> {
+        replayed.diff(&self.consensus.get_state().vetomint.vetomint)
     }
 
     /// Gets the current status of the p2p network.
@@ -296,11 +425,28 @@ impl SimperbyNode {
         unimplemented!()
     }
 
-    pub async fn serve(self, ms: u64) -> Result<Self> {
+    /// Serves all the network components for the given time.
+    ///
+    /// If `consensus_progress_interval_ms` is set, it also makes progress on the consensus,
+    /// syncing the repository to the block if finalized.
+    pub async fn serve(mut self, ms: u64) -> Result<Self> {
         let repository_port = self.config.repository_port;
+        let consensus_progress_interval_ms = self.config.consensus_progress_interval_ms;
+        let mut consensus_results =
+            consensus_progress_interval_ms.map(|_| self.consensus.subscribe());
 
         let t1 = tokio::spawn(async move { self.governance.serve(ms).await.unwrap() });
-        let t2 = tokio::spawn(async move { self.consensus.serve(ms).await.unwrap() });
+        let t2 = tokio::spawn(async move {
+            if let Some(interval) = consensus_progress_interval_ms {
+                self.consensus
+                    .serve_and_progress(interval, Some(ms))
+                    .await
+                    .unwrap()
+            } else {
+                self.consensus.serve(ms).await.unwrap()
+            }
+        });
+        let t4 = tokio::spawn(async move { self.chat.serve(ms).await.unwrap() });
         let path = self.path.clone();
         let t3 = tokio::spawn(async move {
             let server = simperby_repository::server::run_server_legacy(
@@ -315,24 +461,35 @@ impl SimperbyNode {
         let governance = t1.await?;
         let consensus = t2.await?;
         t3.await?;
+        let chat = t4.await?;
 
-        Ok(Self {
+        let mut node = Self {
             governance,
             consensus,
+            chat,
             config: self.config,
             repository: self.repository,
             last_reserved_state: self.last_reserved_state,
             last_finalized_header: self.last_finalized_header,
             path: self.path,
             network_config: self.network_config,
-        })
+        };
+        if let Some(consensus_results) = &mut consensus_results {
+            while let Ok(result) = consensus_results.try_recv() {
+                if let ProgressResult::Finalized(hash, _, proof) = result {
+                    node.repository.sync(&hash, &proof).await?;
+                }
+            }
+        }
+        Ok(node)
     }
 
     pub async fn fetch(&mut self) -> Result<()> {
         let t1 = async { self.governance.fetch().await };
         let t2 = async { self.consensus.fetch().await };
         let t3 = async { self.repository.fetch().await };
-        futures::try_join!(t1, t2, t3)?;
+        let t4 = async { self.chat.fetch().await };
+        futures::try_join!(t1, t2, t3, t4)?;
 
         // Update governance
         let governance_set = self
@@ -379,6 +536,28 @@ impl SimperbyNode {
                 .register_verified_block_hash(block_hash)
                 .await?;
         }
+        // Accept the blocks attached to the proposals, which may not have been fetched yet.
+        let last_header = self.repository.get_last_finalized_block_header().await?;
+        let agenda_proofs = self
+            .repository
+            .get_agenda_proofs()
+            .await?
+            .into_iter()
+            .map(|(_, hash)| hash)
+            .collect::<Vec<_>>();
+        for block in self.consensus.get_pending_proposed_blocks().await? {
+            if block.header.previous_hash != last_header.to_hash256() {
+                continue;
+            }
+            if let Some(agenda_proof_hash) = block.agenda_proof_hash {
+                if !agenda_proofs.contains(&agenda_proof_hash) {
+                    continue;
+                }
+            }
+            self.consensus
+                .register_verified_block_hash(block.header.to_hash256())
+                .await?;
+        }
         Ok(())
     }
 
@@ -387,7 +566,8 @@ impl SimperbyNode {
         let t1 = async { self.governance.broadcast().await };
         let t2 = async { self.consensus.broadcast().await };
         let t3 = async { self.repository.broadcast().await };
-        futures::try_join!(t1, t2, t3)?;
+        let t4 = async { self.chat.broadcast().await };
+        futures::try_join!(t1, t2, t3, t4)?;
         // TODO: report the result
         Ok(vec![])
     }
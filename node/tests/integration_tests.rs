@@ -12,10 +12,15 @@ fn generate_config(key: PrivateKey, chain_name: String) -> Config {
         private_key: key,
         broadcast_interval_ms: None,
         fetch_interval_ms: None,
+        consensus_progress_interval_ms: None,
         public_repo_url: vec![],
         governance_port: dispense_port(),
         consensus_port: dispense_port(),
+        chat_port: dispense_port(),
         repository_port: dispense_port(),
+        governance_gossip_port: None,
+        consensus_gossip_port: None,
+        chat_gossip_port: None,
     }
 }
 
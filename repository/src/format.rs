@@ -23,10 +23,18 @@ pub fn to_semantic_commit(
         Commit::Block(block_header) => {
             let title = format!(">block: {}", block_header.height);
             let body = serde_spb::to_string(block_header).unwrap();
+            // The block commit carries the delegations expired at this block, if any.
+            let mut next_reserved_state = reserved_state.clone();
+            next_reserved_state.expire_delegations(block_header);
+            let diff = if next_reserved_state != reserved_state {
+                Diff::Reserved(Box::new(next_reserved_state))
+            } else {
+                Diff::None
+            };
             Ok(SemanticCommit {
                 title,
                 body,
-                diff: Diff::None,
+                diff,
                 author: if block_header.author == PublicKey::zero() {
                     "genesis".to_owned()
                 } else {
@@ -89,12 +97,51 @@ pub fn to_semantic_commit(
                         timestamp: tx.data.timestamp,
                     })
                 }
-                ExtraAgendaTransaction::Report(_) => {
-                    unimplemented!("report is not implemented yet.")
+                ExtraAgendaTransaction::Report(tx) => {
+                    let offender = reserved_state
+                        .query_name(tx.first_vote.signature.signer())
+                        .ok_or_else(|| {
+                            eyre!(
+                                "failed to query the name of the reported validator: {}",
+                                tx.first_vote.signature.signer()
+                            )
+                        })?;
+                    let title = format!(">tx-report: {offender}");
+                    let diff = Diff::Reserved(Box::new(
+                        reserved_state.apply_report(tx).map_err(|e| eyre!(e))?,
+                    ));
+                    Ok(SemanticCommit {
+                        title,
+                        body,
+                        diff,
+                        author: UNKNOWN_COMMIT_AUTHOR.to_owned(),
+                        timestamp: tx.timestamp,
+                    })
                 }
             }
         }
-        Commit::ChatLog(_) => unimplemented!(),
+        Commit::ChatLog(chat_log) => {
+            let title = format!(">chat: {}", chat_log.height);
+            let body = serde_spb::to_string(chat_log).unwrap();
+            let last_chat = chat_log
+                .chats
+                .last()
+                .ok_or_else(|| eyre!("the chat log is empty"))?;
+            Ok(SemanticCommit {
+                title,
+                body,
+                diff: Diff::None,
+                author: reserved_state
+                    .query_name(last_chat.signature.signer())
+                    .ok_or_else(|| {
+                        eyre!(
+                            "failed to query the name of the chat log leader: {}",
+                            last_chat.signature.signer()
+                        )
+                    })?,
+                timestamp: last_chat.chat.timestamp,
+            })
+        }
     }
 }
 
@@ -103,7 +150,7 @@ pub fn to_semantic_commit(
 /// TODO: retrieve author and timestamp from the commit metadata.
 pub fn from_semantic_commit(semantic_commit: SemanticCommit) -> Result<Commit, Error> {
     let pattern = Regex::new(
-        r"^>(((agenda)|(block)|(agenda-proof)): (\d+))|((tx-delegate): ((\D+)-(\d+)) to ((\D+)-(\d+)))|((tx-undelegate): ((\D+)-(\d+)))$"
+        r"^>(((agenda)|(block)|(agenda-proof)): (\d+))|((tx-delegate): ((\D+)-(\d+)) to ((\D+)-(\d+)))|((tx-undelegate): ((\D+)-(\d+)))|((tx-report): ((\D+)-(\d+)))|((chat): (\d+))$"
     )
     .unwrap();
     let captures = pattern.captures(&semantic_commit.title);
@@ -112,6 +159,8 @@ pub fn from_semantic_commit(semantic_commit: SemanticCommit) -> Result<Commit, E
             .get(2)
             .or_else(|| captures.get(8))
             .or_else(|| captures.get(16))
+            .or_else(|| captures.get(21))
+            .or_else(|| captures.get(26))
             .map(|m| m.as_str())
             .ok_or_else(|| {
                 eyre!(
@@ -235,6 +284,33 @@ pub fn from_semantic_commit(semantic_commit: SemanticCommit) -> Result<Commit, E
                     _ => Err(eyre!("expected undelegation transaction, got {:?}", tx)),
                 }
             }
+            "tx-report" => {
+                // Note that the reported member name in the title can't be checked here
+                // because the evidence only carries the public key of the offender.
+                let tx: ExtraAgendaTransaction = serde_spb::from_str(&semantic_commit.body)?;
+                match tx {
+                    ExtraAgendaTransaction::Report(_) => Ok(Commit::ExtraAgendaTransaction(tx)),
+                    _ => Err(eyre!("expected report transaction, got {:?}", tx)),
+                }
+            }
+            "chat" => {
+                let chat_log: ChatLog = serde_spb::from_str(&semantic_commit.body)?;
+                let height = captures.get(27).map(|m| m.as_str()).ok_or_else(|| {
+                    eyre!(
+                        "failed to parse height from the commit title: {}",
+                        semantic_commit.title
+                    )
+                })?;
+                let height = height.parse::<u64>()?;
+                if height != chat_log.height {
+                    return Err(eyre!(
+                        "chat log height mismatch: expected {}, got {}",
+                        chat_log.height,
+                        height
+                    ));
+                }
+                Ok(Commit::ChatLog(chat_log))
+            }
             _ => Err(eyre!("unknown commit type: {}", commit_type)),
         }
     } else {
@@ -373,11 +449,17 @@ mod tests {
             block_height: 0,
             timestamp: 0,
             chain_name: reserved_state.genesis_info.chain_name.clone(),
+            conditions: vec![],
         };
         let delegation_transaction =
             Commit::ExtraAgendaTransaction(ExtraAgendaTransaction::Delegate(TxDelegate {
                 data: delegation_transaction_data.clone(),
-                proof: TypedSignature::sign(&delegation_transaction_data, &keys[0].1).unwrap(),
+                proof: TypedSignature::sign(
+                    &delegation_transaction_data,
+                    &reserved_state.genesis_info.chain_id(),
+                    &keys[0].1,
+                )
+                .unwrap(),
             }));
         assert_eq!(
             delegation_transaction,
@@ -402,7 +484,12 @@ mod tests {
         let undelegation_transaction =
             Commit::ExtraAgendaTransaction(ExtraAgendaTransaction::Undelegate(TxUndelegate {
                 data: undelegation_transaction_data.clone(),
-                proof: TypedSignature::sign(&undelegation_transaction_data, &keys[0].1).unwrap(),
+                proof: TypedSignature::sign(
+                    &undelegation_transaction_data,
+                    &reserved_state.genesis_info.chain_id(),
+                    &keys[0].1,
+                )
+                .unwrap(),
             }));
         assert_eq!(
             undelegation_transaction,
@@ -413,6 +500,74 @@ mod tests {
         );
     }
 
+    #[test]
+    fn format_extra_agenda_transaction_commit3() {
+        let (reserved_state, keys) = generate_standard_genesis(4);
+        let sign_vote = |block_hash: &str| {
+            let vote = ConsensusVote {
+                kind: ConsensusVoteKind::Prevote,
+                height: 1,
+                round: 0,
+                block_hash: Some(Hash256::hash(block_hash)),
+            };
+            SignedConsensusVote {
+                signature: TypedSignature::sign(
+                    &vote,
+                    &reserved_state.genesis_info.chain_id(),
+                    &keys[1].1,
+                )
+                .unwrap(),
+                vote,
+            }
+        };
+        let report_transaction =
+            Commit::ExtraAgendaTransaction(ExtraAgendaTransaction::Report(TxReport {
+                first_vote: sign_vote("block1"),
+                second_vote: sign_vote("block2"),
+                timestamp: 0,
+            }));
+        let semantic_commit =
+            to_semantic_commit(&report_transaction, reserved_state.clone()).unwrap();
+        assert_eq!(semantic_commit.title, ">tx-report: member-0001");
+        assert_eq!(
+            report_transaction,
+            from_semantic_commit(semantic_commit).unwrap()
+        );
+    }
+
+    #[test]
+    fn format_chat_log_commit() {
+        let (reserved_state, keys) = generate_standard_genesis(4);
+        let mut chat_log = ChatLog {
+            height: 3,
+            chats: vec![],
+        };
+        for (content, key) in [
+            (ChatContent::Text("hello".to_owned()), &keys[2].1),
+            (ChatContent::Ack, &keys[0].1),
+        ] {
+            let chat = Chat {
+                height: 3,
+                timestamp: 0,
+                content,
+                previous_hash: chat_log.chats.last().map(|x| x.to_hash256()),
+            };
+            chat_log.chats.push(SignedChat {
+                signature: TypedSignature::sign(
+                    &chat,
+                    &reserved_state.genesis_info.chain_id(),
+                    key,
+                )
+                .unwrap(),
+                chat,
+            });
+        }
+        let chat_log = Commit::ChatLog(chat_log);
+        let semantic_commit = to_semantic_commit(&chat_log, reserved_state).unwrap();
+        assert_eq!(semantic_commit.author, "member-0000");
+        assert_eq!(chat_log, from_semantic_commit(semantic_commit).unwrap());
+    }
+
     #[test]
     fn format_fp() {
         let fp = LastFinalizationProof {
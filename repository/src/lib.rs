@@ -11,13 +11,16 @@ use futures::prelude::*;
 use log::{info, warn};
 use raw::RawRepository;
 use serde::{Deserialize, Serialize};
+use simperby_common::merkle_tree::{StateMerkleTree, StateProof};
 use simperby_common::reserved::ReservedState;
 use simperby_common::utils::get_timestamp;
 use simperby_common::verify::CommitSequenceVerifier;
 use simperby_common::*;
 use simperby_network::{NetworkConfig, Peer, SharedKnownPeers};
 use std::{collections::HashSet, fmt};
-use utils::{read_commits, retrieve_local_branches};
+use utils::{
+    create_state_tree, read_commits, retrieve_local_branches, verify_repository_merkle_roots,
+};
 pub type Branch = String;
 pub type Tag = String;
 
@@ -200,6 +203,50 @@ impl<T: RawRepository> DistributedRepository<T> {
         self.raw.read_reserved_state().await.map_err(|e| eyre!(e))
     }
 
+    /// Creates a proof of the content of the given path (or the absence of it)
+    /// in the repository state of the finalized block at the given height.
+    ///
+    /// It returns the content as well, which is `None` if the path doesn't exist.
+    pub async fn create_state_proof(
+        &self,
+        path: &str,
+        height: BlockHeight,
+    ) -> Result<(Option<Vec<u8>>, StateProof), Error> {
+        if height == 0 {
+            return Err(eyre!(
+                "the genesis block has no repository state commitment"
+            ));
+        }
+        let finalized_commit = self.raw.locate_branch(FINALIZED_BRANCH_NAME.into()).await?;
+        let commits = std::iter::once(finalized_commit)
+            .chain(self.raw.list_ancestors(finalized_commit, None).await?)
+            .collect::<Vec<_>>();
+        for (i, commit_hash) in commits.iter().enumerate() {
+            let header = match self.read_commit(*commit_hash).await? {
+                Commit::Block(header) => header,
+                _ => continue,
+            };
+            if header.height > height {
+                continue;
+            }
+            if header.height < height {
+                break;
+            }
+            let previous_commit_hash = commits.get(i + 1).ok_or_else(|| {
+                eyre!(IntegrityError::new(format!(
+                    "the block commit {commit_hash} has no parent"
+                )))
+            })?;
+            let files = self.raw.read_files(*previous_commit_hash).await?;
+            let content = files
+                .iter()
+                .find(|(file_path, _)| file_path == path)
+                .map(|(_, content)| content.clone());
+            return Ok((content, StateMerkleTree::create(files).create_proof(path)));
+        }
+        Err(eyre!("no finalized block at height {height}"))
+    }
+
     /// Cleans all the outdated commits, remote repositories and branches.
     ///
     /// It will leave only
@@ -437,6 +484,9 @@ impl<T: RawRepository> DistributedRepository<T> {
         verifier
             .verify_last_header_finalization(last_block_proof)
             .map_err(|e| eyre!("verification error on the last block header: {}", e))?;
+        verify_repository_merkle_roots(&self.raw, current_finalized_commit, &new_commits)
+            .await?
+            .map_err(|e| eyre!("verification error on the repository state: {}", e))?;
 
         // If commit sequence verification is done and the finalization proof is verified,
         // move the `finalized` branch to the given block commit hash.
@@ -471,6 +521,34 @@ impl<T: RawRepository> DistributedRepository<T> {
     /// Returns the currently valid and height-acceptable agendas in the repository.
     pub async fn get_agendas(&self) -> Result<Vec<(CommitHash, Hash256)>, Error> {
         let mut agendas: Vec<(CommitHash, Hash256)> = vec![];
+        let last_header = self.get_last_finalized_block_header().await?;
+        for (commit, hash) in self.read_agenda_branch_commits().await? {
+            if let Commit::Agenda(agenda) = commit {
+                if agenda.height == last_header.height + 1 {
+                    agendas.push((hash, agenda.to_hash256()));
+                }
+            }
+        }
+        Ok(agendas)
+    }
+
+    /// Returns the currently valid and height-acceptable agenda proofs in the repository.
+    pub async fn get_agenda_proofs(&self) -> Result<Vec<(CommitHash, Hash256)>, Error> {
+        let mut agenda_proofs: Vec<(CommitHash, Hash256)> = vec![];
+        let last_header = self.get_last_finalized_block_header().await?;
+        for (commit, hash) in self.read_agenda_branch_commits().await? {
+            if let Commit::AgendaProof(agenda_proof) = commit {
+                if agenda_proof.height == last_header.height + 1 {
+                    agenda_proofs.push((hash, agenda_proof.to_hash256()));
+                }
+            }
+        }
+        Ok(agenda_proofs)
+    }
+
+    /// Reads the commits of the agenda branches which are rebased on top of the `finalized` branch.
+    async fn read_agenda_branch_commits(&self) -> Result<Vec<(Commit, CommitHash)>, Error> {
+        let mut agenda_branch_commits = vec![];
         let branches = retrieve_local_branches(&self.raw).await?;
         let last_header_commit_hash = self.raw.locate_branch(FINALIZED_BRANCH_NAME.into()).await?;
         for (branch, branch_commit_hash) in branches {
@@ -499,20 +577,11 @@ impl<T: RawRepository> DistributedRepository<T> {
                     continue;
                 }
 
-                // Push currently valid and height-acceptable agendas to the list
-                let commits =
-                    read_commits(self, last_header_commit_hash, branch_commit_hash).await?;
-                let last_header = self.get_last_finalized_block_header().await?;
-                for (commit, hash) in commits {
-                    if let Commit::Agenda(agenda) = commit {
-                        if agenda.height == last_header.height + 1 {
-                            agendas.push((hash, agenda.to_hash256()));
-                        }
-                    }
-                }
+                agenda_branch_commits
+                    .extend(read_commits(self, last_header_commit_hash, branch_commit_hash).await?);
             }
         }
-        Ok(agendas)
+        Ok(agenda_branch_commits)
     }
 
     /// Returns the currently valid and height-acceptable blocks in the repository.
@@ -561,6 +630,19 @@ impl<T: RawRepository> DistributedRepository<T> {
         Ok(blocks)
     }
 
+    /// Returns the hash of the agenda-proof commit that the given block commit is built on, if any.
+    pub async fn get_agenda_proof_hash(
+        &self,
+        block_commit_hash: CommitHash,
+    ) -> Result<Option<Hash256>, Error> {
+        let last_header_commit_hash = self.raw.locate_branch(FINALIZED_BRANCH_NAME.into()).await?;
+        let commits = read_commits(self, last_header_commit_hash, block_commit_hash).await?;
+        Ok(commits.into_iter().find_map(|(commit, _)| match commit {
+            Commit::AgendaProof(_) => Some(commit.to_hash256()),
+            _ => None,
+        }))
+    }
+
     /// Informs that the given agenda has been approved.
     ///
     ///
@@ -763,6 +845,24 @@ impl<T: RawRepository> DistributedRepository<T> {
         }
     }
 
+    /// Removes the 'veto' tag from the commit.
+    pub async fn unveto(&mut self, commit_hash: CommitHash) -> Result<(), Error> {
+        let veto_tags: Vec<_> = self
+            .raw
+            .get_tag(commit_hash)
+            .await?
+            .into_iter()
+            .filter(|tag| tag.starts_with("veto-"))
+            .collect();
+        if veto_tags.is_empty() {
+            return Err(eyre!("commit {} is not vetoed", commit_hash));
+        }
+        for tag in veto_tags {
+            self.raw.remove_tag(tag).await?;
+        }
+        Ok(())
+    }
+
     /// Creates a block commit on top of the `work` branch.
     pub async fn create_block(
         &mut self,
@@ -814,7 +914,7 @@ impl<T: RawRepository> DistributedRepository<T> {
         let finalization_proof = fp_from_semantic_commit(fp_semantic_commit).unwrap().proof;
 
         // Create block commit
-        let block_header = BlockHeader {
+        let mut block_header = BlockHeader {
             author: author.clone(),
             prev_block_finalization_proof: finalization_proof,
             previous_hash: last_header.to_hash256(),
@@ -826,10 +926,14 @@ impl<T: RawRepository> DistributedRepository<T> {
                     .map(|(commit, _)| commit.clone())
                     .collect::<Vec<_>>(),
             ),
-            repository_merkle_root: Hash256::zero(), // TODO
-            validator_set: reserved_state.get_validator_set().unwrap(),
+            repository_merkle_root: create_state_tree(&self.raw, work_commit).await?.root(),
+            validator_set: vec![],
             version: SIMPERBY_CORE_PROTOCOL_VERSION.to_string(),
         };
+        // The delegations expired at this block are no longer effective for the next one.
+        let mut next_reserved_state = reserved_state.clone();
+        next_reserved_state.expire_delegations(&block_header);
+        block_header.validator_set = next_reserved_state.get_validator_set().unwrap();
         let block_commit = Commit::Block(block_header.clone());
         verifier.apply_commit(&block_commit).map_err(|_| {
             eyre!("block commit cannot be created on top of the current commit sequence")
@@ -905,4 +1009,48 @@ impl<T: RawRepository> DistributedRepository<T> {
         let result = self.raw.create_semantic_commit(semantic_commit).await?;
         Ok(result)
     }
+
+    /// Creates a chat log commit on top of the `work` branch.
+    pub async fn create_chat_log(&mut self, chat_log: &ChatLog) -> Result<CommitHash, Error> {
+        let work_commit = self.raw.locate_branch(WORK_BRANCH_NAME.into()).await?;
+        let last_header_commit = self.raw.locate_branch(FINALIZED_BRANCH_NAME.into()).await?;
+        let reserved_state = self.get_reserved_state().await?;
+
+        // Check if the `work` branch is rebased on top of the `finalized` branch.
+        if self
+            .raw
+            .find_merge_base(last_header_commit, work_commit)
+            .await?
+            != last_header_commit
+        {
+            return Err(eyre!(
+                "branch {} should be rebased on {}",
+                WORK_BRANCH_NAME,
+                FINALIZED_BRANCH_NAME
+            ));
+        }
+
+        // Check the validity of the commit sequence
+        let commits = read_commits(self, last_header_commit, work_commit).await?;
+        let last_header = self.get_last_finalized_block_header().await?;
+        let mut verifier = CommitSequenceVerifier::new(last_header.clone(), reserved_state.clone())
+            .map_err(|e| eyre!("failed to create a commit sequence verifier: {}", e))?;
+        for (commit, hash) in commits.iter() {
+            verifier
+                .apply_commit(commit)
+                .map_err(|e| eyre!("verification error on commit {}: {}", hash, e))?;
+        }
+
+        let chat_log_commit = Commit::ChatLog(chat_log.clone());
+        verifier.apply_commit(&chat_log_commit).map_err(|e| {
+            eyre!("chat log commit cannot be created on top of the current commit sequence: {e}")
+        })?;
+
+        let semantic_commit = to_semantic_commit(&chat_log_commit, reserved_state)?;
+
+        self.raw.checkout_clean().await?;
+        self.raw.checkout(WORK_BRANCH_NAME.into()).await?;
+        let result = self.raw.create_semantic_commit(semantic_commit).await?;
+        Ok(result)
+    }
 }
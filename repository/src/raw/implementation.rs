@@ -525,6 +525,37 @@ impl RawRepositoryImplInner {
         Ok(email)
     }
 
+    pub(crate) fn read_files(
+        &self,
+        commit_hash: CommitHash,
+    ) -> Result<Vec<(String, Vec<u8>)>, Error> {
+        let oid = git2::Oid::from_bytes(&commit_hash.hash)?;
+        let tree = self.repo.find_commit(oid)?.tree()?;
+        let mut files = Vec::new();
+        let mut error = None;
+        let result = tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+            if entry.kind() != Some(ObjectType::Blob) {
+                return git2::TreeWalkResult::Ok;
+            }
+            let path = format!("{root}{}", entry.name().unwrap_or_default());
+            match self.repo.find_blob(entry.id()) {
+                Ok(blob) => {
+                    files.push((path, blob.content().to_vec()));
+                    git2::TreeWalkResult::Ok
+                }
+                Err(e) => {
+                    error = Some(e);
+                    git2::TreeWalkResult::Abort
+                }
+            }
+        });
+        if let Some(e) = error {
+            return Err(e.into());
+        }
+        result?;
+        Ok(files)
+    }
+
     pub(crate) fn list_ancestors(
         &self,
         commit_hash: CommitHash,
@@ -183,6 +183,11 @@ pub trait RawRepository: Send + Sync + 'static {
     /// Returns the diff of the given commit.
     async fn show_commit(&self, commit_hash: CommitHash) -> Result<String, Error>;
 
+    /// Reads all the files in the tree of the given commit, as pairs of the path and the content.
+    ///
+    /// The paths are relative to the root of the repository, separated by `/`.
+    async fn read_files(&self, commit_hash: CommitHash) -> Result<Vec<(String, Vec<u8>)>, Error>;
+
     /// Lists the ancestor commits of the given commit (The first element is the direct parent).
     ///
     /// It fails if there is a merge commit.
@@ -577,6 +582,10 @@ impl RawRepository for RawRepositoryImpl {
         helper_1(self, RawRepositoryImplInner::show_commit, commit_hash).await
     }
 
+    async fn read_files(&self, commit_hash: CommitHash) -> Result<Vec<(String, Vec<u8>)>, Error> {
+        helper_1(self, RawRepositoryImplInner::read_files, commit_hash).await
+    }
+
     async fn list_ancestors(
         &self,
         commit_hash: CommitHash,
@@ -31,11 +31,20 @@ pub async fn read_reserved_state(path: &str) -> Result<ReservedState, Error> {
     let version = fs::read_to_string(format!("{}/{}", path, "reserved/version")).await?;
     let version: String = serde_spb::from_str(version.as_str())?;
 
+    // The repositories created before the parameters were stored have the default ones.
+    let consensus_params_path = format!("{}/{}", path, "reserved/consensus_params.json");
+    let consensus_params: ConsensusParams = if Path::new(&consensus_params_path).exists() {
+        serde_spb::from_str(fs::read_to_string(consensus_params_path).await?.as_str())?
+    } else {
+        ConsensusParams::default()
+    };
+
     let reserved_state = ReservedState {
         genesis_info,
         members,
         consensus_leader_order,
         version,
+        consensus_params,
     };
 
     Ok(reserved_state)
@@ -46,6 +55,7 @@ pub async fn write_reserved_state(path: &str, state: &ReservedState) -> Result<(
     let genesis_info = serde_spb::to_string(&state.genesis_info)?;
     let consensus_leader_order = serde_spb::to_string(&state.consensus_leader_order)?;
     let version = serde_spb::to_string(&state.version)?;
+    let consensus_params = serde_spb::to_string(&state.consensus_params)?;
 
     // Create files of reserved state.
     let path = format!("{}/{}", path, "reserved");
@@ -68,6 +78,11 @@ pub async fn write_reserved_state(path: &str, state: &ReservedState) -> Result<(
     )
     .await?;
     fs::write(format!("{}/{}", path.as_str(), "version"), version).await?;
+    fs::write(
+        format!("{}/{}", path.as_str(), "consensus_params.json"),
+        consensus_params,
+    )
+    .await?;
 
     let path = format!("{}/{}", path.as_str(), "members");
     let members_path = Path::new(path.as_str());
@@ -91,6 +106,24 @@ mod tests {
 
     #[tokio::test]
     async fn format_reserved_state() {
+        let (mut reserved_state, _) = generate_standard_genesis(10);
+        reserved_state.consensus_params.timeout_growth = TimeoutGrowth::Linear {
+            increment_ms: 1_000,
+            max_timeout_ms: 60_000,
+        };
+
+        let td = TempDir::new().unwrap();
+        let path = td.path();
+        let path = path.to_str().unwrap();
+
+        write_reserved_state(path, &reserved_state).await.unwrap();
+        let read_reserved_state = read_reserved_state(path).await.unwrap();
+
+        assert_eq!(reserved_state, read_reserved_state);
+    }
+
+    #[tokio::test]
+    async fn read_reserved_state_without_consensus_params() {
         let (reserved_state, _) = generate_standard_genesis(10);
 
         let td = TempDir::new().unwrap();
@@ -98,8 +131,15 @@ mod tests {
         let path = path.to_str().unwrap();
 
         write_reserved_state(path, &reserved_state).await.unwrap();
+        fs::remove_file(format!("{path}/reserved/consensus_params.json"))
+            .await
+            .unwrap();
         let read_reserved_state = read_reserved_state(path).await.unwrap();
 
+        assert_eq!(
+            read_reserved_state.consensus_params,
+            ConsensusParams::default()
+        );
         assert_eq!(reserved_state, read_reserved_state);
     }
 }
@@ -79,6 +79,11 @@ pub async fn receive<T: RawRepository>(
                 )));
             }
         }
+        if let Err(e) =
+            verify_repository_merkle_roots(&this.raw, last_finalized_commit_hash, &commits).await?
+        {
+            return Ok(Err(e));
+        }
 
         let (last_commit, last_commit_hash) = commits.last().expect(
             "already checked that the received commit is not same as the last finalized block",
@@ -118,6 +123,11 @@ pub async fn receive<T: RawRepository>(
                 )));
             }
         }
+        if let Err(e) =
+            verify_repository_merkle_roots(&this.raw, last_finalized_commit_hash, &commits).await?
+        {
+            return Ok(Err(e));
+        }
 
         // If the commit sequence contains block commit(s) that can be finalized
         let headers = csv.get_block_headers();
@@ -78,3 +78,39 @@ pub async fn read_commits<T: RawRepository>(
         .map_err(|(e, c)| CommitError::Commit(e, c))?;
     Ok(commits)
 }
+
+/// Creates the state Merkle tree of the repository at the given commit.
+pub async fn create_state_tree<T: RawRepository>(
+    raw: &T,
+    commit_hash: CommitHash,
+) -> Result<StateMerkleTree, raw::Error> {
+    Ok(StateMerkleTree::create(raw.read_files(commit_hash).await?))
+}
+
+/// Verifies `repository_merkle_root` of the block commits in the given sequence,
+/// which starts right after `ancestor`.
+///
+/// The repository state of a block is the one of the commit right before the block commit.
+///
+/// - Returns `Ok(Ok(()))` if all the roots are valid.
+/// - Returns `Ok(Err(_))` if any of them is invalid, with the reason.
+pub async fn verify_repository_merkle_roots<T: RawRepository>(
+    raw: &T,
+    ancestor: CommitHash,
+    commits: &[(Commit, CommitHash)],
+) -> Result<Result<(), String>, Error> {
+    let mut previous_commit_hash = ancestor;
+    for (commit, commit_hash) in commits {
+        if let Commit::Block(block_header) = commit {
+            let root = create_state_tree(raw, previous_commit_hash).await?.root();
+            if root != block_header.repository_merkle_root {
+                return Ok(Err(format!(
+                    "invalid repository merkle root at {commit_hash}: expected {root}, got {}",
+                    block_header.repository_merkle_root
+                )));
+            }
+        }
+        previous_commit_hash = *commit_hash;
+    }
+    Ok(Ok(()))
+}
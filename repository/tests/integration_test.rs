@@ -73,7 +73,9 @@ async fn basic_1() {
         .approve(
             &agenda.to_hash256(),
             keys.iter()
-                .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+                .map(|(_, private_key)| {
+                    TypedSignature::sign(&agenda, &rs.genesis_info.chain_id(), private_key).unwrap()
+                })
                 .collect(),
             0,
         )
@@ -98,7 +100,9 @@ async fn basic_1() {
     // Step 2: finalize a block and let the client update that
     let block_proof = keys
         .iter()
-        .map(|(_, private_key)| TypedSignature::sign(&block, private_key).unwrap())
+        .map(|(_, private_key)| {
+            TypedSignature::sign(&block, &rs.genesis_info.chain_id(), private_key).unwrap()
+        })
         .collect();
     server_node_repo
         .sync(&block.to_hash256(), &block_proof)
@@ -113,5 +117,26 @@ async fn basic_1() {
         block
     );
 
+    // Step 3: prove the repository state of the block to a light client
+    let mut light_client =
+        light_client::LightClient::new(rs.genesis_info.header.clone(), rs.genesis_info.chain_id());
+    light_client.update(block.clone(), block_proof).unwrap();
+    let (content, proof) = server_node_repo
+        .create_state_proof("reserved/consensus_leader_order.json", 1)
+        .await
+        .unwrap();
+    assert!(light_client.verify_state_commitment(
+        "reserved/consensus_leader_order.json",
+        Some(&content.unwrap()),
+        1,
+        proof
+    ));
+    let (content, proof) = server_node_repo
+        .create_state_proof("budget/2026.json", 1)
+        .await
+        .unwrap();
+    assert_eq!(content, None);
+    assert!(light_client.verify_state_commitment("budget/2026.json", None, 1, proof));
+
     git_server.await.unwrap();
 }
@@ -68,4 +68,15 @@ pub trait SettlementChain: Send + Sync {
         block_height: u64,
         proof: MerkleProof,
     ) -> Result<(), Error>;
+
+    /// Delivers several execution transactions of the same block at once with a single multiproof.
+    ///
+    /// - `transactions`: The transactions to deliver, in the order of their leaf indices in the proof.
+    /// - `block_height`: The height of the block that the transactions are included in.
+    async fn execute_batch(
+        &self,
+        transactions: Vec<Transaction>,
+        block_height: u64,
+        proof: MerkleMultiProof,
+    ) -> Result<(), Error>;
 }
@@ -65,8 +65,8 @@ pub struct MythereumTreasuryContract {
 }
 
 impl MythereumTreasuryContract {
-    pub fn new(header: BlockHeader) -> Result<Self, String> {
-        let light_client = light_client::LightClient::new(header);
+    pub fn new(header: BlockHeader, chain_id: Hash256) -> Result<Self, String> {
+        let light_client = light_client::LightClient::new(header, chain_id);
         Ok(Self {
             light_client,
             sequence: 0,
@@ -138,6 +138,7 @@ impl MythereumTreasuryContract {
 fn relay_1() {
     let (reserved_state, keys) = test_utils::generate_standard_genesis(4);
     let genesis_info = reserved_state.genesis_info.clone();
+    let chain_id = genesis_info.chain_id();
     let genesis_header = reserved_state.genesis_info.header.clone();
 
     let mut csv = CommitSequenceVerifier::new(
@@ -188,7 +189,7 @@ fn relay_1() {
         agenda_hash: agenda.to_hash256(),
         proof: keys
             .iter()
-            .map(|(_, private_key)| TypedSignature::sign(&agenda, private_key).unwrap())
+            .map(|(_, private_key)| TypedSignature::sign(&agenda, &chain_id, private_key).unwrap())
             .collect::<Vec<_>>(),
         timestamp: 0,
     }))
@@ -210,7 +211,9 @@ fn relay_1() {
         .unwrap();
     let fp = keys
         .iter()
-        .map(|(_, private_key)| TypedSignature::sign(&block_header, private_key).unwrap())
+        .map(|(_, private_key)| {
+            TypedSignature::sign(&block_header, &chain_id, private_key).unwrap()
+        })
         .collect::<Vec<_>>();
 
     // Setup Mythereum
@@ -224,7 +227,7 @@ fn relay_1() {
         tether,
     };
     let mut treasury = MythereumTreasuryContract {
-        light_client: LightClient::new(genesis_header),
+        light_client: LightClient::new(genesis_header, chain_id),
         sequence: 0,
     };
     treasury
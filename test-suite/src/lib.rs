@@ -101,6 +101,7 @@ pub fn dispense_port() -> u16 {
 pub async fn create_test_dms(
     network_config: NetworkConfig,
     dms_key: String,
+    chain_id: Hash256,
     peers: SharedKnownPeers,
 ) -> Dms {
     let path = create_temp_dir();
@@ -113,6 +114,7 @@ pub async fn create_test_dms(
             fetch_interval: Some(std::time::Duration::from_millis(500)),
             broadcast_interval: Some(std::time::Duration::from_millis(500)),
             network_config,
+            chain_id,
         },
         peers,
     )
@@ -124,27 +126,28 @@ pub async fn setup_server_client_nodes(
     network_id: String,
     client_n: usize,
 ) -> (NetworkConfig, Vec<NetworkConfig>, SharedKnownPeers) {
-    let (public_key, private_key) = generate_keypair_random();
+    let keys: Vec<_> = (0..=client_n).map(|_| generate_keypair_random()).collect();
+    let members: Vec<_> = keys.iter().map(|(x, _)| x.clone()).collect();
+    let (public_key, private_key) = keys[0].clone();
     let server = NetworkConfig {
         network_id: network_id.clone(),
         ports: vec![(format!("dms-{network_id}"), dispense_port())]
             .into_iter()
             .collect(),
-        members: Vec::new(),
+        members: members.clone(),
         public_key,
         private_key,
     };
     let mut clients = Vec::new();
-    for _ in 0..client_n {
-        let (public_key, private_key) = generate_keypair_random();
+    for (public_key, private_key) in keys.into_iter().skip(1) {
         let network_config = NetworkConfig {
             network_id: network_id.clone(),
             ports: vec![(format!("dms-{network_id}"), dispense_port())]
                 .into_iter()
                 .collect(),
-            members: Vec::new(),
+            members: members.clone(),
             public_key,
-            private_key: private_key.clone(),
+            private_key,
         };
         clients.push(network_config);
     }
@@ -1,8 +1,13 @@
 mod progress;
+mod replay;
+pub mod simulation;
 mod state;
 
 use serde::{Deserialize, Serialize};
 
+pub use replay::{EventRecord, EventTrace, ReplayError, Replayer, StateDifference};
+pub use state::ConsensusStep;
+
 /// An index of the validator, which is for a single height. (Mapping from the actual public key to the index may differ for different heights.)
 pub type ValidatorIndex = usize;
 /// An identifier of the block, which is uniquely mapped to a block. Like `ValidatorIndex`, it is for a single height. (Mapping from the actual block to the index may differ for different heights.)
@@ -11,13 +16,76 @@ pub type BlockIdentifier = usize;
 pub type Round = usize;
 /// A voting power.
 pub type VotingPower = u64;
+
+/// The maximum number of the rounds after which `LeaderSchedule::WeightedRoundRobin` repeats itself.
+pub const MAX_WEIGHTED_ROUND_ROBIN_CYCLE: usize = 1000;
 /// A UNIX timestamp measured in milliseconds.
 pub type Timestamp = i64;
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct ConsensusParams {
+    /// The timeout of the round 0, for both the propose and the precommit steps.
     pub timeout_ms: u64,
     pub repeat_round_for_first_leader: usize,
+    /// How the timeouts grow as the rounds go on.
+    #[serde(default)]
+    pub timeout_growth: TimeoutGrowth,
+    /// If enabled, the rounds are aligned to the wall clock, starting from `HeightInfo::timestamp`,
+    /// each lasting for its timeout (see `decide_round()`).
+    ///
+    /// A node then jumps to the round of the current time whenever it is behind,
+    /// so that one which has been offline rejoins the right round without replaying the messages.
+    #[serde(default)]
+    pub wall_clock_rounds: bool,
+    /// Which validator leads each round (see `decide_proposer()`).
+    #[serde(default)]
+    pub leader_schedule: LeaderSchedule,
+}
+
+/// The growth of the timeouts over the rounds,
+/// which lets the rounds eventually be long enough for the rarely-online nodes.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub enum TimeoutGrowth {
+    /// Every round has the same timeout.
+    #[default]
+    Constant,
+    /// The timeout increases by `increment_ms` every round, up to `max_timeout_ms`.
+    Linear {
+        increment_ms: u64,
+        max_timeout_ms: u64,
+    },
+    /// The timeout is multiplied by `factor_percent / 100` every round, up to `max_timeout_ms`.
+    Exponential {
+        factor_percent: u64,
+        max_timeout_ms: u64,
+    },
+}
+
+/// A rule deciding the leader (proposer) of each round.
+///
+/// A schedule that names no validator leading for at least a round falls back to the default.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub enum LeaderSchedule {
+    /// The validator `0` leads the first `repeat_round_for_first_leader` rounds,
+    /// then all the validators take turns in the index order, one round each.
+    #[default]
+    FirstLeaderRepeated,
+    /// The listed validators take turns, each leading for the given number of consecutive rounds,
+    /// and the list repeats itself.
+    ///
+    /// For example, `[(1, 3), (2, 2), (3, 1)]` gives `1, 1, 1, 2, 2, 3, 1, 1, 1, ...`.
+    Explicit(Vec<(ValidatorIndex, usize)>),
+    /// Weighted round robin, in which each validator leads in proportion to its voting power.
+    ///
+    /// The leaders are spread out rather than grouped, by the smooth weighted round robin:
+    /// every round, each validator gains its power as a priority,
+    /// and the one with the highest priority (the lowest index for a tie) leads
+    /// and loses the total power from its priority.
+    ///
+    /// The schedule repeats itself after every validator has led as many rounds as its power
+    /// (divided by their common divisor), or after `MAX_WEIGHTED_ROUND_ROBIN_CYCLE` rounds if sooner,
+    /// so that deciding the leader of any round takes a bounded time.
+    WeightedRoundRobin,
 }
 
 /// An event that (potentially) triggers a state transition of `StateMachine`.
@@ -26,7 +94,7 @@ pub struct ConsensusParams {
 /// the lower layer's responsibility to verifiy and refine the raw messages (containing such cryptography-related info) into this abstracted data.
 /// Also all the identifiers (for blocks and validators) become integer indices here, and
 /// the lower layer will keep the mapping from the actual data to the indices.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub enum ConsensusEvent {
     /// Signals to start the process
     Start,
@@ -62,7 +130,7 @@ pub enum ConsensusEvent {
 }
 
 /// A response that the consensus might emit for a given event, which must be properly handled by the lower layer.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub enum ConsensusResponse {
     BroadcastProposal {
         proposal: BlockIdentifier,
@@ -83,10 +151,94 @@ pub enum ConsensusResponse {
     },
     ViolationReport {
         violator: ValidatorIndex,
-        description: String,
+        violation: Violation,
     },
 }
 
+/// A misbehavior of a validator, detected from the conflicting events that it caused.
+///
+/// Only the first of the conflicting messages is taken into the consensus;
+/// the others are reported with this, which identifies both of them
+/// so that the lower layer can find the original messages to build an evidence.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub enum Violation {
+    /// Proposed two different blocks in the same round.
+    DoubleProposal {
+        round: Round,
+        first: BlockIdentifier,
+        second: BlockIdentifier,
+    },
+    /// Prevoted twice differently in the same round.
+    DoublePrevote {
+        round: Round,
+        first: Option<BlockIdentifier>,
+        second: Option<BlockIdentifier>,
+    },
+    /// Precommitted twice differently in the same round.
+    DoublePrecommit {
+        round: Round,
+        first: Option<BlockIdentifier>,
+        second: Option<BlockIdentifier>,
+    },
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::DoubleProposal {
+                round,
+                first,
+                second,
+            } => write!(f, "double proposal in round {round}: {first} and {second}"),
+            Violation::DoublePrevote {
+                round,
+                first,
+                second,
+            } => write!(
+                f,
+                "double prevote in round {round}: {first:?} and {second:?}"
+            ),
+            Violation::DoublePrecommit {
+                round,
+                first,
+                second,
+            } => write!(
+                f,
+                "double precommit in round {round}: {first:?} and {second:?}"
+            ),
+        }
+    }
+}
+
+/// A read-only snapshot of the state of `Vetomint`, which is for inspecting how the consensus goes.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ConsensusStateSnapshot {
+    pub round: Round,
+    pub step: ConsensusStep,
+    pub locked_value: Option<BlockIdentifier>,
+    pub locked_round: Option<Round>,
+    pub valid_value: Option<BlockIdentifier>,
+    pub valid_round: Option<Round>,
+    /// The total voting power, which the thresholds of the tallies are relative to.
+    pub total_voting_power: VotingPower,
+    /// The tallies of the rounds that have received any vote, in ascending order of the round.
+    pub tallies: Vec<VoteTally>,
+    /// The pending timeouts of the propose step, as `(round, timestamp)`.
+    pub propose_timeout_schedules: Vec<(Round, Timestamp)>,
+    /// The pending timeouts of the precommit step, as `(round, timestamp)`.
+    pub precommit_timeout_schedules: Vec<(Round, Timestamp)>,
+    /// The finalized block with the validators who precommitted it, if finalized.
+    pub finalized: Option<(BlockIdentifier, Vec<ValidatorIndex>)>,
+}
+
+/// The voting powers of the votes in a round, summed for each block (`None` for nil).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct VoteTally {
+    pub round: Round,
+    pub prevotes: Vec<(Option<BlockIdentifier>, VotingPower)>,
+    pub precommits: Vec<(Option<BlockIdentifier>, VotingPower)>,
+}
+
 /// An immutable set of information that is used to perform the consensus for a single height.
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct HeightInfo {
@@ -109,15 +261,27 @@ pub struct HeightInfo {
     pub initial_block_candidate: BlockIdentifier,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Vetomint {
     state: state::ConsensusState,
+    /// The records of `progress()` not taken yet, if recording (see `start_recording()`).
+    #[serde(skip)]
+    recorder: Option<Vec<EventRecord>>,
+}
+
+impl PartialEq for Vetomint {
+    fn eq(&self, other: &Self) -> bool {
+        self.state == other.state
+    }
 }
 
+impl Eq for Vetomint {}
+
 impl Vetomint {
     pub fn new(height_info: HeightInfo) -> Self {
         Self {
             state: state::ConsensusState::new(height_info),
+            recorder: None,
         }
     }
 
@@ -125,11 +289,16 @@ impl Vetomint {
         &self.state.height_info
     }
 
+    pub fn get_state_snapshot(&self) -> ConsensusStateSnapshot {
+        self.state.snapshot()
+    }
+
     pub fn progress(
         &mut self,
         event: ConsensusEvent,
         timestamp: Timestamp,
     ) -> Vec<ConsensusResponse> {
+        let recorded_event = self.recorder.as_ref().map(|_| event.clone());
         let mut responses = progress::progress(&mut self.state, event, timestamp);
         let mut final_responses = responses.clone();
         // feedback to myself
@@ -185,19 +354,132 @@ impl Vetomint {
             final_responses.extend(responses_.clone());
             responses = responses_;
         }
+        if let (Some(recorder), Some(event)) = (&mut self.recorder, recorded_event) {
+            recorder.push(EventRecord {
+                event,
+                timestamp,
+                responses: final_responses.clone(),
+            });
+        }
         final_responses
     }
 }
 
+/// Decides the leader of the given round, following `ConsensusParams::leader_schedule`.
 pub fn decide_proposer(round: usize, height_info: &HeightInfo) -> ValidatorIndex {
+    let validators = &height_info.validators;
+    match &height_info.consensus_params.leader_schedule {
+        LeaderSchedule::Explicit(schedule) => {
+            let schedule: Vec<_> = schedule
+                .iter()
+                .filter(|(index, repeat)| *index < validators.len() && *repeat > 0)
+                .collect();
+            let cycle: usize = schedule.iter().map(|(_, repeat)| repeat).sum();
+            if cycle > 0 {
+                let mut offset = round % cycle;
+                for (index, repeat) in schedule {
+                    if offset < *repeat {
+                        return *index;
+                    }
+                    offset -= repeat;
+                }
+            }
+        }
+        LeaderSchedule::WeightedRoundRobin => {
+            let total: i128 = validators.iter().map(|power| *power as i128).sum();
+            if total > 0 {
+                // The priorities are back to zero after every validator has led as many rounds
+                // as its power (divided by their common divisor), so the schedule repeats itself.
+                let divisor = validators
+                    .iter()
+                    .fold(0, |divisor, power| gcd(divisor, *power as i128));
+                let cycle = (total / divisor).min(MAX_WEIGHTED_ROUND_ROBIN_CYCLE as i128);
+                let round = (round as i128 % cycle) as usize;
+                let mut priorities = vec![0i128; validators.len()];
+                let mut leader = 0;
+                for _ in 0..=round {
+                    for (priority, power) in priorities.iter_mut().zip(validators) {
+                        *priority += *power as i128;
+                    }
+                    leader = (0..priorities.len())
+                        .rev()
+                        .max_by_key(|index| priorities[*index])
+                        .expect("total power is positive");
+                    priorities[leader] -= total;
+                }
+                return leader;
+            }
+        }
+        LeaderSchedule::FirstLeaderRepeated => (),
+    }
     if round < height_info.consensus_params.repeat_round_for_first_leader {
         0
     } else {
-        (round - height_info.consensus_params.repeat_round_for_first_leader + 1)
-            % height_info.validators.len()
+        (round - height_info.consensus_params.repeat_round_for_first_leader + 1) % validators.len()
+    }
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
     }
 }
 
-pub fn decide_timeout(params: &ConsensusParams, _round: usize) -> Timestamp {
-    params.timeout_ms as i64
+/// Decides the timeout of the given round, which is a duration (not an absolute time).
+pub fn decide_timeout(params: &ConsensusParams, round: usize) -> Timestamp {
+    let timeout = match params.timeout_growth {
+        TimeoutGrowth::Constant => params.timeout_ms,
+        TimeoutGrowth::Linear {
+            increment_ms,
+            max_timeout_ms,
+        } => increment_ms
+            .saturating_mul(round as u64)
+            .saturating_add(params.timeout_ms)
+            .min(max_timeout_ms.max(params.timeout_ms)),
+        TimeoutGrowth::Exponential {
+            factor_percent,
+            max_timeout_ms,
+        } => {
+            let max_timeout_ms = max_timeout_ms.max(params.timeout_ms);
+            let mut timeout = params.timeout_ms;
+            // It never grows with a factor not greater than 1.
+            if factor_percent > 100 {
+                for _ in 0..round {
+                    if timeout >= max_timeout_ms {
+                        break;
+                    }
+                    timeout = (timeout.saturating_mul(factor_percent) / 100).max(timeout + 1);
+                }
+            }
+            timeout.min(max_timeout_ms)
+        }
+    };
+    timeout.min(Timestamp::MAX as u64) as Timestamp
+}
+
+/// Decides the round of the given time, when the rounds are aligned to the wall clock
+/// (see `ConsensusParams::wall_clock_rounds`).
+///
+/// The round 0 starts at `round_zero_timestamp`, and each round lasts for its timeout.
+pub fn decide_round(
+    params: &ConsensusParams,
+    round_zero_timestamp: Timestamp,
+    timestamp: Timestamp,
+) -> Round {
+    let mut round = 0;
+    let mut round_start = round_zero_timestamp;
+    loop {
+        let timeout = decide_timeout(params, round).max(1);
+        if timestamp < round_start.saturating_add(timeout) {
+            return round;
+        }
+        // Once the timeout stops growing, the rest of the rounds can be skipped at once.
+        if timeout == decide_timeout(params, round + 1).max(1) {
+            return round + ((timestamp - round_start) / timeout) as Round;
+        }
+        round += 1;
+        round_start += timeout;
+    }
 }
@@ -1,5 +1,6 @@
 use super::*;
 use state::*;
+use std::collections::BTreeSet;
 
 pub(crate) fn progress(
     state: &mut ConsensusState,
@@ -10,7 +11,10 @@ pub(crate) fn progress(
         return vec![ConsensusResponse::FinalizeBlock { proposal, proof }];
     }
     match event {
-        ConsensusEvent::Start => start_round(state, 0, timestamp),
+        ConsensusEvent::Start => {
+            let round = wall_clock_round(state, timestamp).unwrap_or(0);
+            start_round(state, round, timestamp)
+        }
         ConsensusEvent::BlockProposalReceived {
             proposal,
             valid,
@@ -19,8 +23,25 @@ pub(crate) fn progress(
             round,
             favor,
         } => {
-            state.proposals.insert(
-                proposal,
+            let first = state
+                .first_proposals
+                .range((round, proposer, 0)..=(round, proposer, BlockIdentifier::MAX))
+                .next()
+                .map(|(_, _, first)| *first);
+            if let Some(first) = first.filter(|first| *first != proposal) {
+                return report_violation(
+                    state,
+                    proposer,
+                    Violation::DoubleProposal {
+                        round,
+                        first,
+                        second: proposal,
+                    },
+                );
+            }
+            state.first_proposals.insert((round, proposer, proposal));
+            on_proposal_received(
+                state,
                 Proposal {
                     proposal,
                     valid,
@@ -29,24 +50,12 @@ pub(crate) fn progress(
                     round,
                     favor,
                 },
-            );
-            let mut response = Vec::new();
-            if valid_round.is_some() {
-                response.extend(on_4f_non_nil_prevote_in_propose_step(
-                    state, round, proposal,
-                ));
-            } else {
-                response.extend(on_proposal(state, round, proposal));
-            }
-            response.extend(on_4f_non_nil_prevote_in_prevote_step(
-                state, round, proposal,
-            ));
-            response.extend(on_4f_non_nil_precommit(state, round, proposal));
-            response
+            )
         }
-        ConsensusEvent::SkipRound { round } => progress(
+        // A fake proposal, which must not be taken as a double proposal.
+        ConsensusEvent::SkipRound { round } => on_proposal_received(
             state,
-            ConsensusEvent::BlockProposalReceived {
+            Proposal {
                 proposal: 0,
                 valid: false,
                 valid_round: None,
@@ -54,7 +63,6 @@ pub(crate) fn progress(
                 round,
                 favor: false,
             },
-            timestamp,
         ),
         ConsensusEvent::BlockCandidateUpdated { proposal } => {
             state.block_candidate = proposal;
@@ -65,6 +73,18 @@ pub(crate) fn progress(
             signer,
             round,
         } => {
+            let first = first_vote_of(&state.prevotes, signer, round);
+            if let Some(first) = first.filter(|first| *first != proposal) {
+                return report_violation(
+                    state,
+                    signer,
+                    Violation::DoublePrevote {
+                        round,
+                        first,
+                        second: proposal,
+                    },
+                );
+            }
             state.prevotes.insert(Vote {
                 proposal,
                 signer,
@@ -89,13 +109,25 @@ pub(crate) fn progress(
             signer,
             round,
         } => {
+            let first = first_vote_of(&state.precommits, signer, round);
+            if let Some(first) = first.filter(|first| *first != proposal) {
+                return report_violation(
+                    state,
+                    signer,
+                    Violation::DoublePrecommit {
+                        round,
+                        first,
+                        second: proposal,
+                    },
+                );
+            }
             state.precommits.insert(Vote {
                 proposal,
                 signer,
                 round,
             });
             let mut response = Vec::new();
-            response.extend(on_5f_precommit(state, round));
+            response.extend(on_5f_precommit(state, round, timestamp));
             response.extend(on_4f_nil_precommit(state, round, timestamp));
             if let Some(proposal) = proposal {
                 response.extend(on_4f_non_nil_precommit(state, round, proposal));
@@ -104,6 +136,11 @@ pub(crate) fn progress(
         }
         ConsensusEvent::Timer => {
             let mut response = Vec::new();
+            if let Some(round) = wall_clock_round(state, timestamp) {
+                if round > state.round && state.step != ConsensusStep::Initial {
+                    response.extend(start_round(state, round, timestamp));
+                }
+            }
             for (round, timeout) in state.propose_timeout_schedules.clone() {
                 if timestamp >= timeout
                     && round == state.round
@@ -127,6 +164,65 @@ pub(crate) fn progress(
     }
 }
 
+fn on_proposal_received(state: &mut ConsensusState, proposal: Proposal) -> Vec<ConsensusResponse> {
+    let (round, valid_round, block) = (proposal.round, proposal.valid_round, proposal.proposal);
+    state.proposals.insert(block, proposal);
+    let mut response = Vec::new();
+    if valid_round.is_some() {
+        response.extend(on_4f_non_nil_prevote_in_propose_step(state, round, block));
+    } else {
+        response.extend(on_proposal(state, round, block));
+    }
+    response.extend(on_4f_non_nil_prevote_in_prevote_step(state, round, block));
+    response.extend(on_4f_non_nil_precommit(state, round, block));
+    response
+}
+
+/// Returns what the signer has voted for in the round, if it has voted.
+fn first_vote_of(
+    votes: &BTreeSet<Vote>,
+    signer: ValidatorIndex,
+    round: Round,
+) -> Option<Option<BlockIdentifier>> {
+    votes
+        .iter()
+        .find(|vote| vote.signer == signer && vote.round == round)
+        .map(|vote| vote.proposal)
+}
+
+/// Reports the violation unless it has been reported already.
+///
+/// The conflicting event is not taken into the state at all,
+/// so that each validator counts only once toward the thresholds.
+fn report_violation(
+    state: &mut ConsensusState,
+    violator: ValidatorIndex,
+    violation: Violation,
+) -> Vec<ConsensusResponse> {
+    if state.violations.insert((violator, violation.clone())) {
+        vec![ConsensusResponse::ViolationReport {
+            violator,
+            violation,
+        }]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Returns the round of the wall clock, if the rounds are aligned to it.
+fn wall_clock_round(state: &ConsensusState, timestamp: Timestamp) -> Option<Round> {
+    let height_info = &state.height_info;
+    if height_info.consensus_params.wall_clock_rounds {
+        Some(decide_round(
+            &height_info.consensus_params,
+            height_info.timestamp,
+            timestamp,
+        ))
+    } else {
+        None
+    }
+}
+
 fn start_round(
     state: &mut ConsensusState,
     round: usize,
@@ -151,7 +247,19 @@ fn start_round(
             round,
             timestamp + decide_timeout(&state.height_info.consensus_params, round),
         ));
-        Vec::new()
+        // The proposal may have arrived while this node was still in a previous round.
+        let received: Vec<_> = state
+            .proposals
+            .values()
+            .filter(|proposal| proposal.round == round)
+            .map(|proposal| proposal.proposal)
+            .collect();
+        let mut response = Vec::new();
+        for block in received {
+            response.extend(on_proposal(state, round, block));
+            response.extend(on_4f_non_nil_prevote_in_propose_step(state, round, block));
+        }
+        response
     }
 }
 
@@ -340,7 +448,11 @@ fn on_5f_prevote(
     }
 }
 
-fn on_5f_precommit(state: &mut ConsensusState, target_round: Round) -> Vec<ConsensusResponse> {
+fn on_5f_precommit(
+    state: &mut ConsensusState,
+    target_round: Round,
+    timestamp: Timestamp,
+) -> Vec<ConsensusResponse> {
     if target_round != state.round {
         return Vec::new();
     }
@@ -348,9 +460,10 @@ fn on_5f_precommit(state: &mut ConsensusState, target_round: Round) -> Vec<Conse
         && state.get_total_precommits(target_round) * 6 > state.get_total_voting_power() * 5
     {
         state.for_the_first_time_2.insert(target_round);
-        state
-            .precommit_timeout_schedules
-            .insert((target_round, 1000));
+        state.precommit_timeout_schedules.insert((
+            target_round,
+            timestamp + decide_timeout(&state.height_info.consensus_params, target_round),
+        ));
     }
     Vec::new()
 }
@@ -372,8 +485,8 @@ fn on_4f_nil_precommit(
 
 fn on_4f_non_nil_precommit(
     state: &mut ConsensusState,
-    target_proposal: BlockIdentifier,
     target_round: Round,
+    target_proposal: BlockIdentifier,
 ) -> Vec<ConsensusResponse> {
     let valid_proposer = decide_proposer(target_round, &state.height_info);
     let proposal = if let Some(proposal) = state.proposals.get(&target_proposal) {
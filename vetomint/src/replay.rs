@@ -0,0 +1,159 @@
+//! Recording the events fed to `Vetomint` and replaying them offline.
+//!
+//! A trace starts from a full `Vetomint` state (not necessarily a fresh one),
+//! so the recording may begin at any point of a height.
+//! Replaying it rebuilds the exact state of each step, checking that the same responses come out.
+use super::*;
+use thiserror::Error;
+
+/// A single call of `Vetomint::progress()`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct EventRecord {
+    pub event: ConsensusEvent,
+    pub timestamp: Timestamp,
+    /// All the responses of the call, including those from the feedback to itself.
+    pub responses: Vec<ConsensusResponse>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct EventTrace {
+    /// The state from which the recording began.
+    pub initial_state: Vetomint,
+    pub records: Vec<EventRecord>,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// When the replayed event yields responses other than the recorded ones,
+    /// which means that the trace is broken or the state machine has changed.
+    #[error(
+        "replay diverged at record {position}: recorded {recorded:?} but replayed {replayed:?}"
+    )]
+    Diverged {
+        position: usize,
+        recorded: Vec<ConsensusResponse>,
+        replayed: Vec<ConsensusResponse>,
+    },
+}
+
+/// A field of the state which differs between two `Vetomint`s.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct StateDifference {
+    pub field: String,
+    /// The value in this side, serialized in JSON.
+    pub this: String,
+    /// The value in the other side, serialized in JSON.
+    pub other: String,
+}
+
+/// Steps through an `EventTrace`, one record at a time.
+#[derive(Debug, Clone)]
+pub struct Replayer {
+    trace: EventTrace,
+    vetomint: Vetomint,
+    position: usize,
+}
+
+impl Replayer {
+    pub fn new(trace: EventTrace) -> Self {
+        let mut vetomint = trace.initial_state.clone();
+        vetomint.stop_recording();
+        Self {
+            trace,
+            vetomint,
+            position: 0,
+        }
+    }
+
+    /// Returns the number of the records replayed so far.
+    pub fn get_position(&self) -> usize {
+        self.position
+    }
+
+    pub fn get_trace(&self) -> &EventTrace {
+        &self.trace
+    }
+
+    /// Returns the state after the records replayed so far.
+    pub fn get_vetomint(&self) -> &Vetomint {
+        &self.vetomint
+    }
+
+    /// Replays the next record, returning it, or `None` if all the records have been replayed.
+    pub fn step(&mut self) -> Result<Option<&EventRecord>, ReplayError> {
+        let Some(record) = self.trace.records.get(self.position) else {
+            return Ok(None);
+        };
+        let replayed = self
+            .vetomint
+            .progress(record.event.clone(), record.timestamp);
+        if replayed != record.responses {
+            return Err(ReplayError::Diverged {
+                position: self.position,
+                recorded: record.responses.clone(),
+                replayed,
+            });
+        }
+        self.position += 1;
+        Ok(Some(record))
+    }
+
+    /// Replays all the remaining records.
+    pub fn run_to_end(&mut self) -> Result<(), ReplayError> {
+        while self.step()?.is_some() {}
+        Ok(())
+    }
+
+    /// Compares the replayed state with the given one (e.g., a live state), field by field.
+    pub fn diff(&self, other: &Vetomint) -> Vec<StateDifference> {
+        self.vetomint.diff(other)
+    }
+}
+
+impl Vetomint {
+    /// Starts recording every call of `progress()`, unless it is already being recorded.
+    pub fn start_recording(&mut self) {
+        self.recorder.get_or_insert_with(Vec::new);
+    }
+
+    /// Stops recording, discarding the records that have not been taken.
+    pub fn stop_recording(&mut self) {
+        self.recorder = None;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Takes the records made since the last call, while keeping recording.
+    pub fn take_records(&mut self) -> Vec<EventRecord> {
+        self.recorder
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Compares the state with the given one, field by field.
+    pub fn diff(&self, other: &Vetomint) -> Vec<StateDifference> {
+        let to_fields = |vetomint: &Vetomint| match serde_json::to_value(&vetomint.state) {
+            Ok(serde_json::Value::Object(fields)) => fields,
+            _ => panic!("the state must be serialized into a JSON object"),
+        };
+        let (this, other) = (to_fields(self), to_fields(other));
+        // Sorted by the name, regardless of how `serde_json` orders the fields.
+        let mut fields: Vec<_> = this.iter().collect();
+        fields.sort_by_key(|(field, _)| *field);
+        fields
+            .into_iter()
+            .filter(|(field, value)| other.get(*field) != Some(value))
+            .map(|(field, value)| StateDifference {
+                field: field.clone(),
+                this: value.to_string(),
+                other: other
+                    .get(field)
+                    .map(|value| value.to_string())
+                    .unwrap_or_default(),
+            })
+            .collect()
+    }
+}
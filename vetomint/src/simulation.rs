@@ -0,0 +1,394 @@
+//! A deterministic simulation of multiple `Vetomint` instances over an adversarial network.
+//!
+//! Every source of nondeterminism (message delays, drops, duplications and Byzantine choices)
+//! is drawn from a PRNG seeded by `SimulationConfig::seed`,
+//! so a failing run is reproduced exactly by running the same config again.
+//!
+//! Time is virtual; the simulation jumps to the next message delivery or timer tick.
+//! All blocks are valid, and each validator proposes the block identified by its own index.
+use super::*;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// How a validator behaves in a simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Behavior {
+    Honest,
+    /// Never sends any message, as if it has crashed.
+    Silent,
+    /// Sends conflicting proposals and votes to different halves of the validators.
+    Equivocating,
+    /// Follows the protocol but is never in favor of any proposal.
+    Vetoing,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SimulationConfig {
+    pub seed: u64,
+    pub validators: Vec<VotingPower>,
+    /// The behaviors of the validators, indexed by `ValidatorIndex`.
+    pub behaviors: Vec<Behavior>,
+    pub consensus_params: ConsensusParams,
+    /// The interval of `ConsensusEvent::Timer` for every node.
+    pub timer_interval_ms: Timestamp,
+    /// The maximum delay of a message, by which the messages get reordered.
+    pub max_delay_ms: Timestamp,
+    /// The probability (in percent) that a message is dropped before `global_stabilization_time`.
+    pub drop_percent: u64,
+    /// The probability (in percent) that a message is delivered twice.
+    pub duplicate_percent: u64,
+    /// The time after which the network is synchronous;
+    /// no message sent from then on is dropped.
+    pub global_stabilization_time: Timestamp,
+    /// If set, every honest node must finalize a block by this time.
+    ///
+    /// It is meaningful only for a synchronous network without equivocation;
+    /// messages are never retransmitted, so a dropped one is lost for good.
+    pub liveness_deadline: Option<Timestamp>,
+    /// The time at which the simulation stops even if not all the honest nodes have finalized.
+    pub max_time: Timestamp,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SimulationError {
+    /// When two honest nodes finalize different blocks.
+    #[error("safety violation (seed {seed}, time {time}): node {first_node} finalized {first_block} but node {second_node} finalized {second_block}")]
+    SafetyViolation {
+        seed: u64,
+        time: Timestamp,
+        first_node: ValidatorIndex,
+        first_block: BlockIdentifier,
+        second_node: ValidatorIndex,
+        second_block: BlockIdentifier,
+    },
+    /// When some honest nodes have not finalized by `SimulationConfig::liveness_deadline`.
+    #[error(
+        "liveness violation (seed {seed}): nodes {pending:?} have not finalized by {deadline}"
+    )]
+    LivenessViolation {
+        seed: u64,
+        deadline: Timestamp,
+        pending: Vec<ValidatorIndex>,
+    },
+}
+
+/// An event fed to a node and the responses of it, in the order of the simulation.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TraceEntry {
+    pub time: Timestamp,
+    pub node: ValidatorIndex,
+    pub event: ConsensusEvent,
+    pub responses: Vec<ConsensusResponse>,
+}
+
+/// The result of a simulation that has kept all the invariants.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulationResult {
+    /// The block and the time of the finalization of each node, if finalized.
+    pub finalized: Vec<Option<(BlockIdentifier, Timestamp)>>,
+    /// The time at which the simulation ended.
+    pub end_time: Timestamp,
+}
+
+/// A SplitMix64 generator, which is enough for a reproducible simulation.
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `[0, bound]`.
+    fn up_to(&mut self, bound: u64) -> u64 {
+        self.next() % (bound + 1)
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.next() % 100 < percent
+    }
+}
+
+pub struct Simulation {
+    config: SimulationConfig,
+    nodes: Vec<Vetomint>,
+    rng: Rng,
+    time: Timestamp,
+    /// The pending deliveries, ordered by the time and then by the order of scheduling.
+    queue: BTreeMap<(Timestamp, u64), (ValidatorIndex, ConsensusEvent)>,
+    sequence: u64,
+    next_timer: Timestamp,
+    finalized: Vec<Option<(BlockIdentifier, Timestamp)>>,
+    trace: Vec<TraceEntry>,
+}
+
+impl Simulation {
+    pub fn new(config: SimulationConfig) -> Self {
+        assert_eq!(config.validators.len(), config.behaviors.len());
+        let nodes = (0..config.validators.len())
+            .map(|index| {
+                Vetomint::new(HeightInfo {
+                    validators: config.validators.clone(),
+                    this_node_index: Some(index),
+                    timestamp: 0,
+                    consensus_params: config.consensus_params.clone(),
+                    initial_block_candidate: index,
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut simulation = Self {
+            rng: Rng(config.seed),
+            finalized: vec![None; nodes.len()],
+            nodes,
+            time: 0,
+            queue: BTreeMap::new(),
+            sequence: 0,
+            next_timer: config.timer_interval_ms,
+            trace: Vec::new(),
+            config,
+        };
+        for node in 0..simulation.nodes.len() {
+            simulation.schedule(0, node, ConsensusEvent::Start);
+        }
+        simulation
+    }
+
+    pub fn get_time(&self) -> Timestamp {
+        self.time
+    }
+
+    pub fn get_node(&self, index: ValidatorIndex) -> &Vetomint {
+        &self.nodes[index]
+    }
+
+    /// Returns the events fed so far with the responses to them.
+    pub fn get_trace(&self) -> &[TraceEntry] {
+        &self.trace
+    }
+
+    /// Dumps the trace in JSON, to be examined or checked against the specification.
+    pub fn dump_trace(&self) -> String {
+        serde_json::to_string_pretty(&self.trace).expect("trace is always serializable")
+    }
+
+    /// Runs until every honest node finalizes or `SimulationConfig::max_time` is reached.
+    pub fn run(&mut self) -> Result<SimulationResult, SimulationError> {
+        while self.time <= self.config.max_time && !self.all_honest_finalized() {
+            self.step()?;
+        }
+        Ok(SimulationResult {
+            finalized: self.finalized.clone(),
+            end_time: self.time,
+        })
+    }
+
+    /// Performs the next delivery (or the timer ticks if they come first),
+    /// checking the invariants after it.
+    pub fn step(&mut self) -> Result<(), SimulationError> {
+        let next_delivery = self.queue.keys().next().map(|(time, _)| *time);
+        match next_delivery {
+            Some(time) if time < self.next_timer => {
+                let (_, (node, event)) = self.queue.pop_first().expect("checked to exist");
+                self.time = time;
+                self.feed(node, event);
+            }
+            _ => {
+                self.time = self.next_timer;
+                self.next_timer += self.config.timer_interval_ms;
+                for node in 0..self.nodes.len() {
+                    self.feed(node, ConsensusEvent::Timer);
+                }
+            }
+        }
+        self.check_invariants()
+    }
+
+    fn all_honest_finalized(&self) -> bool {
+        self.honest_nodes()
+            .all(|node| self.finalized[node].is_some())
+    }
+
+    fn honest_nodes(&self) -> impl Iterator<Item = ValidatorIndex> + '_ {
+        (0..self.nodes.len()).filter(|&node| self.config.behaviors[node] != Behavior::Equivocating)
+    }
+
+    fn check_invariants(&self) -> Result<(), SimulationError> {
+        let mut honest = self
+            .honest_nodes()
+            .filter_map(|node| self.finalized[node].map(|(block, _)| (node, block)));
+        if let Some((first_node, first_block)) = honest.next() {
+            if let Some((second_node, second_block)) =
+                honest.find(|(_, block)| *block != first_block)
+            {
+                return Err(SimulationError::SafetyViolation {
+                    seed: self.config.seed,
+                    time: self.time,
+                    first_node,
+                    first_block,
+                    second_node,
+                    second_block,
+                });
+            }
+        }
+        if let Some(deadline) = self.config.liveness_deadline {
+            if self.time > deadline {
+                let pending = self
+                    .honest_nodes()
+                    .filter(|&node| self.finalized[node].is_none())
+                    .collect::<Vec<_>>();
+                if !pending.is_empty() {
+                    return Err(SimulationError::LivenessViolation {
+                        seed: self.config.seed,
+                        deadline,
+                        pending,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn schedule(&mut self, time: Timestamp, node: ValidatorIndex, event: ConsensusEvent) {
+        self.queue.insert((time, self.sequence), (node, event));
+        self.sequence += 1;
+    }
+
+    fn feed(&mut self, node: ValidatorIndex, mut event: ConsensusEvent) {
+        if self.finalized[node].is_some() {
+            return;
+        }
+        if let ConsensusEvent::BlockProposalReceived { favor, .. } = &mut event {
+            *favor = self.config.behaviors[node] != Behavior::Vetoing;
+        }
+        let responses = self.nodes[node].progress(event.clone(), self.time);
+        self.trace.push(TraceEntry {
+            time: self.time,
+            node,
+            event,
+            responses: responses.clone(),
+        });
+        for response in responses {
+            if let ConsensusResponse::FinalizeBlock { proposal, .. } = response {
+                self.finalized[node] = Some((proposal, self.time));
+                continue;
+            }
+            if let Some(message) = to_event(&response, node) {
+                self.broadcast(node, message);
+            }
+        }
+    }
+
+    fn broadcast(&mut self, sender: ValidatorIndex, message: ConsensusEvent) {
+        let behavior = self.config.behaviors[sender];
+        if behavior == Behavior::Silent {
+            return;
+        }
+        let conflicting = conflicting_event(&message, self.nodes.len());
+        for receiver in (0..self.nodes.len()).filter(|&receiver| receiver != sender) {
+            let message = if behavior == Behavior::Equivocating && self.rng.chance(50) {
+                conflicting.clone()
+            } else {
+                message.clone()
+            };
+            self.send(receiver, message);
+        }
+    }
+
+    fn send(&mut self, receiver: ValidatorIndex, message: ConsensusEvent) {
+        if self.time < self.config.global_stabilization_time
+            && self.rng.chance(self.config.drop_percent)
+        {
+            return;
+        }
+        let copies = if self.rng.chance(self.config.duplicate_percent) {
+            2
+        } else {
+            1
+        };
+        for _ in 0..copies {
+            let delay = self.rng.up_to(self.config.max_delay_ms as u64) as Timestamp;
+            self.schedule(self.time + delay, receiver, message.clone());
+        }
+    }
+}
+
+/// Converts a response to broadcast into the event that the other nodes receive.
+fn to_event(response: &ConsensusResponse, sender: ValidatorIndex) -> Option<ConsensusEvent> {
+    match *response {
+        ConsensusResponse::BroadcastProposal {
+            proposal,
+            valid_round,
+            round,
+        } => Some(ConsensusEvent::BlockProposalReceived {
+            proposal,
+            valid: true,
+            valid_round,
+            proposer: sender,
+            round,
+            favor: true,
+        }),
+        ConsensusResponse::BroadcastPrevote { proposal, round } => Some(ConsensusEvent::Prevote {
+            proposal,
+            signer: sender,
+            round,
+        }),
+        ConsensusResponse::BroadcastPrecommit { proposal, round } => {
+            Some(ConsensusEvent::Precommit {
+                proposal,
+                signer: sender,
+                round,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Makes an event that conflicts with the given one, for an equivocating sender.
+///
+/// A conflicting block is identified beyond the indices of the validators.
+fn conflicting_event(event: &ConsensusEvent, validators: usize) -> ConsensusEvent {
+    let conflict = |proposal: Option<BlockIdentifier>| match proposal {
+        Some(_) => None,
+        None => Some(validators),
+    };
+    match event.clone() {
+        ConsensusEvent::BlockProposalReceived {
+            proposal,
+            valid,
+            valid_round,
+            proposer,
+            round,
+            favor,
+        } => ConsensusEvent::BlockProposalReceived {
+            proposal: proposal + validators,
+            valid,
+            valid_round,
+            proposer,
+            round,
+            favor,
+        },
+        ConsensusEvent::Prevote {
+            proposal,
+            signer,
+            round,
+        } => ConsensusEvent::Prevote {
+            proposal: conflict(proposal),
+            signer,
+            round,
+        },
+        ConsensusEvent::Precommit {
+            proposal,
+            signer,
+            round,
+        } => ConsensusEvent::Precommit {
+            proposal: conflict(proposal),
+            signer,
+            round,
+        },
+        event => event,
+    }
+}
@@ -1,8 +1,10 @@
 use super::*;
 use std::collections::{BTreeMap, BTreeSet};
 
+/// A step of a round.
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
-pub(crate) enum ConsensusStep {
+pub enum ConsensusStep {
+    /// Before the consensus has started.
     Initial,
     Propose,
     Prevote,
@@ -37,6 +39,8 @@ pub(crate) struct ConsensusState {
     pub valid_round: Option<Round>,
     pub block_candidate: BlockIdentifier,
     pub proposals: BTreeMap<BlockIdentifier, Proposal>,
+    /// The first block that each proposer has proposed in each round.
+    pub first_proposals: BTreeSet<(Round, ValidatorIndex, BlockIdentifier)>,
     pub prevotes: BTreeSet<Vote>,
     pub precommits: BTreeSet<Vote>,
     pub propose_timeout_schedules: BTreeSet<(Round, Timestamp)>,
@@ -44,11 +48,14 @@ pub(crate) struct ConsensusState {
     pub for_the_first_time_1: BTreeSet<Round>,
     pub for_the_first_time_2: BTreeSet<Round>,
     pub finalized: Option<(BlockIdentifier, Vec<ValidatorIndex>)>,
+    /// The violations reported so far, not to report the same one twice.
+    pub violations: BTreeSet<(ValidatorIndex, Violation)>,
 }
 
 impl ConsensusState {
     pub(crate) fn new(height_info: HeightInfo) -> Self {
         ConsensusState {
+            block_candidate: height_info.initial_block_candidate,
             height_info,
             round: 0,
             step: ConsensusStep::Initial,
@@ -56,8 +63,8 @@ impl ConsensusState {
             locked_round: None,
             valid_value: None,
             valid_round: None,
-            block_candidate: BlockIdentifier::default(),
             proposals: Default::default(),
+            first_proposals: Default::default(),
             prevotes: Default::default(),
             precommits: Default::default(),
             propose_timeout_schedules: Default::default(),
@@ -65,6 +72,51 @@ impl ConsensusState {
             for_the_first_time_1: Default::default(),
             for_the_first_time_2: Default::default(),
             finalized: None,
+            violations: Default::default(),
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> ConsensusStateSnapshot {
+        let mut tallies = BTreeMap::<Round, VoteTally>::new();
+        let tally = |votes: &mut Vec<(Option<BlockIdentifier>, VotingPower)>, vote: &Vote| {
+            let power = self.height_info.validators[vote.signer];
+            match votes
+                .iter_mut()
+                .find(|(proposal, _)| *proposal == vote.proposal)
+            {
+                Some((_, sum)) => *sum += power,
+                None => votes.push((vote.proposal, power)),
+            }
+        };
+        let empty_tally = |round| VoteTally {
+            round,
+            prevotes: Vec::new(),
+            precommits: Vec::new(),
+        };
+        for vote in &self.prevotes {
+            let entry = tallies
+                .entry(vote.round)
+                .or_insert_with(|| empty_tally(vote.round));
+            tally(&mut entry.prevotes, vote);
+        }
+        for vote in &self.precommits {
+            let entry = tallies
+                .entry(vote.round)
+                .or_insert_with(|| empty_tally(vote.round));
+            tally(&mut entry.precommits, vote);
+        }
+        ConsensusStateSnapshot {
+            round: self.round,
+            step: self.step.clone(),
+            locked_value: self.locked_value,
+            locked_round: self.locked_round,
+            valid_value: self.valid_value,
+            valid_round: self.valid_round,
+            total_voting_power: self.get_total_voting_power(),
+            tallies: tallies.into_values().collect(),
+            propose_timeout_schedules: self.propose_timeout_schedules.iter().copied().collect(),
+            precommit_timeout_schedules: self.precommit_timeout_schedules.iter().copied().collect(),
+            finalized: self.finalized.clone(),
         }
     }
 
@@ -0,0 +1,136 @@
+use vetomint::simulation::*;
+use vetomint::*;
+
+fn config(seed: u64, behaviors: Vec<Behavior>) -> SimulationConfig {
+    SimulationConfig {
+        seed,
+        validators: vec![1; behaviors.len()],
+        behaviors,
+        consensus_params: ConsensusParams {
+            timeout_ms: 300,
+            repeat_round_for_first_leader: 1,
+            timeout_growth: TimeoutGrowth::Linear {
+                increment_ms: 100,
+                max_timeout_ms: 1000,
+            },
+            wall_clock_rounds: false,
+            leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+        },
+        timer_interval_ms: 10,
+        max_delay_ms: 50,
+        drop_percent: 0,
+        duplicate_percent: 0,
+        global_stabilization_time: 0,
+        liveness_deadline: Some(10_000),
+        max_time: 10_000,
+    }
+}
+
+fn run(config: SimulationConfig) -> SimulationResult {
+    let mut simulation = Simulation::new(config);
+    match simulation.run() {
+        Ok(result) => result,
+        Err(e) => panic!("{e}\n{}", simulation.dump_trace()),
+    }
+}
+
+#[test]
+fn synchronous_honest() {
+    for seed in 0..20 {
+        let result = run(config(seed, vec![Behavior::Honest; 4]));
+        // The first leader's block is finalized in the first round.
+        assert!(result
+            .finalized
+            .iter()
+            .all(|x| matches!(x, Some((0, time)) if *time < 300)));
+    }
+}
+
+#[test]
+fn lossy_network_before_stabilization() {
+    for seed in 0..20 {
+        let mut config = config(seed, vec![Behavior::Honest; 4]);
+        config.drop_percent = 30;
+        config.duplicate_percent = 20;
+        config.max_delay_ms = 200;
+        config.global_stabilization_time = 2_000;
+        // A dropped message is never retransmitted, so only the safety is checked.
+        config.liveness_deadline = None;
+        config.max_time = 20_000;
+        run(config);
+    }
+}
+
+#[test]
+fn reordering_and_duplication() {
+    for seed in 0..20 {
+        let mut config = config(seed, vec![Behavior::Honest; 4]);
+        config.duplicate_percent = 30;
+        config.max_delay_ms = 250;
+        run(config);
+    }
+}
+
+#[test]
+fn byzantine_minority() {
+    for behavior in [Behavior::Silent, Behavior::Vetoing] {
+        for seed in 0..20 {
+            // The protocol tolerates `f` byzantine validators out of `6f + 1`.
+            let mut behaviors = vec![Behavior::Honest; 7];
+            // Also as the first leader.
+            behaviors[seed as usize % 7] = behavior;
+            let mut config = config(seed, behaviors);
+            config.duplicate_percent = 10;
+            run(config);
+        }
+    }
+}
+
+#[test]
+fn equivocating_minority() {
+    for seed in 0..50 {
+        let mut behaviors = vec![Behavior::Honest; 4];
+        behaviors[seed as usize % 4] = Behavior::Equivocating;
+        let mut config = config(seed, behaviors);
+        config.duplicate_percent = 10;
+        // Honest nodes may take different votes of the equivocator,
+        // and without any vote gossip they are not guaranteed to agree on a lock again.
+        config.liveness_deadline = None;
+        run(config);
+    }
+}
+
+#[test]
+fn reproducible() {
+    let mut behaviors = vec![Behavior::Honest; 4];
+    behaviors[0] = Behavior::Equivocating;
+    let mut config = config(42, behaviors);
+    config.drop_percent = 20;
+    config.duplicate_percent = 20;
+    config.global_stabilization_time = 1_000;
+    config.liveness_deadline = None;
+
+    let mut first = Simulation::new(config.clone());
+    let first_result = first.run().unwrap();
+    let mut second = Simulation::new(config.clone());
+    assert_eq!(second.run().unwrap(), first_result);
+    assert_eq!(first.get_trace(), second.get_trace());
+    assert_eq!(first.dump_trace(), second.dump_trace());
+
+    config.seed = 43;
+    let mut third = Simulation::new(config);
+    third.run().unwrap();
+    assert_ne!(first.get_trace(), third.get_trace());
+}
+
+#[test]
+fn liveness_violation_with_too_many_silent() {
+    let mut behaviors = vec![Behavior::Honest; 4];
+    behaviors[1] = Behavior::Silent;
+    behaviors[2] = Behavior::Silent;
+    let mut simulation = Simulation::new(config(0, behaviors));
+    assert!(matches!(
+        simulation.run(),
+        Err(SimulationError::LivenessViolation { seed: 0, .. })
+    ));
+}
@@ -10,6 +10,9 @@ fn normal_1() {
         consensus_params: ConsensusParams {
             timeout_ms: 100,
             repeat_round_for_first_leader: 1,
+            timeout_growth: TimeoutGrowth::Constant,
+            wall_clock_rounds: false,
+            leader_schedule: LeaderSchedule::FirstLeaderRepeated,
         },
         initial_block_candidate: 0,
     };
@@ -125,3 +128,626 @@ fn normal_1() {
         );
     }
 }
+
+/// A scenario where a validator proposes and votes twice differently in the same round.
+#[test]
+fn double_votes_1() {
+    let height_info = HeightInfo {
+        validators: vec![1, 1, 1, 1],
+        this_node_index: Some(3),
+        timestamp: 0,
+        consensus_params: ConsensusParams {
+            timeout_ms: 100,
+            repeat_round_for_first_leader: 1,
+            timeout_growth: TimeoutGrowth::Constant,
+            wall_clock_rounds: false,
+            leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+        },
+        initial_block_candidate: 0,
+    };
+    let mut node = Vetomint::new(height_info);
+    assert_eq!(node.progress(ConsensusEvent::Start, 0), vec![]);
+    let proposal = |proposal| ConsensusEvent::BlockProposalReceived {
+        proposal,
+        valid: true,
+        valid_round: None,
+        proposer: 0,
+        round: 0,
+        favor: true,
+    };
+    let prevote = |proposal, signer| ConsensusEvent::Prevote {
+        proposal,
+        signer,
+        round: 0,
+    };
+    let precommit = |proposal, signer| ConsensusEvent::Precommit {
+        proposal,
+        signer,
+        round: 0,
+    };
+
+    assert_eq!(
+        node.progress(proposal(0), 1),
+        vec![ConsensusResponse::BroadcastPrevote {
+            proposal: Some(0),
+            round: 0,
+        }]
+    );
+    let double_proposal = vec![ConsensusResponse::ViolationReport {
+        violator: 0,
+        violation: Violation::DoubleProposal {
+            round: 0,
+            first: 0,
+            second: 1,
+        },
+    }];
+    assert_eq!(node.progress(proposal(1), 1), double_proposal);
+    // The same violation is reported only once.
+    assert_eq!(node.progress(proposal(1), 1), vec![]);
+
+    assert_eq!(node.progress(prevote(Some(0), 1), 2), vec![]);
+    assert_eq!(
+        node.progress(prevote(None, 1), 2),
+        vec![ConsensusResponse::ViolationReport {
+            violator: 1,
+            violation: Violation::DoublePrevote {
+                round: 0,
+                first: Some(0),
+                second: None,
+            },
+        }]
+    );
+    // Receiving the same vote again is not a violation.
+    assert_eq!(node.progress(prevote(Some(0), 1), 2), vec![]);
+    assert_eq!(
+        node.progress(prevote(Some(0), 2), 2),
+        vec![ConsensusResponse::BroadcastPrecommit {
+            proposal: Some(0),
+            round: 0,
+        }]
+    );
+
+    assert_eq!(node.progress(precommit(Some(0), 1), 3), vec![]);
+    assert_eq!(
+        node.progress(precommit(Some(1), 1), 3),
+        vec![ConsensusResponse::ViolationReport {
+            violator: 1,
+            violation: Violation::DoublePrecommit {
+                round: 0,
+                first: Some(0),
+                second: Some(1),
+            },
+        }]
+    );
+    // The violator is counted only once in the proof.
+    assert_eq!(
+        node.progress(precommit(Some(0), 2), 3),
+        vec![ConsensusResponse::FinalizeBlock {
+            proposal: 0,
+            proof: vec![1, 2, 3],
+        }]
+    );
+}
+
+/// Checks the snapshot of a round waiting for more prevotes.
+#[test]
+fn snapshot_1() {
+    let height_info = HeightInfo {
+        validators: vec![1, 1, 1, 2],
+        this_node_index: Some(3),
+        timestamp: 0,
+        consensus_params: ConsensusParams {
+            timeout_ms: 100,
+            repeat_round_for_first_leader: 1,
+            timeout_growth: TimeoutGrowth::Constant,
+            wall_clock_rounds: false,
+            leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+        },
+        initial_block_candidate: 0,
+    };
+    let mut node = Vetomint::new(height_info);
+    node.progress(ConsensusEvent::Start, 0);
+    node.progress(
+        ConsensusEvent::BlockProposalReceived {
+            proposal: 0,
+            valid: true,
+            valid_round: None,
+            proposer: 0,
+            round: 0,
+            favor: true,
+        },
+        1,
+    );
+    node.progress(
+        ConsensusEvent::Prevote {
+            proposal: None,
+            signer: 1,
+            round: 0,
+        },
+        2,
+    );
+    assert_eq!(
+        node.get_state_snapshot(),
+        ConsensusStateSnapshot {
+            round: 0,
+            step: ConsensusStep::Prevote,
+            locked_value: None,
+            locked_round: None,
+            valid_value: None,
+            valid_round: None,
+            total_voting_power: 5,
+            tallies: vec![VoteTally {
+                round: 0,
+                prevotes: vec![(None, 1), (Some(0), 2)],
+                precommits: vec![],
+            }],
+            propose_timeout_schedules: vec![(0, 100)],
+            precommit_timeout_schedules: vec![],
+            finalized: None,
+        }
+    );
+}
+
+#[test]
+fn timeout_growth_1() {
+    let mut params = ConsensusParams {
+        timeout_ms: 100,
+        repeat_round_for_first_leader: 1,
+        timeout_growth: TimeoutGrowth::Constant,
+        wall_clock_rounds: true,
+        leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+    };
+    assert_eq!(decide_timeout(&params, 0), 100);
+    assert_eq!(decide_timeout(&params, 1000), 100);
+    assert_eq!(decide_round(&params, 1000, 1000), 0);
+    assert_eq!(decide_round(&params, 1000, 1099), 0);
+    assert_eq!(decide_round(&params, 1000, 1100), 1);
+    assert_eq!(decide_round(&params, 1000, 1000 + 100 * 12345 + 1), 12345);
+
+    params.timeout_growth = TimeoutGrowth::Linear {
+        increment_ms: 50,
+        max_timeout_ms: 200,
+    };
+    assert_eq!(
+        (0..5)
+            .map(|i| decide_timeout(&params, i))
+            .collect::<Vec<_>>(),
+        vec![100, 150, 200, 200, 200]
+    );
+    // Rounds start at 0, 100, 250, 450, 650, ...
+    assert_eq!(decide_round(&params, 0, 249), 1);
+    assert_eq!(decide_round(&params, 0, 250), 2);
+    assert_eq!(decide_round(&params, 0, 650), 4);
+    assert_eq!(decide_round(&params, 0, 849), 4);
+
+    params.timeout_growth = TimeoutGrowth::Exponential {
+        factor_percent: 200,
+        max_timeout_ms: 1000,
+    };
+    assert_eq!(
+        (0..6)
+            .map(|i| decide_timeout(&params, i))
+            .collect::<Vec<_>>(),
+        vec![100, 200, 400, 800, 1000, 1000]
+    );
+    assert_eq!(decide_timeout(&params, usize::MAX), 1000);
+}
+
+/// A node that starts late joins the round of the wall clock.
+#[test]
+fn wall_clock_rounds_1() {
+    let height_info = HeightInfo {
+        validators: vec![1, 1, 1, 1],
+        this_node_index: Some(2),
+        timestamp: 1000,
+        consensus_params: ConsensusParams {
+            timeout_ms: 100,
+            repeat_round_for_first_leader: 1,
+            timeout_growth: TimeoutGrowth::Constant,
+            wall_clock_rounds: true,
+            leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+        },
+        initial_block_candidate: 0,
+    };
+    let mut node = Vetomint::new(height_info);
+    assert_eq!(
+        node.progress(ConsensusEvent::Start, 1250),
+        vec![
+            ConsensusResponse::BroadcastProposal {
+                proposal: 0,
+                valid_round: None,
+                round: 2,
+            },
+            ConsensusResponse::BroadcastPrevote {
+                proposal: Some(0),
+                round: 2,
+            }
+        ]
+    );
+    assert_eq!(node.progress(ConsensusEvent::Timer, 1299), vec![]);
+    assert_eq!(node.get_state_snapshot().round, 2);
+    assert_eq!(node.progress(ConsensusEvent::Timer, 1310), vec![]);
+    assert_eq!(node.get_state_snapshot().round, 3);
+    assert_eq!(
+        node.get_state_snapshot().propose_timeout_schedules,
+        vec![(3, 1410)]
+    );
+}
+
+/// The precommit timeout is scheduled relative to the time it is triggered.
+#[test]
+fn precommit_timeout_1() {
+    let height_info = HeightInfo {
+        validators: vec![1, 1, 1, 1],
+        this_node_index: Some(3),
+        timestamp: 0,
+        consensus_params: ConsensusParams {
+            timeout_ms: 100,
+            repeat_round_for_first_leader: 1,
+            timeout_growth: TimeoutGrowth::Linear {
+                increment_ms: 100,
+                max_timeout_ms: 1000,
+            },
+            wall_clock_rounds: false,
+            leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+        },
+        initial_block_candidate: 0,
+    };
+    let mut node = Vetomint::new(height_info);
+    node.progress(ConsensusEvent::Start, 0);
+    for signer in 0..4 {
+        node.progress(
+            ConsensusEvent::Precommit {
+                proposal: None,
+                signer,
+                round: 0,
+            },
+            5000,
+        );
+    }
+    assert_eq!(
+        node.get_state_snapshot().precommit_timeout_schedules,
+        vec![(0, 5100)]
+    );
+    // Only the propose timeout, which has passed long ago, fires.
+    assert_eq!(
+        node.progress(ConsensusEvent::Timer, 5099),
+        vec![ConsensusResponse::BroadcastPrevote {
+            proposal: None,
+            round: 0,
+        }]
+    );
+    assert_eq!(node.get_state_snapshot().round, 0);
+    node.progress(ConsensusEvent::Timer, 5100);
+    let snapshot = node.get_state_snapshot();
+    assert_eq!(snapshot.round, 1);
+    // The timeout of the round 1 is longer.
+    assert_eq!(
+        snapshot.propose_timeout_schedules,
+        vec![(0, 100), (1, 5300)]
+    );
+}
+
+/// A block is finalized even if its identifier differs from the round.
+#[test]
+fn finalize_1() {
+    let height_info = HeightInfo {
+        validators: vec![1, 1, 1, 1],
+        this_node_index: Some(3),
+        timestamp: 0,
+        consensus_params: ConsensusParams {
+            timeout_ms: 100,
+            repeat_round_for_first_leader: 1,
+            timeout_growth: TimeoutGrowth::Constant,
+            wall_clock_rounds: false,
+            leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+        },
+        initial_block_candidate: 0,
+    };
+    let mut node = Vetomint::new(height_info);
+    node.progress(ConsensusEvent::Start, 0);
+    node.progress(
+        ConsensusEvent::BlockProposalReceived {
+            proposal: 1,
+            valid: true,
+            valid_round: None,
+            proposer: 0,
+            round: 0,
+            favor: true,
+        },
+        1,
+    );
+    let mut response = Vec::new();
+    for signer in 0..3 {
+        response = node.progress(
+            ConsensusEvent::Precommit {
+                proposal: Some(1),
+                signer,
+                round: 0,
+            },
+            2,
+        );
+    }
+    assert_eq!(
+        response,
+        vec![ConsensusResponse::FinalizeBlock {
+            proposal: 1,
+            proof: vec![0, 1, 2],
+        }]
+    );
+}
+
+/// The leader proposes the initial block candidate unless it is updated.
+#[test]
+fn initial_block_candidate_1() {
+    let height_info = HeightInfo {
+        validators: vec![1, 1, 1, 1],
+        this_node_index: Some(0),
+        timestamp: 0,
+        consensus_params: ConsensusParams {
+            timeout_ms: 100,
+            repeat_round_for_first_leader: 1,
+            timeout_growth: TimeoutGrowth::Constant,
+            wall_clock_rounds: false,
+            leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+        },
+        initial_block_candidate: 1,
+    };
+    let mut node = Vetomint::new(height_info);
+    assert_eq!(
+        node.progress(ConsensusEvent::Start, 0),
+        vec![
+            ConsensusResponse::BroadcastProposal {
+                proposal: 1,
+                valid_round: None,
+                round: 0,
+            },
+            ConsensusResponse::BroadcastPrevote {
+                proposal: Some(1),
+                round: 0
+            }
+        ]
+    );
+}
+
+/// A proposal received before its round is acted on once the round starts.
+#[test]
+fn early_proposal_1() {
+    let height_info = HeightInfo {
+        validators: vec![1, 1, 1, 1],
+        this_node_index: Some(3),
+        timestamp: 0,
+        consensus_params: ConsensusParams {
+            timeout_ms: 100,
+            repeat_round_for_first_leader: 1,
+            timeout_growth: TimeoutGrowth::Constant,
+            wall_clock_rounds: false,
+            leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+        },
+        initial_block_candidate: 0,
+    };
+    let mut node = Vetomint::new(height_info);
+    node.progress(ConsensusEvent::Start, 0);
+    // The leader of the round 1 proposes while this node is still in the round 0.
+    assert_eq!(
+        node.progress(
+            ConsensusEvent::BlockProposalReceived {
+                proposal: 0,
+                valid: true,
+                valid_round: None,
+                proposer: 1,
+                round: 1,
+                favor: true,
+            },
+            1,
+        ),
+        vec![]
+    );
+    for signer in 0..4 {
+        node.progress(
+            ConsensusEvent::Precommit {
+                proposal: None,
+                signer,
+                round: 0,
+            },
+            2,
+        );
+    }
+    // The round 0 ends with the precommit timeout.
+    assert_eq!(
+        node.progress(ConsensusEvent::Timer, 102),
+        vec![
+            ConsensusResponse::BroadcastPrevote {
+                proposal: None,
+                round: 0,
+            },
+            ConsensusResponse::BroadcastPrevote {
+                proposal: Some(0),
+                round: 1,
+            }
+        ]
+    );
+}
+
+#[test]
+fn leader_schedule_1() {
+    let mut height_info = HeightInfo {
+        validators: vec![1, 3, 2],
+        this_node_index: Some(0),
+        timestamp: 0,
+        consensus_params: ConsensusParams {
+            timeout_ms: 100,
+            repeat_round_for_first_leader: 2,
+            timeout_growth: TimeoutGrowth::Constant,
+            wall_clock_rounds: false,
+            leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+        },
+        initial_block_candidate: 0,
+    };
+    let leaders = |height_info: &HeightInfo| {
+        (0..8)
+            .map(|round| decide_proposer(round, height_info))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(leaders(&height_info), vec![0, 0, 1, 2, 0, 1, 2, 0]);
+
+    height_info.consensus_params.leader_schedule =
+        LeaderSchedule::Explicit(vec![(1, 3), (2, 2), (0, 0), (0, 1)]);
+    assert_eq!(leaders(&height_info), vec![1, 1, 1, 2, 2, 0, 1, 1]);
+
+    // Proportional to the voting powers over every 6 rounds.
+    height_info.consensus_params.leader_schedule = LeaderSchedule::WeightedRoundRobin;
+    assert_eq!(leaders(&height_info), vec![1, 2, 0, 1, 2, 1, 1, 2]);
+
+    // An empty schedule falls back to the default.
+    height_info.consensus_params.leader_schedule = LeaderSchedule::Explicit(Vec::new());
+    assert_eq!(leaders(&height_info), vec![0, 0, 1, 2, 0, 1, 2, 0]);
+}
+
+#[test]
+fn weighted_round_robin_cycle() {
+    let mut height_info = HeightInfo {
+        validators: vec![2_000_000_000_000, 1_000_000_000_000, 1_000_000_000_000],
+        this_node_index: Some(0),
+        timestamp: 0,
+        consensus_params: ConsensusParams {
+            timeout_ms: 100,
+            repeat_round_for_first_leader: 1,
+            timeout_growth: TimeoutGrowth::Constant,
+            wall_clock_rounds: false,
+            leader_schedule: LeaderSchedule::WeightedRoundRobin,
+        },
+        initial_block_candidate: 0,
+    };
+    let leaders = |height_info: &HeightInfo, rounds: std::ops::Range<usize>| {
+        rounds
+            .map(|round| decide_proposer(round, height_info))
+            .collect::<Vec<_>>()
+    };
+    // The same as for the powers of `[2, 1, 1]`, repeating every 4 rounds.
+    assert_eq!(leaders(&height_info, 0..8), vec![0, 1, 2, 0, 0, 1, 2, 0]);
+    let far = 1_000_000_000;
+    assert_eq!(leaders(&height_info, far..far + 4), vec![0, 1, 2, 0]);
+    height_info.validators = vec![2, 1, 1];
+    assert_eq!(leaders(&height_info, far..far + 4), vec![0, 1, 2, 0]);
+}
+
+#[test]
+fn weighted_round_robin_large_powers() {
+    let height_info = HeightInfo {
+        validators: vec![1_000_000_007, 998_244_353, 500_000_003, 3],
+        this_node_index: Some(0),
+        timestamp: 0,
+        consensus_params: ConsensusParams {
+            timeout_ms: 100,
+            repeat_round_for_first_leader: 1,
+            timeout_growth: TimeoutGrowth::Constant,
+            wall_clock_rounds: false,
+            leader_schedule: LeaderSchedule::WeightedRoundRobin,
+        },
+        initial_block_candidate: 0,
+    };
+    let leaders = |rounds: std::ops::Range<usize>| {
+        rounds
+            .map(|round| decide_proposer(round, &height_info))
+            .collect::<Vec<_>>()
+    };
+    // The coprime powers would take billions of rounds to repeat, so the schedule is capped.
+    let cycle = MAX_WEIGHTED_ROUND_ROBIN_CYCLE;
+    let first_cycle = leaders(0..cycle);
+    let far = 1_000_000_000_000;
+    let far = far - far % cycle;
+    assert_eq!(leaders(far..far + cycle), first_cycle);
+    // Still proportional to the voting powers within the cycle.
+    let total: u64 = height_info.validators.iter().sum();
+    for (index, power) in height_info.validators.iter().enumerate() {
+        let count = first_cycle
+            .iter()
+            .filter(|leader| **leader == index)
+            .count() as f64;
+        let expected = cycle as f64 * *power as f64 / total as f64;
+        assert!(
+            (count - expected).abs() <= 1.0,
+            "{count} rounds for {index}"
+        );
+    }
+}
+
+#[test]
+fn replay_1() {
+    let height_info = HeightInfo {
+        validators: vec![1, 1, 1, 1],
+        this_node_index: Some(0),
+        timestamp: 0,
+        consensus_params: ConsensusParams {
+            timeout_ms: 100,
+            repeat_round_for_first_leader: 1,
+            timeout_growth: TimeoutGrowth::Constant,
+            wall_clock_rounds: false,
+            leader_schedule: LeaderSchedule::FirstLeaderRepeated,
+        },
+        initial_block_candidate: 0,
+    };
+    let mut node = Vetomint::new(height_info);
+    // Not recorded.
+    node.progress(ConsensusEvent::Start, 0);
+    let initial_state = node.clone();
+    node.start_recording();
+    for signer in 1..3 {
+        node.progress(
+            ConsensusEvent::Prevote {
+                proposal: Some(0),
+                signer,
+                round: 0,
+            },
+            10,
+        );
+    }
+    node.progress(ConsensusEvent::Timer, 20);
+    let trace = EventTrace {
+        initial_state,
+        records: node.take_records(),
+    };
+    assert_eq!(trace.records.len(), 3);
+    assert_eq!(
+        trace.records[1].responses,
+        vec![ConsensusResponse::BroadcastPrecommit {
+            proposal: Some(0),
+            round: 0,
+        }]
+    );
+    assert!(node.take_records().is_empty());
+
+    let mut replayer = Replayer::new(trace.clone());
+    assert_eq!(replayer.step().unwrap(), Some(&trace.records[0]));
+    assert_eq!(
+        replayer
+            .diff(&node)
+            .into_iter()
+            .map(|difference| difference.field)
+            .collect::<Vec<_>>(),
+        vec![
+            "locked_round",
+            "locked_value",
+            "precommits",
+            "prevotes",
+            "step",
+            "valid_round",
+            "valid_value"
+        ]
+    );
+    replayer.run_to_end().unwrap();
+    assert_eq!(replayer.step().unwrap(), None);
+    assert_eq!(replayer.get_vetomint(), &node);
+    assert!(replayer.diff(&node).is_empty());
+
+    // A tampered trace diverges.
+    let mut tampered = trace;
+    tampered.records[1].responses.clear();
+    let mut replayer = Replayer::new(tampered);
+    assert!(matches!(
+        replayer.run_to_end(),
+        Err(ReplayError::Diverged { position: 1, .. })
+    ));
+    assert_eq!(replayer.get_position(), 1);
+}